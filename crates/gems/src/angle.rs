@@ -0,0 +1,27 @@
+use core::f32::consts::{PI, TAU};
+
+/// Interpolates between two angles in radians along the shorter arc. The result is not wrapped
+/// into a canonical range.
+pub fn lerp_angle(a: f32, b: f32, q: f32) -> f32 {
+    let delta = (b - a + PI).rem_euclid(TAU) - PI;
+    a + delta * q
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_lerp_angle_takes_shorter_arc() {
+        assert_abs_diff_eq!(lerp_angle(0., 1., 0.5), 0.5);
+        assert_abs_diff_eq!(lerp_angle(1., 0., 0.25), 0.75);
+
+        // across the wrap at +-PI
+        let mid = lerp_angle(3.0, -3.0, 0.5);
+        assert_abs_diff_eq!(mid.rem_euclid(TAU), PI, epsilon = 1e-5);
+
+        // full turns are ignored
+        assert_abs_diff_eq!(lerp_angle(0., TAU + 0.5, 1.), 0.5, epsilon = 1e-5);
+    }
+}
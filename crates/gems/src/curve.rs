@@ -0,0 +1,69 @@
+/// Piecewise linear function defined by control points
+///
+/// Outside of the control points the curve is constant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve1D {
+    points: Vec<(f32, f32)>,
+}
+
+impl Curve1D {
+    /// Creates a curve from control points `(x, y)` which must be sorted by `x`
+    pub fn new(points: impl IntoIterator<Item = (f32, f32)>) -> Self {
+        let points: Vec<_> = points.into_iter().collect();
+        assert!(
+            !points.is_empty(),
+            "Curve1D::new: at least one point required"
+        );
+        assert!(
+            points.windows(2).all(|w| w[0].0 <= w[1].0),
+            "Curve1D::new: points must be sorted by x"
+        );
+        Self { points }
+    }
+
+    pub fn points(&self) -> &[(f32, f32)] {
+        &self.points
+    }
+
+    pub fn sample(&self, x: f32) -> f32 {
+        let i = self.points.partition_point(|&(px, _)| px <= x);
+        if i == 0 {
+            return self.points[0].1;
+        }
+        if i == self.points.len() {
+            return self.points[i - 1].1;
+        }
+
+        let (x0, y0) = self.points[i - 1];
+        let (x1, y1) = self.points[i];
+        let q = (x - x0) / (x1 - x0);
+        y0 + (y1 - y0) * q
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_curve_1d_sample() {
+        let curve = Curve1D::new([(1.0, 0.0), (3.0, 1.0), (4.0, 1.0), (6.0, -1.0)]);
+
+        assert_abs_diff_eq!(curve.sample(-5.0), 0.0);
+        assert_abs_diff_eq!(curve.sample(1.0), 0.0);
+        assert_abs_diff_eq!(curve.sample(2.0), 0.5);
+        assert_abs_diff_eq!(curve.sample(3.0), 1.0);
+        assert_abs_diff_eq!(curve.sample(3.5), 1.0);
+        assert_abs_diff_eq!(curve.sample(5.5), -0.5);
+        assert_abs_diff_eq!(curve.sample(6.0), -1.0);
+        assert_abs_diff_eq!(curve.sample(100.0), -1.0);
+
+        // steps are allowed
+        let step = Curve1D::new([(0.0, 0.0), (1.0, 0.0), (1.0, 2.0)]);
+        assert_abs_diff_eq!(step.sample(0.5), 0.0);
+        assert_abs_diff_eq!(step.sample(1.0), 2.0);
+
+        assert_abs_diff_eq!(Curve1D::new([(2.0, 7.0)]).sample(0.0), 7.0);
+    }
+}
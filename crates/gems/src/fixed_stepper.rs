@@ -0,0 +1,116 @@
+use std::ops::Range;
+
+/// Runs a simulation with a fixed time step from variable wall-clock deltas
+///
+/// Wall-clock time is accumulated and consumed in whole steps. The remainder is exposed as an
+/// interpolation factor for rendering between the last two simulation states. If the simulation
+/// falls behind by more than `max_catch_up` steps the excess steps are dropped.
+#[derive(Debug, Clone)]
+pub struct FixedStepper {
+    step: f64,
+    max_catch_up: usize,
+    accumulator: f64,
+    step_count: u64,
+    dropped_steps: u64,
+}
+
+impl FixedStepper {
+    pub fn new(step: f64, max_catch_up: usize) -> Self {
+        assert!(step > 0., "FixedStepper::new: step must be positive");
+        assert!(
+            max_catch_up > 0,
+            "FixedStepper::new: max_catch_up must be positive"
+        );
+        Self {
+            step,
+            max_catch_up,
+            accumulator: 0.,
+            step_count: 0,
+            dropped_steps: 0,
+        }
+    }
+
+    /// Feeds a wall-clock delta and returns the indices of the steps which are due. Negative or
+    /// invalid deltas, e.g. when the clock jumps backwards, are ignored.
+    pub fn advance(&mut self, dt: f64) -> Range<u64> {
+        if dt.is_finite() && dt > 0. {
+            self.accumulator += dt;
+        }
+
+        let due = (self.accumulator / self.step).floor() as u64;
+        self.accumulator -= due as f64 * self.step;
+        self.accumulator = self.accumulator.clamp(0., self.step);
+
+        let run = due.min(self.max_catch_up as u64);
+        self.dropped_steps += due - run;
+
+        let first = self.step_count;
+        self.step_count += run;
+        first..self.step_count
+    }
+
+    /// Interpolation factor in [0, 1) between the previous and the current simulation state
+    pub fn alpha(&self) -> f64 {
+        (self.accumulator / self.step).min(1. - f64::EPSILON)
+    }
+
+    pub fn step_duration(&self) -> f64 {
+        self.step
+    }
+
+    /// Number of steps which were run so far
+    pub fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    /// Number of steps which were skipped because the simulation fell behind
+    pub fn dropped_steps(&self) -> u64 {
+        self.dropped_steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_fixed_stepper_alpha() {
+        let mut stepper = FixedStepper::new(0.25, 4);
+        assert_eq!(stepper.advance(0.125), 0..0);
+        assert_abs_diff_eq!(stepper.alpha(), 0.5);
+
+        // exactly on a step boundary
+        assert_eq!(stepper.advance(0.125), 0..1);
+        assert_abs_diff_eq!(stepper.alpha(), 0.);
+
+        assert_eq!(stepper.advance(0.5625), 1..3);
+        assert_abs_diff_eq!(stepper.alpha(), 0.25);
+        assert!(stepper.alpha() < 1.);
+    }
+
+    #[test]
+    fn test_fixed_stepper_catch_up_clamping() {
+        let mut stepper = FixedStepper::new(0.1, 3);
+        assert_eq!(stepper.advance(1.05).count(), 3);
+        assert_eq!(stepper.dropped_steps(), 7);
+        assert_abs_diff_eq!(stepper.alpha(), 0.5, epsilon = 1e-9);
+
+        // the backlog was dropped and the stepper continues normally
+        assert_eq!(stepper.advance(0.05), 3..4);
+        assert_eq!(stepper.step_count(), 4);
+        assert_eq!(stepper.dropped_steps(), 7);
+    }
+
+    #[test]
+    fn test_fixed_stepper_clock_jumps_backwards() {
+        let mut stepper = FixedStepper::new(0.1, 3);
+        stepper.advance(0.05);
+        assert_eq!(stepper.advance(-5.), 0..0);
+        assert_abs_diff_eq!(stepper.alpha(), 0.5, epsilon = 1e-9);
+        assert_eq!(stepper.advance(f64::NAN), 0..0);
+
+        assert_eq!(stepper.advance(0.05), 0..1);
+        assert_eq!(stepper.dropped_steps(), 0);
+    }
+}
@@ -1,11 +1,18 @@
+use serde::{Deserialize, Serialize};
+
 pub fn disk_area(r: f64) -> f64 {
     r * r * core::f64::consts::PI
 }
 
-pub fn disk_circumfence(r: f64) -> f64 {
+pub fn disk_circumference(r: f64) -> f64 {
     2. * r * core::f64::consts::PI
 }
 
+#[deprecated(note = "use disk_circumference")]
+pub fn disk_circumfence(r: f64) -> f64 {
+    disk_circumference(r)
+}
+
 pub fn cylinder_volume(radius: f64, length: f64) -> f64 {
     disk_area(radius) * length
 }
@@ -15,16 +22,38 @@ pub fn cylinder_radius(volume: f64, length: f64) -> f64 {
 }
 
 pub fn cylinder_area(radius: f64, length: f64) -> f64 {
-    disk_circumfence(radius) * length
+    disk_circumference(radius) * length
 }
 
-#[derive(Clone, Debug)]
+/// A cylinder (tube) with given radius and length
+///
+/// ```
+/// use gems::{Cylinder, VolumeModel};
+///
+/// let tube = Cylinder::new(0.01, 0.5).with_length(1.0);
+/// approx::assert_relative_eq!(tube.nominal_volume(), 0.0001 * core::f64::consts::PI);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Cylinder {
     pub radius: f64,
     pub length: f64,
 }
 
 impl Cylinder {
+    pub fn new(radius: f64, length: f64) -> Self {
+        Self { radius, length }
+    }
+
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_length(mut self, length: f64) -> Self {
+        self.length = length;
+        self
+    }
+
     pub fn is_non_zero(&self) -> bool {
         self.radius > 0. && self.length > 0.
     }
@@ -33,8 +62,65 @@ impl Cylinder {
         disk_area(self.radius)
     }
 
+    /// Area of the mantle (without the two end disks)
     pub fn surface_area(&self) -> f64 {
-        disk_circumfence(self.radius) * self.length
+        disk_circumference(self.radius) * self.length
+    }
+}
+
+/// A conical frustum (truncated cone) with radius `radius_a` at one end and `radius_b` at the
+/// other end.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Frustum {
+    pub radius_a: f64,
+    pub radius_b: f64,
+    pub length: f64,
+}
+
+impl Frustum {
+    pub fn new(radius_a: f64, radius_b: f64, length: f64) -> Self {
+        Self {
+            radius_a,
+            radius_b,
+            length,
+        }
+    }
+
+    pub fn with_radius(mut self, radius_a: f64, radius_b: f64) -> Self {
+        self.radius_a = radius_a;
+        self.radius_b = radius_b;
+        self
+    }
+
+    pub fn with_length(mut self, length: f64) -> Self {
+        self.length = length;
+        self
+    }
+
+    pub fn is_non_zero(&self) -> bool {
+        (self.radius_a > 0. || self.radius_b > 0.) && self.length > 0.
+    }
+
+    /// Cross section area at relative position `q` along the axis (0 = end A, 1 = end B)
+    pub fn cross_section_area(&self, q: f64) -> f64 {
+        disk_area(self.radius_a + (self.radius_b - self.radius_a) * q)
+    }
+
+    /// Area of the mantle (without the two end disks)
+    pub fn surface_area(&self) -> f64 {
+        let slant = (self.radius_a - self.radius_b).hypot(self.length);
+        core::f64::consts::PI * (self.radius_a + self.radius_b) * slant
+    }
+
+    pub fn volume(&self) -> f64 {
+        let (ra, rb) = (self.radius_a, self.radius_b);
+        core::f64::consts::PI * self.length / 3. * (ra * ra + ra * rb + rb * rb)
+    }
+}
+
+impl From<Cylinder> for Frustum {
+    fn from(cylinder: Cylinder) -> Self {
+        Frustum::new(cylinder.radius, cylinder.radius, cylinder.length)
     }
 }
 
@@ -51,7 +137,13 @@ pub trait AreaVolumeModel {
 
 impl VolumeModel for Cylinder {
     fn nominal_volume(&self) -> f64 {
-        self.cross_section_area() * self.length
+        cylinder_volume(self.radius, self.length)
+    }
+}
+
+impl VolumeModel for Frustum {
+    fn nominal_volume(&self) -> f64 {
+        self.volume()
     }
 }
 
@@ -94,8 +186,8 @@ impl AreaVolumeModel for Cylinder {
 #[cfg(test)]
 mod test {
     use crate::{
-        Cylinder,
-        geometry::{AreaVolumeModel, VolumeModel},
+        Cylinder, Frustum,
+        geometry::{AreaVolumeModel, VolumeModel, disk_area, disk_circumference},
     };
     use std::f64::consts::PI;
 
@@ -118,6 +210,56 @@ mod test {
         approx::assert_abs_diff_eq!(c.volume(2. * AREA_0), 2. * VOL_0);
     }
 
+    #[test]
+    fn test_disk() {
+        approx::assert_abs_diff_eq!(disk_area(0.), 0.);
+        approx::assert_abs_diff_eq!(disk_area(2.), 4. * PI);
+        approx::assert_abs_diff_eq!(disk_circumference(2.), 4. * PI);
+    }
+
+    #[test]
+    fn test_cylinder_builders() {
+        let c = Cylinder::new(0.01, 0.5).with_radius(0.02).with_length(2.0);
+        assert_eq!(c, Cylinder::new(0.02, 2.0));
+        approx::assert_abs_diff_eq!(c.nominal_volume(), 0.0008 * PI);
+        assert!(!c.with_radius(0.).is_non_zero());
+    }
+
+    #[test]
+    fn test_frustum() {
+        // a frustum with equal radii is a cylinder
+        let c = Cylinder::new(0.01, 0.5);
+        let f = Frustum::from(c);
+        approx::assert_relative_eq!(f.volume(), c.nominal_volume());
+        approx::assert_relative_eq!(f.surface_area(), c.surface_area());
+        approx::assert_relative_eq!(f.cross_section_area(0.5), c.cross_section_area());
+
+        // a frustum with one zero radius is a cone
+        let cone = Frustum::new(3., 0., 4.);
+        approx::assert_relative_eq!(cone.volume(), 12. * PI);
+        approx::assert_relative_eq!(cone.surface_area(), 15. * PI);
+        approx::assert_relative_eq!(cone.nominal_volume(), 12. * PI);
+        approx::assert_relative_eq!(cone.cross_section_area(0.), 9. * PI);
+        approx::assert_relative_eq!(cone.cross_section_area(1.), 0.);
+
+        let f = Frustum::new(1., 2., 3.);
+        approx::assert_relative_eq!(f.volume(), 7. * PI);
+        approx::assert_relative_eq!(f.surface_area(), 3. * PI * 10f64.sqrt());
+        assert_eq!(f.with_radius(2., 1.).volume(), f.volume());
+    }
+
+    #[test]
+    fn test_cylinder_serde() {
+        let c = Cylinder::new(0.01, 0.5);
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(json, r#"{"radius":0.01,"length":0.5}"#);
+        assert_eq!(serde_json::from_str::<Cylinder>(&json).unwrap(), c);
+
+        let f = Frustum::new(0.01, 0.02, 0.5);
+        let json = serde_json::to_string(&f).unwrap();
+        assert_eq!(serde_json::from_str::<Frustum>(&json).unwrap(), f);
+    }
+
     // #[test]
     // fn test_capital_cylinder() {
     //     let c = CapitalCylinder {
@@ -1,21 +1,34 @@
+mod angle;
+mod curve;
 mod cycle;
 mod fair_alloc;
+mod fixed_stepper;
 mod geometry;
 mod int_map;
 mod kinematics;
 mod materials;
 mod modifier;
+mod money;
+pub mod ode;
+mod pid;
 mod rescale;
+mod rolling;
 mod runge_kutta;
 mod units;
 
+pub use angle::*;
+pub use curve::*;
 pub use cycle::*;
 pub use fair_alloc::*;
+pub use fixed_stepper::*;
 pub use geometry::*;
 pub use int_map::*;
 pub use kinematics::*;
 pub use materials::*;
 pub use modifier::*;
+pub use money::*;
+pub use pid::*;
 pub use rescale::*;
+pub use rolling::*;
 pub use runge_kutta::*;
 pub use units::*;
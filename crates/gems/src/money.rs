@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Fixed-point amount of money stored as signed integer minor units (cents)
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct Money(i128);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoneyError {
+    Overflow,
+    DivisionByZero,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::Overflow => write!(f, "money arithmetic overflow"),
+            MoneyError::DivisionByZero => write!(f, "money division by zero"),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl Money {
+    /// Number of decimal digits of the minor unit
+    pub const DECIMALS: u32 = 2;
+
+    /// Number of minor units in one major unit
+    pub const SCALE: i128 = 10_i128.pow(Self::DECIMALS);
+
+    pub const ZERO: Self = Self(0);
+    pub const MAX: Self = Self(i128::MAX);
+    pub const MIN: Self = Self(i128::MIN);
+
+    pub const fn from_minor(minor: i128) -> Self {
+        Self(minor)
+    }
+
+    pub fn from_major(major: i128) -> Result<Self, MoneyError> {
+        major
+            .checked_mul(Self::SCALE)
+            .map(Self)
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Amount in minor units
+    pub const fn minor(&self) -> i128 {
+        self.0
+    }
+
+    /// Amount in minor units if it fits into an i64
+    pub fn to_minor_i64(&self) -> Result<i64, MoneyError> {
+        i64::try_from(self.0).map_err(|_| MoneyError::Overflow)
+    }
+
+    /// Amount in minor units if it is non-negative and fits into an u64
+    pub fn to_minor_u64(&self) -> Result<u64, MoneyError> {
+        u64::try_from(self.0).map_err(|_| MoneyError::Overflow)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.0 < 0
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, MoneyError> {
+        self.0
+            .checked_add(other.0)
+            .map(Self)
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, MoneyError> {
+        self.0
+            .checked_sub(other.0)
+            .map(Self)
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_neg(self) -> Result<Self, MoneyError> {
+        self.0.checked_neg().map(Self).ok_or(MoneyError::Overflow)
+    }
+
+    /// Price times quantity, e.g. the notional of an order
+    pub fn checked_mul_quantity(self, quantity: i64) -> Result<Self, MoneyError> {
+        self.0
+            .checked_mul(quantity as i128)
+            .map(Self)
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Divides by an integer with round half to even (banker's rounding)
+    pub fn checked_div_round(self, divisor: i128) -> Result<Self, MoneyError> {
+        div_round_half_even(self.0, divisor).map(Self)
+    }
+
+    /// Multiplies by the ratio `numerator / denominator` with banker's rounding, e.g. to compute
+    /// a fee in basis points with `checked_mul_ratio(bps, 10_000)`.
+    pub fn checked_mul_ratio(self, numerator: i128, denominator: i128) -> Result<Self, MoneyError> {
+        let product = self.0.checked_mul(numerator).ok_or(MoneyError::Overflow)?;
+        div_round_half_even(product, denominator).map(Self)
+    }
+}
+
+fn div_round_half_even(dividend: i128, divisor: i128) -> Result<i128, MoneyError> {
+    if divisor == 0 {
+        return Err(MoneyError::DivisionByZero);
+    }
+
+    let negative = (dividend < 0) != (divisor < 0);
+    let a = dividend.unsigned_abs();
+    let b = divisor.unsigned_abs();
+
+    let mut q = a / b;
+    let r = a % b;
+
+    // r < b <= 2^127 thus 2 r does not overflow
+    let twice_r = 2 * r;
+    if twice_r > b || (twice_r == b && !q.is_multiple_of(2)) {
+        q += 1;
+    }
+
+    if negative {
+        if q > i128::MIN.unsigned_abs() {
+            Err(MoneyError::Overflow)
+        } else {
+            Ok((q as i128).wrapping_neg())
+        }
+    } else {
+        i128::try_from(q).map_err(|_| MoneyError::Overflow)
+    }
+}
+
+impl fmt::Display for Money {
+    /// Formats with thousands separators, e.g. `-1,234.50`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let abs = self.0.unsigned_abs();
+        let scale = Self::SCALE as u128;
+        let major = (abs / scale).to_string();
+        let minor = abs % scale;
+
+        let mut grouped = String::with_capacity(major.len() + major.len() / 3);
+        for (i, c) in major.chars().enumerate() {
+            if i > 0 && (major.len() - i).is_multiple_of(3) {
+                grouped.push(',');
+            }
+            grouped.push(c);
+        }
+
+        let sign = if self.0 < 0 { "-" } else { "" };
+        write!(
+            f,
+            "{sign}{grouped}.{minor:0width$}",
+            width = Self::DECIMALS as usize
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference implementation with exact rational arithmetic on small values
+    fn reference_round_half_even(a: i128, b: i128) -> i128 {
+        let floor = a.div_euclid(b.abs()) * b.signum();
+        let candidates = [floor - 1, floor, floor + 1];
+        let dist = |q: i128| (a - q * b).abs();
+        let best = candidates.iter().map(|&q| dist(q)).min().unwrap();
+        let ties: Vec<_> = candidates
+            .into_iter()
+            .filter(|&q| dist(q) == best)
+            .collect();
+        if ties.len() == 1 {
+            ties[0]
+        } else {
+            *ties.iter().find(|&&q| q % 2 == 0).unwrap()
+        }
+    }
+
+    #[test]
+    fn test_money_div_round_half_even() {
+        assert_eq!(div_round_half_even(5, 2), Ok(2));
+        assert_eq!(div_round_half_even(7, 2), Ok(4));
+        assert_eq!(div_round_half_even(-5, 2), Ok(-2));
+        assert_eq!(div_round_half_even(-7, 2), Ok(-4));
+        assert_eq!(div_round_half_even(5, -2), Ok(-2));
+        assert_eq!(div_round_half_even(25, 10), Ok(2));
+        assert_eq!(div_round_half_even(35, 10), Ok(4));
+        assert_eq!(div_round_half_even(26, 10), Ok(3));
+        assert_eq!(div_round_half_even(1, 0), Err(MoneyError::DivisionByZero));
+
+        for a in -200..=200 {
+            for b in (-12..=12).filter(|&b| b != 0) {
+                assert_eq!(
+                    div_round_half_even(a, b),
+                    Ok(reference_round_half_even(a, b)),
+                    "{a} / {b}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_money_overflow() {
+        assert_eq!(
+            Money::MAX.checked_add(Money::from_minor(1)),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(
+            Money::MIN.checked_sub(Money::from_minor(1)),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(Money::MIN.checked_neg(), Err(MoneyError::Overflow));
+        assert_eq!(
+            Money::MAX.checked_mul_quantity(2),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(Money::MIN.checked_div_round(-1), Err(MoneyError::Overflow));
+        assert_eq!(Money::MIN.checked_div_round(1), Ok(Money::MIN));
+        assert_eq!(Money::from_major(i128::MAX), Err(MoneyError::Overflow));
+        assert_eq!(
+            Money::from_minor(-1).to_minor_u64(),
+            Err(MoneyError::Overflow)
+        );
+        assert_eq!(
+            Money::from_minor(i64::MAX as i128 + 1).to_minor_i64(),
+            Err(MoneyError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_money_arithmetic() {
+        let price = Money::from_minor(1_25);
+        let notional = price.checked_mul_quantity(300).unwrap();
+        assert_eq!(notional, Money::from_major(375).unwrap());
+
+        // 7.5 bps fee on 375.00 is 0.28125 which rounds to 0.28
+        assert_eq!(
+            notional.checked_mul_ratio(75, 100_000),
+            Ok(Money::from_minor(28))
+        );
+
+        let pnl = Money::from_minor(100)
+            .checked_sub(Money::from_minor(250))
+            .unwrap();
+        assert_eq!(pnl, Money::from_minor(-150));
+        assert!(pnl.is_negative());
+    }
+
+    #[test]
+    fn test_money_display() {
+        assert_eq!(Money::ZERO.to_string(), "0.00");
+        assert_eq!(Money::from_minor(5).to_string(), "0.05");
+        assert_eq!(Money::from_minor(-5).to_string(), "-0.05");
+        assert_eq!(Money::from_minor(99_999).to_string(), "999.99");
+        assert_eq!(Money::from_minor(100_000).to_string(), "1,000.00");
+        assert_eq!(Money::from_minor(-123_456_789).to_string(), "-1,234,567.89");
+        assert_eq!(
+            Money::MIN.to_string(),
+            "-1,701,411,834,604,692,317,316,873,037,158,841,057.28"
+        );
+    }
+
+    #[test]
+    fn test_money_serde() {
+        for m in [0, 1, -1, 123_456, i64::MIN as i128, i128::MAX, i128::MIN] {
+            let m = Money::from_minor(m);
+            let json = serde_json::to_string(&m).unwrap();
+            assert_eq!(json, m.minor().to_string());
+            assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), m);
+        }
+    }
+}
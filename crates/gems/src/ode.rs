@@ -0,0 +1,407 @@
+//! Integration of ODE's over many steps
+//!
+//! [solve_fixed] takes a fixed number of equal steps. [solve_adaptive] uses the Dormand-Prince
+//! 4(5) pair and adjusts the step size to keep the local error within the given tolerances.
+//! Steps which exceed the tolerance are rejected and repeated with a smaller step size.
+//!
+//! Reference: https://en.wikipedia.org/wiki/Dormand%E2%80%93Prince_method
+
+use crate::ODE;
+use std::fmt;
+
+/// State vector of an ODE
+pub trait OdeState: Clone {
+    /// Computes `self + x * a`
+    fn add_scaled(&self, x: &Self, a: f64) -> Self;
+
+    /// Components of the state, used to measure the integration error
+    fn components(&self) -> &[f64];
+}
+
+impl OdeState for f64 {
+    fn add_scaled(&self, x: &Self, a: f64) -> Self {
+        self + x * a
+    }
+
+    fn components(&self) -> &[f64] {
+        std::slice::from_ref(self)
+    }
+}
+
+impl<const N: usize> OdeState for [f64; N] {
+    fn add_scaled(&self, x: &Self, a: f64) -> Self {
+        std::array::from_fn(|i| self[i] + x[i] * a)
+    }
+
+    fn components(&self) -> &[f64] {
+        self
+    }
+}
+
+impl<const N: usize> OdeState for nalgebra::SVector<f64, N> {
+    fn add_scaled(&self, x: &Self, a: f64) -> Self {
+        self + x * a
+    }
+
+    fn components(&self) -> &[f64] {
+        self.as_slice()
+    }
+}
+
+impl OdeState for nalgebra::DVector<f64> {
+    fn add_scaled(&self, x: &Self, a: f64) -> Self {
+        self + x * a
+    }
+
+    fn components(&self) -> &[f64] {
+        self.as_slice()
+    }
+}
+
+/// Integration method of [solve_fixed]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Forward Euler: first order
+    Euler,
+
+    /// Classic Runge-Kutta: fourth order
+    Rk4,
+}
+
+/// Integrates `steps` steps of size `dt` starting at `(t0, y0)` and returns the final state
+pub fn solve_fixed<S: OdeState>(
+    ode: impl ODE<S>,
+    t0: f64,
+    y0: S,
+    dt: f64,
+    steps: usize,
+    method: Method,
+) -> S {
+    let mut y = y0;
+    for i in 0..steps {
+        let t = t0 + i as f64 * dt;
+        y = match method {
+            Method::Euler => y.add_scaled(&ode.eval(t, y.clone()), dt),
+            Method::Rk4 => rk4_step(&ode, t, &y, dt),
+        };
+    }
+    y
+}
+
+fn rk4_step<S: OdeState>(ode: &impl ODE<S>, t: f64, y: &S, h: f64) -> S {
+    let h2 = h * 0.5;
+    let k1 = ode.eval(t, y.clone());
+    let k2 = ode.eval(t + h2, y.add_scaled(&k1, h2));
+    let k3 = ode.eval(t + h2, y.add_scaled(&k2, h2));
+    let k4 = ode.eval(t + h, y.add_scaled(&k3, h));
+    combine(y, h / 6., &[(1., &k1), (2., &k2), (2., &k3), (1., &k4)])
+}
+
+/// Result of [solve_adaptive]
+#[derive(Debug, Clone)]
+pub struct AdaptiveResult<S> {
+    /// End time of the integration
+    pub t: f64,
+
+    /// State at time `t`
+    pub y: S,
+
+    /// Number of accepted steps
+    pub steps: usize,
+
+    /// Number of steps which were repeated with a smaller step size
+    pub rejected: usize,
+
+    /// Step size suggested for continuing the integration
+    pub next_dt: f64,
+}
+
+/// Error of [solve_adaptive]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OdeError {
+    /// The step size fell below a tiny fraction of the interval at time `t`, e.g. because the
+    /// derivative is not finite
+    StepSizeTooSmall { t: f64 },
+}
+
+impl fmt::Display for OdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OdeError::StepSizeTooSmall { t } => write!(f, "ODE step size too small at t={t}"),
+        }
+    }
+}
+
+impl std::error::Error for OdeError {}
+
+/// Integrates from `(t0, y0)` to `t0 + dt` with adaptive step size
+///
+/// The local error of each step is kept below `atol + rtol * |y|` per component. The first step
+/// tries to cover the whole interval. Steps with a non-finite error are rejected like steps with
+/// a large error. Fails if the step size falls below a tiny fraction of `dt`.
+pub fn solve_adaptive<S: OdeState>(
+    ode: impl ODE<S>,
+    t0: f64,
+    y0: S,
+    dt: f64,
+    rtol: f64,
+    atol: f64,
+) -> Result<AdaptiveResult<S>, OdeError> {
+    const SAFETY: f64 = 0.9;
+    const MIN_SCALE: f64 = 0.2;
+    const MAX_SCALE: f64 = 5.0;
+    const MIN_STEP_FRACTION: f64 = 1e-12;
+
+    let t_end = t0 + dt;
+    let min_step = dt.abs() * MIN_STEP_FRACTION;
+
+    let mut result = AdaptiveResult {
+        t: t0,
+        y: y0,
+        steps: 0,
+        rejected: 0,
+        next_dt: dt,
+    };
+    if dt <= 0. {
+        return Ok(result);
+    }
+
+    let mut h = dt;
+    let mut k1 = ode.eval(result.t, result.y.clone());
+    while result.t < t_end {
+        let remaining = t_end - result.t;
+        let last = h >= remaining;
+        let step = if last { remaining } else { h };
+
+        let (y_next, k7, err) = dormand_prince_step(&ode, result.t, &result.y, &k1, step);
+        let norm = error_norm(&err, &result.y, &y_next, rtol, atol);
+
+        let scale = if !norm.is_finite() {
+            MIN_SCALE
+        } else if norm > 0. {
+            (SAFETY * norm.powf(-0.2)).clamp(MIN_SCALE, MAX_SCALE)
+        } else {
+            MAX_SCALE
+        };
+
+        if norm <= 1. {
+            result.t = if last { t_end } else { result.t + step };
+            result.y = y_next;
+            result.steps += 1;
+            k1 = k7;
+            // a shortened last step says little about the step size which would be possible
+            if !last {
+                h = step * scale;
+            }
+            result.next_dt = h;
+        } else {
+            result.rejected += 1;
+            h = step * scale;
+            if h < min_step {
+                return Err(OdeError::StepSizeTooSmall { t: result.t });
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Takes one Dormand-Prince step. Returns the 5th order solution, the derivative at the new
+/// state and the difference to the 4th order solution.
+fn dormand_prince_step<S: OdeState>(ode: &impl ODE<S>, t: f64, y: &S, k1: &S, h: f64) -> (S, S, S) {
+    let k2 = ode.eval(t + h / 5., combine(y, h, &[(1. / 5., k1)]));
+    let k3 = ode.eval(
+        t + h * 3. / 10.,
+        combine(y, h, &[(3. / 40., k1), (9. / 40., &k2)]),
+    );
+    let k4 = ode.eval(
+        t + h * 4. / 5.,
+        combine(y, h, &[(44. / 45., k1), (-56. / 15., &k2), (32. / 9., &k3)]),
+    );
+    let k5 = ode.eval(
+        t + h * 8. / 9.,
+        combine(
+            y,
+            h,
+            &[
+                (19372. / 6561., k1),
+                (-25360. / 2187., &k2),
+                (64448. / 6561., &k3),
+                (-212. / 729., &k4),
+            ],
+        ),
+    );
+    let k6 = ode.eval(
+        t + h,
+        combine(
+            y,
+            h,
+            &[
+                (9017. / 3168., k1),
+                (-355. / 33., &k2),
+                (46732. / 5247., &k3),
+                (49. / 176., &k4),
+                (-5103. / 18656., &k5),
+            ],
+        ),
+    );
+    let y_next = combine(
+        y,
+        h,
+        &[
+            (35. / 384., k1),
+            (500. / 1113., &k3),
+            (125. / 192., &k4),
+            (-2187. / 6784., &k5),
+            (11. / 84., &k6),
+        ],
+    );
+    // first same as last: the derivative at the new state is the first stage of the next step
+    let k7 = ode.eval(t + h, y_next.clone());
+
+    let zero = y.add_scaled(y, -1.);
+    let err = combine(
+        &zero,
+        h,
+        &[
+            (71. / 57600., k1),
+            (-71. / 16695., &k3),
+            (71. / 1920., &k4),
+            (-17253. / 339200., &k5),
+            (22. / 525., &k6),
+            (-1. / 40., &k7),
+        ],
+    );
+
+    (y_next, k7, err)
+}
+
+/// Computes `y + h * sum(c_i * k_i)`
+fn combine<S: OdeState>(y: &S, h: f64, terms: &[(f64, &S)]) -> S {
+    terms
+        .iter()
+        .fold(y.clone(), |acc, (c, k)| acc.add_scaled(k, h * c))
+}
+
+/// Root mean square of the error relative to the tolerance. The step is accepted if the norm is
+/// at most 1.
+fn error_norm<S: OdeState>(err: &S, y0: &S, y1: &S, rtol: f64, atol: f64) -> f64 {
+    let err = err.components();
+    if err.is_empty() {
+        return 0.;
+    }
+    let sum: f64 = err
+        .iter()
+        .zip(y0.components().iter().zip(y1.components()))
+        .map(|(e, (a, b))| {
+            let tol = atol + rtol * a.abs().max(b.abs());
+            (e / tol).powi(2)
+        })
+        .sum();
+    (sum / err.len() as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FnODE;
+    use nalgebra::{DVector, Vector2};
+
+    fn decay() -> FnODE<impl Fn(f64, f64) -> f64> {
+        FnODE(|_, y: f64| -y)
+    }
+
+    /// Error at t = 1 of y' = -y when integrated with n steps
+    fn fixed_error(method: Method, n: usize) -> f64 {
+        let y = solve_fixed(decay(), 0., 1., 1. / n as f64, n, method);
+        (y - (-1_f64).exp()).abs()
+    }
+
+    #[test]
+    fn test_solve_fixed_convergence_order() {
+        for (method, order) in [(Method::Euler, 1.), (Method::Rk4, 4.)] {
+            let observed = (fixed_error(method, 20) / fixed_error(method, 40)).log2();
+            approx::assert_abs_diff_eq!(observed, order, epsilon = 0.1);
+        }
+    }
+
+    #[test]
+    fn test_solve_fixed_states() {
+        // harmonic oscillator in different state types
+        let expected = (1_f64.cos(), -1_f64.sin());
+
+        let y = solve_fixed(
+            FnODE(|_, y: [f64; 2]| [y[1], -y[0]]),
+            0.,
+            [1., 0.],
+            0.01,
+            100,
+            Method::Rk4,
+        );
+        approx::assert_abs_diff_eq!(y[0], expected.0, epsilon = 1e-9);
+        approx::assert_abs_diff_eq!(y[1], expected.1, epsilon = 1e-9);
+
+        let y = solve_fixed(
+            FnODE(|_, y: Vector2<f64>| Vector2::new(y[1], -y[0])),
+            0.,
+            Vector2::new(1., 0.),
+            0.01,
+            100,
+            Method::Rk4,
+        );
+        approx::assert_abs_diff_eq!(y[0], expected.0, epsilon = 1e-9);
+
+        let y = solve_fixed(
+            FnODE(|_, y: DVector<f64>| DVector::from_vec(vec![y[1], -y[0]])),
+            0.,
+            DVector::from_vec(vec![1., 0.]),
+            0.01,
+            100,
+            Method::Rk4,
+        );
+        approx::assert_abs_diff_eq!(y[1], expected.1, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn test_solve_adaptive_accuracy() {
+        let result = solve_adaptive(decay(), 0., 1., 5., 1e-9, 1e-12).unwrap();
+        assert_eq!(result.t, 5.);
+        approx::assert_relative_eq!(result.y, (-5_f64).exp(), max_relative = 1e-7);
+
+        // tighter tolerances need more steps
+        let coarse = solve_adaptive(decay(), 0., 1., 5., 1e-4, 1e-6).unwrap();
+        assert!(coarse.steps < result.steps);
+        approx::assert_relative_eq!(coarse.y, (-5_f64).exp(), max_relative = 1e-3);
+    }
+
+    #[test]
+    fn test_solve_adaptive_stiff_decay() {
+        // fast decay towards the equilibrium y = 0
+        let stiff = FnODE(|_, y: f64| -50. * y);
+
+        let transient = solve_adaptive(&stiff, 0., 1., 0.5, 1e-6, 1e-9).unwrap();
+        approx::assert_abs_diff_eq!(transient.y, (-25_f64).exp(), epsilon = 1e-8);
+
+        let settled = solve_adaptive(&stiff, 0.5, transient.y, 0.5, 1e-6, 1e-9).unwrap();
+        assert!(
+            settled.steps < transient.steps,
+            "{} >= {}",
+            settled.steps,
+            transient.steps
+        );
+        assert!(settled.y.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_solve_adaptive_non_finite() {
+        // the derivative is NaN from t = 0.5 on
+        let broken = FnODE(|t, y: f64| if t < 0.5 { -y } else { f64::NAN });
+        let err = solve_adaptive(&broken, 0., 1., 1., 1e-6, 1e-9).unwrap_err();
+        let OdeError::StepSizeTooSmall { t } = err;
+        assert!((0. ..0.5).contains(&t), "{t}");
+
+        // the step before the NaN region is still accurate
+        let result = solve_adaptive(&broken, 0., 1., 0.4, 1e-6, 1e-9).unwrap();
+        approx::assert_abs_diff_eq!(result.y, (-0.4_f64).exp(), epsilon = 1e-6);
+    }
+}
@@ -0,0 +1,185 @@
+/// PID controller with anti-windup
+///
+/// The derivative term acts on the measurement instead of the error so that a step change of the
+/// setpoint does not produce a spike in the output. The integral term is clamped to
+/// `integral_limits` and does not grow while the output is saturated in the direction of the
+/// error.
+#[derive(Debug, Clone)]
+pub struct Pid {
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+
+    /// Minimum and maximum output
+    pub output_limits: (f64, f64),
+
+    /// Minimum and maximum contribution of the integral term to the output
+    pub integral_limits: (f64, f64),
+
+    integral: f64,
+    last_measurement: Option<f64>,
+}
+
+impl Pid {
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        PidBuilder::new(kp, ki, kd).build()
+    }
+
+    pub fn builder(kp: f64, ki: f64, kd: f64) -> PidBuilder {
+        PidBuilder::new(kp, ki, kd)
+    }
+
+    /// Advances the controller by `dt` and returns the control output. Invalid time steps only
+    /// give the proportional and integral terms without updating the controller.
+    pub fn step(&mut self, dt: f64, setpoint: f64, measurement: f64) -> f64 {
+        let error = setpoint - measurement;
+        if !(dt.is_finite() && dt > 0.) {
+            return self.clamp_output(self.kp * error + self.integral);
+        }
+
+        let derivative = match self.last_measurement {
+            Some(last) => -self.kd * (measurement - last) / dt,
+            None => 0.,
+        };
+        self.last_measurement = Some(measurement);
+
+        let previous_integral = self.integral;
+        self.integral = (self.integral + self.ki * error * dt)
+            .clamp(self.integral_limits.0, self.integral_limits.1);
+
+        let unclamped = self.kp * error + self.integral + derivative;
+        let output = self.clamp_output(unclamped);
+
+        // conditional integration: do not wind up while saturated
+        if output != unclamped && (unclamped - output).signum() == error.signum() {
+            self.integral = previous_integral;
+        }
+
+        output
+    }
+
+    /// Forgets the accumulated integral and the last measurement
+    pub fn reset(&mut self) {
+        self.integral = 0.;
+        self.last_measurement = None;
+    }
+
+    /// Current contribution of the integral term to the output
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+
+    fn clamp_output(&self, output: f64) -> f64 {
+        output.clamp(self.output_limits.0, self.output_limits.1)
+    }
+}
+
+/// Creates a [Pid] controller. Limits are unbounded by default.
+#[derive(Debug, Clone)]
+pub struct PidBuilder {
+    kp: f64,
+    ki: f64,
+    kd: f64,
+    output_limits: (f64, f64),
+    integral_limits: (f64, f64),
+}
+
+impl PidBuilder {
+    pub fn new(kp: f64, ki: f64, kd: f64) -> Self {
+        Self {
+            kp,
+            ki,
+            kd,
+            output_limits: (f64::NEG_INFINITY, f64::INFINITY),
+            integral_limits: (f64::NEG_INFINITY, f64::INFINITY),
+        }
+    }
+
+    pub fn with_output_limits(mut self, min: f64, max: f64) -> Self {
+        assert!(min <= max, "PidBuilder: output min must not exceed max");
+        self.output_limits = (min, max);
+        self
+    }
+
+    pub fn with_integral_limits(mut self, min: f64, max: f64) -> Self {
+        assert!(min <= max, "PidBuilder: integral min must not exceed max");
+        self.integral_limits = (min, max);
+        self
+    }
+
+    pub fn build(self) -> Pid {
+        Pid {
+            kp: self.kp,
+            ki: self.ki,
+            kd: self.kd,
+            output_limits: self.output_limits,
+            integral_limits: self.integral_limits,
+            integral: 0.,
+            last_measurement: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    /// Controls the plant x' = u - x - 0.5 towards 1 and returns the final x
+    fn simulate(pid: &mut Pid, steps: usize) -> f64 {
+        let dt = 0.01;
+        let mut x = 0.;
+        for _ in 0..steps {
+            let u = pid.step(dt, 1., x);
+            x += (u - x - 0.5) * dt;
+        }
+        x
+    }
+
+    #[test]
+    fn test_pid_integral_removes_steady_state_error() {
+        let x = simulate(&mut Pid::new(2., 0., 0.), 3000);
+        assert!((1. - x).abs() > 0.1, "{x}");
+
+        let x = simulate(&mut Pid::new(2., 1., 0.), 3000);
+        assert_abs_diff_eq!(x, 1., epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_pid_output_clamping() {
+        let mut pid = Pid::builder(10., 5., 0.)
+            .with_output_limits(-1., 1.)
+            .with_integral_limits(-0.5, 0.5)
+            .build();
+        for _ in 0..100 {
+            let u = pid.step(0.1, 10., 0.);
+            assert!((-1. ..=1.).contains(&u));
+        }
+        // saturated from the first step on so the integral did not wind up
+        assert_eq!(pid.integral(), 0.);
+
+        let mut pid = Pid::builder(0., 5., 0.)
+            .with_integral_limits(-0.5, 0.5)
+            .build();
+        for _ in 0..100 {
+            pid.step(0.1, 10., 0.);
+        }
+        assert_eq!(pid.integral(), 0.5);
+
+        pid.reset();
+        assert_eq!(pid.integral(), 0.);
+    }
+
+    #[test]
+    fn test_pid_no_derivative_kick() {
+        let mut pid = Pid::new(1., 0., 10.);
+        assert_abs_diff_eq!(pid.step(0.1, 0., 0.), 0.);
+        // the setpoint jumps while the measurement stays put
+        assert_abs_diff_eq!(pid.step(0.1, 5., 0.), 5.);
+        // the measurement moves: derivative damps the output
+        assert_abs_diff_eq!(pid.step(0.1, 5., 1.), 4. - 100.);
+
+        assert_abs_diff_eq!(pid.step(0., 5., 1.), 4.);
+        assert_abs_diff_eq!(pid.step(f64::NAN, 5., 1.), 4.);
+    }
+}
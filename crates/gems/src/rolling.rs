@@ -0,0 +1,413 @@
+use std::{cmp::Ordering, collections::VecDeque};
+
+/// Statistics over the samples of the last `window` seconds
+///
+/// Samples are kept in arrival order for eviction and in an order statistic tree for
+/// percentiles. Pushing and evicting a sample and computing a percentile take O(log n) expected
+/// time. A sample pushed at time `t0` is evicted once a sample at time `t0 + window` or later
+/// arrives, i.e. the window covers `(t - window, t]`.
+#[derive(Debug, Clone)]
+pub struct RollingWindow<T> {
+    window: f64,
+    samples: VecDeque<(f64, T)>,
+    sorted: OrderStatistics<T>,
+    sum: f64,
+}
+
+/// Common statistics of a [RollingWindow]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RollingStats<T> {
+    pub count: usize,
+    pub min: T,
+    pub max: T,
+    pub mean: f64,
+    pub median: T,
+    pub p95: T,
+}
+
+impl<T> RollingWindow<T>
+where
+    T: Copy + PartialOrd + Into<f64>,
+{
+    pub fn new(window: f64) -> Self {
+        assert!(window > 0., "RollingWindow::new: window must be positive");
+        Self {
+            window,
+            samples: VecDeque::new(),
+            sorted: OrderStatistics::new(),
+            sum: 0.,
+        }
+    }
+
+    /// Adds a sample at time `t` and evicts samples which left the window. Returns false and
+    /// ignores the sample if the value is NaN, the time is not finite or earlier than the last
+    /// sample.
+    pub fn push(&mut self, t: f64, value: T) -> bool {
+        let x: f64 = value.into();
+        if x.is_nan() || !t.is_finite() || self.samples.back().is_some_and(|(last, _)| t < *last) {
+            return false;
+        }
+
+        self.evict(t);
+        self.samples.push_back((t, value));
+        self.sorted.insert(Key(value));
+        self.sum += x;
+        true
+    }
+
+    /// Evicts samples which are not within the window ending at time `t`
+    pub fn evict(&mut self, t: f64) {
+        while let Some(&(t0, value)) = self.samples.front() {
+            if t0 > t - self.window {
+                break;
+            }
+            self.samples.pop_front();
+            self.sorted.remove(&Key(value));
+            self.sum -= value.into();
+        }
+        if self.samples.is_empty() {
+            // avoid drift from repeated additions and subtractions
+            self.sum = 0.;
+        }
+    }
+
+    pub fn window(&self) -> f64 {
+        self.window
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn min(&self) -> Option<T> {
+        self.sorted.nth(0)
+    }
+
+    pub fn max(&self) -> Option<T> {
+        self.sorted.nth(self.len().checked_sub(1)?)
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (!self.is_empty()).then(|| self.sum / self.len() as f64)
+    }
+
+    /// Nearest-rank percentile for `p` in [0, 1], e.g. 0.5 for the median
+    pub fn percentile(&self, p: f64) -> Option<T> {
+        if self.is_empty() || p.is_nan() {
+            return None;
+        }
+        let rank = ((p.clamp(0., 1.) * self.len() as f64).ceil() as usize).clamp(1, self.len());
+        self.sorted.nth(rank - 1)
+    }
+
+    pub fn stats(&self) -> Option<RollingStats<T>> {
+        Some(RollingStats {
+            count: self.len(),
+            min: self.min()?,
+            max: self.max()?,
+            mean: self.mean()?,
+            median: self.percentile(0.5)?,
+            p95: self.percentile(0.95)?,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.sorted.clear();
+        self.sum = 0.;
+    }
+}
+
+/// Multiset of values with lookup by rank, implemented as a treap
+///
+/// Every node holds one distinct value with its multiplicity and the number of values in its
+/// subtree. Nodes are stored in an arena and reused after removal.
+#[derive(Debug, Clone)]
+struct OrderStatistics<T> {
+    nodes: Vec<Node<T>>,
+    free: Vec<usize>,
+    root: Option<usize>,
+
+    /// State of the xorshift generator for node priorities
+    seed: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Node<T> {
+    key: Key<T>,
+    count: usize,
+    size: usize,
+    priority: u64,
+    left: Option<usize>,
+    right: Option<usize>,
+}
+
+impl<T: Copy + PartialOrd> OrderStatistics<T> {
+    fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            seed: 0x9e37_79b9_7f4a_7c15,
+        }
+    }
+
+    fn insert(&mut self, key: Key<T>) {
+        self.root = Some(self.insert_at(self.root, key));
+    }
+
+    /// Removes one occurrence of the key if present
+    fn remove(&mut self, key: &Key<T>) {
+        self.root = self.remove_at(self.root, key);
+    }
+
+    /// Value with the given 0-based rank
+    fn nth(&self, mut rank: usize) -> Option<T> {
+        let mut current = self.root;
+        while let Some(ix) = current {
+            let node = &self.nodes[ix];
+            let left = self.size(node.left);
+            if rank < left {
+                current = node.left;
+            } else if rank < left + node.count {
+                return Some(node.key.0);
+            } else {
+                rank -= left + node.count;
+                current = node.right;
+            }
+        }
+        None
+    }
+
+    fn clear(&mut self) {
+        self.nodes.clear();
+        self.free.clear();
+        self.root = None;
+    }
+
+    fn size(&self, node: Option<usize>) -> usize {
+        node.map_or(0, |ix| self.nodes[ix].size)
+    }
+
+    fn update(&mut self, ix: usize) {
+        let node = &self.nodes[ix];
+        self.nodes[ix].size = node.count + self.size(node.left) + self.size(node.right);
+    }
+
+    fn next_priority(&mut self) -> u64 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 7;
+        self.seed ^= self.seed << 17;
+        self.seed
+    }
+
+    fn insert_at(&mut self, node: Option<usize>, key: Key<T>) -> usize {
+        let Some(ix) = node else {
+            let node = Node {
+                key,
+                count: 1,
+                size: 1,
+                priority: self.next_priority(),
+                left: None,
+                right: None,
+            };
+            return match self.free.pop() {
+                Some(ix) => {
+                    self.nodes[ix] = node;
+                    ix
+                }
+                None => {
+                    self.nodes.push(node);
+                    self.nodes.len() - 1
+                }
+            };
+        };
+
+        let ix = match key.cmp(&self.nodes[ix].key) {
+            Ordering::Equal => {
+                self.nodes[ix].count += 1;
+                ix
+            }
+            Ordering::Less => {
+                let child = self.insert_at(self.nodes[ix].left, key);
+                self.nodes[ix].left = Some(child);
+                if self.nodes[child].priority > self.nodes[ix].priority {
+                    self.rotate_right(ix)
+                } else {
+                    ix
+                }
+            }
+            Ordering::Greater => {
+                let child = self.insert_at(self.nodes[ix].right, key);
+                self.nodes[ix].right = Some(child);
+                if self.nodes[child].priority > self.nodes[ix].priority {
+                    self.rotate_left(ix)
+                } else {
+                    ix
+                }
+            }
+        };
+        self.update(ix);
+        ix
+    }
+
+    fn remove_at(&mut self, node: Option<usize>, key: &Key<T>) -> Option<usize> {
+        let ix = node?;
+        match key.cmp(&self.nodes[ix].key) {
+            Ordering::Less => self.nodes[ix].left = self.remove_at(self.nodes[ix].left, key),
+            Ordering::Greater => self.nodes[ix].right = self.remove_at(self.nodes[ix].right, key),
+            Ordering::Equal if self.nodes[ix].count > 1 => self.nodes[ix].count -= 1,
+            Ordering::Equal => {
+                self.free.push(ix);
+                return self.merge(self.nodes[ix].left, self.nodes[ix].right);
+            }
+        }
+        self.update(ix);
+        Some(ix)
+    }
+
+    /// Joins two treaps where all keys of `a` are smaller than the keys of `b`
+    fn merge(&mut self, a: Option<usize>, b: Option<usize>) -> Option<usize> {
+        let (Some(a), Some(b)) = (a, b) else {
+            return a.or(b);
+        };
+        let root = if self.nodes[a].priority > self.nodes[b].priority {
+            self.nodes[a].right = self.merge(self.nodes[a].right, Some(b));
+            a
+        } else {
+            self.nodes[b].left = self.merge(Some(a), self.nodes[b].left);
+            b
+        };
+        self.update(root);
+        Some(root)
+    }
+
+    fn rotate_right(&mut self, ix: usize) -> usize {
+        let left = self.nodes[ix].left.unwrap();
+        self.nodes[ix].left = self.nodes[left].right;
+        self.nodes[left].right = Some(ix);
+        self.update(ix);
+        left
+    }
+
+    fn rotate_left(&mut self, ix: usize) -> usize {
+        let right = self.nodes[ix].right.unwrap();
+        self.nodes[ix].right = self.nodes[right].left;
+        self.nodes[right].left = Some(ix);
+        self.update(ix);
+        right
+    }
+}
+
+/// Total order for values which are known not to be NaN
+#[derive(Debug, Clone, Copy)]
+struct Key<T>(T);
+
+impl<T: PartialOrd> PartialEq for Key<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: PartialOrd> Eq for Key<T> {}
+
+impl<T: PartialOrd> PartialOrd for Key<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: PartialOrd> Ord for Key<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.partial_cmp(&other.0).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_window_eviction_at_boundary() {
+        let mut window = RollingWindow::new(1.0);
+        assert!(window.push(0.0, 5.0));
+        assert!(window.push(0.5, 1.0));
+        assert_eq!(window.len(), 2);
+
+        // exactly one window later the first sample is evicted
+        assert!(window.push(1.0, 3.0));
+        assert_eq!(window.len(), 2);
+        assert_eq!(window.min(), Some(1.0));
+        assert_eq!(window.max(), Some(3.0));
+        assert_eq!(window.mean(), Some(2.0));
+
+        window.evict(1.5);
+        assert_eq!(window.len(), 1);
+        window.evict(10.0);
+        assert!(window.is_empty());
+        assert_eq!(window.stats(), None);
+    }
+
+    #[test]
+    fn test_rolling_window_rejects_invalid_samples() {
+        let mut window = RollingWindow::new(1.0);
+        assert!(!window.push(0.0, f64::NAN));
+        assert!(!window.push(f64::NAN, 1.0));
+        assert!(window.push(0.5, 1.0));
+        // time must not go backwards
+        assert!(!window.push(0.4, 2.0));
+        assert_eq!(window.len(), 1);
+        assert_eq!(window.max(), Some(1.0));
+    }
+
+    #[test]
+    fn test_rolling_window_percentile() {
+        let mut window = RollingWindow::new(100.0);
+        for (i, value) in [3_u32, 1, 4, 1, 5, 9, 2, 6, 5, 3].into_iter().enumerate() {
+            window.push(i as f64, value);
+        }
+        assert_eq!(window.percentile(0.0), Some(1));
+        assert_eq!(window.percentile(0.5), Some(3));
+        assert_eq!(window.percentile(0.9), Some(6));
+        assert_eq!(window.percentile(1.0), Some(9));
+
+        let stats = window.stats().unwrap();
+        assert_eq!(stats.count, 10);
+        assert_eq!(
+            (stats.min, stats.max, stats.median, stats.p95),
+            (1, 9, 3, 9)
+        );
+        assert_eq!(stats.mean, 3.9);
+    }
+
+    #[test]
+    fn test_rolling_window_percentile_matches_sorted() {
+        // pseudo random values with duplicates, compared against a sorted copy of the window
+        let mut window = RollingWindow::new(50.0);
+        let mut x = 7_u32;
+        for i in 0..500 {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345) % 1000;
+            window.push(i as f64, x % 37);
+
+            let mut sorted: Vec<u32> = window.samples.iter().map(|(_, v)| *v).collect();
+            sorted.sort();
+            for p in [0.0, 0.1, 0.5, 0.95, 1.0] {
+                let rank = ((p * sorted.len() as f64).ceil() as usize).max(1);
+                assert_eq!(window.percentile(p), Some(sorted[rank - 1]));
+            }
+            assert_eq!(window.min(), sorted.first().copied());
+            assert_eq!(window.max(), sorted.last().copied());
+        }
+        assert_eq!(window.len(), 50);
+        // removed nodes are reused
+        assert!(
+            window.sorted.nodes.len() <= 60,
+            "{}",
+            window.sorted.nodes.len()
+        );
+    }
+}
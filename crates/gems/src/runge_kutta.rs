@@ -87,7 +87,8 @@ pub trait ODE<D> {
 //     Ralston,
 // }
 
-pub struct FnODE<F>(F);
+/// ODE given by a closure `|t, y| y'`
+pub struct FnODE<F>(pub F);
 
 impl<D, F> ODE<D> for FnODE<F>
 where
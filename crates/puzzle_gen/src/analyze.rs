@@ -0,0 +1,347 @@
+use crate::{EntityId, GateId, Puzzle, PuzzleSpec, RoomId, TargetSpec, solve::StateSpace};
+use petgraph::visit::EdgeRef;
+use std::fmt;
+
+/// Maximum number of states expanded by [Puzzle::analyze]
+pub const ANALYSIS_MAX_NODES: usize = 10000;
+
+/// Findings about rooms and entities which do not contribute to a puzzle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PuzzleAnalysis {
+    /// Number of reachable states or None if the node budget was exceeded. All other findings are
+    /// empty in that case.
+    pub state_count: Option<usize>,
+
+    pub solvable: bool,
+
+    /// Rooms which the player never enters
+    pub unreachable_rooms: Vec<RoomId>,
+
+    /// Entities which are either always or never active
+    pub constant_entities: Vec<EntityId>,
+
+    /// Gates which are never open while the player stands next to them
+    pub untraversed_gates: Vec<GateId>,
+
+    /// Entities which can be removed and the puzzle is still solvable
+    pub removable_entities: Vec<EntityId>,
+
+    /// Number of states in which a gate closed behind the player and the puzzle can no longer be
+    /// solved
+    pub trapped_states: usize,
+}
+
+impl PuzzleAnalysis {
+    /// True if nothing suspicious was found
+    pub fn is_clean(&self) -> bool {
+        self.state_count.is_some()
+            && self.solvable
+            && self.unreachable_rooms.is_empty()
+            && self.constant_entities.is_empty()
+            && self.untraversed_gates.is_empty()
+            && self.removable_entities.is_empty()
+            && self.trapped_states == 0
+    }
+}
+
+impl Puzzle {
+    /// Expands all reachable states and reports unreachable rooms and useless entities
+    pub fn analyze(&self) -> PuzzleAnalysis {
+        self.analyze_bounded(ANALYSIS_MAX_NODES)
+    }
+
+    /// Like [Puzzle::analyze] with a custom node budget
+    pub fn analyze_bounded(&self, max_nodes: usize) -> PuzzleAnalysis {
+        let Some(space) = StateSpace::explore(self, max_nodes) else {
+            return PuzzleAnalysis {
+                state_count: None,
+                solvable: false,
+                unreachable_rooms: Vec::new(),
+                constant_entities: Vec::new(),
+                untraversed_gates: Vec::new(),
+                removable_entities: Vec::new(),
+                trapped_states: 0,
+            };
+        };
+
+        let g = &self.room_graph;
+
+        let mut visited = vec![false; g.node_count()];
+        let mut traversed = vec![false; g.edge_count()];
+        for (ix, state) in space.states.iter().enumerate() {
+            visited[state.player_room.index()] = true;
+
+            // States in the win room are not expanded
+            if space.is_win(self, ix) {
+                continue;
+            }
+            for edge in g.edges(*state.player_room) {
+                if state.entities[*edge.weight().entity].is_active
+                    && self.gate_passable_from(GateId(edge.id()), state.player_room)
+                {
+                    traversed[edge.id().index()] = true;
+                }
+            }
+        }
+
+        let can_win = space.can_win(self);
+        let trapped_states = space
+            .states
+            .iter()
+            .zip(&can_win)
+            .filter(|(state, can_win)| {
+                !**can_win
+                    && g.edges(*state.player_room).any(|edge| {
+                        edge.weight().closes_behind
+                            && !state.entities[*edge.weight().entity].is_active
+                    })
+            })
+            .count();
+
+        let first = &space.states[0];
+        let constant_entities = (0..self.entities.len())
+            .filter(|&i| {
+                space
+                    .states
+                    .iter()
+                    .all(|state| state.entities[i].is_active == first.entities[i].is_active)
+            })
+            .map(EntityId)
+            .collect();
+
+        // Each reduced puzzle gets the same budget. Entities whose reduced puzzle exceeds it are
+        // not reported.
+        let spec = self.to_spec();
+        let removable_entities = (0..self.entities.len())
+            .map(EntityId)
+            .filter(|&entity| {
+                Puzzle::from_spec(&spec_without_entity(&spec, entity)).is_ok_and(|reduced| {
+                    StateSpace::explore(&reduced, max_nodes)
+                        .is_some_and(|space| space.solution_depth(&reduced).is_some())
+                })
+            })
+            .collect();
+
+        PuzzleAnalysis {
+            state_count: Some(space.states.len()),
+            solvable: space.solution_depth(self).is_some(),
+            unreachable_rooms: g
+                .node_indices()
+                .filter(|room| !visited[room.index()])
+                .map(RoomId)
+                .collect(),
+            constant_entities,
+            untraversed_gates: g
+                .edge_indices()
+                .filter(|edge| !traversed[edge.index()])
+                .map(GateId)
+                .collect(),
+            removable_entities,
+            trapped_states,
+        }
+    }
+}
+
+/// Removes an entity together with the gates it controls and all references to it
+fn spec_without_entity(spec: &PuzzleSpec, entity: EntityId) -> PuzzleSpec {
+    let name = &spec.entities[*entity].name;
+
+    let mut out = spec.clone();
+    out.reference_solution = None;
+    out.entities.remove(*entity);
+    out.gates.retain(|gate| &gate.entity != name);
+    for other in &mut out.entities {
+        match &mut other.target {
+            TargetSpec::None => {}
+            TargetSpec::Fixed(target) => {
+                if target == name {
+                    other.target = TargetSpec::None;
+                }
+            }
+            TargetSpec::Changable(targets) => targets.retain(|target| target != name),
+        }
+    }
+    out
+}
+
+impl fmt::Display for PuzzleAnalysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn write_list<T: fmt::Display>(
+            f: &mut fmt::Formatter<'_>,
+            label: &str,
+            items: &[T],
+        ) -> fmt::Result {
+            write!(f, "{label}:")?;
+            if items.is_empty() {
+                write!(f, " -")?;
+            }
+            for item in items {
+                write!(f, " {item}")?;
+            }
+            writeln!(f)
+        }
+
+        let Some(state_count) = self.state_count else {
+            return writeln!(f, "Analysis aborted due to maximum number of nodes reached");
+        };
+
+        writeln!(f, "Reachable states: {state_count}")?;
+        writeln!(f, "Solvable: {}", if self.solvable { "yes" } else { "no" })?;
+        write_list(f, "Unreachable rooms", &self.unreachable_rooms)?;
+        write_list(f, "Constant entities", &self.constant_entities)?;
+        write_list(f, "Untraversed gates", &self.untraversed_gates)?;
+        write_list(f, "Removable entities", &self.removable_entities)?;
+        writeln!(f, "Trapped states: {}", self.trapped_states)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Action, Entity, Gate, Power, PowerCondition, PowerKind,
+        levels::{all_levels, laser, level_4, puzzle_basis, rift_switch},
+    };
+    use petgraph::Direction;
+
+    #[test]
+    fn test_analyze_level_4() {
+        let analysis = level_4().analyze();
+        assert!(analysis.solvable);
+        assert_eq!(analysis.unreachable_rooms, vec![]);
+        assert_eq!(analysis.untraversed_gates, vec![]);
+
+        // lasers are always on
+        assert_eq!(analysis.constant_entities, vec![EntityId(4), EntityId(5)]);
+        assert_eq!(analysis.trapped_states, 0);
+    }
+
+    #[test]
+    fn test_level_4_player_can_always_retreat() {
+        let puzzle = level_4();
+        let main = puzzle.room_id_by_name("main").unwrap();
+        let room_2 = puzzle.room_id_by_name("room_2").unwrap();
+        let space = StateSpace::explore(&puzzle, ANALYSIS_MAX_NODES).unwrap();
+
+        for (ix, state) in space.states.iter().enumerate() {
+            if state.player_room() != room_2 {
+                continue;
+            }
+
+            let mut open = vec![ix];
+            let mut seen = vec![false; space.states.len()];
+            let mut retreated = false;
+            while let Some(current) = open.pop() {
+                if space.states[current].player_room() == main {
+                    retreated = true;
+                    break;
+                }
+                for &next in &space.successors[current] {
+                    if !seen[next] {
+                        seen[next] = true;
+                        open.push(next);
+                    }
+                }
+            }
+            assert!(retreated, "trapped in {state}");
+        }
+    }
+
+    #[test]
+    fn test_analyze_trapped_behind_gate() {
+        let mut builder = puzzle_basis("trap", 0);
+        let main = builder.room_id_by_name("main").unwrap();
+        let cell = builder.add_room("cell");
+        // [2] Door which the player holds open from the main room. Player power can be withdrawn
+        // from anywhere which closes the door behind the player.
+        let door = builder.add_entity(
+            Some(main),
+            Entity {
+                condition: PowerCondition::Power {
+                    latch: false,
+                    power: Power::one(PowerKind::Player),
+                },
+                ..Default::default()
+            },
+        );
+        builder.add_gate(
+            main,
+            cell,
+            Gate {
+                entity: door,
+                closes_behind: true,
+                ..door.into()
+            },
+        );
+
+        let analysis = builder.clone().build().unwrap().analyze();
+        assert!(analysis.solvable);
+        assert!(analysis.trapped_states > 0);
+
+        // a one-way exit out of the cell keeps the puzzle solvable from everywhere
+        let exit = builder.add_entity(
+            None,
+            Entity {
+                condition: PowerCondition::Always,
+                ..Default::default()
+            },
+        );
+        builder.add_gate(
+            cell,
+            main,
+            Gate {
+                entity: exit,
+                one_way: Some(Direction::Outgoing),
+                ..exit.into()
+            },
+        );
+        let puzzle = builder.build().unwrap();
+        let analysis = puzzle.analyze();
+        assert!(analysis.solvable);
+        assert_eq!(analysis.trapped_states, 0);
+
+        // the exit cannot be used to enter the cell
+        let start = puzzle.initalize();
+        assert!(
+            puzzle
+                .actions(&start)
+                .iter()
+                .all(|action| !matches!(action, Action::MovePlayer { room } if *room == cell))
+        );
+    }
+
+    #[test]
+    fn test_analyze_finds_useless_parts() {
+        let mut builder = puzzle_basis("useless", 1);
+        let main = builder.room_id_by_name("main").unwrap();
+        // [2] Rift Switch
+        builder.add_entity(Some(main), rift_switch());
+        // [3] Laser
+        builder.add_entity(Some(main), laser(vec![EntityId(2)]));
+        // [4] Second laser which is not needed
+        builder.add_entity(Some(main), laser(vec![EntityId(2)]));
+        // [5] Gate which never opens
+        let closet = builder.add_room("closet");
+        let gate = builder.add_gate(main, closet, EntityId(5));
+        builder.add_entity(None, Default::default());
+
+        let analysis = builder.build().unwrap().analyze();
+        assert!(analysis.solvable);
+        assert_eq!(analysis.unreachable_rooms, vec![closet]);
+        assert_eq!(analysis.untraversed_gates, vec![gate]);
+        assert_eq!(
+            analysis.removable_entities,
+            vec![EntityId(3), EntityId(4), EntityId(5)]
+        );
+        assert!(!analysis.is_clean());
+    }
+
+    #[test]
+    fn test_analyze_levels_are_solvable() {
+        for puzzle in all_levels() {
+            let analysis = puzzle.analyze();
+            assert!(analysis.solvable, "{}", puzzle.name());
+            assert!(analysis.unreachable_rooms.is_empty(), "{}", puzzle.name());
+        }
+    }
+}
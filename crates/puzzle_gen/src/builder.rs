@@ -0,0 +1,268 @@
+use crate::{
+    Action, Effect, Entity, EntityId, Gate, GateId, Puzzle, PuzzleState, Room, RoomGraph, RoomId,
+    TargetKind,
+};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    fmt,
+};
+
+/// Constructs a [Puzzle] room by room and entity by entity
+///
+/// Entities are identified by the order in which they are added. Gates and targets may refer to
+/// entities which are added later on. All references are checked by [PuzzleBuilder::build].
+#[derive(Debug, Clone)]
+pub struct PuzzleBuilder {
+    name: String,
+    rooms_by_name: HashMap<String, RoomId>,
+    entities: Vec<Entity>,
+    room_graph: RoomGraph,
+    win_room: Option<RoomId>,
+    player_start: Option<RoomId>,
+    archetypes: HashMap<String, Entity>,
+    reference_solution: Option<Vec<Action>>,
+
+    /// First room name which was used twice
+    duplicate_room_name: Option<String>,
+
+    /// First archetype which was spawned without being registered
+    unknown_archetype: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleBuildError {
+    DuplicateRoomName(String),
+    UnknownArchetype(String),
+    MissingWinRoom,
+    MissingPlayerStart,
+
+    /// An entity targets an entity which does not exist
+    DanglingTarget {
+        entity: EntityId,
+        target: EntityId,
+    },
+
+    /// A gate is controlled by an entity which does not exist
+    MissingGateEntity {
+        gate: GateId,
+        entity: EntityId,
+    },
+
+    /// An entity provides power to an entity which is not activated by power
+    UnpoweredTarget {
+        entity: EntityId,
+        target: EntityId,
+    },
+
+    /// The traversal cost of a gate is not a positive number
+    InvalidTraversalCost(GateId),
+}
+
+impl fmt::Display for PuzzleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PuzzleBuildError::DuplicateRoomName(name) => write!(f, "duplicate room name '{name}'"),
+            PuzzleBuildError::UnknownArchetype(name) => write!(f, "unknown archetype '{name}'"),
+            PuzzleBuildError::MissingWinRoom => write!(f, "win room not set"),
+            PuzzleBuildError::MissingPlayerStart => write!(f, "player start room not set"),
+            PuzzleBuildError::DanglingTarget { entity, target } => {
+                write!(f, "{entity} targets missing entity {target}")
+            }
+            PuzzleBuildError::MissingGateEntity { gate, entity } => {
+                write!(f, "{gate} is controlled by missing entity {entity}")
+            }
+            PuzzleBuildError::UnpoweredTarget { entity, target } => {
+                write!(
+                    f,
+                    "{entity} provides power to {target} which cannot be powered"
+                )
+            }
+            PuzzleBuildError::InvalidTraversalCost(gate) => {
+                write!(f, "traversal cost of {gate} is not a positive number")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PuzzleBuildError {}
+
+impl PuzzleBuilder {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            rooms_by_name: HashMap::new(),
+            entities: Vec::new(),
+            room_graph: RoomGraph::new_undirected(),
+            win_room: None,
+            player_start: None,
+            archetypes: HashMap::new(),
+            reference_solution: None,
+            duplicate_room_name: None,
+            unknown_archetype: None,
+        }
+    }
+
+    /// Adds an empty room. Room names must be unique.
+    pub fn add_room(&mut self, name: impl Into<String>) -> RoomId {
+        let id = RoomId(self.room_graph.add_node(Room::default()));
+        let name = name.into();
+        match self.rooms_by_name.entry(name) {
+            Entry::Occupied(entry) => {
+                // reported by build to keep adding rooms infallible
+                self.duplicate_room_name.get_or_insert(entry.key().clone());
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(id);
+            }
+        }
+        id
+    }
+
+    /// Connects two rooms with a gate which is open while the gate entity is active
+    pub fn add_gate(&mut self, room_1: RoomId, room_2: RoomId, gate: impl Into<Gate>) -> GateId {
+        GateId(self.room_graph.add_edge(*room_1, *room_2, gate.into()))
+    }
+
+    /// Adds an entity and optionally places it in a room so that the player can interact with it
+    pub fn add_entity(&mut self, room: Option<RoomId>, entity: Entity) -> EntityId {
+        let id = EntityId(self.entities.len());
+        self.entities.push(entity);
+        if let Some(room) = room {
+            self.room_graph[*room].entities.push(id);
+        }
+        id
+    }
+
+    /// Registers an entity under an archetype name so that it can be added with
+    /// [PuzzleBuilder::spawn]. Registering a name again replaces the previous entity.
+    pub fn add_archetype(&mut self, name: impl Into<String>, mut entity: Entity) {
+        let name = name.into();
+        entity.archetype = Some(name.clone());
+        self.archetypes.insert(name, entity);
+    }
+
+    pub fn archetype(&self, name: &str) -> Option<&Entity> {
+        self.archetypes.get(name)
+    }
+
+    /// Adds a copy of a registered archetype like [PuzzleBuilder::add_entity]
+    pub fn spawn(&mut self, room: Option<RoomId>, name: &str) -> EntityId {
+        let entity = match self.archetypes.get(name) {
+            Some(entity) => entity.clone(),
+            None => {
+                // reported by build to keep spawning infallible
+                self.unknown_archetype.get_or_insert(name.into());
+                Entity::default()
+            }
+        };
+        self.add_entity(room, entity)
+    }
+
+    pub fn set_win_room(&mut self, room: RoomId) {
+        self.win_room = Some(room);
+    }
+
+    pub fn set_player_start(&mut self, room: RoomId) {
+        self.player_start = Some(room);
+    }
+
+    /// Sets the solution intended by the designer. Minimal solutions which use other actions are
+    /// reported as shortcuts.
+    pub fn set_reference_solution(&mut self, actions: Vec<Action>) {
+        self.reference_solution = Some(actions);
+    }
+
+    pub fn room_id_by_name(&self, name: &str) -> Option<RoomId> {
+        self.rooms_by_name.get(name).cloned()
+    }
+
+    pub fn build(mut self) -> Result<Puzzle, PuzzleBuildError> {
+        if let Some(name) = self.duplicate_room_name {
+            return Err(PuzzleBuildError::DuplicateRoomName(name));
+        }
+        if let Some(name) = self.unknown_archetype {
+            return Err(PuzzleBuildError::UnknownArchetype(name));
+        }
+        let win_room = self.win_room.ok_or(PuzzleBuildError::MissingWinRoom)?;
+        let player_start = self
+            .player_start
+            .ok_or(PuzzleBuildError::MissingPlayerStart)?;
+
+        for (i, entity) in self.entities.iter().enumerate() {
+            let entity_id = EntityId(i);
+            let targets = match &entity.target {
+                TargetKind::None => &[][..],
+                TargetKind::Fixed(target) => std::slice::from_ref(target),
+                TargetKind::Changable(targets) => &targets[..],
+            };
+            for &target in targets {
+                let Some(target_entity) = self.entities.get(*target) else {
+                    return Err(PuzzleBuildError::DanglingTarget {
+                        entity: entity_id,
+                        target,
+                    });
+                };
+                if matches!(entity.effect, Some(Effect::ProvidePower(_)))
+                    && !target_entity.condition.accepts_power()
+                {
+                    return Err(PuzzleBuildError::UnpoweredTarget {
+                        entity: entity_id,
+                        target,
+                    });
+                }
+            }
+        }
+
+        for edge in self.room_graph.edge_indices() {
+            let gate = &self.room_graph[edge];
+            if *gate.entity >= self.entities.len() {
+                return Err(PuzzleBuildError::MissingGateEntity {
+                    gate: GateId(edge),
+                    entity: gate.entity,
+                });
+            }
+            if !(gate.traversal_cost.is_finite() && gate.traversal_cost > 0.) {
+                return Err(PuzzleBuildError::InvalidTraversalCost(GateId(edge)));
+            }
+        }
+
+        assign_display_names(&mut self.entities);
+
+        let initial_state = PuzzleState::new(player_start, self.entities.len());
+
+        Ok(Puzzle {
+            name: self.name,
+            rooms_by_name: self.rooms_by_name,
+            entities: self.entities,
+            room_graph: self.room_graph,
+            win_room,
+            initial_state,
+            reference_solution: self.reference_solution,
+        })
+    }
+}
+
+/// Names unnamed entities after their archetype. Archetypes which occur more than once are
+/// numbered in the order of the entities, e.g. `Rift Switch 1` and `Rift Switch 2`.
+fn assign_display_names(entities: &mut [Entity]) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for archetype in entities.iter().filter_map(|e| e.archetype.as_ref()) {
+        *counts.entry(archetype.clone()).or_default() += 1;
+    }
+
+    let mut numbers: HashMap<String, usize> = HashMap::new();
+    for entity in entities {
+        let Some(archetype) = &entity.archetype else {
+            continue;
+        };
+        let number = numbers.entry(archetype.clone()).or_default();
+        *number += 1;
+        if entity.display_name.is_none() {
+            entity.display_name = Some(if counts[archetype] > 1 {
+                format!("{archetype} {number}")
+            } else {
+                archetype.clone()
+            });
+        }
+    }
+}
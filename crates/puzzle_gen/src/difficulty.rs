@@ -0,0 +1,167 @@
+use crate::{
+    ANALYSIS_MAX_NODES, Puzzle,
+    solve::{ActionCostModel, StateSpace, astar},
+};
+use std::fmt;
+
+/// Weights of the metrics which are combined into [DifficultyScore::total]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyWeights {
+    /// Per action of the shortest solution
+    pub solution_depth: f64,
+
+    /// Per halving of the fraction of reachable states which are in the win room
+    pub win_rarity: f64,
+
+    /// Per entity which changes its state along the optimal solution
+    pub changed_entities: f64,
+
+    /// Per average number of actions available in a state
+    pub mean_branching: f64,
+
+    /// Per latched entity as latches make actions irreversible
+    pub latched_entities: f64,
+}
+
+impl Default for DifficultyWeights {
+    fn default() -> Self {
+        Self {
+            solution_depth: 1.0,
+            win_rarity: 0.5,
+            changed_entities: 1.0,
+            mean_branching: 0.5,
+            latched_entities: 0.5,
+        }
+    }
+}
+
+/// Metrics of a puzzle and their weighted sum
+#[derive(Debug, Clone, PartialEq)]
+pub struct DifficultyScore {
+    /// Weighted sum of the metrics. Infinite if the puzzle cannot be solved or has too many states
+    /// to be expanded.
+    pub total: f64,
+
+    /// Number of actions of the shortest solution
+    pub solution_depth: Option<usize>,
+
+    /// Fraction of reachable states which are in the win room
+    pub win_ratio: f64,
+
+    /// Number of entities which change their state along the optimal solution
+    pub changed_entities: usize,
+
+    /// Average number of actions over all states which are not in the win room
+    pub mean_branching: f64,
+
+    pub max_branching: usize,
+
+    pub latched_entities: usize,
+}
+
+impl Puzzle {
+    /// Ranks the puzzle by metrics of its state space. The same puzzle always gives the same
+    /// score.
+    pub fn difficulty(&self, weights: &DifficultyWeights) -> DifficultyScore {
+        let latched_entities = self
+            .entities
+            .iter()
+            .filter(|entity| entity.condition.has_latch())
+            .count();
+
+        let mut score = DifficultyScore {
+            total: f64::INFINITY,
+            solution_depth: None,
+            win_ratio: 0.,
+            changed_entities: 0,
+            mean_branching: 0.,
+            max_branching: 0,
+            latched_entities,
+        };
+
+        let Some(space) = StateSpace::explore(self, ANALYSIS_MAX_NODES) else {
+            return score;
+        };
+
+        let wins = (0..space.states.len())
+            .filter(|&ix| space.is_win(self, ix))
+            .count();
+        score.win_ratio = wins as f64 / space.states.len() as f64;
+
+        let branching: Vec<usize> = (0..space.states.len())
+            .filter(|&ix| !space.is_win(self, ix))
+            .map(|ix| space.successors[ix].len())
+            .collect();
+        if !branching.is_empty() {
+            score.mean_branching = branching.iter().sum::<usize>() as f64 / branching.len() as f64;
+            score.max_branching = branching.iter().copied().max().unwrap_or(0);
+        }
+
+        score.solution_depth = space.solution_depth(self);
+        let Some(solution_depth) = score.solution_depth else {
+            return score;
+        };
+
+        if let Some(solution) = astar(self, &ActionCostModel::default()) {
+            let initial = self.initalize();
+            let mut changed = vec![false; self.entities.len()];
+            let mut state = initial.clone();
+            for action in &solution.actions {
+                state.apply(self, action);
+                for (i, (now, before)) in state.entities.iter().zip(&initial.entities).enumerate() {
+                    if now.is_active != before.is_active || now.target != before.target {
+                        changed[i] = true;
+                    }
+                }
+            }
+            score.changed_entities = changed.iter().filter(|&&c| c).count();
+        }
+
+        score.total = weights.solution_depth * solution_depth as f64
+            - weights.win_rarity * score.win_ratio.log2()
+            + weights.changed_entities * score.changed_entities as f64
+            + weights.mean_branching * score.mean_branching
+            + weights.latched_entities * score.latched_entities as f64;
+
+        score
+    }
+}
+
+impl fmt::Display for DifficultyScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.2} (solution actions:", self.total)?;
+        match self.solution_depth {
+            Some(depth) => write!(f, "{depth}")?,
+            None => write!(f, "-")?,
+        }
+        write!(
+            f,
+            ", win ratio:{:.3}, changed:{}, branching:{:.2}/{}, latched:{})",
+            self.win_ratio,
+            self.changed_entities,
+            self.mean_branching,
+            self.max_branching,
+            self.latched_entities
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::{level_1, level_2, level_5};
+
+    #[test]
+    fn test_difficulty_ranks_levels() {
+        let weights = DifficultyWeights::default();
+        let easy = level_1().difficulty(&weights);
+        let medium = level_2().difficulty(&weights);
+        let hard = level_5().difficulty(&weights);
+
+        assert!(easy.total < medium.total, "{easy} >= {medium}");
+        assert!(medium.total < hard.total, "{medium} >= {hard}");
+
+        // scores are reproducible
+        assert_eq!(level_5().difficulty(&weights), hard);
+    }
+}
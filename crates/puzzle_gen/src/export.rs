@@ -0,0 +1,142 @@
+//! Export of state graphs for external viewers
+//!
+//! Graphviz DOT files can be rendered with `dot -Tsvg`. GraphML files can be opened with yEd or
+//! Gephi. Winning states are drawn green and truncated states dashed.
+
+use crate::solve::StateGraph;
+use petgraph::visit::EdgeRef;
+use std::{fmt::Write, fs, io, path::Path};
+
+/// Writes the graph in Graphviz DOT format
+pub fn write_dot(graph: &StateGraph, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, to_dot(graph))
+}
+
+/// Writes the graph in GraphML format
+pub fn write_graphml(graph: &StateGraph, path: impl AsRef<Path>) -> io::Result<()> {
+    fs::write(path, to_graphml(graph))
+}
+
+pub fn to_dot(graph: &StateGraph) -> String {
+    let g = &graph.graph;
+    let mut out = String::new();
+
+    writeln!(out, "digraph states {{").unwrap();
+    writeln!(out, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+    for ix in g.node_indices() {
+        let label = dot_escape(&g[ix].fmt_compact().to_string());
+        let style = if graph.is_win(ix) {
+            ", style=filled, fillcolor=palegreen"
+        } else if graph.is_truncated(ix) {
+            ", style=dashed"
+        } else {
+            ""
+        };
+        writeln!(out, "  {} [label=\"{label}\"{style}];", ix.index()).unwrap();
+    }
+    for edge in g.edge_references() {
+        writeln!(
+            out,
+            "  {} -> {} [label=\"{}\"];",
+            edge.source().index(),
+            edge.target().index(),
+            dot_escape(&edge.weight().to_string())
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+
+    out
+}
+
+pub fn to_graphml(graph: &StateGraph) -> String {
+    let g = &graph.graph;
+    let mut out = String::new();
+
+    writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        out,
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+    )
+    .unwrap();
+    for (id, domain, ty) in [
+        ("label", "node", "string"),
+        ("win", "node", "boolean"),
+        ("truncated", "node", "boolean"),
+        ("action", "edge", "string"),
+    ] {
+        writeln!(
+            out,
+            r#"  <key id="{id}" for="{domain}" attr.name="{id}" attr.type="{ty}"/>"#
+        )
+        .unwrap();
+    }
+    writeln!(out, r#"  <graph id="states" edgedefault="directed">"#).unwrap();
+    for ix in g.node_indices() {
+        writeln!(
+            out,
+            r#"    <node id="n{}"><data key="label">{}</data><data key="win">{}</data><data key="truncated">{}</data></node>"#,
+            ix.index(),
+            xml_escape(&g[ix].fmt_compact().to_string()),
+            graph.is_win(ix),
+            graph.is_truncated(ix)
+        )
+        .unwrap();
+    }
+    for edge in g.edge_references() {
+        writeln!(
+            out,
+            r#"    <edge source="n{}" target="n{}"><data key="action">{}</data></edge>"#,
+            edge.source().index(),
+            edge.target().index(),
+            xml_escape(&edge.weight().to_string())
+        )
+        .unwrap();
+    }
+    writeln!(out, "  </graph>").unwrap();
+    writeln!(out, "</graphml>").unwrap();
+
+    out
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{levels::level_1, solve::expand_to_graph};
+
+    #[test]
+    fn test_export_level_1() {
+        let graph = expand_to_graph(&level_1(), 100);
+
+        let dot = to_dot(&graph);
+        assert!(dot.starts_with("digraph states {"));
+        assert!(dot.contains("fillcolor=palegreen"));
+        assert!(!dot.contains("style=dashed"));
+        assert_eq!(
+            dot.matches(" -> ").count(),
+            graph.graph.edge_count(),
+            "{dot}"
+        );
+
+        let graphml = to_graphml(&graph);
+        assert_eq!(graphml.matches("<node ").count(), graph.graph.node_count());
+        assert!(graphml.contains(r#"<data key="win">true</data>"#));
+        // state labels contain targets like E1>E0
+        assert!(!graphml.contains("E1>E0"));
+        assert!(graphml.contains("E1&gt;E0"));
+
+        let truncated = expand_to_graph(&level_1(), 1);
+        assert!(to_dot(&truncated).contains("style=dashed"));
+    }
+}
@@ -0,0 +1,233 @@
+//! Procedural puzzle generation
+//!
+//! Candidates are built randomly from the level archetypes and kept if their shortest solution
+//! and number of dead ends are within the requested bounds.
+
+use crate::{PowerKind, Puzzle, levels::*, solve::StateSpace};
+use rand::{Rng, SeedableRng, seq::SliceRandom};
+use rand_xoshiro::Xoshiro256PlusPlus;
+use std::{fmt, ops::RangeInclusive};
+
+/// Entities which the generator may place in addition to the rift and its switches
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Archetype {
+    /// Laser which can be pointed at switches and overgrowth
+    Laser,
+
+    /// Gate between rooms which is open while its barrier switch is powered
+    Barrier,
+
+    /// Gate between rooms which opens for good when burned by a laser
+    Overgrowth,
+
+    /// Laser power token which the player can carry to a switch
+    Token,
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratorConfig {
+    /// Number of rooms including the exit room and the main room
+    pub room_count: RangeInclusive<usize>,
+
+    /// Number of switches which must be powered to activate the rift
+    pub rift_switch_power: usize,
+
+    pub archetypes: Vec<Archetype>,
+
+    /// Number of actions of the shortest solution
+    pub solution_depth: RangeInclusive<usize>,
+
+    /// Maximum number of reachable states from which the puzzle can no longer be solved
+    pub max_dead_ends: Option<usize>,
+
+    /// Number of candidates which are tried before giving up
+    pub max_attempts: usize,
+
+    /// Candidates with more reachable states are rejected
+    pub max_nodes: usize,
+}
+
+impl Default for GeneratorConfig {
+    fn default() -> Self {
+        Self {
+            room_count: 2..=4,
+            rift_switch_power: 2,
+            archetypes: vec![Archetype::Laser, Archetype::Barrier, Archetype::Overgrowth],
+            solution_depth: 6..=16,
+            max_dead_ends: None,
+            max_attempts: 200,
+            max_nodes: 10000,
+        }
+    }
+}
+
+/// No candidate met the constraints within the attempt budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GenerationFailed {
+    pub attempts: usize,
+}
+
+impl fmt::Display for GenerationFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "no puzzle met the constraints after {} attempts",
+            self.attempts
+        )
+    }
+}
+
+impl std::error::Error for GenerationFailed {}
+
+/// Generates a random puzzle which meets the constraints of the config. The same seed and config
+/// always give the same puzzle.
+pub fn random_puzzle(rng_seed: u64, config: &GeneratorConfig) -> Result<Puzzle, GenerationFailed> {
+    let mut rng = Xoshiro256PlusPlus::seed_from_u64(rng_seed);
+
+    for attempt in 0..config.max_attempts {
+        let name = format!("generated-{rng_seed}-{attempt}");
+        let Some(puzzle) = candidate(&mut rng, config, &name) else {
+            continue;
+        };
+
+        let Some(space) = StateSpace::explore(&puzzle, config.max_nodes) else {
+            continue;
+        };
+        let Some(depth) = space.solution_depth(&puzzle) else {
+            continue;
+        };
+        if !config.solution_depth.contains(&depth) {
+            continue;
+        }
+        if config
+            .max_dead_ends
+            .is_some_and(|max| space.dead_ends(&puzzle) > max)
+        {
+            continue;
+        }
+
+        return Ok(puzzle);
+    }
+
+    Err(GenerationFailed {
+        attempts: config.max_attempts,
+    })
+}
+
+fn candidate(rng: &mut impl Rng, config: &GeneratorConfig, name: &str) -> Option<Puzzle> {
+    let mut builder = puzzle_basis(name, config.rift_switch_power);
+    let main = builder.room_id_by_name("main")?;
+
+    let gate_kinds: Vec<_> = config
+        .archetypes
+        .iter()
+        .copied()
+        .filter(|kind| matches!(kind, Archetype::Barrier | Archetype::Overgrowth))
+        .collect();
+
+    // Entities which are powered by lasers
+    let mut laser_targets = Vec::new();
+
+    // Rooms are added as a tree behind the main room
+    let mut rooms = vec![main];
+    let room_count = rng.gen_range(config.room_count.clone());
+    for i in 2..room_count {
+        let kind = *gate_kinds.choose(rng)?;
+        let from = *rooms.choose(rng)?;
+        let room = builder.add_room(format!("room_{i}"));
+
+        let gate = match kind {
+            Archetype::Barrier => {
+                let gate = builder.add_entity(None, barrier());
+                builder.add_gate(from, room, barrier_gate(gate));
+                gate
+            }
+            Archetype::Overgrowth => {
+                let gate = builder.add_entity(None, overgrowth());
+                builder.add_gate(from, room, gate);
+                laser_targets.push(gate);
+                gate
+            }
+            Archetype::Laser | Archetype::Token => unreachable!(),
+        };
+        rooms.push(room);
+
+        if kind == Archetype::Barrier {
+            let switch_room = *rooms.choose(rng)?;
+            laser_targets.push(builder.add_entity(Some(switch_room), barrier_switch(gate)));
+        }
+    }
+
+    for _ in 0..config.rift_switch_power {
+        let room = *rooms.choose(rng)?;
+        laser_targets.push(builder.add_entity(Some(room), rift_switch()));
+    }
+
+    if config.archetypes.contains(&Archetype::Laser) && !laser_targets.is_empty() {
+        let laser_count = rng.gen_range(1..=laser_targets.len());
+        for _ in 0..laser_count {
+            let room = *rooms.choose(rng)?;
+            let target_count = rng.gen_range(1..=laser_targets.len().min(3));
+            let targets: Vec<_> = laser_targets
+                .choose_multiple(rng, target_count)
+                .copied()
+                .collect();
+            builder.add_entity(Some(room), laser(targets));
+        }
+    }
+
+    if config.archetypes.contains(&Archetype::Token) {
+        let room = *rooms.choose(rng)?;
+        builder.add_entity(Some(room), token_source(PowerKind::Laser));
+    }
+
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_puzzle_is_deterministic() {
+        let config = GeneratorConfig::default();
+        let a = random_puzzle(7, &config).unwrap();
+        let b = random_puzzle(7, &config).unwrap();
+        assert_eq!(a.to_string(), b.to_string());
+        assert_eq!(a.name(), b.name());
+
+        let space = StateSpace::explore(&a, config.max_nodes).unwrap();
+        let depth = space.solution_depth(&a).unwrap();
+        assert!(config.solution_depth.contains(&depth), "{depth}");
+    }
+
+    #[test]
+    fn test_random_puzzle_gives_up() {
+        let config = GeneratorConfig {
+            solution_depth: 1000..=1000,
+            max_attempts: 5,
+            ..Default::default()
+        };
+        assert_eq!(
+            random_puzzle(7, &config).unwrap_err(),
+            GenerationFailed { attempts: 5 }
+        );
+
+        // switches cannot be powered without lasers
+        let config = GeneratorConfig {
+            archetypes: vec![Archetype::Barrier],
+            max_attempts: 5,
+            ..Default::default()
+        };
+        assert!(random_puzzle(7, &config).is_err());
+
+        // unless the player carries a laser token to the only switch
+        let config = GeneratorConfig {
+            rift_switch_power: 1,
+            archetypes: vec![Archetype::Barrier, Archetype::Token],
+            solution_depth: 1..=16,
+            ..Default::default()
+        };
+        assert!(random_puzzle(7, &config).is_ok());
+    }
+}
@@ -0,0 +1,422 @@
+//! Puzzles of the hand-made levels
+
+use crate::{
+    Action, Effect, Entity, EntityId, Gate, Power, PowerCondition, PowerKind, PowerProvider,
+    Puzzle, PuzzleBuilder, TargetKind,
+};
+
+pub fn exit_gate() -> Entity {
+    Entity {
+        condition: PowerCondition::Power {
+            latch: true,
+            power: Power::one(PowerKind::Switch),
+        },
+        archetype: Some("Exit Gate".into()),
+        ..Default::default()
+    }
+}
+
+pub fn rift(switch_count: usize) -> Entity {
+    Entity {
+        condition: PowerCondition::Power {
+            latch: true,
+            power: Power::one(PowerKind::Player) + Power::one(PowerKind::Switch) * switch_count,
+        },
+        target: TargetKind::Fixed(EntityId(0)),
+        effect: Some(Effect::ProvidePower(PowerProvider {
+            kind: PowerKind::Switch,
+        })),
+        archetype: Some("Rift".into()),
+        ..Default::default()
+    }
+}
+
+pub fn switch(target: EntityId) -> Entity {
+    Entity {
+        condition: PowerCondition::Power {
+            latch: false,
+            power: Power::one(PowerKind::Laser),
+        },
+        target: TargetKind::Fixed(target),
+        effect: Some(Effect::ProvidePower(PowerProvider {
+            kind: PowerKind::Switch,
+        })),
+        archetype: Some("Switch".into()),
+        ..Default::default()
+    }
+}
+
+pub fn rift_switch() -> Entity {
+    Entity {
+        archetype: Some("Rift Switch".into()),
+        ..switch(EntityId(1))
+    }
+}
+
+pub fn laser(targets: impl IntoIterator<Item = EntityId>) -> Entity {
+    Entity {
+        condition: PowerCondition::Always,
+        target: TargetKind::Changable(targets.into_iter().collect()),
+        effect: Some(Effect::ProvidePower(PowerProvider {
+            kind: PowerKind::Laser,
+        })),
+        archetype: Some("Laser".into()),
+        ..Default::default()
+    }
+}
+
+pub fn overgrowth() -> Entity {
+    Entity {
+        condition: PowerCondition::Power {
+            latch: true,
+            power: Power::one(PowerKind::Laser),
+        },
+        archetype: Some("Overgrowth".into()),
+        ..Default::default()
+    }
+}
+
+pub fn barrier_switch(target: EntityId) -> Entity {
+    Entity {
+        archetype: Some("Barrier Switch".into()),
+        ..switch(target)
+    }
+}
+
+pub fn barrier() -> Entity {
+    Entity {
+        condition: PowerCondition::Power {
+            latch: false,
+            power: Power::one(PowerKind::Switch),
+        },
+        archetype: Some("Barrier".into()),
+        ..Default::default()
+    }
+}
+
+/// Always open gate
+pub fn passage() -> Entity {
+    Entity {
+        condition: PowerCondition::Always,
+        archetype: Some("Passage".into()),
+        ..Default::default()
+    }
+}
+
+/// Pedestal with a power token which the player can carry to another entity
+pub fn token_source(kind: PowerKind) -> Entity {
+    Entity {
+        condition: PowerCondition::Always,
+        effect: Some(Effect::GrantToken(kind)),
+        archetype: Some("Token Source".into()),
+        ..Default::default()
+    }
+}
+
+/// Gate controlled by a barrier which closes again when its switch loses power
+pub fn barrier_gate(barrier: EntityId) -> Gate {
+    Gate {
+        entity: barrier,
+        closes_behind: true,
+        ..barrier.into()
+    }
+}
+
+/// Creates a puzzle with an exit room (0) linked to a start room(1) and a rift in the start room
+pub fn puzzle_basis(name: &str, rift_switch_power: usize) -> PuzzleBuilder {
+    let mut builder = PuzzleBuilder::new(name);
+
+    // Exit room
+    let room_0 = builder.add_room("exit");
+    // Main room
+    let room_1 = builder.add_room("main");
+
+    // [0] Exit gate
+    let gate = builder.add_entity(None, exit_gate());
+    // [1] Rift
+    builder.add_entity(Some(room_1), rift(rift_switch_power));
+
+    builder.add_gate(room_0, room_1, gate);
+    builder.set_win_room(room_0);
+    builder.set_player_start(room_1);
+
+    builder
+}
+
+pub fn level_1() -> Puzzle {
+    let mut basis = puzzle_basis("level_1", 0);
+    let exit = basis.room_id_by_name("exit").unwrap();
+    basis.set_reference_solution(vec![
+        Action::ProvidePlayerPower {
+            target: Some(EntityId(1)),
+        },
+        Action::MovePlayer { room: exit },
+    ]);
+    basis.build().unwrap()
+}
+
+pub fn level_2() -> Puzzle {
+    let mut basis = puzzle_basis("Level 1-2", 2);
+    let main = basis.room_id_by_name("main");
+
+    // [2] Rift Switch 1
+    basis.add_entity(main, rift_switch());
+    // [3] Rift Switch 2
+    basis.add_entity(main, rift_switch());
+    // [4] Laser 1
+    basis.add_entity(main, laser(vec![EntityId(2), EntityId(3)]));
+    // [5] Laser 2
+    basis.add_entity(main, laser(vec![EntityId(2)]));
+
+    basis.build().unwrap()
+}
+
+pub fn level_3() -> Puzzle {
+    let mut basis = puzzle_basis("Level 1-3", 3);
+    let main = basis.room_id_by_name("main");
+
+    // overgrowth gate
+    let green_room = basis.add_room("green_room");
+    basis.add_gate(main.unwrap(), green_room, EntityId(8));
+
+    // [2] Rift Switch 1 "center"
+    basis.add_entity(main, rift_switch());
+    // [3] Rift Switch 2 "left"
+    basis.add_entity(main, rift_switch());
+    // [4] Rift Switch 3 "right"
+    basis.add_entity(main, rift_switch());
+    // [5] Laser 1 "first"
+    basis.add_entity(main, laser(vec![EntityId(2), EntityId(3), EntityId(8)]));
+    // [6] Laser 2 "green room"
+    basis.add_entity(Some(green_room), laser(vec![EntityId(4)]));
+    // [7] Laser 3 "alcove room"
+    basis.add_entity(main, laser(vec![EntityId(2)]));
+    // [8] Gate from main room to green room
+    basis.add_entity(None, overgrowth());
+
+    basis.build().unwrap()
+}
+
+pub fn level_4() -> Puzzle {
+    let mut basis = puzzle_basis("Level 1-4", 2);
+    let main = basis.room_id_by_name("main");
+
+    // barrier gate
+    let room_2 = basis.add_room("room_2");
+    basis.add_gate(main.unwrap(), room_2, barrier_gate(EntityId(7)));
+
+    // [2] Rift Switch 1
+    basis.add_entity(main, rift_switch());
+    // [3] Rift Switch 2
+    basis.add_entity(Some(room_2), rift_switch());
+    // [4] Laser 1
+    basis.add_entity(main, laser(vec![EntityId(2), EntityId(6)]));
+    // [5] Laser 2
+    basis.add_entity(Some(room_2), laser(vec![EntityId(3), EntityId(6)]));
+    // [6] Barrier Switch
+    basis.add_entity(Some(room_2), barrier_switch(EntityId(7)));
+    // [7] Barrier
+    basis.add_entity(None, barrier());
+
+    basis.build().unwrap()
+}
+
+pub fn level_5() -> Puzzle {
+    let mut basis = puzzle_basis("Level 1-5", 3);
+    let main = basis.room_id_by_name("main");
+
+    // start room
+    let start = basis.add_room("start");
+    basis.add_gate(main.unwrap(), start, barrier_gate(EntityId(2)));
+    basis.set_player_start(start);
+
+    // annex room
+    let annex = basis.add_room("annex");
+    basis.add_gate(main.unwrap(), annex, barrier_gate(EntityId(3)));
+
+    // [2] Barrier
+    basis.add_entity(None, barrier());
+    // [3] Barrier
+    basis.add_entity(None, barrier());
+    // [4] Rift Switch 1
+    basis.add_entity(main, rift_switch());
+    // [5] Rift Switch 2
+    basis.add_entity(main, rift_switch());
+    // [6] Rift Switch 3
+    basis.add_entity(Some(annex), rift_switch());
+    // [7] Laser 1
+    basis.add_entity(
+        Some(start),
+        laser(vec![EntityId(5), EntityId(10), EntityId(11)]),
+    );
+    // [8] Laser 2
+    basis.add_entity(main, laser(vec![EntityId(4), EntityId(5), EntityId(10)]));
+    // [9] Laser 3
+    basis.add_entity(Some(annex), laser(vec![EntityId(6), EntityId(11)]));
+    // [10] Barrier Switch
+    basis.add_entity(main, barrier_switch(EntityId(2)));
+    // [11] Barrier Switch
+    basis.add_entity(Some(annex), barrier_switch(EntityId(3)));
+
+    basis.build().unwrap()
+}
+
+pub fn level_6() -> Puzzle {
+    let mut basis = puzzle_basis("Level 1-6", 1);
+    let main = basis.room_id_by_name("main");
+
+    // vault room
+    let vault = basis.add_room("vault");
+    basis.add_gate(main.unwrap(), vault, EntityId(2));
+
+    // [2] Passage to the vault
+    basis.add_entity(None, passage());
+    // [3] Rift Switch
+    basis.add_entity(main, rift_switch());
+    // [4] Laser token which must be carried to the rift switch
+    basis.add_entity(Some(vault), token_source(PowerKind::Laser));
+
+    basis.build().unwrap()
+}
+
+/// All hand-made levels in order
+pub fn all_levels() -> Vec<Puzzle> {
+    vec![
+        level_1(),
+        level_2(),
+        level_3(),
+        level_4(),
+        level_5(),
+        level_6(),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PuzzleBuildError;
+
+    #[test]
+    fn test_levels_build() {
+        assert_eq!(all_levels().len(), 6);
+    }
+
+    #[test]
+    fn test_level_6_needs_carried_token() {
+        let puzzle = level_6();
+        let solution = crate::solve::astar(&puzzle, &Default::default()).unwrap();
+        assert_eq!(solution.actions.len(), 6);
+
+        // walk to the vault, pick up the token and carry it back to the rift switch
+        let mut state = puzzle.initalize();
+        for action in &solution.actions[..3] {
+            state.apply(&puzzle, action);
+        }
+        assert_eq!(state.player_room(), puzzle.room_id_by_name("main").unwrap());
+        assert_eq!(state.inventory().tokens(), &[PowerKind::Laser]);
+
+        // the token is consumed when deposited
+        let deposit = crate::Action::Deposit {
+            kind: PowerKind::Laser,
+            target: EntityId(3),
+        };
+        state.apply(&puzzle, &deposit);
+        assert!(state.inventory().is_empty());
+        assert!(
+            puzzle
+                .actions(&state)
+                .iter()
+                .all(|action| !matches!(action, Action::Deposit { .. }))
+        );
+    }
+
+    #[test]
+    fn test_build_rejects_dangling_references() {
+        let mut builder = puzzle_basis("dangling target", 1);
+        let main = builder.room_id_by_name("main");
+        builder.add_entity(main, laser(vec![EntityId(7)]));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            PuzzleBuildError::DanglingTarget {
+                entity: EntityId(2),
+                target: EntityId(7)
+            }
+        );
+
+        let mut builder = puzzle_basis("missing gate", 1);
+        let main = builder.room_id_by_name("main").unwrap();
+        let room = builder.add_room("room");
+        let gate = builder.add_gate(main, room, EntityId(2));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            PuzzleBuildError::MissingGateEntity {
+                gate,
+                entity: EntityId(2)
+            }
+        );
+
+        let mut builder = puzzle_basis("twice", 1);
+        builder.add_room("main");
+        assert_eq!(
+            builder.build().unwrap_err(),
+            PuzzleBuildError::DuplicateRoomName("main".into())
+        );
+
+        let mut builder = puzzle_basis("unknown archetype", 1);
+        builder.spawn(None, "Mirror");
+        assert_eq!(
+            builder.build().unwrap_err(),
+            PuzzleBuildError::UnknownArchetype("Mirror".into())
+        );
+    }
+
+    #[test]
+    fn test_level_3_display() {
+        let display = level_3().to_string();
+        let (entities, _initial) = display.split_once("  initial:").unwrap();
+        assert_eq!(
+            entities,
+            "\
+Puzzle[rooms:3, gates:2, entities:9]
+  R0 exit: []
+  R1 main: [Rift, Rift Switch 1, Rift Switch 2, Rift Switch 3, Laser 1, Laser 3]
+  R2 green_room: [Laser 2]
+  R0 --Exit Gate-- R1
+  R1 --Overgrowth-- R2
+  E00 Exit Gate: needs S1 (latch)
+  E01 Rift: needs P1 S3 (latch), provides S → Exit Gate
+  E02 Rift Switch 1: needs L1, provides S → Rift
+  E03 Rift Switch 2: needs L1, provides S → Rift
+  E04 Rift Switch 3: needs L1, provides S → Rift
+  E05 Laser 1: always, provides L → Rift Switch 1 | Rift Switch 2 | Overgrowth
+  E06 Laser 2: always, provides L → Rift Switch 3
+  E07 Laser 3: always, provides L → Rift Switch 1
+  E08 Overgrowth: needs L1 (latch)
+"
+        );
+
+        let puzzle = level_3();
+        let action = Action::SetTarget {
+            entity: EntityId(5),
+            target: Some(EntityId(3)),
+        };
+        assert_eq!(
+            puzzle.fmt_action(&action).to_string(),
+            "SetTarget Laser 1 → Rift Switch 2"
+        );
+        assert_eq!(action.to_string(), "SetTarget E5 → E3");
+    }
+
+    #[test]
+    fn test_spawn_archetype() {
+        let mut builder = puzzle_basis("spawn", 1);
+        let main = builder.room_id_by_name("main");
+        builder.add_archetype("Beacon", laser([EntityId(1)]));
+        let first = builder.spawn(main, "Beacon");
+        let second = builder.spawn(main, "Beacon");
+        let puzzle = builder.build().unwrap();
+        assert_eq!(puzzle.entity_name(first), "Beacon 1");
+        assert_eq!(puzzle.entity_name(second), "Beacon 2");
+        assert_eq!(puzzle.entity_name(EntityId(1)), "Rift");
+    }
+}
@@ -0,0 +1,1708 @@
+//! Puzzel generator
+//!
+//! A puzzle is set of rules.
+//! A state defines the room in which the player is located and the state of other objects.
+//! Each state offers a set of actions based on the puzzle rules.
+//! Graph search algorithms can be used to expand states and find puzzle solutions.
+//! Proc-gen can be used to generate puzzles.
+
+mod analyze;
+mod builder;
+mod difficulty;
+pub mod export;
+pub mod generate;
+pub mod levels;
+pub mod play;
+pub mod solve;
+mod spec;
+
+pub use analyze::*;
+pub use builder::*;
+pub use difficulty::*;
+pub use spec::*;
+
+use bitmask_enum::bitmask;
+use petgraph::{
+    Direction,
+    algo::dijkstra,
+    graph::{EdgeReference, UnGraph},
+    visit::{EdgeFiltered, EdgeRef},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::{Add, Deref, Mul, Sub},
+};
+
+#[derive(Debug, Clone)]
+pub struct Puzzle {
+    name: String,
+    rooms_by_name: HashMap<String, RoomId>,
+    entities: Vec<Entity>,
+    room_graph: RoomGraph,
+    win_room: RoomId,
+    initial_state: PuzzleState,
+    reference_solution: Option<Vec<Action>>,
+}
+
+/// Elements of a puzzle. We use an uber-entity architecture for simplicity and because
+/// components are quite bounded.
+#[derive(Debug, Default, Clone)]
+pub struct Entity {
+    /// If this condition is met
+    pub condition: PowerCondition,
+
+    /// Valid targets of this entity
+    pub target: TargetKind,
+
+    /// This effect is applied
+    pub effect: Option<Effect>,
+
+    /// Kind of entity, e.g. `Laser`. Entities of the same archetype share their condition and
+    /// effect.
+    pub archetype: Option<String>,
+
+    /// Name shown to the player. Assigned from the archetype by [PuzzleBuilder::build] if not set,
+    /// e.g. `Rift Switch 2`.
+    pub display_name: Option<String>,
+}
+
+#[derive(Debug, Default, Clone)]
+pub enum PowerCondition {
+    #[default]
+    Never,
+    Always,
+    Power {
+        /// If enabled the entity stays active after being powered the first time
+        latch: bool,
+        /// Amount of power necessary to activate (all must be fulfilled)
+        power: Power,
+    },
+    /// Active if any of the alternatives is fulfilled
+    AnyOf(Vec<Power>),
+    /// Active while the inner condition is not met, e.g. for entities which turn off when
+    /// powered. A latch of the inner condition keeps the entity off for good.
+    Not(Box<PowerCondition>),
+}
+
+impl PowerCondition {
+    /// True if the condition is met for the given power. `latched` is true if a latching
+    /// condition was met before.
+    pub fn is_met(&self, power: &Power, latched: bool) -> bool {
+        match self {
+            PowerCondition::Never => false,
+            PowerCondition::Always => true,
+            PowerCondition::Power {
+                latch,
+                power: required,
+            } => (*latch && latched) || power.ge(required),
+            PowerCondition::AnyOf(alternatives) => {
+                alternatives.iter().any(|required| power.ge(required))
+            }
+            PowerCondition::Not(inner) => !inner.is_met(power, latched),
+        }
+    }
+
+    /// True if the condition contains a latch which is triggered by the given power
+    pub fn triggers_latch(&self, power: &Power) -> bool {
+        match self {
+            PowerCondition::Power {
+                latch: true,
+                power: required,
+            } => power.ge(required),
+            PowerCondition::Not(inner) => inner.triggers_latch(power),
+            _ => false,
+        }
+    }
+
+    pub fn has_latch(&self) -> bool {
+        match self {
+            PowerCondition::Power { latch, .. } => *latch,
+            PowerCondition::Not(inner) => inner.has_latch(),
+            _ => false,
+        }
+    }
+
+    /// True if the entity reacts to provided power at all
+    pub fn accepts_power(&self) -> bool {
+        match self {
+            PowerCondition::Never | PowerCondition::Always => false,
+            PowerCondition::Power { .. } | PowerCondition::AnyOf(_) => true,
+            PowerCondition::Not(inner) => inner.accepts_power(),
+        }
+    }
+
+    /// True if one more unit of power of the given kind may change the activation of the entity
+    ///
+    /// For plain power conditions this is the original rule of the solver, which also offers
+    /// power to entities holding more than the required power. Changing it changes the explored
+    /// state space and thus the expansion counts of all levels.
+    pub fn is_affected_by(&self, power: &Power, latched: bool, kind: PowerKind) -> bool {
+        let more = *power + Power::one(kind);
+        match self {
+            PowerCondition::Never | PowerCondition::Always => false,
+            PowerCondition::Power {
+                power: required, ..
+            } => more.min(required) != *power,
+            PowerCondition::AnyOf(alternatives) => {
+                !self.is_met(power, latched)
+                    && alternatives
+                        .iter()
+                        .any(|required| more.min(required) != power.min(required))
+            }
+            PowerCondition::Not(inner) => {
+                !(inner.has_latch() && latched) && inner.is_affected_by(power, latched, kind)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub enum TargetKind {
+    #[default]
+    None,
+
+    /// The target cannot be changed
+    Fixed(EntityId),
+
+    /// The target can be changed to one of the list (or None)
+    Changable(Vec<EntityId>),
+}
+
+#[derive(Debug, Clone)]
+pub enum Effect {
+    ProvidePower(PowerProvider),
+
+    /// While active the player can pick up a token of this power kind once
+    GrantToken(PowerKind),
+}
+
+#[derive(Debug, Clone)]
+pub struct PowerProvider {
+    pub kind: PowerKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId(pub usize);
+
+impl Deref for EntityId {
+    type Target = usize;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[bitmask]
+pub enum PowerKind {
+    Player,
+    Laser,
+    Switch,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Power {
+    laser: usize,
+    player: usize,
+    switch: usize,
+}
+
+impl Power {
+    pub const ZERO: Self = Self {
+        laser: 0,
+        player: 0,
+        switch: 0,
+    };
+
+    pub fn one(kind: PowerKind) -> Self {
+        let mut out = Power::default();
+        out.inc(kind);
+        out
+    }
+
+    pub fn inc(&mut self, kind: PowerKind) {
+        if kind.contains(PowerKind::Laser) {
+            self.laser += 1;
+        }
+        if kind.contains(PowerKind::Player) {
+            self.player += 1;
+        }
+        if kind.contains(PowerKind::Switch) {
+            self.switch += 1;
+        }
+    }
+
+    pub fn dec(&mut self, kind: PowerKind) {
+        if kind.contains(PowerKind::Laser) {
+            assert!(self.laser >= 1);
+            self.laser -= 1;
+        }
+        if kind.contains(PowerKind::Player) {
+            assert!(self.player >= 1);
+            self.player -= 1;
+        }
+        if kind.contains(PowerKind::Switch) {
+            assert!(self.switch >= 1);
+            self.switch -= 1;
+        }
+    }
+
+    /// Like [Power::dec] but stays at zero instead of panicking
+    pub fn saturating_dec(&mut self, kind: PowerKind) {
+        *self = *self - Power::one(kind);
+    }
+
+    /// Difference if every kind of `other` is covered by this power
+    pub fn checked_sub(&self, other: &Power) -> Option<Power> {
+        Some(Power {
+            laser: self.laser.checked_sub(other.laser)?,
+            player: self.player.checked_sub(other.player)?,
+            switch: self.switch.checked_sub(other.switch)?,
+        })
+    }
+
+    /// Amount of power of the given kinds
+    pub fn get(&self, kind: PowerKind) -> usize {
+        self.iter_kinds()
+            .filter(|(k, _)| kind.contains(*k))
+            .map(|(_, n)| n)
+            .sum()
+    }
+
+    pub fn total(&self) -> usize {
+        self.laser + self.player + self.switch
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == Power::ZERO
+    }
+
+    /// Amount of power per kind in display order
+    pub fn iter_kinds(&self) -> impl Iterator<Item = (PowerKind, usize)> {
+        [
+            (PowerKind::Laser, self.laser),
+            (PowerKind::Player, self.player),
+            (PowerKind::Switch, self.switch),
+        ]
+        .into_iter()
+    }
+
+    /// Displays this provided power against the `required` power, e.g. `L1/1 S0/2 (need S2)`
+    pub fn fmt_vs<'a>(&'a self, required: &'a Power) -> PowerVs<'a> {
+        PowerVs {
+            provided: self,
+            required,
+        }
+    }
+
+    pub fn ge(&self, other: &Power) -> bool {
+        self.laser >= other.laser && self.player >= other.player && self.switch >= other.switch
+    }
+
+    pub fn lt(&self, other: &Power) -> bool {
+        !self.ge(other)
+    }
+
+    pub fn min(&self, other: &Power) -> Power {
+        Power {
+            laser: self.laser.min(other.laser),
+            player: self.player.min(other.player),
+            switch: self.switch.min(other.switch),
+        }
+    }
+}
+
+impl Mul<usize> for Power {
+    type Output = Power;
+
+    fn mul(self, other: usize) -> Self::Output {
+        Power {
+            laser: self.laser * other,
+            player: self.player * other,
+            switch: self.switch * other,
+        }
+    }
+}
+
+/// Saturating difference per kind
+impl Sub<Power> for Power {
+    type Output = Power;
+
+    fn sub(self, other: Power) -> Self::Output {
+        Power {
+            laser: self.laser.saturating_sub(other.laser),
+            player: self.player.saturating_sub(other.player),
+            switch: self.switch.saturating_sub(other.switch),
+        }
+    }
+}
+
+impl Add<Power> for Power {
+    type Output = Power;
+
+    fn add(self, other: Power) -> Self::Output {
+        Power {
+            laser: self.laser + other.laser,
+            player: self.player + other.player,
+            switch: self.switch + other.switch,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct Room {
+    entities: Vec<EntityId>,
+}
+
+impl Room {
+    pub fn from_entities(entities: impl IntoIterator<Item = EntityId>) -> Self {
+        Room {
+            entities: entities.into_iter().collect(),
+        }
+    }
+}
+
+/// Default of [Gate::traversal_cost]
+pub const DEFAULT_GATE_TRAVERSAL_COST: f64 = 1.0;
+
+/// Connection between two rooms which is open while its entity is active
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gate {
+    pub entity: EntityId,
+
+    /// The gate needs power to stay open and may close while the player is on the other side
+    pub closes_behind: bool,
+
+    /// If set the gate can only be passed in one direction. [Direction::Outgoing] allows passing
+    /// from the first to the second room the gate was added with.
+    pub one_way: Option<Direction>,
+
+    /// Relative effort to walk from one room to the other, e.g. for long corridors
+    pub traversal_cost: f64,
+}
+
+impl Gate {
+    /// True if the gate lets the player pass when coming from the given side
+    pub fn passable_from(&self, from_first_room: bool) -> bool {
+        match self.one_way {
+            None => true,
+            Some(Direction::Outgoing) => from_first_room,
+            Some(Direction::Incoming) => !from_first_room,
+        }
+    }
+}
+
+impl From<EntityId> for Gate {
+    fn from(entity: EntityId) -> Self {
+        Gate {
+            entity,
+            closes_behind: false,
+            one_way: None,
+            traversal_cost: DEFAULT_GATE_TRAVERSAL_COST,
+        }
+    }
+}
+
+type RoomGraph = UnGraph<Room, Gate>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RoomId(petgraph::prelude::NodeIndex);
+
+impl Deref for RoomId {
+    type Target = petgraph::prelude::NodeIndex;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct GateId(petgraph::prelude::EdgeIndex);
+
+impl Deref for GateId {
+    type Target = petgraph::prelude::EdgeIndex;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PuzzleState {
+    player_room: RoomId,
+    player_power_target: Option<EntityId>,
+    inventory: Inventory,
+    entities: Vec<EntityState>,
+}
+
+/// Power tokens carried by the player
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Inventory {
+    /// Kept sorted so that equal inventories compare equal
+    tokens: Vec<PowerKind>,
+}
+
+impl Inventory {
+    pub fn tokens(&self) -> &[PowerKind] {
+        &self.tokens
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn contains(&self, kind: PowerKind) -> bool {
+        self.tokens.contains(&kind)
+    }
+
+    fn add(&mut self, kind: PowerKind) {
+        let ix = self.tokens.partition_point(|k| *k <= kind);
+        self.tokens.insert(ix, kind);
+    }
+
+    /// Removes one token of the given kind. Returns false if there is none.
+    fn remove(&mut self, kind: PowerKind) -> bool {
+        match self.tokens.iter().position(|k| *k == kind) {
+            Some(ix) => {
+                self.tokens.remove(ix);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Distinct kinds of carried tokens
+    fn kinds(&self) -> impl Iterator<Item = PowerKind> + '_ {
+        self.tokens
+            .iter()
+            .enumerate()
+            .filter(|(i, kind)| *i == 0 || self.tokens[i - 1] != **kind)
+            .map(|(_, kind)| *kind)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct EntityState {
+    /// Amount of power currently provided
+    power: Power,
+
+    /// If the entity is activated
+    is_active: bool,
+
+    /// Current target for Effect::ProvidePower
+    target: Option<EntityId>,
+
+    /// Power provided permanently by tokens which the player deposited
+    deposited: Power,
+
+    /// The token of Effect::GrantToken was picked up
+    token_taken: bool,
+
+    /// A latching condition was met and the entity no longer reacts to power
+    latched: bool,
+}
+
+/// Actions change the state of a puzzle
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Player moves to another room. Only
+    MovePlayer { room: RoomId },
+
+    /// Player provides power to an entity.
+    /// Can only target entities in the same room.
+    /// This will remove power from the current player target.
+    ProvidePlayerPower { target: Option<EntityId> },
+
+    /// Change power target of an entity.
+    /// Can only target entities from the target list.
+    /// This will remove power from the current target.
+    SetTarget {
+        entity: EntityId,
+        target: Option<EntityId>,
+    },
+
+    /// Player picks up the token of an entity with Effect::GrantToken.
+    /// Can only pick up from active entities in the same room.
+    PickUp { entity: EntityId },
+
+    /// Player deposits a carried token at an entity in the same room.
+    /// The entity is powered by the token for the rest of the puzzle.
+    Deposit { kind: PowerKind, target: EntityId },
+}
+
+/// Previous values of everything an action changed, see [PuzzleState::apply_with_undo]
+#[derive(Debug, Clone)]
+pub struct UndoToken {
+    player_room: RoomId,
+    player_power_target: Option<EntityId>,
+    inventory: Inventory,
+    entities: Vec<(EntityId, EntityState)>,
+}
+
+impl PuzzleState {
+    pub fn new(player_room: RoomId, entity_count: usize) -> Self {
+        PuzzleState {
+            player_room,
+            player_power_target: None,
+            inventory: Inventory::default(),
+            entities: (0..entity_count).map(|_| EntityState::default()).collect(),
+        }
+    }
+
+    pub fn player_room(&self) -> RoomId {
+        self.player_room
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    /// Displays the state on a single short line, e.g. `R1 P:E1 on:E0,E1 E4>E2`
+    pub fn fmt_compact(&self) -> CompactState<'_> {
+        CompactState(self)
+    }
+
+    pub fn branch(&self, spec: &Puzzle, action: &Action) -> Self {
+        let mut out = self.clone();
+        out.apply(spec, action);
+        out
+    }
+
+    /// Applies an action in place. The returned token reverts the action with [PuzzleState::undo].
+    pub fn apply_with_undo(&mut self, spec: &Puzzle, action: &Action) -> UndoToken {
+        // Power only propagates along targets. Targets of other entities do not change while
+        // applying the action, thus only entities downstream of the action can change.
+        let mut seeds = Vec::new();
+        match *action {
+            Action::MovePlayer { .. } => {}
+            Action::SetTarget { entity, target } => {
+                seeds.push(entity);
+                seeds.extend(self.entities[*entity].target);
+                seeds.extend(target);
+            }
+            Action::ProvidePlayerPower { target } => {
+                seeds.extend(self.player_power_target);
+                seeds.extend(target);
+            }
+            Action::PickUp { entity } => seeds.push(entity),
+            Action::Deposit { target, .. } => seeds.push(target),
+        }
+
+        let mut entities: Vec<(EntityId, EntityState)> = Vec::new();
+        while let Some(entity) = seeds.pop() {
+            if entities.iter().any(|(id, _)| *id == entity) {
+                continue;
+            }
+            let state = &self.entities[*entity];
+            seeds.extend(state.target);
+            entities.push((entity, state.clone()));
+        }
+
+        let token = UndoToken {
+            player_room: self.player_room,
+            player_power_target: self.player_power_target,
+            inventory: self.inventory.clone(),
+            entities,
+        };
+        self.apply(spec, action);
+        token
+    }
+
+    /// Reverts an action applied with [PuzzleState::apply_with_undo]. Tokens must be undone in
+    /// reverse order.
+    pub fn undo(&mut self, _spec: &Puzzle, token: UndoToken) {
+        self.player_room = token.player_room;
+        self.player_power_target = token.player_power_target;
+        self.inventory = token.inventory;
+        for (entity, state) in token.entities {
+            self.entities[*entity] = state;
+        }
+    }
+
+    /// Hash of the fields which determine how the puzzle can continue
+    ///
+    /// The power of an entity is not hashed as it is derived from the targets of active entities
+    /// and the player target. This matters for latched entities which stay active once powered.
+    pub fn canonical_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.player_room.hash(&mut hasher);
+        self.player_power_target.hash(&mut hasher);
+        self.inventory.hash(&mut hasher);
+        for entity in &self.entities {
+            entity.is_active.hash(&mut hasher);
+            entity.target.hash(&mut hasher);
+            entity.deposited.hash(&mut hasher);
+            entity.token_taken.hash(&mut hasher);
+            entity.latched.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    pub fn setup(&mut self, spec: &Puzzle) {
+        for (i, entity_spec) in spec.entities.iter().enumerate() {
+            let entity = EntityId(i);
+
+            // Set target entity for entities with fixed target
+            match entity_spec.target {
+                TargetKind::Fixed(target) => {
+                    self.entities[*entity].target = Some(target);
+                    if let Some(Effect::ProvidePower(pp)) = &spec.entities[*entity].effect {
+                        self.provide_power(spec, Some(entity), target, pp.kind);
+                    }
+                }
+                _ => {}
+            }
+
+            // Power on entities which are always powered or active without power
+            self.update_activation(spec, entity);
+        }
+    }
+
+    pub fn apply(&mut self, spec: &Puzzle, action: &Action) {
+        self.apply_impl(spec, action);
+
+        #[cfg(debug_assertions)]
+        if let Err(err) = self.debug_validate(spec) {
+            panic!("invalid state after {action}: {err}\n{self}");
+        }
+    }
+
+    /// Recomputes power and activation of all entities from scratch and compares them with the
+    /// incrementally updated state
+    pub fn debug_validate(&self, spec: &Puzzle) -> Result<(), StateInvariantError> {
+        let mut expected: Vec<Power> = self.entities.iter().map(|es| es.deposited).collect();
+        if let Some(target) = self.player_power_target {
+            expected[*target].inc(PowerKind::Player);
+        }
+        for (entity_spec, es) in spec.entities.iter().zip(&self.entities) {
+            if let (Some(Effect::ProvidePower(pp)), Some(target), true) =
+                (&entity_spec.effect, es.target, es.is_active)
+            {
+                expected[*target].inc(pp.kind);
+            }
+        }
+
+        for (i, (entity_spec, es)) in spec.entities.iter().zip(&self.entities).enumerate() {
+            let entity = EntityId(i);
+            if es.power != expected[i] {
+                return Err(StateInvariantError::StalePower {
+                    entity,
+                    expected: expected[i],
+                    actual: es.power,
+                });
+            }
+            if !es.latched && entity_spec.condition.is_met(&es.power, false) != es.is_active {
+                return Err(StateInvariantError::StaleActivation {
+                    entity,
+                    is_active: es.is_active,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_impl(&mut self, spec: &Puzzle, action: &Action) {
+        match *action {
+            Action::MovePlayer { room } => {
+                self.player_room = room;
+            }
+            Action::SetTarget { entity, target } => {
+                if let Some(Effect::ProvidePower(pp)) = &spec.entities[*entity].effect {
+                    if let Some(old_target) = self.entities[*entity].target {
+                        self.remove_power(spec, Some(entity), old_target, pp.kind);
+                    }
+
+                    self.entities[*entity].target = target;
+
+                    if let Some(new_target) = target {
+                        self.provide_power(spec, Some(entity), new_target, pp.kind);
+                    }
+                } else {
+                    panic!("invalid action");
+                }
+            }
+            Action::ProvidePlayerPower { target } => {
+                if let Some(old_target) = self.player_power_target {
+                    self.remove_power(spec, None, old_target, PowerKind::Player);
+                }
+
+                self.player_power_target = target;
+
+                if let Some(new_target) = target {
+                    self.provide_power(spec, None, new_target, PowerKind::Player);
+                }
+            }
+            Action::PickUp { entity } => {
+                if let Some(Effect::GrantToken(kind)) = &spec.entities[*entity].effect {
+                    assert!(!self.entities[*entity].token_taken, "invalid action");
+                    self.entities[*entity].token_taken = true;
+                    self.inventory.add(*kind);
+                } else {
+                    panic!("invalid action");
+                }
+            }
+            Action::Deposit { kind, target } => {
+                assert!(self.inventory.remove(kind), "invalid action");
+                self.entities[*target].deposited.inc(kind);
+                self.provide_power(spec, None, target, kind);
+            }
+        }
+    }
+
+    /// Provide power to an entity
+    fn provide_power(
+        &mut self,
+        spec: &Puzzle,
+        src: Option<EntityId>,
+        target: EntityId,
+        power: PowerKind,
+    ) {
+        if let Some(src) = src {
+            if !self.entities[*src].is_active {
+                return;
+            }
+        }
+
+        // provide power to the entity
+        self.entities[*target].power.inc(power);
+
+        self.update_activation(spec, target);
+    }
+
+    /// Activates or deactivates an entity if its condition changed
+    fn update_activation(&mut self, spec: &Puzzle, entity: EntityId) {
+        let condition = &spec.entities[*entity].condition;
+        let entity_state = &mut self.entities[*entity];
+
+        if entity_state.latched {
+            return;
+        }
+        let is_met = condition.is_met(&entity_state.power, false);
+        if condition.triggers_latch(&entity_state.power) {
+            entity_state.latched = true;
+        }
+
+        if is_met && !entity_state.is_active {
+            self.activate(spec, entity);
+        } else if !is_met && entity_state.is_active {
+            self.deactivate(spec, entity);
+        }
+    }
+
+    fn activate(&mut self, spec: &Puzzle, entity: EntityId) {
+        let entity_spec = &spec.entities[*entity];
+        let entity_state = &mut self.entities[*entity];
+
+        entity_state.is_active = true;
+
+        // apply the power effect
+        match &entity_spec.effect {
+            Some(Effect::ProvidePower(pp)) => {
+                if let Some(target) = entity_state.target {
+                    self.provide_power(spec, Some(entity), target, pp.kind);
+                }
+            }
+            Some(Effect::GrantToken(_)) | None => {}
+        }
+    }
+
+    /// Remove power from an entity
+    fn remove_power(
+        &mut self,
+        spec: &Puzzle,
+        src: Option<EntityId>,
+        target: EntityId,
+        power: PowerKind,
+    ) {
+        if let Some(src) = src {
+            if !self.entities[*src].is_active {
+                return;
+            }
+        }
+
+        // remove power from the entity
+        self.entities[*target].power.dec(power);
+
+        self.update_activation(spec, target);
+    }
+
+    fn deactivate(&mut self, spec: &Puzzle, entity: EntityId) {
+        let entity_spec = &spec.entities[*entity];
+        let entity_state = &mut self.entities[*entity];
+
+        // remove the power effect
+        match &entity_spec.effect {
+            Some(Effect::ProvidePower(pp)) => {
+                if let Some(next_target) = entity_state.target {
+                    self.remove_power(spec, Some(entity), next_target, pp.kind);
+                }
+            }
+            Some(Effect::GrantToken(_)) | None => {}
+        }
+
+        self.entities[*entity].is_active = false;
+    }
+}
+
+/// Inconsistency found by [PuzzleState::debug_validate]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateInvariantError {
+    /// The power of an entity does not match the sum of its active sources
+    StalePower {
+        entity: EntityId,
+        expected: Power,
+        actual: Power,
+    },
+
+    /// A non-latched entity is active although its condition is not met or vice versa
+    StaleActivation { entity: EntityId, is_active: bool },
+}
+
+impl fmt::Display for StateInvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateInvariantError::StalePower {
+                entity,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{entity} has power {actual} but its sources provide {expected}"
+            ),
+            StateInvariantError::StaleActivation { entity, is_active } => write!(
+                f,
+                "{entity} is {} but its condition says otherwise",
+                if *is_active { "on" } else { "off" }
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateInvariantError {}
+
+impl Puzzle {
+    pub fn initalize(&self) -> PuzzleState {
+        let mut state = self.initial_state.clone();
+        state.setup(self);
+        state
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The puzzle is solved when the player reaches this room
+    pub fn win_room(&self) -> RoomId {
+        self.win_room
+    }
+
+    /// Solution intended by the designer, see [solve::count_minimal_solutions]
+    pub fn reference_solution(&self) -> Option<&[Action]> {
+        self.reference_solution.as_deref()
+    }
+
+    pub fn room_id_by_name(&self, name: &str) -> Option<RoomId> {
+        self.rooms_by_name.get(name).cloned()
+    }
+
+    pub fn room_name(&self, room: RoomId) -> &str {
+        self.rooms_by_name
+            .iter()
+            .find(|(_, id)| **id == room)
+            .map(|(name, _)| name.as_str())
+            .unwrap_or_default()
+    }
+
+    /// Display name of the entity or its ID if it has none
+    pub fn entity_name(&self, entity: EntityId) -> String {
+        match &self.entities[*entity].display_name {
+            Some(name) => name.clone(),
+            None => entity.to_string(),
+        }
+    }
+
+    /// Formats an action with the names of rooms and entities instead of their IDs
+    pub fn fmt_action<'a>(&'a self, action: &'a Action) -> NamedAction<'a> {
+        NamedAction {
+            puzzle: self,
+            action,
+        }
+    }
+
+    pub fn gate(&self, gate: GateId) -> &Gate {
+        &self.room_graph[*gate]
+    }
+
+    /// True if the player can pass the gate from the given room while it is open
+    pub fn gate_passable_from(&self, gate: GateId, room: RoomId) -> bool {
+        let (first, _) = self.room_graph.edge_endpoints(*gate).unwrap();
+        self.room_graph[*gate].passable_from(first == *room)
+    }
+
+    /// Shortest walking distance between two rooms through gates which are open in the given
+    /// state. None if `b` cannot be reached.
+    pub fn room_distance(&self, state: &PuzzleState, a: RoomId, b: RoomId) -> Option<f64> {
+        let open = EdgeFiltered::from_fn(&self.room_graph, |edge: EdgeReference<'_, Gate>| {
+            state.entities[*edge.weight().entity].is_active
+                && self.gate_passable_from(GateId(edge.id()), RoomId(edge.source()))
+        });
+        dijkstra(&open, *a, Some(*b), |edge| edge.weight().traversal_cost)
+            .get(&*b)
+            .copied()
+    }
+
+    /// Shortest walking distance between two rooms as if all gates were open and passable in both
+    /// directions. Used as a lower bound for heuristics.
+    pub fn room_distance_ignoring_gates(&self, a: RoomId, b: RoomId) -> Option<f64> {
+        dijkstra(&self.room_graph, *a, Some(*b), |edge| {
+            edge.weight().traversal_cost
+        })
+        .get(&*b)
+        .copied()
+    }
+
+    pub fn room_by_name(&self, name: &str) -> Option<&Room> {
+        let node_id = *self.rooms_by_name.get(name)?;
+        Some(&self.room_graph[*node_id])
+    }
+
+    /// Actions which are possible in the given state
+    pub fn actions(&self, state: &PuzzleState) -> Vec<Action> {
+        let mut out = vec![];
+
+        // move player through open gates
+        for edge in self.room_graph.edges(*state.player_room) {
+            if !self.gate_passable_from(GateId(edge.id()), state.player_room) {
+                continue;
+            }
+            if state.entities[*edge.weight().entity].is_active {
+                out.push(Action::MovePlayer {
+                    room: RoomId(edge.target()),
+                });
+            }
+        }
+
+        // Interaction with entities in current room
+        for entity in &self.room_graph[*state.player_room].entities {
+            let entity_spec = &self.entities[**entity];
+            let entity_state = &state.entities[**entity];
+
+            // modify entity target
+            match &entity_spec.target {
+                TargetKind::None | TargetKind::Fixed(_) => {}
+                TargetKind::Changable(targets) => {
+                    // change target
+                    for &target in targets {
+                        if Some(target) != entity_state.target {
+                            out.push(Action::SetTarget {
+                                entity: *entity,
+                                target: Some(target),
+                            });
+                        }
+                    }
+
+                    // clear target
+                    if entity_state.target.is_some() {
+                        out.push(Action::SetTarget {
+                            entity: *entity,
+                            target: None,
+                        });
+                    }
+                }
+            }
+
+            // provide player power to entity if it could change its activation
+            let condition = &entity_spec.condition;
+            if condition.is_affected_by(
+                &entity_state.power,
+                entity_state.latched,
+                PowerKind::Player,
+            ) {
+                out.push(Action::ProvidePlayerPower {
+                    target: Some(*entity),
+                });
+            }
+
+            // deposit carried tokens if they could change its activation
+            for kind in state.inventory.kinds() {
+                if condition.is_affected_by(&entity_state.power, entity_state.latched, kind) {
+                    out.push(Action::Deposit {
+                        kind,
+                        target: *entity,
+                    });
+                }
+            }
+
+            // pick up token
+            if let Some(Effect::GrantToken(_)) = &entity_spec.effect
+                && entity_state.is_active
+                && !entity_state.token_taken
+            {
+                out.push(Action::PickUp { entity: *entity });
+            }
+        }
+
+        // remove player power if currently providing power
+        if state.player_power_target.is_some() {
+            out.push(Action::ProvidePlayerPower { target: None });
+        }
+
+        out
+    }
+}
+
+// Display
+
+// --- Atomics ---------------------------------------------------------------
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "E{}", self.0)
+    }
+}
+
+impl fmt::Display for RoomId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "R{}", self.0.index())
+    }
+}
+
+impl fmt::Display for GateId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "G{}", self.0.index())
+    }
+}
+
+impl fmt::Display for Power {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Compact: omit zero fields; print Ø when all zero.
+        let mut first = true;
+        let mut write_field = |name: &str, val: usize| -> fmt::Result {
+            if val > 0 {
+                if !first {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}{}", name, val)?;
+                first = false;
+            }
+            Ok(())
+        };
+        write_field("L", self.laser)?;
+        write_field("P", self.player)?;
+        write_field("S", self.switch)?;
+        if first { write!(f, "Ø") } else { Ok(()) }
+    }
+}
+
+/// Provided versus required power, see [Power::fmt_vs]
+pub struct PowerVs<'a> {
+    provided: &'a Power,
+    required: &'a Power,
+}
+
+impl fmt::Display for PowerVs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for ((kind, provided), (_, required)) in
+            self.provided.iter_kinds().zip(self.required.iter_kinds())
+        {
+            if provided > 0 || required > 0 {
+                if !first {
+                    write!(f, " ")?;
+                }
+                write!(f, "{}{provided}/{required}", power_kind_label(kind))?;
+                first = false;
+            }
+        }
+        if first {
+            write!(f, "Ø")?;
+        }
+
+        let missing = *self.required - *self.provided;
+        if !missing.is_zero() {
+            write!(f, " (need {missing})")?;
+        }
+        Ok(())
+    }
+}
+
+fn power_kind_label(kind: PowerKind) -> &'static str {
+    if kind == PowerKind::Laser {
+        "L"
+    } else if kind == PowerKind::Player {
+        "P"
+    } else {
+        "S"
+    }
+}
+
+impl fmt::Display for EntityState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{{power:{}, {}, target:{}",
+            self.power,
+            if self.is_active { "on" } else { "off" },
+            match self.target {
+                Some(t) => format!("{t}"),
+                None => "-".to_string(),
+            }
+        )?;
+        // Token bookkeeping is only shown for puzzles which use tokens
+        if !self.deposited.is_zero() {
+            write!(f, ", deposited:{}", self.deposited)?;
+        }
+        if self.token_taken {
+            write!(f, ", taken")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+impl fmt::Display for Inventory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, kind) in self.tokens.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", power_kind_label(*kind))?;
+        }
+        write!(f, "]")
+    }
+}
+
+// --- High-level states -----------------------------------------------------
+
+impl fmt::Display for PuzzleState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "room:{}, player_target:{}",
+            self.player_room,
+            match self.player_power_target {
+                Some(id) => format!("{id}"),
+                None => "-".to_string(),
+            }
+        )?;
+        if !self.inventory.is_empty() {
+            write!(f, ", inventory:{}", self.inventory)?;
+        }
+        write!(f, ", entities:[")?;
+        for (i, es) in self.entities.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "#{i}:{es}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Short form of a state, see [PuzzleState::fmt_compact]
+pub struct CompactState<'a>(&'a PuzzleState);
+
+impl fmt::Display for CompactState<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.0;
+        write!(f, "{}", state.player_room)?;
+        if let Some(target) = state.player_power_target {
+            write!(f, " P:{target}")?;
+        }
+        if !state.inventory.is_empty() {
+            write!(f, " inv:{}", state.inventory)?;
+        }
+
+        write!(f, " on:")?;
+        let mut first = true;
+        for (i, es) in state.entities.iter().enumerate() {
+            if es.is_active {
+                if !first {
+                    write!(f, ",")?;
+                }
+                write!(f, "{}", EntityId(i))?;
+                first = false;
+            }
+        }
+        if first {
+            write!(f, "-")?;
+        }
+
+        for (i, es) in state.entities.iter().enumerate() {
+            if let Some(target) = es.target {
+                write!(f, " {}>{target}", EntityId(i))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Puzzle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let g = &self.room_graph;
+        let rooms = g.node_count();
+        let gates = g.edge_count();
+        let ents = self.entities.len();
+
+        writeln!(f, "Puzzle[rooms:{rooms}, gates:{gates}, entities:{ents}]")?;
+
+        // Rooms with their entity lists.
+        for room in g.node_indices() {
+            let room_id = RoomId(room);
+            write!(f, "  {room_id} {}: [", self.room_name(room_id))?;
+            for (i, eid) in g[room].entities.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", self.entity_name(*eid))?;
+            }
+            writeln!(f, "]")?;
+        }
+
+        // Gates as edges labeled by the gate entity. One-way gates are shown as arrows.
+        for e in g.edge_references() {
+            let a = e.source().index();
+            let b = e.target().index();
+            let gate = e.weight();
+            let (left, right) = match gate.one_way {
+                None => ("--", "--"),
+                Some(Direction::Outgoing) => ("--", "->"),
+                Some(Direction::Incoming) => ("<-", "--"),
+            };
+            write!(
+                f,
+                "  R{a} {left}{}{right} R{b}",
+                self.entity_name(gate.entity)
+            )?;
+            if gate.closes_behind {
+                write!(f, " (closes behind)")?;
+            }
+            if gate.traversal_cost != DEFAULT_GATE_TRAVERSAL_COST {
+                write!(f, " (cost {})", gate.traversal_cost)?;
+            }
+            writeln!(f)?;
+        }
+
+        for (i, e) in self.entities.iter().enumerate() {
+            write!(f, "  E{i:02}")?;
+            if let Some(name) = &e.display_name {
+                write!(f, " {name}")?;
+            }
+            write!(f, ": {}", e.condition)?;
+            if let Some(effect) = &e.effect {
+                write!(f, ", {effect}")?;
+            }
+            match &e.target {
+                TargetKind::None => {}
+                TargetKind::Fixed(target) => write!(f, " → {}", self.entity_name(*target))?,
+                TargetKind::Changable(targets) => {
+                    write!(f, " → ")?;
+                    for (j, target) in targets.iter().enumerate() {
+                        if j > 0 {
+                            write!(f, " | ")?;
+                        }
+                        write!(f, "{}", self.entity_name(*target))?;
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+
+        // Initial state summary on a single line for quick scans.
+        write!(f, "  initial: {}", self.initial_state)
+    }
+}
+
+impl fmt::Display for PowerCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PowerCondition::Never => write!(f, "never"),
+            PowerCondition::Always => write!(f, "always"),
+            PowerCondition::Power { latch, power } => {
+                write!(f, "needs {power}")?;
+                if *latch {
+                    write!(f, " (latch)")?;
+                }
+                Ok(())
+            }
+            PowerCondition::AnyOf(alternatives) => {
+                write!(f, "needs any of ")?;
+                for (i, power) in alternatives.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{power}")?;
+                }
+                Ok(())
+            }
+            PowerCondition::Not(inner) => write!(f, "not ({inner})"),
+        }
+    }
+}
+
+impl fmt::Display for Effect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Effect::ProvidePower(pp) => write!(f, "provides {}", power_kinds_label(pp.kind)),
+            Effect::GrantToken(kind) => write!(f, "grants {} token", power_kinds_label(*kind)),
+        }
+    }
+}
+
+/// Labels of all kinds in the mask, e.g. `LS`
+fn power_kinds_label(kind: PowerKind) -> String {
+    [PowerKind::Laser, PowerKind::Player, PowerKind::Switch]
+        .into_iter()
+        .filter(|k| kind.contains(*k))
+        .map(power_kind_label)
+        .collect()
+}
+
+// --- Actions ---------------------------------------------------------------
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Action::MovePlayer { room } => {
+                write!(f, "MovePlayer → {}", room)
+            }
+            Action::ProvidePlayerPower { target } => match target {
+                Some(t) => write!(f, "ProvidePlayerPower → {}", t),
+                None => write!(f, "ProvidePlayerPower → (none)"),
+            },
+            Action::SetTarget { entity, target } => match target {
+                Some(t) => write!(f, "SetTarget {} → {}", entity, t),
+                None => write!(f, "ClearTarget {}", entity),
+            },
+            Action::PickUp { entity } => write!(f, "PickUp {}", entity),
+            Action::Deposit { kind, target } => {
+                write!(f, "Deposit {} → {}", power_kind_label(*kind), target)
+            }
+        }
+    }
+}
+
+/// Action with names of rooms and entities, see [Puzzle::fmt_action]
+pub struct NamedAction<'a> {
+    puzzle: &'a Puzzle,
+    action: &'a Action,
+}
+
+impl fmt::Display for NamedAction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |entity: &EntityId| self.puzzle.entity_name(*entity);
+        match self.action {
+            Action::MovePlayer { room } => {
+                write!(f, "MovePlayer → {}", self.puzzle.room_name(*room))
+            }
+            Action::ProvidePlayerPower { target } => match target {
+                Some(t) => write!(f, "ProvidePlayerPower → {}", name(t)),
+                None => write!(f, "ProvidePlayerPower → (none)"),
+            },
+            Action::SetTarget { entity, target } => match target {
+                Some(t) => write!(f, "SetTarget {} → {}", name(entity), name(t)),
+                None => write!(f, "ClearTarget {}", name(entity)),
+            },
+            Action::PickUp { entity } => write!(f, "PickUp {}", name(entity)),
+            Action::Deposit { kind, target } => {
+                write!(f, "Deposit {} → {}", power_kind_label(*kind), name(target))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn power(laser: usize, player: usize, switch: usize) -> Power {
+        Power {
+            laser,
+            player,
+            switch,
+        }
+    }
+
+    #[test]
+    fn test_power_sub_saturates() {
+        assert_eq!(power(2, 1, 0) - power(1, 3, 1), power(1, 0, 0));
+        assert_eq!(power(2, 1, 0).checked_sub(&power(1, 3, 1)), None);
+        assert_eq!(
+            power(2, 1, 1).checked_sub(&power(1, 1, 0)),
+            Some(power(1, 0, 1))
+        );
+
+        let mut p = power(0, 1, 0);
+        p.saturating_dec(PowerKind::Player | PowerKind::Switch);
+        assert!(p.is_zero());
+        assert_eq!(power(1, 2, 3).total(), 6);
+        assert_eq!(power(1, 2, 3).get(PowerKind::Laser | PowerKind::Switch), 4);
+    }
+
+    #[test]
+    fn test_undo_and_canonical_hash() {
+        let puzzle = crate::levels::level_5();
+        let space = crate::solve::StateSpace::explore(&puzzle, 10000).unwrap();
+
+        let mut hashes = std::collections::HashSet::new();
+        for state in &space.states {
+            assert!(hashes.insert(state.canonical_hash()));
+
+            let mut current = state.clone();
+            for action in puzzle.actions(state) {
+                let token = current.apply_with_undo(&puzzle, &action);
+                assert_eq!(current, state.branch(&puzzle, &action));
+                current.undo(&puzzle, token);
+                assert_eq!(&current, state);
+            }
+        }
+    }
+
+    #[test]
+    fn test_power_display_vs_required() {
+        let required = power(1, 1, 2);
+        assert_eq!(
+            power(1, 0, 1).fmt_vs(&required).to_string(),
+            "L1/1 P0/1 S1/2 (need P1 S1)"
+        );
+        assert_eq!(
+            power(1, 1, 2).fmt_vs(&required).to_string(),
+            "L1/1 P1/1 S2/2"
+        );
+        assert_eq!(Power::ZERO.fmt_vs(&Power::ZERO).to_string(), "Ø");
+    }
+
+    /// Puzzle with a single entity in the main room and a laser which can target it
+    fn single_entity_puzzle(condition: PowerCondition) -> Puzzle {
+        let mut builder = crate::levels::puzzle_basis("condition", 0);
+        let main = builder.room_id_by_name("main");
+        // [2] Entity under test
+        builder.add_entity(
+            main,
+            Entity {
+                condition,
+                ..Default::default()
+            },
+        );
+        // [3] Laser
+        builder.add_entity(main, crate::levels::laser(vec![EntityId(2)]));
+        builder.build().unwrap()
+    }
+
+    fn offers_player_power(puzzle: &Puzzle, state: &PuzzleState) -> bool {
+        puzzle.actions(state).iter().any(|action| {
+            matches!(action, Action::ProvidePlayerPower { target: Some(target) } if *target == EntityId(2))
+        })
+    }
+
+    const PLAYER_ON: Action = Action::ProvidePlayerPower {
+        target: Some(EntityId(2)),
+    };
+    const PLAYER_OFF: Action = Action::ProvidePlayerPower { target: None };
+    const PLAYER_ON_RIFT: Action = Action::ProvidePlayerPower {
+        target: Some(EntityId(1)),
+    };
+    const LASER_ON: Action = Action::SetTarget {
+        entity: EntityId(3),
+        target: Some(EntityId(2)),
+    };
+    const LASER_OFF: Action = Action::SetTarget {
+        entity: EntityId(3),
+        target: None,
+    };
+
+    #[test]
+    fn test_condition_any_of() {
+        let puzzle =
+            single_entity_puzzle(PowerCondition::AnyOf(vec![power(0, 1, 0), power(1, 0, 0)]));
+        let mut state = puzzle.initalize();
+        assert!(!state.entities[2].is_active);
+        assert!(offers_player_power(&puzzle, &state));
+
+        // the laser alone is enough and player power would not change anything
+        state.apply(&puzzle, &LASER_ON);
+        assert!(state.entities[2].is_active);
+        assert!(!offers_player_power(&puzzle, &state));
+
+        // deactivates when no alternative is met anymore
+        state.apply(&puzzle, &LASER_OFF);
+        assert!(!state.entities[2].is_active);
+
+        // the other alternative keeps it active while the laser is toggled
+        state.apply(&puzzle, &PLAYER_ON);
+        assert!(state.entities[2].is_active);
+        state.apply(&puzzle, &LASER_ON);
+        state.apply(&puzzle, &PLAYER_OFF);
+        assert!(state.entities[2].is_active);
+        state.apply(&puzzle, &LASER_OFF);
+        assert!(!state.entities[2].is_active);
+    }
+
+    #[test]
+    fn test_condition_not() {
+        let puzzle = single_entity_puzzle(PowerCondition::Not(Box::new(PowerCondition::Power {
+            latch: false,
+            power: power(0, 1, 0),
+        })));
+        let mut state = puzzle.initalize();
+        assert!(state.entities[2].is_active);
+
+        // laser power is not part of the inner condition
+        state.apply(&puzzle, &LASER_ON);
+        assert!(state.entities[2].is_active);
+
+        state.apply(&puzzle, &PLAYER_ON);
+        assert!(!state.entities[2].is_active);
+        state.apply(&puzzle, &PLAYER_OFF);
+        assert!(state.entities[2].is_active);
+    }
+
+    #[test]
+    fn test_condition_latch() {
+        // a latched entity stays on, player power is still offered as in the original solver
+        let puzzle = single_entity_puzzle(PowerCondition::Power {
+            latch: true,
+            power: power(0, 1, 0),
+        });
+        let mut state = puzzle.initalize();
+        state.apply(&puzzle, &PLAYER_ON);
+        state.apply(&puzzle, &PLAYER_OFF);
+        assert!(state.entities[2].is_active);
+        assert!(offers_player_power(&puzzle, &state));
+        state.apply(&puzzle, &PLAYER_ON);
+        assert!(state.entities[2].is_active);
+
+        // an inverted latch turns the entity off for good
+        let puzzle = single_entity_puzzle(PowerCondition::Not(Box::new(PowerCondition::Power {
+            latch: true,
+            power: power(0, 1, 0),
+        })));
+        let mut state = puzzle.initalize();
+        assert!(state.entities[2].is_active);
+        state.apply(&puzzle, &PLAYER_ON);
+        state.apply(&puzzle, &PLAYER_OFF);
+        assert!(!state.entities[2].is_active);
+        assert!(!offers_player_power(&puzzle, &state));
+    }
+
+    #[test]
+    fn test_switch_chain_deactivates_when_laser_retargets() {
+        let relay = |target: EntityId| Entity {
+            condition: PowerCondition::Power {
+                latch: false,
+                power: power(0, 0, 1),
+            },
+            target: TargetKind::Fixed(target),
+            effect: Some(Effect::ProvidePower(PowerProvider {
+                kind: PowerKind::Switch,
+            })),
+            ..Default::default()
+        };
+
+        let mut builder = crate::levels::puzzle_basis("chain", 1);
+        let main = builder.room_id_by_name("main");
+        // [2] Switch powered by the laser
+        builder.add_entity(main, crate::levels::switch(EntityId(3)));
+        // [3] Relay
+        builder.add_entity(None, relay(EntityId(4)));
+        // [4] Relay powering the rift
+        builder.add_entity(None, relay(EntityId(1)));
+        // [5] Second switch which the laser can target instead
+        builder.add_entity(main, crate::levels::switch(EntityId(0)));
+        // [6] Laser
+        builder.add_entity(main, crate::levels::laser(vec![EntityId(2), EntityId(5)]));
+        let puzzle = builder.build().unwrap();
+
+        let mut state = puzzle.initalize();
+        state.debug_validate(&puzzle).unwrap();
+
+        let aim = |target| Action::SetTarget {
+            entity: EntityId(6),
+            target: Some(target),
+        };
+        state.apply(&puzzle, &aim(EntityId(2)));
+        for i in 2..=4 {
+            assert!(state.entities[i].is_active, "E{i}");
+        }
+        assert_eq!(state.entities[1].power, power(0, 0, 1));
+
+        // retargeting the laser turns off the whole chain
+        state.apply(&puzzle, &aim(EntityId(5)));
+        for i in 2..=4 {
+            assert!(!state.entities[i].is_active, "E{i}");
+            assert!(state.entities[i].power.is_zero(), "E{i}");
+        }
+        assert!(state.entities[1].power.is_zero());
+        state.debug_validate(&puzzle).unwrap();
+
+        // the rift latches and stays on when the chain turns off again
+        state.apply(&puzzle, &aim(EntityId(2)));
+        state.apply(&puzzle, &PLAYER_ON_RIFT);
+        state.apply(&puzzle, &aim(EntityId(5)));
+        assert!(state.entities[1].is_active);
+        assert_eq!(state.entities[1].power, power(0, 1, 0));
+        state.debug_validate(&puzzle).unwrap();
+
+        // every reachable state is consistent
+        let space = crate::solve::StateSpace::explore(&puzzle, 10000).unwrap();
+        for state in &space.states {
+            state.debug_validate(&puzzle).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_room_distance() {
+        let puzzle = crate::levels::level_1();
+        let main = puzzle.room_id_by_name("main").unwrap();
+        let exit = puzzle.room_id_by_name("exit").unwrap();
+
+        let mut state = puzzle.initalize();
+        assert_eq!(puzzle.room_distance(&state, main, exit), None);
+        assert_eq!(puzzle.room_distance(&state, main, main), Some(0.));
+        assert_eq!(puzzle.room_distance_ignoring_gates(main, exit), Some(1.));
+
+        state.apply(&puzzle, &PLAYER_ON_RIFT);
+        assert_eq!(puzzle.room_distance(&state, main, exit), Some(1.));
+    }
+
+    #[test]
+    fn test_gate_traversal_cost() {
+        let mut builder = crate::levels::puzzle_basis("corridor", 0);
+        let main = builder.room_id_by_name("main").unwrap();
+        let exit = builder.room_id_by_name("exit").unwrap();
+        let hall = builder.add_room("hall");
+        // [2] Open passage
+        builder.add_entity(None, crate::levels::passage());
+        builder.add_gate(
+            main,
+            hall,
+            Gate {
+                traversal_cost: 2.5,
+                ..EntityId(2).into()
+            },
+        );
+        builder.add_gate(hall, exit, EntityId(2));
+        let puzzle = builder.build().unwrap();
+
+        // the detour through the hall is longer than the exit gate
+        let state = puzzle.initalize();
+        assert_eq!(puzzle.room_distance(&state, main, hall), Some(2.5));
+        assert_eq!(puzzle.room_distance(&state, main, exit), Some(3.5));
+        assert_eq!(puzzle.room_distance_ignoring_gates(main, exit), Some(1.));
+
+        // powering the rift and taking the exit gate is cheaper than walking through the hall
+        let solution = crate::solve::astar(&puzzle, &Default::default()).unwrap();
+        assert_eq!(solution.cost, 1 + 3);
+        let distances = crate::solve::RoomDistances::new(&puzzle);
+        assert_eq!(distances.step(main, hall), Some(2.5));
+        assert_eq!(distances.get(main, hall), Some(2.));
+        assert!(matches!(
+            solution.actions[0],
+            Action::ProvidePlayerPower { .. }
+        ));
+    }
+}
@@ -0,0 +1,123 @@
+//! Interactive play of a puzzle on the command line
+//!
+//! The current state and the numbered actions are printed after every step. A number applies
+//! the action, `u` undoes the last action, `s` prints the optimal remaining actions and `q` quits.
+
+use crate::{
+    Action, Puzzle, PuzzleState, UndoToken,
+    solve::{ActionCostModel, astar_from},
+};
+use std::io::{self, BufRead, Write};
+
+/// How a play session ended
+#[derive(Debug, Clone)]
+pub enum PlayOutcome {
+    /// The player reached the win room with these actions
+    Won { actions: Vec<Action> },
+
+    /// The player quit or the input ended
+    Quit,
+}
+
+/// Plays a puzzle with commands read line by line from `input`
+pub fn play(puzzle: &Puzzle, input: impl BufRead, mut out: impl Write) -> io::Result<PlayOutcome> {
+    let mut state = puzzle.initalize();
+    let mut history: Vec<(Action, UndoToken)> = Vec::new();
+    let mut lines = input.lines();
+
+    loop {
+        if state.player_room() == puzzle.win_room() {
+            writeln!(out, "Solved in {} actions!", history.len())?;
+            return Ok(PlayOutcome::Won {
+                actions: history.into_iter().map(|(action, _)| action).collect(),
+            });
+        }
+
+        let actions = puzzle.actions(&state);
+        print_state(&mut out, puzzle, &state, &actions)?;
+        write!(out, "> ")?;
+        out.flush()?;
+
+        let Some(line) = lines.next().transpose()? else {
+            return Ok(PlayOutcome::Quit);
+        };
+        match line.trim() {
+            "q" => return Ok(PlayOutcome::Quit),
+            "u" => match history.pop() {
+                Some((action, token)) => {
+                    state.undo(puzzle, token);
+                    writeln!(out, "Undo {}", puzzle.fmt_action(&action))?;
+                }
+                None => writeln!(out, "Nothing to undo")?,
+            },
+            "s" => match astar_from(puzzle, &state, &ActionCostModel::default()) {
+                Some(solution) => {
+                    writeln!(out, "Optimal remaining actions (cost {}):", solution.cost)?;
+                    for action in &solution.actions {
+                        writeln!(out, "  {}", puzzle.fmt_action(action))?;
+                    }
+                }
+                None => writeln!(out, "No solution from this state")?,
+            },
+            cmd => match cmd
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| actions.get(n.wrapping_sub(1)))
+            {
+                Some(action) => {
+                    let token = state.apply_with_undo(puzzle, action);
+                    history.push((action.clone(), token));
+                }
+                None => writeln!(out, "Unknown command '{cmd}'")?,
+            },
+        }
+    }
+}
+
+fn print_state(
+    out: &mut impl Write,
+    puzzle: &Puzzle,
+    state: &PuzzleState,
+    actions: &[Action],
+) -> io::Result<()> {
+    writeln!(out)?;
+    writeln!(out, "{state}")?;
+    for (i, action) in actions.iter().enumerate() {
+        writeln!(out, "  {}: {}", i + 1, puzzle.fmt_action(action))?;
+    }
+    writeln!(out, "  u: undo, s: solve, q: quit")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::level_1;
+
+    fn play_str(puzzle: &Puzzle, input: &str) -> (PlayOutcome, String) {
+        let mut out = Vec::new();
+        let outcome = play(puzzle, input.as_bytes(), &mut out).unwrap();
+        (outcome, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_play_level_1() {
+        let puzzle = level_1();
+
+        // level 1: power the rift, then walk out
+        let (outcome, out) = play_str(&puzzle, "x\nu\ns\n1\nu\n1\n1\n");
+        assert!(out.contains("Unknown command 'x'"));
+        assert!(out.contains("Nothing to undo"));
+        assert!(out.contains("Optimal remaining actions (cost 4):"));
+        assert!(out.contains("Undo ProvidePlayerPower → Rift"));
+        assert!(out.contains("Solved in 2 actions!"));
+        let PlayOutcome::Won { actions } = outcome else {
+            panic!("not solved: {out}");
+        };
+        assert_eq!(actions.len(), 2);
+
+        let (outcome, _) = play_str(&puzzle, "1\nq\n");
+        assert!(matches!(outcome, PlayOutcome::Quit));
+        let (outcome, _) = play_str(&puzzle, "");
+        assert!(matches!(outcome, PlayOutcome::Quit));
+    }
+}
@@ -0,0 +1,1025 @@
+//! Cost-weighted puzzle solving
+//!
+//! Breadth-first expansion finds the solution with the fewest actions. Walking between rooms
+//! takes the player longer than flipping a target though, so actions are weighted by an
+//! [ActionCostModel] and solved with A*.
+//!
+//! [count_minimal_solutions] finds all solutions of minimal cost to check whether a puzzle can
+//! only be solved as intended.
+//!
+//! [expand] and [expand_parallel] count expansions and solutions without storing the state graph.
+//! [expand_to_graph] keeps the graph, e.g. to export it with [crate::export].
+
+use crate::{Action, Puzzle, PuzzleState, RoomId};
+use petgraph::{
+    algo::dijkstra,
+    graph::{Graph, NodeIndex},
+    visit::EdgeRef,
+};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt,
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+/// Cost of each kind of action. Moves are scaled by [crate::Gate::traversal_cost].
+#[derive(Debug, Clone)]
+pub struct ActionCostModel {
+    /// Cost per room the player walks through
+    pub move_per_room: usize,
+
+    pub provide_player_power: usize,
+
+    pub set_target: usize,
+
+    /// Cost of picking up or depositing a power token
+    pub handle_token: usize,
+}
+
+impl Default for ActionCostModel {
+    fn default() -> Self {
+        Self {
+            move_per_room: 3,
+            provide_player_power: 1,
+            set_target: 1,
+            handle_token: 1,
+        }
+    }
+}
+
+/// Optimal action sequence found by the solver
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub actions: Vec<Action>,
+    pub cost: usize,
+
+    /// Number of states which were expanded
+    pub visited: usize,
+}
+
+/// Walking distances between rooms ignoring whether gates are open, see
+/// [Puzzle::room_distance_ignoring_gates]
+#[derive(Debug, Clone)]
+pub struct RoomDistances {
+    paths: HashMap<(RoomId, RoomId), f64>,
+
+    /// Cheapest gate between two neighboring rooms
+    steps: HashMap<(RoomId, RoomId), f64>,
+}
+
+impl RoomDistances {
+    pub fn new(puzzle: &Puzzle) -> Self {
+        let graph = &puzzle.room_graph;
+        let mut paths = HashMap::new();
+        for a in graph.node_indices() {
+            for (b, d) in dijkstra(graph, a, None, |edge| edge.weight().traversal_cost) {
+                paths.insert((RoomId(a), RoomId(b)), d);
+            }
+        }
+
+        let mut steps = HashMap::new();
+        for edge in graph.edge_references() {
+            let cost = edge.weight().traversal_cost;
+            for key in [
+                (RoomId(edge.source()), RoomId(edge.target())),
+                (RoomId(edge.target()), RoomId(edge.source())),
+            ] {
+                steps
+                    .entry(key)
+                    .and_modify(|c: &mut f64| *c = c.min(cost))
+                    .or_insert(cost);
+            }
+        }
+
+        Self { paths, steps }
+    }
+
+    /// Shortest distance from `a` to `b`
+    pub fn get(&self, a: RoomId, b: RoomId) -> Option<f64> {
+        self.paths.get(&(a, b)).copied()
+    }
+
+    /// Distance of a single move between two neighboring rooms
+    pub fn step(&self, a: RoomId, b: RoomId) -> Option<f64> {
+        self.steps.get(&(a, b)).copied()
+    }
+}
+
+/// Integer costs of moves under an [ActionCostModel]
+///
+/// The cost of passing a gate is `move_per_room * traversal_cost` rounded down. The heuristic
+/// sums the same integer costs along the shortest path which keeps it admissible.
+#[derive(Debug, Clone)]
+pub struct MoveCosts {
+    /// Cheapest move between two neighboring rooms
+    steps: HashMap<(RoomId, RoomId), usize>,
+
+    /// Cheapest walk from a room to the win room ignoring whether gates are open
+    to_win: HashMap<RoomId, usize>,
+}
+
+impl MoveCosts {
+    /// Panics if the cost model turns a gate into a free move, e.g. for a traversal cost of 0.2
+    /// and 3 per room.
+    pub fn new(puzzle: &Puzzle, cost: &ActionCostModel) -> Self {
+        let graph = &puzzle.room_graph;
+        let gate_cost = |traversal_cost: f64| {
+            let scaled = (cost.move_per_room as f64 * traversal_cost).floor() as usize;
+            assert!(
+                scaled > 0,
+                "MoveCosts::new: traversal cost {traversal_cost} gives a free move"
+            );
+            scaled
+        };
+
+        let mut steps = HashMap::new();
+        for edge in graph.edge_references() {
+            let scaled = gate_cost(edge.weight().traversal_cost);
+            for key in [
+                (RoomId(edge.source()), RoomId(edge.target())),
+                (RoomId(edge.target()), RoomId(edge.source())),
+            ] {
+                steps
+                    .entry(key)
+                    .and_modify(|c: &mut usize| *c = (*c).min(scaled))
+                    .or_insert(scaled);
+            }
+        }
+
+        let to_win = dijkstra(graph, *puzzle.win_room, None, |edge| {
+            gate_cost(edge.weight().traversal_cost)
+        })
+        .into_iter()
+        .map(|(room, d)| (RoomId(room), d))
+        .collect();
+
+        Self { steps, to_win }
+    }
+
+    /// Cost of a single move between two neighboring rooms
+    pub fn step(&self, a: RoomId, b: RoomId) -> Option<usize> {
+        self.steps.get(&(a, b)).copied()
+    }
+}
+
+impl ActionCostModel {
+    pub fn cost(&self, moves: &MoveCosts, state: &PuzzleState, action: &Action) -> usize {
+        match action {
+            Action::MovePlayer { room } => moves
+                .step(state.player_room, *room)
+                .unwrap_or(self.move_per_room),
+            Action::ProvidePlayerPower { .. } => self.provide_player_power,
+            Action::SetTarget { .. } => self.set_target,
+            Action::PickUp { .. } | Action::Deposit { .. } => self.handle_token,
+        }
+    }
+
+    /// Lower bound on the cost to reach the win room: walking there as if all gates were open
+    fn heuristic(&self, moves: &MoveCosts, state: &PuzzleState) -> usize {
+        moves.to_win.get(&state.player_room).copied().unwrap_or(0)
+    }
+}
+
+/// Finds the solution with the lowest total action cost
+pub fn astar(puzzle: &Puzzle, cost: &ActionCostModel) -> Option<Solution> {
+    astar_from(puzzle, &puzzle.initalize(), cost)
+}
+
+/// Like [astar] but starts from the given state
+pub fn astar_from(
+    puzzle: &Puzzle,
+    start: &PuzzleState,
+    cost: &ActionCostModel,
+) -> Option<Solution> {
+    let moves = MoveCosts::new(puzzle, cost);
+
+    let start = start.clone();
+    let mut states = vec![start.clone()];
+    let mut index_of = HashMap::from([(start.clone(), 0)]);
+    let mut best_cost = vec![0];
+    let mut came_from: Vec<Option<(usize, Action)>> = vec![None];
+    let mut closed = vec![false];
+
+    let mut open = BinaryHeap::new();
+    open.push(Reverse((cost.heuristic(&moves, &start), 0_usize, 0_usize)));
+
+    let mut visited = 0;
+    while let Some(Reverse((_, current_cost, current))) = open.pop() {
+        if closed[current] || current_cost > best_cost[current] {
+            continue;
+        }
+        closed[current] = true;
+        visited += 1;
+
+        let state = states[current].clone();
+        if state.player_room == puzzle.win_room {
+            let mut actions = Vec::new();
+            let mut ix = current;
+            while let Some((prev, action)) = &came_from[ix] {
+                actions.push(action.clone());
+                ix = *prev;
+            }
+            actions.reverse();
+            return Some(Solution {
+                actions,
+                cost: current_cost,
+                visited,
+            });
+        }
+
+        for action in puzzle.actions(&state) {
+            let next = state.branch(puzzle, &action);
+            let next_cost = current_cost + cost.cost(&moves, &state, &action);
+
+            let next_ix = match index_of.get(&next) {
+                Some(&ix) => {
+                    if closed[ix] || next_cost >= best_cost[ix] {
+                        continue;
+                    }
+                    ix
+                }
+                None => {
+                    let ix = states.len();
+                    index_of.insert(next.clone(), ix);
+                    states.push(next.clone());
+                    best_cost.push(usize::MAX);
+                    came_from.push(None);
+                    closed.push(false);
+                    ix
+                }
+            };
+
+            best_cost[next_ix] = next_cost;
+            came_from[next_ix] = Some((current, action));
+            let estimate = next_cost + cost.heuristic(&moves, &next);
+            open.push(Reverse((estimate, next_cost, next_ix)));
+        }
+    }
+
+    None
+}
+
+/// All solutions of minimal cost, see [count_minimal_solutions]
+#[derive(Debug, Clone)]
+pub struct MinimalSolutionReport {
+    /// Cost of the minimal solutions. None if there is no solution.
+    pub cost: Option<usize>,
+
+    /// Number of distinct action sequences with minimal cost
+    pub count: usize,
+
+    /// One of the minimal solutions
+    pub example: Option<Vec<Action>>,
+
+    /// Actions which are part of some minimal solution but not of the reference solution of the
+    /// puzzle. Empty if the puzzle has no reference solution.
+    pub shortcut_actions: Vec<Action>,
+
+    /// Number of states which were expanded
+    pub visited: usize,
+
+    /// True if the search stopped after expanding `budget` states. The report is incomplete.
+    pub aborted: bool,
+}
+
+impl MinimalSolutionReport {
+    pub fn is_unique(&self) -> bool {
+        self.count == 1 && !self.aborted
+    }
+}
+
+/// Finds all solutions with minimal cost under the default [ActionCostModel]
+///
+/// Like [astar] but every state keeps all predecessors which reach it with its best cost. The
+/// search continues until the remaining frontier can only lead to more expensive solutions.
+/// States are reopened if they are reached with a lower cost later on. At most `budget` states
+/// are expanded.
+pub fn count_minimal_solutions(puzzle: &Puzzle, budget: usize) -> MinimalSolutionReport {
+    let cost = ActionCostModel::default();
+    let moves = MoveCosts::new(puzzle, &cost);
+
+    let start = puzzle.initalize();
+    let mut states = vec![start.clone()];
+    let mut index_of = HashMap::from([(start.clone(), 0)]);
+    let mut best_cost = vec![0];
+    let mut came_from: Vec<Vec<(usize, Action)>> = vec![Vec::new()];
+
+    let mut open = BinaryHeap::new();
+    open.push(Reverse((cost.heuristic(&moves, &start), 0_usize, 0_usize)));
+
+    let mut report = MinimalSolutionReport {
+        cost: None,
+        count: 0,
+        example: None,
+        shortcut_actions: Vec::new(),
+        visited: 0,
+        aborted: false,
+    };
+    let mut wins = Vec::new();
+
+    while let Some(Reverse((estimate, current_cost, current))) = open.pop() {
+        if report.cost.is_some_and(|c| estimate > c) {
+            break;
+        }
+        if current_cost > best_cost[current] {
+            continue;
+        }
+        if report.visited >= budget {
+            report.aborted = true;
+            break;
+        }
+        report.visited += 1;
+
+        let state = states[current].clone();
+        if state.player_room == puzzle.win_room {
+            report.cost.get_or_insert(current_cost);
+            if !wins.contains(&current) {
+                wins.push(current);
+            }
+            continue;
+        }
+
+        for action in puzzle.actions(&state) {
+            let next = state.branch(puzzle, &action);
+            let next_cost = current_cost + cost.cost(&moves, &state, &action);
+
+            let next_ix = match index_of.get(&next) {
+                Some(&ix) => {
+                    if next_cost > best_cost[ix] {
+                        continue;
+                    }
+                    if next_cost == best_cost[ix] {
+                        // equally good path to a known state
+                        if !came_from[ix].contains(&(current, action.clone())) {
+                            came_from[ix].push((current, action));
+                        }
+                        continue;
+                    }
+                    ix
+                }
+                None => {
+                    let ix = states.len();
+                    index_of.insert(next.clone(), ix);
+                    states.push(next.clone());
+                    best_cost.push(usize::MAX);
+                    came_from.push(Vec::new());
+                    ix
+                }
+            };
+
+            best_cost[next_ix] = next_cost;
+            came_from[next_ix] = vec![(current, action)];
+            let estimate = next_cost + cost.heuristic(&moves, &next);
+            open.push(Reverse((estimate, next_cost, next_ix)));
+        }
+    }
+
+    let Some(min_cost) = report.cost else {
+        return report;
+    };
+    wins.retain(|&ix| best_cost[ix] == min_cost);
+
+    // number of minimal paths from the start to each state
+    let mut path_counts: HashMap<usize, usize> = HashMap::from([(0, 1)]);
+    let mut order: Vec<usize> = (0..states.len()).collect();
+    order.sort_by_key(|&ix| best_cost[ix]);
+    for ix in order {
+        let count = came_from[ix]
+            .iter()
+            .map(|(prev, _)| path_counts.get(prev).copied().unwrap_or(0))
+            .fold(0_usize, usize::saturating_add);
+        if count > 0 {
+            path_counts.insert(ix, count);
+        }
+    }
+    report.count = wins
+        .iter()
+        .map(|ix| path_counts.get(ix).copied().unwrap_or(0))
+        .fold(0, usize::saturating_add);
+
+    if let Some(&win) = wins.first() {
+        let mut actions = Vec::new();
+        let mut ix = win;
+        while let Some((prev, action)) = came_from[ix].first() {
+            actions.push(action.clone());
+            ix = *prev;
+        }
+        actions.reverse();
+        report.example = Some(actions);
+    }
+
+    if let Some(reference) = puzzle.reference_solution() {
+        // walk back from the winning states over all minimal paths
+        let mut on_path = vec![false; states.len()];
+        let mut stack = wins.clone();
+        while let Some(ix) = stack.pop() {
+            if std::mem::replace(&mut on_path[ix], true) {
+                continue;
+            }
+            for (prev, action) in &came_from[ix] {
+                if !reference.contains(action) && !report.shortcut_actions.contains(action) {
+                    report.shortcut_actions.push(action.clone());
+                }
+                stack.push(*prev);
+            }
+        }
+    }
+
+    report
+}
+
+impl fmt::Display for MinimalSolutionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.cost {
+            Some(cost) => write!(f, "{} with cost {cost}", self.count)?,
+            None => write!(f, "none")?,
+        }
+        if self.aborted {
+            write!(f, " (aborted after {} states)", self.visited)?;
+        }
+        if !self.shortcut_actions.is_empty() {
+            write!(f, ", shortcuts:")?;
+            for action in &self.shortcut_actions {
+                write!(f, " [{action}]")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reachable states of a puzzle found by breadth-first expansion from the initial state
+#[derive(Debug, Clone)]
+pub struct StateSpace {
+    pub states: Vec<PuzzleState>,
+
+    /// Number of actions needed to reach each state
+    pub depth: Vec<usize>,
+
+    /// States which are reached by a single action. States in the win room are not expanded.
+    pub successors: Vec<Vec<usize>>,
+}
+
+impl StateSpace {
+    /// Expands all reachable states. Returns None if there are more than `max_nodes` states.
+    pub fn explore(puzzle: &Puzzle, max_nodes: usize) -> Option<Self> {
+        let start = puzzle.initalize();
+        let mut index_of = HashMap::from([(start.clone(), 0)]);
+        let mut space = StateSpace {
+            states: vec![start],
+            depth: vec![0],
+            successors: vec![Vec::new()],
+        };
+
+        let mut current = 0;
+        while current < space.states.len() {
+            let state = space.states[current].clone();
+            if state.player_room == puzzle.win_room {
+                current += 1;
+                continue;
+            }
+
+            for action in puzzle.actions(&state) {
+                let next = state.branch(puzzle, &action);
+                let next_ix = match index_of.get(&next) {
+                    Some(&ix) => ix,
+                    None => {
+                        if space.states.len() >= max_nodes {
+                            return None;
+                        }
+                        let ix = space.states.len();
+                        index_of.insert(next.clone(), ix);
+                        space.states.push(next);
+                        space.depth.push(space.depth[current] + 1);
+                        space.successors.push(Vec::new());
+                        ix
+                    }
+                };
+                space.successors[current].push(next_ix);
+            }
+
+            current += 1;
+        }
+
+        Some(space)
+    }
+
+    pub fn is_win(&self, puzzle: &Puzzle, ix: usize) -> bool {
+        self.states[ix].player_room == puzzle.win_room
+    }
+
+    /// Number of actions of the shortest solution
+    pub fn solution_depth(&self, puzzle: &Puzzle) -> Option<usize> {
+        (0..self.states.len())
+            .filter(|&ix| self.is_win(puzzle, ix))
+            .map(|ix| self.depth[ix])
+            .min()
+    }
+
+    /// Number of states from which the puzzle can no longer be solved
+    pub fn dead_ends(&self, puzzle: &Puzzle) -> usize {
+        self.can_win(puzzle).iter().filter(|&&ok| !ok).count()
+    }
+
+    /// For each state if the win room can still be reached from it
+    pub fn can_win(&self, puzzle: &Puzzle) -> Vec<bool> {
+        let mut predecessors = vec![Vec::new(); self.states.len()];
+        for (ix, successors) in self.successors.iter().enumerate() {
+            for &next in successors {
+                predecessors[next].push(ix);
+            }
+        }
+
+        let mut can_win: Vec<bool> = (0..self.states.len())
+            .map(|ix| self.is_win(puzzle, ix))
+            .collect();
+        let mut open: Vec<usize> = (0..self.states.len()).filter(|&ix| can_win[ix]).collect();
+        while let Some(ix) = open.pop() {
+            for &prev in &predecessors[ix] {
+                if !can_win[prev] {
+                    can_win[prev] = true;
+                    open.push(prev);
+                }
+            }
+        }
+
+        can_win
+    }
+}
+
+/// Statistics of a breadth-first expansion, see [expand]
+#[derive(Debug, Default, Clone)]
+pub struct ExpansionStats {
+    /// Number of applied actions plus one for the initial state
+    pub expanded: usize,
+
+    /// Number of distinct states in the win room
+    pub solutions: usize,
+
+    /// Depth of the state from which the win room was entered first. This is one less than the
+    /// number of actions of the solution, see [StateSpace::solution_depth].
+    pub first_solution_depth: Option<usize>,
+
+    /// Like `first_solution_depth` but for the longest solution
+    pub max_solution_depth: usize,
+
+    /// A win state with the smallest depth
+    pub first_solution: Option<PuzzleState>,
+
+    /// Value of `expanded` when the first solution was found
+    pub first_solution_index: Option<usize>,
+
+    /// The node budget was exhausted before all states were expanded
+    pub aborted: bool,
+}
+
+impl ExpansionStats {
+    fn add_solution(&mut self, depth: usize, state: &PuzzleState) {
+        self.solutions += 1;
+        if self.first_solution_depth.is_none() {
+            self.first_solution_depth = Some(depth);
+            self.first_solution = Some(state.clone());
+            self.first_solution_index = Some(self.expanded);
+        }
+        self.max_solution_depth = self.max_solution_depth.max(depth);
+    }
+}
+
+/// Expands all states breadth-first. Stops after `max_nodes` expansions.
+pub fn expand(puzzle: &Puzzle, max_nodes: usize) -> ExpansionStats {
+    let start = puzzle.initalize();
+    let mut visited = HashSet::from([start.canonical_hash()]);
+    let mut stats = ExpansionStats {
+        expanded: 1,
+        ..Default::default()
+    };
+
+    let mut q = VecDeque::from([(start, 0)]);
+    while let Some((mut current, current_depth)) = q.pop_front() {
+        for action in puzzle.actions(&current) {
+            // Actions are applied in place and undone after the successor was recorded
+            let undo = current.apply_with_undo(puzzle, &action);
+            if visited.insert(current.canonical_hash()) {
+                if current.player_room == puzzle.win_room {
+                    stats.add_solution(current_depth, &current);
+                } else {
+                    q.push_back((current.clone(), current_depth + 1));
+                }
+            }
+            current.undo(puzzle, undo);
+
+            stats.expanded += 1;
+            if stats.expanded >= max_nodes {
+                stats.aborted = true;
+                return stats;
+            }
+        }
+    }
+
+    stats
+}
+
+/// Graph of reachable states with actions as edges, see [expand_to_graph]
+#[derive(Debug, Clone)]
+pub struct StateGraph {
+    pub graph: Graph<PuzzleState, Action>,
+
+    /// States which were not fully expanded because the node budget was exhausted
+    pub truncated: HashSet<NodeIndex>,
+
+    win_room: RoomId,
+}
+
+impl StateGraph {
+    pub fn is_win(&self, ix: NodeIndex) -> bool {
+        self.graph[ix].player_room == self.win_room
+    }
+
+    pub fn is_truncated(&self, ix: NodeIndex) -> bool {
+        self.truncated.contains(&ix)
+    }
+}
+
+/// Expands states breadth-first into a graph with at most `max_nodes` states
+///
+/// States in the win room are not expanded. When the budget is exhausted the remaining states are
+/// kept as leaves and marked as truncated.
+pub fn expand_to_graph(puzzle: &Puzzle, max_nodes: usize) -> StateGraph {
+    let mut out = StateGraph {
+        graph: Graph::new(),
+        truncated: HashSet::new(),
+        win_room: puzzle.win_room,
+    };
+
+    let start = puzzle.initalize();
+    let mut index_of = HashMap::from([(start.canonical_hash(), out.graph.add_node(start))]);
+
+    let mut q = VecDeque::from([NodeIndex::new(0)]);
+    while let Some(from_ix) = q.pop_front() {
+        let mut current = out.graph[from_ix].clone();
+        for action in puzzle.actions(&current) {
+            let undo = current.apply_with_undo(puzzle, &action);
+            let state_ix = match index_of.get(&current.canonical_hash()) {
+                Some(&ix) => Some(ix),
+                None if out.graph.node_count() < max_nodes => {
+                    let ix = out.graph.add_node(current.clone());
+                    index_of.insert(current.canonical_hash(), ix);
+                    if current.player_room != puzzle.win_room {
+                        q.push_back(ix);
+                    }
+                    Some(ix)
+                }
+                None => None,
+            };
+            current.undo(puzzle, undo);
+
+            match state_ix {
+                Some(ix) => {
+                    out.graph.add_edge(from_ix, ix, action);
+                }
+                None => {
+                    // Out of budget: this state and all states not yet expanded stay leaves
+                    out.truncated.insert(from_ix);
+                    out.truncated.extend(q.drain(..));
+                    return out;
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// Number of frontier states a worker takes at once
+const FRONTIER_CHUNK: usize = 16;
+
+/// Like [expand] but expands each depth level with multiple threads
+///
+/// All workers draw from one shared budget of `max_nodes` expansions. Workers take chunks of the
+/// frontier until it is exhausted, thus the expansion order differs between runs. Levels are
+/// expanded one after another which keeps solution depths and counts identical to [expand]
+/// unless the budget is exhausted. The index of the first solution is only known per level and
+/// refers to the end of the level on which it was found.
+pub fn expand_parallel(puzzle: &Puzzle, max_nodes: usize, threads: usize) -> ExpansionStats {
+    let threads = threads.max(1);
+
+    let start = puzzle.initalize();
+    let visited = ShardedHashSet::new(threads * 4);
+    visited.insert(start.canonical_hash());
+
+    let mut stats = ExpansionStats {
+        expanded: 1,
+        ..Default::default()
+    };
+    // counts the initial state like `expanded`
+    let spent = AtomicUsize::new(1);
+    let aborted = AtomicBool::new(false);
+
+    let mut frontier = vec![start];
+    let mut depth = 0;
+    while !frontier.is_empty() {
+        let next_chunk = AtomicUsize::new(0);
+        let results: Vec<LevelExpansion> = thread::scope(|s| {
+            let workers: Vec<_> = (0..threads)
+                .map(|_| {
+                    let (frontier, visited, next_chunk, spent, aborted) =
+                        (&frontier, &visited, &next_chunk, &spent, &aborted);
+                    s.spawn(move || {
+                        let mut out = LevelExpansion::default();
+                        loop {
+                            let begin = next_chunk.fetch_add(FRONTIER_CHUNK, Ordering::Relaxed);
+                            if begin >= frontier.len() || aborted.load(Ordering::Relaxed) {
+                                return out;
+                            }
+                            let end = (begin + FRONTIER_CHUNK).min(frontier.len());
+                            for state in &frontier[begin..end] {
+                                let mut current = state.clone();
+                                for action in puzzle.actions(state) {
+                                    if spent.fetch_add(1, Ordering::Relaxed) >= max_nodes {
+                                        aborted.store(true, Ordering::Relaxed);
+                                        return out;
+                                    }
+                                    out.expanded += 1;
+
+                                    let undo = current.apply_with_undo(puzzle, &action);
+                                    if visited.insert(current.canonical_hash()) {
+                                        if current.player_room == puzzle.win_room {
+                                            out.solutions.push(current.clone());
+                                        } else {
+                                            out.next.push(current.clone());
+                                        }
+                                    }
+                                    current.undo(puzzle, undo);
+                                }
+                            }
+                        }
+                    })
+                })
+                .collect();
+            workers.into_iter().map(|w| w.join().unwrap()).collect()
+        });
+
+        let mut solutions = Vec::new();
+        frontier = Vec::new();
+        for result in results {
+            stats.expanded += result.expanded;
+            solutions.extend(result.solutions);
+            frontier.extend(result.next);
+        }
+
+        // Pick the reported solution independent of the expansion order
+        solutions.sort_by_key(|state| state.canonical_hash());
+        for state in &solutions {
+            stats.add_solution(depth, state);
+        }
+
+        if aborted.load(Ordering::Relaxed) {
+            stats.aborted = true;
+            return stats;
+        }
+        depth += 1;
+    }
+
+    stats
+}
+
+/// States found by one worker while expanding one depth level
+#[derive(Default)]
+struct LevelExpansion {
+    expanded: usize,
+    solutions: Vec<PuzzleState>,
+    next: Vec<PuzzleState>,
+}
+
+/// Set of state hashes which can be shared between threads. Each shard has its own lock.
+struct ShardedHashSet {
+    shards: Vec<Mutex<HashSet<u64>>>,
+}
+
+impl ShardedHashSet {
+    fn new(shard_count: usize) -> Self {
+        Self {
+            shards: (0..shard_count.max(1))
+                .map(|_| Mutex::new(HashSet::new()))
+                .collect(),
+        }
+    }
+
+    /// Returns true if the hash was not present
+    fn insert(&self, hash: u64) -> bool {
+        let shard = (hash % self.shards.len() as u64) as usize;
+        self.shards[shard].lock().unwrap().insert(hash)
+    }
+}
+
+impl fmt::Display for ExpansionStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.aborted {
+            return writeln!(f, "Aborted due to maximum number of nodes reached");
+        }
+        writeln!(f, "Total node expansion: {}", self.expanded)?;
+        writeln!(f, "Total solutions: {}", self.solutions)?;
+        match self.first_solution_depth {
+            Some(depth) => {
+                writeln!(
+                    f,
+                    "First solution depth: {depth} (actions before the win room)"
+                )?;
+                writeln!(
+                    f,
+                    "Max solution depth: {} (actions before the win room)",
+                    self.max_solution_depth
+                )
+            }
+            None => writeln!(f, "No solution found"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::{level_1, level_2, level_3, level_4, level_5};
+
+    #[test]
+    fn test_astar_level_3_costs_more_than_level_1() {
+        let cost = ActionCostModel::default();
+        let easy = astar(&level_1(), &cost).unwrap();
+        let hard = astar(&level_3(), &cost).unwrap();
+        assert!(hard.cost > easy.cost, "{} <= {}", hard.cost, easy.cost);
+        assert!(hard.visited > easy.visited);
+    }
+
+    #[test]
+    fn test_astar_solution_replays_to_win() {
+        let puzzle = level_3();
+        let cost = ActionCostModel::default();
+        let solution = astar(&puzzle, &cost).unwrap();
+
+        let moves = MoveCosts::new(&puzzle, &cost);
+        let mut state = puzzle.initalize();
+        let mut total = 0;
+        for action in &solution.actions {
+            total += cost.cost(&moves, &state, action);
+            state = state.branch(&puzzle, action);
+        }
+        assert_eq!(state.player_room, puzzle.win_room);
+        assert_eq!(total, solution.cost);
+    }
+
+    /// Corridor of three gates with the given traversal cost from the start to the exit
+    fn corridor(traversal_cost: f64) -> Result<Puzzle, crate::PuzzleBuildError> {
+        let mut builder = crate::PuzzleBuilder::new("corridor");
+        let rooms = ["start", "a", "b", "exit"].map(|name| builder.add_room(name));
+        let passage = builder.add_entity(None, crate::levels::passage());
+        for pair in rooms.windows(2) {
+            builder.add_gate(
+                pair[0],
+                pair[1],
+                crate::Gate {
+                    traversal_cost,
+                    ..passage.into()
+                },
+            );
+        }
+        builder.set_player_start(rooms[0]);
+        builder.set_win_room(rooms[3]);
+        builder.build()
+    }
+
+    #[test]
+    fn test_move_costs_admissible() {
+        // 3 * 1.4 per gate: rounding each move and flooring the total would overestimate
+        let puzzle = corridor(1.4).unwrap();
+        let cost = ActionCostModel::default();
+        let moves = MoveCosts::new(&puzzle, &cost);
+        let solution = astar(&puzzle, &cost).unwrap();
+        assert_eq!(solution.cost, 3 * 4);
+
+        let mut state = puzzle.initalize();
+        let mut remaining = solution.cost;
+        for action in &solution.actions {
+            assert!(cost.heuristic(&moves, &state) <= remaining);
+            remaining -= cost.cost(&moves, &state, action);
+            state = state.branch(&puzzle, action);
+        }
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_invalid_traversal_costs() {
+        for traversal_cost in [0., -1., f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                corridor(traversal_cost),
+                Err(crate::PuzzleBuildError::InvalidTraversalCost(_))
+            ));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "free move")]
+    fn test_move_costs_reject_free_moves() {
+        MoveCosts::new(&corridor(0.2).unwrap(), &ActionCostModel::default());
+    }
+
+    #[test]
+    fn test_minimal_solutions() {
+        let report = count_minimal_solutions(&level_1(), 1000);
+        assert_eq!(report.count, 1, "{report}");
+        assert!(report.is_unique());
+        assert!(report.shortcut_actions.is_empty());
+        assert_eq!(report.cost, Some(4));
+        assert_eq!(report.example.as_deref(), level_1().reference_solution());
+
+        // two lasers can power the two rift switches in either assignment
+        let puzzle = level_2();
+        let report = count_minimal_solutions(&puzzle, 10000);
+        let best = astar(&puzzle, &ActionCostModel::default()).unwrap();
+        assert_eq!(report.cost, Some(best.cost));
+        assert!(report.count > 1, "{report}");
+
+        assert!(count_minimal_solutions(&puzzle, 1).aborted);
+    }
+
+    #[test]
+    fn test_state_space_level_4() {
+        let puzzle = level_4();
+        let space = StateSpace::explore(&puzzle, 10000).unwrap();
+        let solution = astar(
+            &puzzle,
+            &ActionCostModel {
+                move_per_room: 1,
+                provide_player_power: 1,
+                set_target: 1,
+                handle_token: 1,
+            },
+        )
+        .unwrap();
+        assert_eq!(space.solution_depth(&puzzle), Some(solution.actions.len()));
+        assert!(space.dead_ends(&puzzle) < space.states.len());
+
+        assert!(StateSpace::explore(&puzzle, 10).is_none());
+    }
+
+    #[test]
+    fn test_expand_level_5_counts() {
+        // reference numbers of the original solver, they must not change with refactorings
+        let stats = expand(&level_5(), 100000);
+        assert!(!stats.aborted);
+        assert_eq!(stats.expanded, 6874);
+        assert_eq!(stats.solutions, 240);
+        assert_eq!(stats.first_solution_depth, Some(14));
+        assert_eq!(stats.first_solution_index, Some(1420));
+        assert_eq!(stats.max_solution_depth, 29);
+    }
+
+    #[test]
+    fn test_expand_parallel_matches_serial() {
+        for puzzle in [level_2(), level_3()] {
+            let serial = expand(&puzzle, 100000);
+            assert!(!serial.aborted);
+            for threads in [1, 3] {
+                let parallel = expand_parallel(&puzzle, 100000, threads);
+                assert!(!parallel.aborted);
+                assert_eq!(parallel.first_solution_depth, serial.first_solution_depth);
+                assert_eq!(parallel.max_solution_depth, serial.max_solution_depth);
+                assert_eq!(parallel.solutions, serial.solutions);
+                assert_eq!(parallel.expanded, serial.expanded);
+            }
+        }
+
+        assert!(expand_parallel(&level_3(), 100, 2).aborted);
+
+        // the budget is shared between all workers
+        for threads in [1, 4] {
+            let stats = expand_parallel(&level_3(), 100, threads);
+            assert!(stats.aborted);
+            assert!(stats.expanded <= 100, "{}", stats.expanded);
+        }
+    }
+
+    #[test]
+    fn test_expand_to_graph_truncates() {
+        let puzzle = level_2();
+        let full = expand_to_graph(&puzzle, 100000);
+        assert!(full.truncated.is_empty());
+        assert_eq!(
+            full.graph
+                .node_indices()
+                .filter(|&ix| full.is_win(ix))
+                .count(),
+            expand(&puzzle, 100000).solutions
+        );
+
+        let partial = expand_to_graph(&puzzle, 10);
+        assert_eq!(partial.graph.node_count(), 10);
+        assert!(!partial.truncated.is_empty());
+        for &ix in &partial.truncated {
+            assert!(!partial.is_win(ix));
+        }
+    }
+}
@@ -0,0 +1,682 @@
+use crate::{
+    Action, DEFAULT_GATE_TRAVERSAL_COST, Effect, Entity, EntityId, Gate, Power, PowerCondition,
+    PowerKind, PowerProvider, Puzzle, PuzzleBuildError, PuzzleBuilder, TargetKind,
+};
+use petgraph::Direction;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+/// Document format of a puzzle
+///
+/// Rooms and entities are referenced by name so that specs can be edited by hand. Entities are
+/// listed in the order of their [EntityId].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PuzzleSpec {
+    pub name: String,
+    pub rooms: Vec<String>,
+    pub gates: Vec<GateSpec>,
+
+    /// Condition and effect shared by all entities of an archetype, keyed by archetype name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub archetypes: BTreeMap<String, ArchetypeSpec>,
+
+    pub entities: Vec<EntitySpec>,
+    pub win_room: String,
+    pub player_start: String,
+
+    /// Solution intended by the designer, see [PuzzleBuilder::set_reference_solution]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference_solution: Option<Vec<ActionSpec>>,
+}
+
+/// Gate between two rooms which is open while the gate entity is active
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GateSpec {
+    pub rooms: [String; 2],
+    pub entity: String,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub closes_behind: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_way: Option<OneWaySpec>,
+
+    #[serde(
+        default = "default_traversal_cost",
+        skip_serializing_if = "is_default_traversal_cost"
+    )]
+    pub traversal_cost: f64,
+}
+
+fn default_traversal_cost() -> f64 {
+    DEFAULT_GATE_TRAVERSAL_COST
+}
+
+fn is_default_traversal_cost(cost: &f64) -> bool {
+    *cost == DEFAULT_GATE_TRAVERSAL_COST
+}
+
+/// Direction in which a one-way gate can be passed relative to the order of its rooms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OneWaySpec {
+    /// From the first to the second room
+    Forward,
+    /// From the second to the first room
+    Backward,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchetypeSpec {
+    #[serde(default)]
+    pub condition: ConditionSpec,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<EffectSpec>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntitySpec {
+    pub name: String,
+
+    /// Room in which the player can interact with the entity
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room: Option<String>,
+
+    /// If the archetype is listed in [PuzzleSpec::archetypes] the condition and effect default to
+    /// the ones of the archetype
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archetype: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    /// Defaults to the condition of the archetype or [ConditionSpec::Never]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<ConditionSpec>,
+
+    #[serde(default)]
+    pub target: TargetSpec,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<EffectSpec>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ConditionSpec {
+    #[default]
+    Never,
+    Always,
+    Power {
+        #[serde(default)]
+        latch: bool,
+        power: Power,
+    },
+    AnyOf(Vec<Power>),
+    Not(Box<ConditionSpec>),
+}
+
+impl From<&PowerCondition> for ConditionSpec {
+    fn from(condition: &PowerCondition) -> Self {
+        match condition {
+            PowerCondition::Never => ConditionSpec::Never,
+            PowerCondition::Always => ConditionSpec::Always,
+            PowerCondition::Power { latch, power } => ConditionSpec::Power {
+                latch: *latch,
+                power: *power,
+            },
+            PowerCondition::AnyOf(alternatives) => ConditionSpec::AnyOf(alternatives.clone()),
+            PowerCondition::Not(inner) => ConditionSpec::Not(Box::new(inner.as_ref().into())),
+        }
+    }
+}
+
+impl From<&ConditionSpec> for PowerCondition {
+    fn from(condition: &ConditionSpec) -> Self {
+        match condition {
+            ConditionSpec::Never => PowerCondition::Never,
+            ConditionSpec::Always => PowerCondition::Always,
+            ConditionSpec::Power { latch, power } => PowerCondition::Power {
+                latch: *latch,
+                power: *power,
+            },
+            ConditionSpec::AnyOf(alternatives) => PowerCondition::AnyOf(alternatives.clone()),
+            ConditionSpec::Not(inner) => PowerCondition::Not(Box::new(inner.as_ref().into())),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TargetSpec {
+    #[default]
+    None,
+    Fixed(String),
+    Changable(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum EffectSpec {
+    ProvidePower { kind: Vec<PowerKindSpec> },
+    GrantToken { kind: Vec<PowerKindSpec> },
+}
+
+impl From<&Effect> for EffectSpec {
+    fn from(effect: &Effect) -> Self {
+        match effect {
+            Effect::ProvidePower(pp) => EffectSpec::ProvidePower {
+                kind: PowerKindSpec::from_kinds(pp.kind),
+            },
+            Effect::GrantToken(kind) => EffectSpec::GrantToken {
+                kind: PowerKindSpec::from_kinds(*kind),
+            },
+        }
+    }
+}
+
+impl From<&EffectSpec> for Effect {
+    fn from(effect: &EffectSpec) -> Self {
+        match effect {
+            EffectSpec::ProvidePower { kind } => Effect::ProvidePower(PowerProvider {
+                kind: PowerKindSpec::to_kinds(kind),
+            }),
+            EffectSpec::GrantToken { kind } => Effect::GrantToken(PowerKindSpec::to_kinds(kind)),
+        }
+    }
+}
+
+/// Action with rooms and entities referenced by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ActionSpec {
+    MovePlayer {
+        room: String,
+    },
+    ProvidePlayerPower {
+        #[serde(default)]
+        target: Option<String>,
+    },
+    SetTarget {
+        entity: String,
+        #[serde(default)]
+        target: Option<String>,
+    },
+    PickUp {
+        entity: String,
+    },
+    Deposit {
+        kind: Vec<PowerKindSpec>,
+        target: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PowerKindSpec {
+    Laser,
+    Player,
+    Switch,
+}
+
+impl PowerKindSpec {
+    const ALL: [PowerKindSpec; 3] = [
+        PowerKindSpec::Laser,
+        PowerKindSpec::Player,
+        PowerKindSpec::Switch,
+    ];
+
+    fn kind(self) -> PowerKind {
+        match self {
+            PowerKindSpec::Laser => PowerKind::Laser,
+            PowerKindSpec::Player => PowerKind::Player,
+            PowerKindSpec::Switch => PowerKind::Switch,
+        }
+    }
+
+    fn from_kinds(kind: PowerKind) -> Vec<PowerKindSpec> {
+        PowerKindSpec::ALL
+            .into_iter()
+            .filter(|k| kind.contains(k.kind()))
+            .collect()
+    }
+
+    fn to_kinds(specs: &[PowerKindSpec]) -> PowerKind {
+        specs
+            .iter()
+            .fold(PowerKind::none(), |acc, k| acc | k.kind())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleSpecError {
+    UnknownRoom(String),
+    UnknownEntity(String),
+    UnknownArchetype(String),
+    DuplicateEntityName(String),
+    Build(PuzzleBuildError),
+}
+
+impl fmt::Display for PuzzleSpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PuzzleSpecError::UnknownRoom(name) => write!(f, "unknown room '{name}'"),
+            PuzzleSpecError::UnknownEntity(name) => write!(f, "unknown entity '{name}'"),
+            PuzzleSpecError::UnknownArchetype(name) => write!(f, "unknown archetype '{name}'"),
+            PuzzleSpecError::DuplicateEntityName(name) => {
+                write!(f, "duplicate entity name '{name}'")
+            }
+            PuzzleSpecError::Build(err) => write!(f, "invalid puzzle: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleSpecError {}
+
+impl From<PuzzleBuildError> for PuzzleSpecError {
+    fn from(err: PuzzleBuildError) -> Self {
+        PuzzleSpecError::Build(err)
+    }
+}
+
+impl Puzzle {
+    /// Creates a spec document of this puzzle. Entities are named after their display name, e.g.
+    /// `rift_switch_2`, and by their ID if they have none, e.g. `E3`.
+    ///
+    /// An archetype is listed once with its condition and effect if all its entities agree on
+    /// them. Otherwise each entity spells out its condition and effect.
+    pub fn to_spec(&self) -> PuzzleSpec {
+        let g = &self.room_graph;
+
+        let mut room_names = vec![String::new(); g.node_count()];
+        for (name, id) in &self.rooms_by_name {
+            room_names[id.index()] = name.clone();
+        }
+
+        let entity_names = spec_entity_names(self);
+        let entity_name = |id: EntityId| entity_names[*id].clone();
+
+        let mut entity_rooms = vec![None; self.entities.len()];
+        for room in g.node_indices() {
+            for entity in &g[room].entities {
+                entity_rooms[**entity] = Some(room_names[room.index()].clone());
+            }
+        }
+
+        let mut archetypes = BTreeMap::new();
+        let mut inconsistent = Vec::new();
+        for entity in &self.entities {
+            let Some(name) = &entity.archetype else {
+                continue;
+            };
+            let archetype = ArchetypeSpec {
+                condition: (&entity.condition).into(),
+                effect: entity.effect.as_ref().map(Into::into),
+            };
+            match archetypes.get(name) {
+                None => {
+                    archetypes.insert(name.clone(), archetype);
+                }
+                Some(existing) if *existing != archetype => inconsistent.push(name.clone()),
+                Some(_) => {}
+            }
+        }
+        for name in inconsistent {
+            archetypes.remove(&name);
+        }
+
+        let entities = self
+            .entities
+            .iter()
+            .zip(entity_rooms)
+            .enumerate()
+            .map(|(i, (entity, room))| {
+                let registered = entity
+                    .archetype
+                    .as_ref()
+                    .is_some_and(|name| archetypes.contains_key(name));
+                EntitySpec {
+                    name: entity_name(EntityId(i)),
+                    room,
+                    archetype: entity.archetype.clone(),
+                    display_name: entity.display_name.clone(),
+                    condition: (!registered).then(|| (&entity.condition).into()),
+                    target: match &entity.target {
+                        TargetKind::None => TargetSpec::None,
+                        TargetKind::Fixed(target) => TargetSpec::Fixed(entity_name(*target)),
+                        TargetKind::Changable(targets) => TargetSpec::Changable(
+                            targets.iter().copied().map(entity_name).collect(),
+                        ),
+                    },
+                    effect: if registered {
+                        None
+                    } else {
+                        entity.effect.as_ref().map(Into::into)
+                    },
+                }
+            })
+            .collect();
+
+        let gates = g
+            .edge_indices()
+            .map(|edge| {
+                let (a, b) = g.edge_endpoints(edge).unwrap();
+                let gate = &g[edge];
+                GateSpec {
+                    rooms: [room_names[a.index()].clone(), room_names[b.index()].clone()],
+                    entity: entity_name(gate.entity),
+                    closes_behind: gate.closes_behind,
+                    one_way: gate.one_way.map(|dir| match dir {
+                        Direction::Outgoing => OneWaySpec::Forward,
+                        Direction::Incoming => OneWaySpec::Backward,
+                    }),
+                    traversal_cost: gate.traversal_cost,
+                }
+            })
+            .collect();
+
+        let reference_solution = self.reference_solution().map(|actions| {
+            actions
+                .iter()
+                .map(|action| match action {
+                    Action::MovePlayer { room } => ActionSpec::MovePlayer {
+                        room: room_names[room.index()].clone(),
+                    },
+                    Action::ProvidePlayerPower { target } => ActionSpec::ProvidePlayerPower {
+                        target: target.map(entity_name),
+                    },
+                    Action::SetTarget { entity, target } => ActionSpec::SetTarget {
+                        entity: entity_name(*entity),
+                        target: target.map(entity_name),
+                    },
+                    Action::PickUp { entity } => ActionSpec::PickUp {
+                        entity: entity_name(*entity),
+                    },
+                    Action::Deposit { kind, target } => ActionSpec::Deposit {
+                        kind: PowerKindSpec::from_kinds(*kind),
+                        target: entity_name(*target),
+                    },
+                })
+                .collect()
+        });
+
+        PuzzleSpec {
+            name: self.name.clone(),
+            win_room: room_names[self.win_room.index()].clone(),
+            player_start: room_names[self.initial_state.player_room.index()].clone(),
+            rooms: room_names,
+            gates,
+            archetypes,
+            entities,
+            reference_solution,
+        }
+    }
+
+    /// Creates a puzzle from a spec document. All references are resolved and validated.
+    pub fn from_spec(spec: &PuzzleSpec) -> Result<Puzzle, PuzzleSpecError> {
+        let mut entity_ids = HashMap::new();
+        for (i, entity) in spec.entities.iter().enumerate() {
+            if entity_ids
+                .insert(entity.name.as_str(), EntityId(i))
+                .is_some()
+            {
+                return Err(PuzzleSpecError::DuplicateEntityName(entity.name.clone()));
+            }
+        }
+        let entity_id = |name: &str| {
+            entity_ids
+                .get(name)
+                .copied()
+                .ok_or_else(|| PuzzleSpecError::UnknownEntity(name.into()))
+        };
+
+        let mut builder = PuzzleBuilder::new(spec.name.clone());
+        for (name, archetype) in &spec.archetypes {
+            builder.add_archetype(
+                name.clone(),
+                Entity {
+                    condition: (&archetype.condition).into(),
+                    effect: archetype.effect.as_ref().map(Into::into),
+                    ..Default::default()
+                },
+            );
+        }
+        for room in &spec.rooms {
+            builder.add_room(room.clone());
+        }
+        let room_id = |builder: &PuzzleBuilder, name: &str| {
+            builder
+                .room_id_by_name(name)
+                .ok_or_else(|| PuzzleSpecError::UnknownRoom(name.into()))
+        };
+
+        for gate in &spec.gates {
+            let a = room_id(&builder, &gate.rooms[0])?;
+            let b = room_id(&builder, &gate.rooms[1])?;
+            builder.add_gate(
+                a,
+                b,
+                Gate {
+                    entity: entity_id(&gate.entity)?,
+                    closes_behind: gate.closes_behind,
+                    one_way: gate.one_way.map(|dir| match dir {
+                        OneWaySpec::Forward => Direction::Outgoing,
+                        OneWaySpec::Backward => Direction::Incoming,
+                    }),
+                    traversal_cost: gate.traversal_cost,
+                },
+            );
+        }
+
+        for entity in &spec.entities {
+            let room = entity
+                .room
+                .as_deref()
+                .map(|name| room_id(&builder, name))
+                .transpose()?;
+
+            let mut out = match &entity.archetype {
+                Some(name) => match builder.archetype(name) {
+                    Some(archetype) => archetype.clone(),
+                    None if entity.condition.is_none() => {
+                        return Err(PuzzleSpecError::UnknownArchetype(name.clone()));
+                    }
+                    None => Entity {
+                        archetype: Some(name.clone()),
+                        ..Default::default()
+                    },
+                },
+                None => Entity::default(),
+            };
+            if let Some(condition) = &entity.condition {
+                out.condition = condition.into();
+            }
+            if let Some(effect) = &entity.effect {
+                out.effect = Some(effect.into());
+            }
+            out.display_name = entity.display_name.clone();
+
+            out.target = match &entity.target {
+                TargetSpec::None => TargetKind::None,
+                TargetSpec::Fixed(target) => TargetKind::Fixed(entity_id(target)?),
+                TargetSpec::Changable(targets) => TargetKind::Changable(
+                    targets
+                        .iter()
+                        .map(|target| entity_id(target))
+                        .collect::<Result<_, _>>()?,
+                ),
+            };
+
+            builder.add_entity(room, out);
+        }
+
+        builder.set_win_room(room_id(&builder, &spec.win_room)?);
+        builder.set_player_start(room_id(&builder, &spec.player_start)?);
+
+        if let Some(actions) = &spec.reference_solution {
+            let actions = actions
+                .iter()
+                .map(|action| {
+                    Ok(match action {
+                        ActionSpec::MovePlayer { room } => Action::MovePlayer {
+                            room: room_id(&builder, room)?,
+                        },
+                        ActionSpec::ProvidePlayerPower { target } => Action::ProvidePlayerPower {
+                            target: target.as_deref().map(entity_id).transpose()?,
+                        },
+                        ActionSpec::SetTarget { entity, target } => Action::SetTarget {
+                            entity: entity_id(entity)?,
+                            target: target.as_deref().map(entity_id).transpose()?,
+                        },
+                        ActionSpec::PickUp { entity } => Action::PickUp {
+                            entity: entity_id(entity)?,
+                        },
+                        ActionSpec::Deposit { kind, target } => Action::Deposit {
+                            kind: PowerKindSpec::to_kinds(kind),
+                            target: entity_id(target)?,
+                        },
+                    })
+                })
+                .collect::<Result<_, PuzzleSpecError>>()?;
+            builder.set_reference_solution(actions);
+        }
+
+        Ok(builder.build()?)
+    }
+}
+
+/// Unique names of entities in a spec derived from their display names
+fn spec_entity_names(puzzle: &Puzzle) -> Vec<String> {
+    let mut names: Vec<String> = (0..puzzle.entities.len())
+        .map(|i| match &puzzle.entities[i].display_name {
+            Some(name) => name
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect(),
+            None => EntityId(i).to_string(),
+        })
+        .collect();
+
+    // display names set by hand may collide
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for name in &names {
+        *counts.entry(name.clone()).or_default() += 1;
+    }
+    for (i, name) in names.iter_mut().enumerate() {
+        if counts[name.as_str()] > 1 {
+            *name = format!("{name}_{}", EntityId(i));
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::all_levels;
+
+    #[test]
+    fn test_spec_round_trip_levels() {
+        for puzzle in all_levels() {
+            let spec = puzzle.to_spec();
+            let json = serde_json::to_string_pretty(&spec).unwrap();
+            let loaded: PuzzleSpec = serde_json::from_str(&json).unwrap();
+            assert_eq!(loaded, spec);
+
+            let restored = Puzzle::from_spec(&loaded).unwrap();
+            assert_eq!(restored.to_spec(), spec);
+            assert_eq!(restored.to_string(), puzzle.to_string());
+        }
+    }
+
+    #[test]
+    fn test_spec_archetypes() {
+        let puzzle = crate::levels::level_3();
+        let spec = puzzle.to_spec();
+        assert_eq!(
+            spec.archetypes.keys().collect::<Vec<_>>(),
+            ["Exit Gate", "Laser", "Overgrowth", "Rift", "Rift Switch"]
+        );
+        assert!(
+            spec.entities
+                .iter()
+                .all(|entity| entity.condition.is_none())
+        );
+        assert_eq!(
+            spec.entities[3].display_name.as_deref(),
+            Some("Rift Switch 2")
+        );
+
+        assert_eq!(
+            spec.entities
+                .iter()
+                .map(|entity| entity.name.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "exit_gate",
+                "rift",
+                "rift_switch_1",
+                "rift_switch_2",
+                "rift_switch_3",
+                "laser_1",
+                "laser_2",
+                "laser_3",
+                "overgrowth",
+            ]
+        );
+        assert_eq!(spec.gates[0].entity, "exit_gate");
+
+        let mut spec = spec;
+        spec.archetypes.remove("Laser");
+        assert_eq!(
+            Puzzle::from_spec(&spec).unwrap_err(),
+            PuzzleSpecError::UnknownArchetype("Laser".into())
+        );
+    }
+
+    #[test]
+    fn test_spec_reference_solution() {
+        let puzzle = crate::levels::level_1();
+        let spec = puzzle.to_spec();
+        assert_eq!(
+            spec.reference_solution,
+            Some(vec![
+                ActionSpec::ProvidePlayerPower {
+                    target: Some("rift".into())
+                },
+                ActionSpec::MovePlayer {
+                    room: "exit".into()
+                },
+            ])
+        );
+
+        let json = serde_json::to_string(&spec).unwrap();
+        let restored = Puzzle::from_spec(&serde_json::from_str(&json).unwrap()).unwrap();
+        assert_eq!(restored.reference_solution(), puzzle.reference_solution());
+
+        let mut spec = spec;
+        spec.reference_solution = Some(vec![ActionSpec::PickUp {
+            entity: "token".into(),
+        }]);
+        assert_eq!(
+            Puzzle::from_spec(&spec).unwrap_err(),
+            PuzzleSpecError::UnknownEntity("token".into())
+        );
+    }
+
+    #[test]
+    fn test_spec_unknown_target() {
+        let mut spec = crate::levels::level_2().to_spec();
+        assert_eq!(spec.entities[4].name, "laser_1");
+        spec.entities[4].target =
+            TargetSpec::Changable(vec!["rift_switch_1".into(), "rift_switch".into()]);
+        assert_eq!(
+            Puzzle::from_spec(&spec).unwrap_err(),
+            PuzzleSpecError::UnknownEntity("rift_switch".into())
+        );
+    }
+}
@@ -0,0 +1,427 @@
+//! Camera bookmarks for reviewing levels
+//!
+//! While free flying in ghost mode, K stores the current camera pose as a bookmark of the level
+//! the player is in. Number keys 1-9 blend the camera to the corresponding bookmark. Bookmarks
+//! are stored per level next to the save games and can be exported as a camera spline.
+
+use crate::{level::*, player::*, save::*};
+use atom::prelude::*;
+use candy::{camera::*, input::*, time::*, utils::WindowDef};
+use eyre::Result;
+use gems::lerp_angle;
+use glam::{Vec2, Vec3Swizzles};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Duration of the camera blend when jumping to a bookmark
+pub const BOOKMARK_BLEND_DURATION: f32 = 0.5;
+
+/// Default duration of a segment of an exported camera spline
+pub const BOOKMARK_SPLINE_SEGMENT_DURATION: f32 = 4.0;
+
+/// A stored camera pose
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmark {
+    pub name: String,
+    pub position: Vec2,
+    pub yaw: f32,
+}
+
+/// Bookmarks of one level in the order they were stored
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraBookmarkList {
+    pub bookmarks: Vec<CameraBookmark>,
+}
+
+impl CameraBookmarkList {
+    /// Sidecar file with the bookmarks of a level
+    pub fn path(dir: impl AsRef<Path>, level: &str) -> PathBuf {
+        dir.as_ref().join(format!("bookmarks-{level}.json"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_json_atomic(self, path)
+    }
+
+    /// Stores a pose and returns the index of the new bookmark
+    pub fn add(&mut self, position: Vec2, yaw: f32) -> usize {
+        let index = self.bookmarks.len();
+        self.bookmarks.push(CameraBookmark {
+            name: format!("bookmark {}", index + 1),
+            position,
+            yaw,
+        });
+        index
+    }
+
+    /// Renames a bookmark. Returns false if there is no bookmark with this index.
+    pub fn rename(&mut self, index: usize, name: impl Into<String>) -> bool {
+        let Some(bookmark) = self.bookmarks.get_mut(index) else {
+            return false;
+        };
+        bookmark.name = name.into();
+        true
+    }
+
+    /// Camera spline which visits all bookmarks in order. Segments without a duration use
+    /// [BOOKMARK_SPLINE_SEGMENT_DURATION].
+    pub fn to_spline(&self, segment_durations: &[f32]) -> CameraSpline {
+        let mut time = 0.;
+        let keys = self
+            .bookmarks
+            .iter()
+            .enumerate()
+            .map(|(i, bookmark)| {
+                if i > 0 {
+                    time += segment_durations
+                        .get(i - 1)
+                        .copied()
+                        .unwrap_or(BOOKMARK_SPLINE_SEGMENT_DURATION);
+                }
+                CameraSplineKey {
+                    time,
+                    position: bookmark.position,
+                    yaw: bookmark.yaw,
+                }
+            })
+            .collect();
+        CameraSpline { keys }
+    }
+}
+
+/// Key of a camera spline
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraSplineKey {
+    /// Time since the start of the spline in seconds
+    pub time: f32,
+    pub position: Vec2,
+    pub yaw: f32,
+}
+
+/// Camera path through a sequence of poses
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CameraSpline {
+    pub keys: Vec<CameraSplineKey>,
+}
+
+impl CameraSpline {
+    /// Time of the last key
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0., |key| key.time)
+    }
+
+    /// Pose of the camera at the given time. Each segment eases in and out.
+    pub fn pose(&self, time: f32) -> Option<(Vec2, f32)> {
+        let first = self.keys.first()?;
+        let i = self.keys.partition_point(|key| key.time <= time);
+        if i == 0 {
+            return Some((first.position, first.yaw));
+        }
+
+        let a = &self.keys[i - 1];
+        let Some(b) = self.keys.get(i) else {
+            return Some((a.position, a.yaw));
+        };
+        let q = ((time - a.time) / (b.time - a.time)).clamp(0., 1.);
+        let q = q * q * (3. - 2. * q);
+        Some((a.position.lerp(b.position, q), lerp_angle(a.yaw, b.yaw, q)))
+    }
+}
+
+/// Smooth camera transition from one pose to another
+#[derive(Debug, Clone)]
+pub struct CameraBlend {
+    from: (Vec2, f32),
+    to: (Vec2, f32),
+    elapsed: f32,
+}
+
+impl CameraBlend {
+    pub fn new(from: (Vec2, f32), to: (Vec2, f32)) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: 0.,
+        }
+    }
+
+    pub fn advance(&mut self, dt: f32) {
+        self.elapsed += dt;
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.elapsed >= BOOKMARK_BLEND_DURATION
+    }
+
+    /// Position and yaw of the camera. The blend eases in and out.
+    pub fn pose(&self) -> (Vec2, f32) {
+        let q = (self.elapsed / BOOKMARK_BLEND_DURATION).clamp(0., 1.);
+        let q = q * q * (3. - 2. * q);
+        (
+            self.from.0.lerp(self.to.0, q),
+            lerp_angle(self.from.1, self.to.1, q),
+        )
+    }
+}
+
+/// Bookmarks of the level the player is in
+#[derive(Singleton, Default)]
+pub struct CameraBookmarks {
+    dir: PathBuf,
+    level: Option<String>,
+    pub list: CameraBookmarkList,
+    blend: Option<CameraBlend>,
+}
+
+impl CameraBookmarks {
+    /// Renames a bookmark of the current level and stores the change
+    pub fn rename(&mut self, index: usize, name: impl Into<String>) -> bool {
+        if !self.list.rename(index, name) {
+            return false;
+        }
+        self.store();
+        true
+    }
+
+    fn store(&self) {
+        let Some(level) = &self.level else {
+            return;
+        };
+        if let Err(err) = self.list.save(CameraBookmarkList::path(&self.dir, level)) {
+            log::error!("failed to save camera bookmarks: {err:?}");
+        }
+    }
+
+    fn switch_level(&mut self, level: &str) {
+        if self.level.as_deref() == Some(level) {
+            return;
+        }
+
+        let path = CameraBookmarkList::path(&self.dir, level);
+        self.list = if path.exists() {
+            CameraBookmarkList::load(&path).unwrap_or_else(|err| {
+                log::warn!("failed to load camera bookmarks: {err:?}");
+                CameraBookmarkList::default()
+            })
+        } else {
+            CameraBookmarkList::default()
+        };
+        self.level = Some(level.to_owned());
+    }
+
+    fn log_list(&self) {
+        for (i, bookmark) in self.list.bookmarks.iter().enumerate() {
+            log::info!("bookmark {}: {}", i + 1, bookmark.name);
+        }
+    }
+}
+
+/// Receives the bookmark keys
+#[derive(Component, Default)]
+pub struct CameraBookmarkInput {
+    store: bool,
+    jump: Option<usize>,
+}
+
+impl CameraBookmarkInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if let InputEvent::KeyboardInput {
+            state: ElementState::Pressed,
+            code,
+            ..
+        } = msg.event
+        {
+            match code {
+                KeyCode::KeyK => self.store = true,
+                KeyCode::Digit1 => self.jump = Some(0),
+                KeyCode::Digit2 => self.jump = Some(1),
+                KeyCode::Digit3 => self.jump = Some(2),
+                KeyCode::Digit4 => self.jump = Some(3),
+                KeyCode::Digit5 => self.jump = Some(4),
+                KeyCode::Digit6 => self.jump = Some(5),
+                KeyCode::Digit7 => self.jump = Some(6),
+                KeyCode::Digit8 => self.jump = Some(7),
+                KeyCode::Digit9 => self.jump = Some(8),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl atom::Agent for CameraBookmarkInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(CameraBookmarkInput::on_input_event);
+    }
+}
+
+/// Camera bookmarks for level review in ghost mode
+pub struct CameraBookmarkMocca;
+
+impl Mocca for CameraBookmarkMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyCameraMocca>();
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PlayerMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<CameraBookmarkInput>();
+        atom::register_agent_components::<CameraBookmarkInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(CameraBookmarks {
+            dir: PathBuf::from(SAVE_DIR),
+            ..Default::default()
+        });
+        world.run(spawn_camera_bookmark_input);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<CameraBookmarkInput, _>);
+        world.run(update_camera_bookmarks);
+    }
+}
+
+fn spawn_camera_bookmark_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, CameraBookmarkInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn update_camera_bookmarks(
+    time: Singleton<SimClock>,
+    player: Singleton<Player>,
+    levels: Singleton<LevelSummary>,
+    mut bookmarks: SingletonMut<CameraBookmarks>,
+    mut query_input: Query<&mut CameraBookmarkInput>,
+    query_cam: Query<&CameraMatrices, With<MainCamera>>,
+    mut query_cam_ctrl: Query<&mut FirstPersonCameraController>,
+) {
+    let Some(input) = query_input.single_mut() else {
+        return;
+    };
+    let input = std::mem::take(input);
+    let Some(cam_ctrl) = query_cam_ctrl.single_mut() else {
+        return;
+    };
+
+    // Bookmarks are only used while free flying
+    if !player.cheat_ghost_mode {
+        bookmarks.blend = None;
+        return;
+    }
+
+    if let Some(level) = levels.nearest_level_name(player.eye_position) {
+        bookmarks.switch_level(level);
+    }
+
+    let position = cam_ctrl.position().xy();
+    let yaw = query_cam.single().map_or(0., |cam| {
+        let dir = cam.center_pixel_ray().direction();
+        dir.y.atan2(dir.x)
+    });
+
+    if input.store && bookmarks.level.is_some() {
+        let index = bookmarks.list.add(position, yaw);
+        log::info!("stored camera bookmark {}", index + 1);
+        bookmarks.store();
+        bookmarks.log_list();
+    }
+
+    if let Some(bookmark) = input.jump.and_then(|i| bookmarks.list.bookmarks.get(i)) {
+        log::info!("jump to camera bookmark: {}", bookmark.name);
+        let target = (bookmark.position, bookmark.yaw);
+        bookmarks.blend = Some(CameraBlend::new((position, yaw), target));
+    }
+
+    if let Some(blend) = bookmarks.blend.as_mut() {
+        blend.advance(time.sim_dt_f32());
+        let (position, yaw) = blend.pose();
+        cam_ctrl.set_position_xy(position);
+        cam_ctrl.set_yaw(yaw);
+        if blend.is_done() {
+            bookmarks.blend = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use std::f32::consts::{PI, TAU};
+
+    #[test]
+    fn test_camera_bookmarks_persistence() {
+        let dir = std::env::temp_dir().join(format!("recola-bookmarks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut list = CameraBookmarkList::default();
+        assert_eq!(list.add(Vec2::new(1., 2.), 0.5), 0);
+        assert_eq!(list.add(Vec2::new(3., 4.), -1.0), 1);
+        assert!(list.rename(1, "broken lighting corner"));
+        assert!(!list.rename(2, "missing"));
+
+        let path = CameraBookmarkList::path(&dir, "level_1");
+        list.save(&path).unwrap();
+        assert_eq!(CameraBookmarkList::load(&path).unwrap(), list);
+        assert!(!CameraBookmarkList::path(&dir, "level_2").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_camera_bookmark_blend() {
+        let from = (Vec2::new(0., 0.), 3.0);
+        let to = (Vec2::new(4., 2.), -3.0);
+        let mut blend = CameraBlend::new(from, to);
+        assert_eq!(blend.pose(), from);
+
+        blend.advance(0.5 * BOOKMARK_BLEND_DURATION);
+        let (position, yaw) = blend.pose();
+        assert_abs_diff_eq!(position.x, 2.);
+        assert_abs_diff_eq!(position.y, 1.);
+        // the yaw turns the short way across ±π
+        assert_abs_diff_eq!(yaw.rem_euclid(TAU), PI, epsilon = 1e-5);
+        assert!(!blend.is_done());
+
+        blend.advance(0.5 * BOOKMARK_BLEND_DURATION);
+        assert!(blend.is_done());
+        let (position, yaw) = blend.pose();
+        assert_eq!(position, to.0);
+        assert_abs_diff_eq!(yaw.rem_euclid(TAU), to.1.rem_euclid(TAU), epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_camera_bookmark_spline_order() {
+        let mut list = CameraBookmarkList::default();
+        for i in 0..4 {
+            list.add(Vec2::new(i as f32, 0.), 0.);
+        }
+
+        let spline = list.to_spline(&[1., 2.]);
+        let times: Vec<_> = spline.keys.iter().map(|key| key.time).collect();
+        let xs: Vec<_> = spline.keys.iter().map(|key| key.position.x).collect();
+        assert_eq!(
+            times,
+            vec![0., 1., 3., 3. + BOOKMARK_SPLINE_SEGMENT_DURATION]
+        );
+        assert_eq!(xs, vec![0., 1., 2., 3.]);
+
+        assert_eq!(spline.duration(), 3. + BOOKMARK_SPLINE_SEGMENT_DURATION);
+        assert_eq!(spline.pose(-1.), Some((Vec2::ZERO, 0.)));
+        assert_eq!(spline.pose(0.5).map(|(p, _)| p.x), Some(0.5));
+        assert_eq!(spline.pose(2.), Some((Vec2::new(1.5, 0.), 0.)));
+        assert_eq!(spline.pose(100.), Some((Vec2::new(3., 0.), 0.)));
+        assert_eq!(CameraSpline::default().pose(0.), None);
+    }
+}
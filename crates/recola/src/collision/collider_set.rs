@@ -1,4 +1,4 @@
-use crate::collision::{PosBall3, PosedCuboid, Ray3};
+use crate::collision::{PosBall3, PosedCuboid, Ray3, aabb_overlap};
 use atom::prelude::*;
 use glam::Vec3;
 use magi::geo::Aabb;
@@ -96,6 +96,18 @@ impl CuboidSet {
                 normal,
             })
     }
+
+    /// True if the bounding box of any collider overlaps the given box. This is conservative for
+    /// rotated colliders.
+    pub fn overlaps_aabb(
+        &self,
+        aabb: &Aabb<Vec3>,
+        exclude: Option<Entity>,
+        layer: CollisionLayer,
+    ) -> bool {
+        self.iter_filtered(exclude, layer)
+            .any(|(_, cub)| aabb_overlap(&cub.aabb(), aabb))
+    }
 }
 
 impl Default for CuboidSet {
@@ -5,7 +5,7 @@ use crate::collision::{
 use atom::prelude::*;
 use candy::scene_tree::*;
 use glam::Vec3;
-use magi::gems::ResultLog;
+use magi::{gems::ResultLog, geo::Aabb};
 use std::{
     collections::HashSet,
     ops::Index,
@@ -66,6 +66,15 @@ impl ColliderWorld {
         self.cuboids.raycast(ray, radius, exclude, layer)
     }
 
+    pub fn overlaps_aabb(
+        &self,
+        aabb: &Aabb<Vec3>,
+        exclude: Option<Entity>,
+        layer: CollisionLayer,
+    ) -> bool {
+        self.cuboids.overlaps_aabb(aabb, exclude, layer)
+    }
+
     pub fn closest_exit_multi_ball(
         &self,
         multi_pball: &[PosBall3],
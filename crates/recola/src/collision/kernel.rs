@@ -1,12 +1,23 @@
 use crate::collision::Ray3;
 use glam::{Affine3A, Mat3A, Vec3};
-use magi::sdf::sd_box_n;
+use magi::{geo::Aabb, sdf::sd_box_n};
 
 /// Signed distance between a point and an axis-aligned box
 pub fn aabb_signed_distance(half_size: Vec3, point: Vec3) -> f32 {
     sd_box_n(point, half_size)
 }
 
+/// True if the interiors of two axis-aligned boxes intersect. Boxes which only touch do not
+/// overlap.
+pub fn aabb_overlap(a: &Aabb<Vec3>, b: &Aabb<Vec3>) -> bool {
+    a.min.x < b.max.x
+        && b.min.x < a.max.x
+        && a.min.y < b.max.y
+        && b.min.y < a.max.y
+        && a.min.z < b.max.z
+        && b.min.z < a.max.z
+}
+
 /// Intersects a ray with an axis-aligned box centered at the origin with half-extents `half_size`.
 /// Returns the nearest non-negative hit distance and corresponding surface normal.
 pub fn aabb_raycast(half_size: Vec3, ray: Ray3) -> Option<(f32, Vec3)> {
@@ -147,3 +158,106 @@ pub fn decompose_transform_3(tf: Affine3A) -> Option<(Affine3A, Vec3)> {
         ))
     }
 }
+
+/// Intersects a ray with a capsule around the segment from `a` to `b`. Returns the nearest
+/// non-negative hit distance. If the ray starts inside the capsule the distance is zero.
+pub fn capsule_raycast(a: Vec3, b: Vec3, radius: f32, ray: &Ray3) -> Option<f32> {
+    let origin = ray.origin;
+    let dir = ray.direction();
+
+    let ba = b - a;
+    let oa = origin - a;
+    let baba = ba.dot(ba);
+
+    // Start inside the capsule
+    let s = if baba > 0. {
+        (oa.dot(ba) / baba).clamp(0., 1.)
+    } else {
+        0.
+    };
+    if (oa - s * ba).length_squared() <= radius * radius {
+        return Some(0.);
+    }
+
+    // Hit on the cylinder part. Rays parallel to the axis can only hit the caps.
+    let bard = ba.dot(dir);
+    let baoa = ba.dot(oa);
+    let qa = baba - bard * bard;
+    if qa > 1e-6 * baba {
+        let qb = baba * dir.dot(oa) - baoa * bard;
+        let qc = baba * oa.dot(oa) - baoa * baoa - radius * radius * baba;
+        let h = qb * qb - qa * qc;
+        if h < 0. {
+            return None;
+        }
+        let t = (-qb - h.sqrt()) / qa;
+        let y = baoa + t * bard;
+        if y > 0. && y < baba {
+            return (t >= 0.).then_some(t);
+        }
+    }
+
+    // Hit on one of the spherical caps
+    [a, b]
+        .into_iter()
+        .filter_map(|center| {
+            let oc = origin - center;
+            let qb = dir.dot(oc);
+            let h = qb * qb - (oc.dot(oc) - radius * radius);
+            if h < 0. {
+                return None;
+            }
+            let t = -qb - h.sqrt();
+            (t >= 0.).then_some(t)
+        })
+        .min_by(f32::total_cmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn ray(origin: Vec3, dir: Vec3) -> Ray3 {
+        Ray3::from_origin_direction(origin, dir.normalize()).unwrap()
+    }
+
+    #[test]
+    fn test_capsule_raycast() {
+        let (a, b, r) = (Vec3::new(0., 0., 0.5), Vec3::new(0., 0., 1.5), 0.5);
+
+        // side of the cylinder
+        let t = capsule_raycast(a, b, r, &ray(Vec3::new(-3., 0., 1.), Vec3::X)).unwrap();
+        assert_abs_diff_eq!(t, 2.5, epsilon = 1e-5);
+
+        // top cap from above along the axis
+        let t = capsule_raycast(a, b, r, &ray(Vec3::new(0., 0., 5.), -Vec3::Z)).unwrap();
+        assert_abs_diff_eq!(t, 3.0, epsilon = 1e-5);
+
+        // grazing the bottom cap at an angle
+        let t = capsule_raycast(a, b, r, &ray(Vec3::new(-3., 0., 0.25), Vec3::X)).unwrap();
+        assert_abs_diff_eq!(t, 3. - 0.1875f32.sqrt(), epsilon = 1e-5);
+
+        // passes above and beside
+        assert_eq!(
+            capsule_raycast(a, b, r, &ray(Vec3::new(-3., 0., 2.1), Vec3::X)),
+            None
+        );
+        assert_eq!(
+            capsule_raycast(a, b, r, &ray(Vec3::new(-3., 0.6, 1.), Vec3::X)),
+            None
+        );
+
+        // capsule is behind the ray
+        assert_eq!(
+            capsule_raycast(a, b, r, &ray(Vec3::new(-3., 0., 1.), -Vec3::X)),
+            None
+        );
+
+        // starts inside
+        assert_eq!(
+            capsule_raycast(a, b, r, &ray(Vec3::new(0.2, 0., 1.), Vec3::X)),
+            Some(0.)
+        );
+    }
+}
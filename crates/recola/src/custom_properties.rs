@@ -41,6 +41,13 @@ impl CustomProperties {
         CustomProperties(out)
     }
 
+    pub fn get_bool(&self, id: impl AsRef<str>) -> Option<bool> {
+        match self.0.get(id.as_ref())? {
+            CustomPropertiesValue::Bool(v) => Some(*v),
+            _ => None,
+        }
+    }
+
     pub fn get_integer(&self, id: impl AsRef<str>) -> Option<i64> {
         match self.0.get(id.as_ref())? {
             CustomPropertiesValue::Integer(v) => Some(*v),
@@ -48,6 +55,22 @@ impl CustomProperties {
         }
     }
 
+    /// Gets a float value; integer values are converted.
+    pub fn get_float(&self, id: impl AsRef<str>) -> Option<f64> {
+        match self.0.get(id.as_ref())? {
+            CustomPropertiesValue::Float(v) => Some(*v),
+            CustomPropertiesValue::Integer(v) => Some(*v as f64),
+            _ => None,
+        }
+    }
+
+    pub fn get_string(&self, id: impl AsRef<str>) -> Option<&str> {
+        match self.0.get(id.as_ref())? {
+            CustomPropertiesValue::String(v) => Some(v.as_str()),
+            _ => None,
+        }
+    }
+
     pub fn get_string_list(&self, id: impl AsRef<str>) -> Option<Vec<String>> {
         match self.0.get(id.as_ref())? {
             CustomPropertiesValue::String(v) => Some(v.split(",").map(|s| s.to_owned()).collect()),
@@ -0,0 +1,589 @@
+//! Deterministic demo recording of player inputs
+//!
+//! All input events for the player controllers pass through the [DemoInputGate]. When recording
+//! the player pose at the start, the simulation timestep and the raw input events of every frame
+//! are stored in a [Demo]. When replaying the start pose is restored and the recorded events are
+//! fed to the controllers instead of live input.
+//!
+//! Every [DEMO_HASH_INTERVAL] frames a hash of the player state is stored in the demo. During
+//! replay the hash is recomputed and compared to detect the first frame where the replay diverges
+//! from the recording. The recorded timestep overrides the simulation timestep during replay.
+//!
+//! The demo steps before the camera controller so that the timestep and the input events of a
+//! frame are applied in the same frame. The game has no dev console, thus like the other
+//! developer cheats recording is toggled with F9 and the last recording is replayed with F10.
+
+use crate::{
+    STATIC_SETTINGS,
+    player::*,
+    props::{door::KeyId, rift::RiftLevel},
+};
+use atom::prelude::*;
+use candy::{camera::*, input::*, time::*};
+use eyre::Result;
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Number of frames between two player state hashes
+pub const DEMO_HASH_INTERVAL: usize = 60;
+
+const DEMO_PATH: &str = "recola.demo";
+
+/// A recorded input session
+#[derive(Default, Serialize, Deserialize)]
+pub struct Demo {
+    pub start: DemoStart,
+    pub frames: Vec<DemoFrame>,
+    pub hashes: Vec<DemoFrameHash>,
+}
+
+/// Player pose and progression at the start of a recording
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DemoStart {
+    pub position: Vec2,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub hours: f32,
+    pub hours_target: f32,
+    pub rift_charges: Vec<i64>,
+    pub keys: Vec<i64>,
+}
+
+/// Inputs recorded for a single frame
+#[derive(Clone, Serialize, Deserialize)]
+pub struct DemoFrame {
+    pub dt: f32,
+    pub events: Vec<InputEventMessage>,
+}
+
+/// Hash of the player state at the beginning of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DemoFrameHash {
+    pub frame: usize,
+    pub hash: u64,
+}
+
+/// First frame at which a replay did not reproduce the recorded player state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DemoDivergence {
+    pub frame: usize,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl Demo {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let bytes = std::fs::read(path)?;
+        let (demo, _) = bincode::serde::decode_from_slice(&bytes, bincode::config::standard())?;
+        Ok(demo)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let bytes = bincode::serde::encode_to_vec(self, bincode::config::standard())?;
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+
+    /// Records inputs of the next frame. `hash` is computed from the player state at the
+    /// beginning of the frame and only stored on hash frames.
+    pub fn record(&mut self, dt: f32, events: Vec<InputEventMessage>, hash: u64) {
+        let frame = self.frames.len();
+        if frame % DEMO_HASH_INTERVAL == 0 {
+            self.hashes.push(DemoFrameHash { frame, hash });
+        }
+        self.frames.push(DemoFrame { dt, events });
+    }
+
+    /// Checks the player state hash computed during replay against the recording
+    pub fn verify(&self, frame: usize, hash: u64) -> Option<DemoDivergence> {
+        if frame % DEMO_HASH_INTERVAL != 0 {
+            return None;
+        }
+
+        let expected = self.hashes.get(frame / DEMO_HASH_INTERVAL)?;
+        debug_assert_eq!(expected.frame, frame);
+
+        (expected.hash != hash).then_some(DemoDivergence {
+            frame,
+            expected: expected.hash,
+            actual: hash,
+        })
+    }
+}
+
+/// Replay of a recorded demo
+pub struct DemoPlayback {
+    demo: Demo,
+    frame: usize,
+    divergence: Option<DemoDivergence>,
+}
+
+impl DemoPlayback {
+    pub fn new(demo: Demo) -> Self {
+        Self {
+            demo,
+            frame: 0,
+            divergence: None,
+        }
+    }
+
+    /// First divergence detected so far
+    pub fn divergence(&self) -> Option<DemoDivergence> {
+        self.divergence
+    }
+
+    /// Verifies the player state at the beginning of the next frame and returns the recorded
+    /// timestep and inputs of that frame. Returns None once all frames were replayed.
+    pub fn next_frame(&mut self, hash: u64) -> Option<&DemoFrame> {
+        if self.divergence.is_none() {
+            self.divergence = self.demo.verify(self.frame, hash);
+            if let Some(d) = self.divergence {
+                log::error!(
+                    "demo replay diverged at frame {}: expected {:016x}, actual {:016x}",
+                    d.frame,
+                    d.expected,
+                    d.actual
+                );
+            }
+        }
+
+        let frame = self.demo.frames.get(self.frame)?;
+        self.frame += 1;
+        Some(frame)
+    }
+}
+
+/// Hash over the player position and the progression state of the player
+///
+/// The hash is stored in demo files and thus must not depend on the platform or the Rust version
+/// like [std::hash::DefaultHasher] does.
+pub fn demo_state_hash(
+    position: Vec3,
+    hours: f32,
+    rift_charges: impl IntoIterator<Item = i64>,
+    keys: impl IntoIterator<Item = i64>,
+) -> u64 {
+    let mut rift_charges = rift_charges.into_iter().collect::<Vec<_>>();
+    rift_charges.sort();
+
+    let mut keys = keys.into_iter().collect::<Vec<_>>();
+    keys.sort();
+
+    let mut hasher = Fnv1a::new();
+    for x in position.to_array() {
+        hasher.write(&x.to_le_bytes());
+    }
+    hasher.write(&hours.to_le_bytes());
+    for ids in [rift_charges, keys] {
+        hasher.write(&(ids.len() as u64).to_le_bytes());
+        for id in ids {
+            hasher.write(&id.to_le_bytes());
+        }
+    }
+    hasher.finish()
+}
+
+/// 64 bit FNV-1a hash
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Default)]
+enum DemoMode {
+    #[default]
+    Live,
+    Record(Demo),
+    Replay(DemoPlayback),
+}
+
+/// Current demo recording or replay
+#[derive(Singleton, Default)]
+pub struct DemoState {
+    mode: DemoMode,
+}
+
+impl DemoState {
+    pub fn is_recording(&self) -> bool {
+        matches!(self.mode, DemoMode::Record(_))
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, DemoMode::Replay { .. })
+    }
+
+    /// First divergence detected during the current replay
+    pub fn divergence(&self) -> Option<DemoDivergence> {
+        match &self.mode {
+            DemoMode::Replay(playback) => playback.divergence(),
+            _ => None,
+        }
+    }
+
+    fn toggle_record(&mut self, start: DemoStart) {
+        match std::mem::take(&mut self.mode) {
+            DemoMode::Record(demo) => {
+                match demo.save(DEMO_PATH) {
+                    Ok(()) => log::info!(
+                        "demo recording saved: {DEMO_PATH} ({} frames)",
+                        demo.frames.len()
+                    ),
+                    Err(err) => log::error!("failed to save demo recording: {err:?}"),
+                }
+                self.mode = DemoMode::Live;
+            }
+            _ => {
+                log::info!("demo recording started");
+                self.mode = DemoMode::Record(Demo {
+                    start,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+
+    /// Starts to replay the last recording and returns the pose to restore
+    fn start_replay(&mut self) -> Option<DemoStart> {
+        match Demo::load(DEMO_PATH) {
+            Ok(demo) => {
+                log::info!("demo replay started ({} frames)", demo.frames.len());
+                Some(self.replay(demo))
+            }
+            Err(err) => {
+                log::error!("failed to load demo recording: {err:?}");
+                None
+            }
+        }
+    }
+
+    fn replay(&mut self, demo: Demo) -> DemoStart {
+        let start = demo.start.clone();
+        self.mode = DemoMode::Replay(DemoPlayback::new(demo));
+        start
+    }
+
+    /// Records or replays the inputs of the next frame. `hash` is computed from the player state
+    /// at the beginning of the frame. Returns the timestep and the input events which the player
+    /// controllers use in this frame.
+    fn next_frame(
+        &mut self,
+        dt: f32,
+        live_events: Vec<InputEventMessage>,
+        hash: u64,
+    ) -> (f32, Vec<InputEventMessage>) {
+        match &mut self.mode {
+            DemoMode::Live => (dt, live_events),
+            DemoMode::Record(recording) => {
+                recording.record(dt, live_events.clone(), hash);
+                (dt, live_events)
+            }
+            DemoMode::Replay(playback) => {
+                if let Some(frame) = playback.next_frame(hash) {
+                    return (frame.dt, frame.events.clone());
+                }
+                if playback.divergence().is_none() {
+                    log::info!("demo replay finished without divergence");
+                }
+                self.mode = DemoMode::Live;
+                (dt, live_events)
+            }
+        }
+    }
+}
+
+/// Receives live input events for the player controllers. Events are forwarded or replaced by
+/// recorded events depending on the [DemoState].
+#[derive(Component, Default)]
+pub struct DemoInputGate {
+    live_events: Vec<InputEventMessage>,
+    toggle_record: bool,
+    start_replay: bool,
+}
+
+impl DemoInputGate {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if STATIC_SETTINGS.enable_cheats {
+            match msg.event {
+                InputEvent::KeyboardInput {
+                    state: ElementState::Pressed,
+                    code: KeyCode::F9,
+                    ..
+                } => {
+                    self.toggle_record = true;
+                    return;
+                }
+                InputEvent::KeyboardInput {
+                    state: ElementState::Pressed,
+                    code: KeyCode::F10,
+                    ..
+                } => {
+                    self.start_replay = true;
+                    return;
+                }
+                _ => {}
+            }
+        }
+
+        self.live_events.push(msg);
+    }
+}
+
+impl atom::Agent for DemoInputGate {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(DemoInputGate::on_input_event);
+    }
+}
+
+/// Records and replays player inputs
+pub struct DemoMocca;
+
+impl Mocca for DemoMocca {
+    fn load(mut deps: MoccaDeps) {
+        // Must not depend on the camera as it has to step before the camera controller
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<DemoInputGate>();
+        atom::register_agent_components::<DemoInputGate, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(DemoState::default());
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<DemoInputGate, _>);
+        world.run(forward_demo_input);
+    }
+}
+
+fn forward_demo_input(
+    mut time: SingletonMut<SimClock>,
+    mut player: SingletonMut<Player>,
+    mut demo: SingletonMut<DemoState>,
+    mut query_gate: Query<&mut DemoInputGate>,
+    query_cam: Query<&CameraMatrices, With<MainCamera>>,
+    mut query_cam_ctrl: Query<&mut FirstPersonCameraController>,
+    mut query_input_raycast: Query<&mut InputRaycastController>,
+) {
+    let Some(gate) = query_gate.single_mut() else {
+        return;
+    };
+    let live_events = std::mem::take(&mut gate.live_events);
+
+    let cam_ctrl = query_cam_ctrl
+        .single_mut()
+        .expect("must have FirstPersonCameraController");
+    let input_raycast = query_input_raycast
+        .single_mut()
+        .expect("must have InputRaycastController");
+
+    if std::mem::take(&mut gate.toggle_record) {
+        let (yaw, pitch) = query_cam.single().map_or((0., 0.), |cam| {
+            let dir = cam.center_pixel_ray().direction();
+            (dir.y.atan2(dir.x), dir.z.asin())
+        });
+        demo.toggle_record(DemoStart {
+            position: cam_ctrl.position().xy(),
+            yaw,
+            pitch,
+            hours: player.hours,
+            hours_target: player.hours_target,
+            rift_charges: player.rift_charges.iter().map(|lvl| lvl.0).collect(),
+            keys: player.keys.iter().map(|key| key.0).collect(),
+        });
+    }
+
+    let replay_start = if std::mem::take(&mut gate.start_replay) {
+        demo.start_replay()
+    } else {
+        None
+    };
+    if let Some(start) = replay_start {
+        cam_ctrl.set_position_xy(start.position);
+        cam_ctrl.set_yaw(start.yaw);
+        cam_ctrl.set_pitch(start.pitch);
+
+        // Teleport without collision checks on the way
+        player.previous_position = start.position;
+        player.hours = start.hours;
+        player.hours_target = start.hours_target;
+        player.rift_charges = start.rift_charges.into_iter().map(RiftLevel).collect();
+        player.keys = start.keys.into_iter().map(KeyId).collect();
+    }
+
+    let hash = demo_state_hash(
+        cam_ctrl.position(),
+        player.hours,
+        player.rift_charges.iter().map(|lvl| lvl.0),
+        player.keys.iter().map(|key| key.0),
+    );
+
+    // live input is suppressed while player input is locked
+    let live_events = if player.input_locked {
+        Vec::new()
+    } else {
+        live_events
+    };
+
+    let (dt, events) = demo.next_frame(time.sim_dt_f32(), live_events, hash);
+    if demo.is_replaying() {
+        // the camera controller and all later systems use the recorded timestep
+        time.set_sim_dt(dt);
+    }
+
+    for msg in events {
+        cam_ctrl.on_input_event(msg.clone());
+        input_raycast.on_input_event(msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WALK_SPEED: f32 = 1.4;
+
+    fn fixture_hash(frame: usize) -> u64 {
+        demo_state_hash(Vec3::new(frame as f32 * 0.1, -4.5, 0.), 12.0, [2, 1], [1])
+    }
+
+    fn walk_hash(x: f32) -> u64 {
+        demo_state_hash(Vec3::new(x, -4.5, 0.), 12.0, [1], [1])
+    }
+
+    #[test]
+    fn test_demo_state_hash_ignores_set_order() {
+        assert_eq!(
+            demo_state_hash(Vec3::ONE, 12.0, [1, 2, 3], [3, 1]),
+            demo_state_hash(Vec3::ONE, 12.0, [3, 1, 2], [1, 3])
+        );
+        assert_ne!(
+            demo_state_hash(Vec3::ONE, 12.0, [1], []),
+            demo_state_hash(Vec3::ONE, 13.0, [1], [])
+        );
+    }
+
+    #[test]
+    fn test_demo_state_hash_is_stable() {
+        // stored in demo files, must not change between builds
+        assert_eq!(
+            demo_state_hash(Vec3::ONE, 12.0, [3, 1, 2], [3, 1]),
+            0xec8a_fb6a_cd8d_8518
+        );
+    }
+
+    /// Walks along x with a constant speed using the timestep given by the demo state
+    fn walk(state: &mut DemoState, x: &mut f32, frames: usize, live_dt: f32) -> usize {
+        let mut replayed = 0;
+        for _ in 0..frames {
+            let was_replaying = state.is_replaying();
+            let (dt, _) = state.next_frame(live_dt, Vec::new(), walk_hash(*x));
+            if was_replaying && state.is_replaying() {
+                replayed += 1;
+            }
+            *x += WALK_SPEED * dt;
+        }
+        replayed
+    }
+
+    fn walk_start(x: f32) -> DemoStart {
+        DemoStart {
+            position: Vec2::new(x, -4.5),
+            hours: 12.0,
+            hours_target: 12.0,
+            rift_charges: vec![1],
+            keys: vec![1],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_demo_replay_recorded_fixture() {
+        // record a walk with a varying frame time
+        let mut state = DemoState::default();
+        state.mode = DemoMode::Record(Demo {
+            start: walk_start(0.),
+            ..Default::default()
+        });
+        let mut x = 0.;
+        for frame in 0..200 {
+            let dt = 0.012 + 0.008 * (frame % 7) as f32 / 7.;
+            walk(&mut state, &mut x, 1, dt);
+        }
+        let DemoMode::Record(demo) = std::mem::take(&mut state.mode) else {
+            panic!("must be recording");
+        };
+        let path = std::env::temp_dir().join(format!("recola-demo-{}", std::process::id()));
+        demo.save(&path).unwrap();
+
+        // replay with a fixed live timestep: the start pose is restored and the recorded
+        // timestep is used
+        let start = state.replay(Demo::load(&path).unwrap());
+        assert_eq!(start, walk_start(0.));
+        let mut x = start.position.x;
+        assert_eq!(walk(&mut state, &mut x, 200, 0.016), 200);
+        assert_eq!(state.divergence(), None);
+
+        // the replay finishes and live input takes over
+        walk(&mut state, &mut x, 1, 0.016);
+        assert!(!state.is_replaying());
+
+        // replaying without restoring the start pose diverges at the first frame
+        let mut x = 7.;
+        state.replay(Demo::load(&path).unwrap());
+        walk(&mut state, &mut x, 1, 0.016);
+        assert_eq!(state.divergence().map(|d| d.frame), Some(0));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_demo_replay_without_divergence() {
+        let mut demo = Demo::default();
+        for frame in 0..200 {
+            demo.record(0.016, Vec::new(), fixture_hash(frame));
+        }
+        assert_eq!(demo.hashes.len(), 4);
+
+        for frame in 0..200 {
+            assert_eq!(demo.verify(frame, fixture_hash(frame)), None);
+        }
+    }
+
+    #[test]
+    fn test_demo_replay_reports_divergence() {
+        let mut demo = Demo::default();
+        for frame in 0..200 {
+            demo.record(0.016, Vec::new(), fixture_hash(frame));
+        }
+
+        assert_eq!(demo.verify(61, 0), None);
+        assert_eq!(
+            demo.verify(120, 0),
+            Some(DemoDivergence {
+                frame: 120,
+                expected: fixture_hash(120),
+                actual: 0
+            })
+        );
+    }
+}
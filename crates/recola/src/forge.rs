@@ -0,0 +1,619 @@
+//! In-game prop placement for designers
+//!
+//! Only available if [STATIC_SETTINGS](crate::STATIC_SETTINGS) enables the forge. F2 toggles
+//! forge mode. While active the prop selected from the palette in props.json is previewed where
+//! the player aims and snapped to a grid. Placed props are stored in a forge layer file which is
+//! loaded again on the next run.
+//!
+//! Keys: Q/E select prop, R rotate, -/= cell size, F place, X delete, Ctrl+Z undo, Ctrl+Y redo.
+
+use crate::{collision::*, foundation::*, mechanics::prop_events::*, player::*, save::*};
+use atom::prelude::*;
+use candy::{camera::*, can::*, input::*, material::*, prims::*, scene_tree::*, utils::WindowDef};
+use eyre::Result;
+use glam::{Quat, Vec3, Vec3Swizzles};
+use magi::{color::SRgbU8Color, geo::Aabb};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    f32::consts::PI,
+    path::Path,
+};
+
+/// Rotation applied per rotate key press
+pub const FORGE_YAW_STEP: f32 = PI / 12.0;
+
+/// Number of edits which can be undone
+pub const FORGE_UNDO_CAPACITY: usize = 50;
+
+pub const FORGE_DEFAULT_CELL_SIZE: f32 = 0.5;
+
+const FORGE_MIN_CELL_SIZE: f32 = 0.125;
+const FORGE_MAX_CELL_SIZE: f32 = 4.0;
+
+/// Shrinks the preview footprint such that props resting on the ground are valid
+const FORGE_OVERLAP_MARGIN: f32 = 0.01;
+
+const FORGE_MAX_DISTANCE: f32 = 30.0;
+
+const FORGE_LAYER_FILE: &str = "forge_layer.json";
+
+const FORGE_VALID_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(80, 220, 120);
+const FORGE_INVALID_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(230, 40, 40);
+
+/// Snaps the horizontal position to the closest grid point. Height is kept.
+pub fn snap_to_grid(position: Vec3, cell_size: f32) -> Vec3 {
+    let xy = (position.xy() / cell_size).round() * cell_size;
+    xy.extend(position.z)
+}
+
+/// Yaw angle in [0, 2 pi) after the given number of rotation steps
+pub fn forge_yaw(steps: i32) -> f32 {
+    let steps_per_turn = (2.0 * PI / FORGE_YAW_STEP).round() as i32;
+    steps.rem_euclid(steps_per_turn) as f32 * FORGE_YAW_STEP
+}
+
+/// Volume occupied by a placement which rests on the given position
+pub fn placement_footprint(position: Vec3, cell_size: f32) -> Aabb<Vec3> {
+    let half = 0.5 * cell_size - FORGE_OVERLAP_MARGIN;
+    Aabb::from_points([
+        position + Vec3::new(-half, -half, FORGE_OVERLAP_MARGIN),
+        position + Vec3::new(half, half, cell_size - FORGE_OVERLAP_MARGIN),
+    ])
+}
+
+/// A prop placed in forge mode
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForgePlacement {
+    pub asset: String,
+    pub position: Vec3,
+    pub yaw: f32,
+}
+
+impl ForgePlacement {
+    pub fn transform(&self) -> Transform3 {
+        Transform3::from_translation(self.position).with_rotation(Quat::from_rotation_z(self.yaw))
+    }
+}
+
+/// A reversible change of the forge layer
+#[derive(Debug, Clone, PartialEq)]
+pub enum ForgeEdit {
+    Place { id: u64, placement: ForgePlacement },
+    Delete { id: u64, placement: ForgePlacement },
+}
+
+impl ForgeEdit {
+    pub fn inverse(&self) -> Self {
+        match self.clone() {
+            ForgeEdit::Place { id, placement } => ForgeEdit::Delete { id, placement },
+            ForgeEdit::Delete { id, placement } => ForgeEdit::Place { id, placement },
+        }
+    }
+}
+
+/// All props placed in forge mode
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ForgeLayer {
+    next_id: u64,
+    placements: BTreeMap<u64, ForgePlacement>,
+}
+
+impl ForgeLayer {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_json_atomic(self, path)
+    }
+
+    pub fn placements(&self) -> impl Iterator<Item = (u64, &ForgePlacement)> {
+        self.placements.iter().map(|(&id, p)| (id, p))
+    }
+
+    pub fn place(&mut self, placement: ForgePlacement) -> ForgeEdit {
+        let id = self.next_id;
+        let edit = ForgeEdit::Place { id, placement };
+        self.apply(&edit);
+        edit
+    }
+
+    pub fn delete(&mut self, id: u64) -> Option<ForgeEdit> {
+        let placement = self.placements.get(&id)?.clone();
+        let edit = ForgeEdit::Delete { id, placement };
+        self.apply(&edit);
+        Some(edit)
+    }
+
+    fn apply(&mut self, edit: &ForgeEdit) {
+        match edit {
+            ForgeEdit::Place { id, placement } => {
+                self.placements.insert(*id, placement.clone());
+                self.next_id = self.next_id.max(id + 1);
+            }
+            ForgeEdit::Delete { id, .. } => {
+                self.placements.remove(id);
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks of forge edits
+#[derive(Debug, Default)]
+pub struct ForgeHistory {
+    undo: VecDeque<ForgeEdit>,
+    redo: Vec<ForgeEdit>,
+}
+
+impl ForgeHistory {
+    /// Records an edit which was applied to the layer. The oldest edit is forgotten once
+    /// [FORGE_UNDO_CAPACITY] is reached.
+    pub fn push(&mut self, edit: ForgeEdit) {
+        if self.undo.len() == FORGE_UNDO_CAPACITY {
+            self.undo.pop_front();
+        }
+        self.undo.push_back(edit);
+        self.redo.clear();
+    }
+
+    /// Reverts the last edit and returns the change applied to the layer
+    pub fn undo(&mut self, layer: &mut ForgeLayer) -> Option<ForgeEdit> {
+        let edit = self.undo.pop_back()?;
+        let inverse = edit.inverse();
+        layer.apply(&inverse);
+        self.redo.push(edit);
+        Some(inverse)
+    }
+
+    /// Applies the last undone edit again and returns it
+    pub fn redo(&mut self, layer: &mut ForgeLayer) -> Option<ForgeEdit> {
+        let edit = self.redo.pop()?;
+        layer.apply(&edit);
+        self.undo.push_back(edit.clone());
+        Some(edit)
+    }
+}
+
+/// Marks a prop which was spawned from the forge layer
+#[derive(Component)]
+pub struct ForgeProp {
+    pub id: u64,
+}
+
+#[derive(Singleton)]
+pub struct Forge {
+    pub enabled: bool,
+    pub palette: Vec<String>,
+    pub selected: usize,
+
+    /// Revision of the asset collection the palette was taken from
+    palette_revision: u64,
+
+    pub yaw_steps: i32,
+    pub cell_size: f32,
+    pub layer: ForgeLayer,
+    pub history: ForgeHistory,
+    preview: Option<(Entity, bool)>,
+}
+
+impl Forge {
+    pub fn selected_asset(&self) -> Option<&str> {
+        self.palette.get(self.selected).map(String::as_str)
+    }
+}
+
+/// Receives the forge mode keys
+#[derive(Component, Default)]
+pub struct ForgeInput {
+    toggle: bool,
+    cycle: i32,
+    rotate: i32,
+    cell_size_change: i32,
+    place: bool,
+    delete: bool,
+    undo: bool,
+    redo: bool,
+    ctrl_held: bool,
+}
+
+impl ForgeInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        let InputEvent::KeyboardInput { state, code, .. } = msg.event else {
+            return;
+        };
+
+        if matches!(code, KeyCode::ControlLeft | KeyCode::ControlRight) {
+            self.ctrl_held = state == ElementState::Pressed;
+            return;
+        }
+        if state != ElementState::Pressed {
+            return;
+        }
+
+        match code {
+            KeyCode::F2 => self.toggle = true,
+            KeyCode::KeyQ => self.cycle -= 1,
+            KeyCode::KeyE => self.cycle += 1,
+            KeyCode::KeyR => self.rotate += 1,
+            KeyCode::Minus => self.cell_size_change -= 1,
+            KeyCode::Equal => self.cell_size_change += 1,
+            KeyCode::KeyF => self.place = true,
+            KeyCode::KeyX => self.delete = true,
+            KeyCode::KeyZ if self.ctrl_held => self.undo = true,
+            KeyCode::KeyY if self.ctrl_held => self.redo = true,
+            _ => {}
+        }
+    }
+}
+
+impl atom::Agent for ForgeInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(ForgeInput::on_input_event);
+    }
+}
+
+/// Prop placement with grid snapping and undo
+pub struct ForgeMocca;
+
+impl Mocca for ForgeMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyCameraMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandyMaterialMocca>();
+        deps.depends_on::<CandyPrimsMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CollidersMocca>();
+        deps.depends_on::<FoundationMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<PropEventsMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<ForgeInput>();
+        world.register_component::<ForgeProp>();
+        atom::register_agent_components::<ForgeInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        let path = Path::new(SAVE_DIR).join(FORGE_LAYER_FILE);
+        let layer = if path.exists() {
+            ForgeLayer::load(&path).unwrap_or_else(|err| {
+                log::warn!("failed to load forge layer: {err:?}");
+                ForgeLayer::default()
+            })
+        } else {
+            ForgeLayer::default()
+        };
+
+        let (palette, palette_revision) = world.run(load_forge_palette);
+
+        world.set_singleton(Forge {
+            enabled: false,
+            palette,
+            selected: 0,
+            palette_revision,
+            yaw_steps: 0,
+            cell_size: FORGE_DEFAULT_CELL_SIZE,
+            layer,
+            history: ForgeHistory::default(),
+            preview: None,
+        });
+        world.run(spawn_forge_input);
+        world.run(spawn_forge_layer);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<ForgeInput, _>);
+        world.run(update_forge);
+    }
+}
+
+fn load_forge_palette(watch: Singleton<AssetCollectionWatch>) -> (Vec<String>, u64) {
+    (watch.names().to_vec(), watch.revision())
+}
+
+fn spawn_forge_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, ForgeInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn spawn_forge_layer(mut cmd: Commands, forge: Singleton<Forge>) {
+    for (id, placement) in forge.layer.placements() {
+        spawn_forge_prop(&mut cmd, id, placement);
+    }
+}
+
+fn spawn_forge_prop(cmd: &mut Commands, id: u64, placement: &ForgePlacement) {
+    cmd.spawn((
+        Name::new(format!("forge-{id}")),
+        placement.transform(),
+        AssetInstance(AssetUid::new(placement.asset.to_owned())),
+        ForgeProp { id },
+    ));
+}
+
+fn update_forge(
+    mut cmd: Commands,
+    colliders: Singleton<ColliderWorld>,
+    mut forge: SingletonMut<Forge>,
+    mut events: SingletonMut<PropEvents>,
+    assets: Singleton<AssetCollectionWatch>,
+    mut query_input: Query<&mut ForgeInput>,
+    query_cam: Query<&CameraMatrices, With<MainCamera>>,
+    query_routing: Query<&CollisionRouting>,
+    query_forge_prop: Query<(Entity, &ForgeProp)>,
+    mut query_tf: Query<&mut Transform3>,
+) {
+    let forge = &mut *forge;
+    let Some(input) = query_input.single_mut() else {
+        return;
+    };
+    let ctrl_held = input.ctrl_held;
+    let input = std::mem::replace(
+        input,
+        ForgeInput {
+            ctrl_held,
+            ..Default::default()
+        },
+    );
+
+    if input.toggle {
+        forge.enabled = !forge.enabled;
+        log::info!("forge mode: {}", forge.enabled);
+    }
+    if !forge.enabled {
+        if let Some((preview, _)) = forge.preview.take() {
+            cmd.despawn_recursive(preview);
+        }
+        return;
+    }
+
+    // Follow reloads of the asset collection and keep the selected prop
+    if forge.palette_revision != assets.revision() {
+        let selected = forge.selected_asset().map(str::to_owned);
+        forge.palette = assets.names().to_vec();
+        forge.palette_revision = assets.revision();
+        forge.selected = selected
+            .and_then(|name| forge.palette.iter().position(|n| *n == name))
+            .unwrap_or(0);
+    }
+
+    if !forge.palette.is_empty() {
+        let n = forge.palette.len() as i32;
+        forge.selected = (forge.selected as i32 + input.cycle).rem_euclid(n) as usize;
+    }
+    forge.yaw_steps += input.rotate;
+    forge.cell_size = (forge.cell_size * 2_f32.powi(input.cell_size_change))
+        .clamp(FORGE_MIN_CELL_SIZE, FORGE_MAX_CELL_SIZE);
+
+    let mut edits = Vec::new();
+    if input.undo {
+        edits.extend(forge.history.undo(&mut forge.layer));
+    }
+    if input.redo {
+        edits.extend(forge.history.redo(&mut forge.layer));
+    }
+
+    let hit = query_cam.single().and_then(|cam| {
+        colliders
+            .raycast(&cam.center_pixel_ray(), 0., None, CollisionLayer::Nav)
+            .filter(|hit| hit.distance < FORGE_MAX_DISTANCE)
+    });
+
+    let target = hit.map(|hit| snap_to_grid(hit.point, forge.cell_size));
+    let valid = target.is_some_and(|target| {
+        let footprint = placement_footprint(target, forge.cell_size);
+        !colliders.overlaps_aabb(&footprint, None, CollisionLayer::Nav)
+    });
+
+    if input.delete {
+        let aimed_id = hit
+            .and_then(|hit| query_routing.get(colliders[hit.id].user))
+            .and_then(|routing| query_forge_prop.get(routing.on_raycast_entity))
+            .map(|(_, prop)| prop.id);
+        if let Some(edit) = aimed_id.and_then(|id| forge.layer.delete(id)) {
+            forge.history.push(edit.clone());
+            edits.push(edit);
+        }
+    }
+
+    if input.place && valid {
+        if let (Some(target), Some(asset)) = (target, forge.selected_asset()) {
+            let placement = ForgePlacement {
+                asset: asset.to_owned(),
+                position: target,
+                yaw: forge_yaw(forge.yaw_steps),
+            };
+            let edit = forge.layer.place(placement);
+            forge.history.push(edit.clone());
+            edits.push(edit);
+        }
+    }
+
+    // Mirror layer changes in the world
+    for edit in &edits {
+        match edit {
+            ForgeEdit::Place { id, placement } => spawn_forge_prop(&mut cmd, *id, placement),
+            ForgeEdit::Delete { id, placement } => {
+                if let Some((entity, _)) = query_forge_prop.iter().find(|(_, p)| p.id == *id) {
+                    cmd.despawn_recursive(entity);
+                    if is_gameplay_prop(&placement.asset) {
+                        events.publish(PropEvent::PropDespawned {
+                            kind: placement.asset.clone(),
+                            entity,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    if !edits.is_empty() {
+        let path = Path::new(SAVE_DIR).join(FORGE_LAYER_FILE);
+        if let Err(err) = forge.layer.save(&path) {
+            log::error!("failed to save forge layer: {err:?}");
+        }
+    }
+
+    update_preview(&mut cmd, forge, target, valid, &mut query_tf);
+}
+
+/// The preview is a box showing the footprint of the placement. It is colored by validity.
+fn update_preview(
+    cmd: &mut Commands,
+    forge: &mut Forge,
+    target: Option<Vec3>,
+    valid: bool,
+    query_tf: &mut Query<&mut Transform3>,
+) {
+    let Some(target) = target else {
+        if let Some((preview, _)) = forge.preview.take() {
+            cmd.despawn_recursive(preview);
+        }
+        return;
+    };
+
+    let tf = Transform3::from_translation(target + 0.5 * forge.cell_size * Vec3::Z)
+        .with_rotation(Quat::from_rotation_z(forge_yaw(forge.yaw_steps)))
+        .with_scale(Vec3::splat(forge.cell_size));
+
+    match forge.preview {
+        Some((preview, was_valid)) => {
+            if let Some(preview_tf) = query_tf.get_mut(preview) {
+                *preview_tf = tf;
+            }
+            if was_valid != valid {
+                cmd.entity(preview).set(preview_material(valid));
+                forge.preview = Some((preview, valid));
+            }
+        }
+        None => {
+            let preview = cmd.spawn((
+                Name::from_str("forge preview"),
+                tf,
+                Cuboid,
+                preview_material(valid),
+                Visibility::Visible,
+                DynamicTransform,
+                HierarchyDirty,
+            ));
+            forge.preview = Some((preview, valid));
+        }
+    }
+}
+
+fn preview_material(valid: bool) -> Material {
+    let color = if valid {
+        FORGE_VALID_COLOR
+    } else {
+        FORGE_INVALID_COLOR
+    };
+    Material::Pbr(PbrMaterial::default().with_emission(color.to_linear() * 0.5))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn crate_at(x: f32) -> ForgePlacement {
+        ForgePlacement {
+            asset: "prop-crate".into(),
+            position: Vec3::new(x, 0., 0.),
+            yaw: 0.,
+        }
+    }
+
+    #[test]
+    fn test_forge_snapping() {
+        let p = snap_to_grid(Vec3::new(1.26, -0.74, 0.3), 0.5);
+        assert_abs_diff_eq!(p.x, 1.5);
+        assert_abs_diff_eq!(p.y, -0.5);
+        assert_abs_diff_eq!(p.z, 0.3);
+
+        let p = snap_to_grid(Vec3::new(1.24, -0.76, 0.), 0.5);
+        assert_abs_diff_eq!(p.x, 1.0);
+        assert_abs_diff_eq!(p.y, -1.0);
+
+        assert_abs_diff_eq!(forge_yaw(0), 0.);
+        assert_abs_diff_eq!(forge_yaw(6), 0.5 * PI, epsilon = 1e-6);
+        assert_abs_diff_eq!(forge_yaw(24), 0.);
+        assert_abs_diff_eq!(forge_yaw(-1), 23. * FORGE_YAW_STEP, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_forge_overlap_validity() {
+        let floor = Aabb::from_points([Vec3::new(-10., -10., -1.), Vec3::new(10., 10., 0.)]);
+        let wall = Aabb::from_points([Vec3::new(2., -10., 0.), Vec3::new(2.2, 10., 3.)]);
+
+        // resting on the floor is fine
+        let footprint = placement_footprint(Vec3::ZERO, 1.0);
+        assert!(!aabb_overlap(&footprint, &floor));
+        assert!(!aabb_overlap(&footprint, &wall));
+
+        // touching the wall is fine but not reaching into it
+        let footprint = placement_footprint(Vec3::new(1.5, 0., 0.), 1.0);
+        assert!(!aabb_overlap(&footprint, &wall));
+        let footprint = placement_footprint(Vec3::new(1.75, 0., 0.), 1.0);
+        assert!(aabb_overlap(&footprint, &wall));
+
+        // sunk into the floor
+        let footprint = placement_footprint(Vec3::new(0., 0., -0.5), 1.0);
+        assert!(aabb_overlap(&footprint, &floor));
+    }
+
+    #[test]
+    fn test_forge_undo_redo() {
+        let mut layer = ForgeLayer::default();
+        let mut history = ForgeHistory::default();
+
+        let a = layer.place(crate_at(0.));
+        history.push(a.clone());
+        let b = layer.place(crate_at(1.));
+        history.push(b.clone());
+        let ForgeEdit::Place { id: id_a, .. } = a else {
+            panic!()
+        };
+        history.push(layer.delete(id_a).unwrap());
+        let after_edits = layer.clone();
+        assert_eq!(layer.placements().count(), 1);
+
+        // undo in reverse order
+        assert_eq!(history.undo(&mut layer), Some(a.clone()));
+        assert_eq!(layer.placements().count(), 2);
+        assert_eq!(history.undo(&mut layer), Some(b.inverse()));
+        assert_eq!(history.undo(&mut layer), Some(a.inverse()));
+        assert_eq!(history.undo(&mut layer), None);
+        assert_eq!(layer.placements().count(), 0);
+
+        // redo restores the same ids
+        while history.redo(&mut layer).is_some() {}
+        assert_eq!(layer, after_edits);
+
+        // a new edit clears the redo stack
+        history.undo(&mut layer);
+        history.push(layer.place(crate_at(2.)));
+        assert_eq!(history.redo(&mut layer), None);
+        assert_eq!(layer.placements().count(), 3);
+    }
+
+    #[test]
+    fn test_forge_undo_capacity() {
+        let mut layer = ForgeLayer::default();
+        let mut history = ForgeHistory::default();
+        for i in 0..FORGE_UNDO_CAPACITY + 10 {
+            history.push(layer.place(crate_at(i as f32)));
+        }
+
+        let mut count = 0;
+        while history.undo(&mut layer).is_some() {
+            count += 1;
+        }
+        assert_eq!(count, FORGE_UNDO_CAPACITY);
+        assert_eq!(layer.placements().count(), 10);
+    }
+}
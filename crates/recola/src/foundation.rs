@@ -2,21 +2,34 @@ use crate::{
     STATIC_SETTINGS,
     collision::*,
     custom_properties::*,
-    mechanics::switch::*,
-    props::{barrier::*, door::*, laser_pointer::*, overgrowth::*, rift::*},
+    mechanics::{
+        audio_emitter::*, link_pulse::*, liquid::*, lod::*, prop_events::*, surface::*, switch::*,
+    },
+    props::{
+        barrier::*, door::*, laser_pointer::*, laser_turret::*, overgrowth::*, reset_lever::*,
+        rift::*, rope::*, swing_door::*,
+    },
     recola_mocca::{CRIMSON, RecolaAssetsMocca},
+    world_state::*,
 };
 use atom::prelude::*;
 use candy::{
     audio::{AudioEmitterBody, SpatialAudioEmitter},
     can::*,
     glassworks::*,
+    material::Material,
     scene_tree::*,
 };
-use eyre::Result;
+use eyre::{Result, eyre};
+use glam::Vec3;
 use magi::{color::colors, geo::Aabb};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
 
 #[derive(Component)]
 pub struct BlueprintApplied;
@@ -25,6 +38,7 @@ pub struct FoundationMocca;
 
 impl Mocca for FoundationMocca {
     fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<AudioEmitterMocca>();
         deps.depends_on::<BarrierMocca>();
         deps.depends_on::<CandyCanMocca>();
         deps.depends_on::<CandyGlassworksMocca>();
@@ -33,10 +47,18 @@ impl Mocca for FoundationMocca {
         deps.depends_on::<CustomPropertiesMocca>();
         deps.depends_on::<DoorMocca>();
         deps.depends_on::<LaserPointerMocca>();
+        deps.depends_on::<LinkPulseMocca>();
+        deps.depends_on::<LiquidMocca>();
+        deps.depends_on::<LodMocca>();
         deps.depends_on::<OvergrowthMocca>();
+        deps.depends_on::<PropEventsMocca>();
         deps.depends_on::<RecolaAssetsMocca>();
         deps.depends_on::<RiftMocca>();
+        deps.depends_on::<RopeMocca>();
+        deps.depends_on::<SurfaceMocca>();
+        deps.depends_on::<SwingDoorMocca>();
         deps.depends_on::<SwitchMocca>();
+        deps.depends_on::<WorldStateMocca>();
     }
 
     fn register_components(world: &mut World) {
@@ -44,11 +66,13 @@ impl Mocca for FoundationMocca {
     }
 
     fn start(world: &mut World) -> Self {
-        world.run(load_assets).unwrap();
+        let watch = world.run(load_assets).unwrap();
+        world.set_singleton(watch);
         Self
     }
 
     fn step(&mut self, world: &mut World) {
+        world.run(hot_reload_assets);
         world.run(load_asset_blueprints);
     }
 
@@ -58,48 +82,260 @@ impl Mocca for FoundationMocca {
 }
 
 #[derive(Serialize, Deserialize)]
-struct AssetCollection {
-    assets: Vec<AssetEntry>,
+pub(crate) struct AssetCollection {
+    pub assets: Vec<AssetEntry>,
 }
 
-#[derive(Serialize, Deserialize)]
-struct AssetEntry {
-    name: String,
-    file: String,
-    scene: String,
-    node: String,
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct AssetEntry {
+    pub name: String,
+    pub file: String,
+    pub scene: String,
+    pub node: String,
+}
+
+/// Asset library of all props
+pub(crate) const PROPS_FILE: &str = "props.json";
+
+/// Time between checks whether the asset collection file was modified
+pub const PROPS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A mistake in an entry of the asset collection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AssetEntryProblem {
+    DuplicateName(String),
+    EmptyScene { name: String },
+    EmptyNode { name: String },
+    MissingFile { name: String, file: String },
+}
+
+impl fmt::Display for AssetEntryProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssetEntryProblem::DuplicateName(name) => write!(f, "duplicate asset name '{name}'"),
+            AssetEntryProblem::EmptyScene { name } => write!(f, "asset '{name}' has no scene"),
+            AssetEntryProblem::EmptyNode { name } => write!(f, "asset '{name}' has no node"),
+            AssetEntryProblem::MissingFile { name, file } => {
+                write!(f, "asset '{name}' refers to missing file '{file}'")
+            }
+        }
+    }
+}
+
+impl AssetCollection {
+    /// Checks all entries and returns every problem found
+    pub fn validate(&self, file_exists: impl Fn(&str) -> bool) -> Vec<AssetEntryProblem> {
+        let mut names = HashSet::new();
+        let mut out = Vec::new();
+        for entry in &self.assets {
+            if !names.insert(entry.name.as_str()) {
+                out.push(AssetEntryProblem::DuplicateName(entry.name.clone()));
+            }
+            if entry.scene.is_empty() {
+                out.push(AssetEntryProblem::EmptyScene {
+                    name: entry.name.clone(),
+                });
+            }
+            if entry.node.is_empty() {
+                out.push(AssetEntryProblem::EmptyNode {
+                    name: entry.name.clone(),
+                });
+            }
+            if !file_exists(&entry.file) {
+                out.push(AssetEntryProblem::MissingFile {
+                    name: entry.name.clone(),
+                    file: entry.file.clone(),
+                });
+            }
+        }
+        out
+    }
+}
+
+/// Change of the asset collection since it was last applied to the asset library
+#[derive(Debug, Default)]
+pub(crate) struct AssetCollectionDiff<'a> {
+    /// Entries which are not loaded yet
+    pub added: Vec<&'a AssetEntry>,
+
+    /// Names of entries which are no longer in the collection
+    pub removed: Vec<String>,
+}
+
+/// Watches the asset collection file and keeps track of the loaded entries
+///
+/// Entries added to the file while the game is running are loaded. Existing entries are not
+/// reloaded and removed entries stay loaded until restart.
+#[derive(Singleton)]
+pub struct AssetCollectionWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+
+    /// Names of all entries which were loaded into the asset library
+    loaded: HashSet<String>,
+
+    /// Names of the entries in the current collection in file order
+    names: Vec<String>,
+
+    /// Incremented whenever the list of names changes
+    revision: u64,
+}
+
+impl AssetCollectionWatch {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            modified: file_modified(&path),
+            path,
+            last_poll: Instant::now(),
+            loaded: HashSet::new(),
+            names: Vec::new(),
+            revision: 0,
+        }
+    }
+
+    /// Names of all assets in the collection
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Applies a new version of the collection and returns the entries which must be loaded
+    pub(crate) fn update<'a>(&mut self, coll: &'a AssetCollection) -> AssetCollectionDiff<'a> {
+        let names: Vec<String> = coll.assets.iter().map(|entry| entry.name.clone()).collect();
+
+        let diff = AssetCollectionDiff {
+            added: coll
+                .assets
+                .iter()
+                .filter(|entry| !self.loaded.contains(&entry.name))
+                .collect(),
+            removed: self
+                .names
+                .iter()
+                .filter(|name| !names.contains(name))
+                .cloned()
+                .collect(),
+        };
+
+        self.loaded
+            .extend(diff.added.iter().map(|entry| entry.name.clone()));
+        if names != self.names {
+            self.names = names;
+            self.revision += 1;
+        }
+
+        diff
+    }
+}
+
+fn file_modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+}
+
+/// Parses and validates the asset collection. All problems are reported at once.
+fn read_asset_collection(assets: &SharedAssetResolver) -> Result<(PathBuf, AssetCollection)> {
+    let path = assets.resolve(PROPS_FILE)?;
+    let coll: AssetCollection = assets.parse(&path)?;
+
+    let problems = coll.validate(|file| assets.resolve(file).is_ok());
+    if !problems.is_empty() {
+        let list: String = problems
+            .iter()
+            .map(|problem| format!("\n  {problem}"))
+            .collect();
+        return Err(eyre!("{PROPS_FILE} has {} problems:{list}", problems.len()));
+    }
+
+    Ok((path, coll))
+}
+
+fn load_asset_entry(
+    assets: &SharedAssetResolver,
+    asli: &mut AssetLibrary,
+    entry: &AssetEntry,
+) -> Result<()> {
+    let path = assets.resolve(&entry.file)?;
+    asli.load_gltf(
+        &AssetUid::new(entry.name.clone()),
+        GltfAssetDescriptor {
+            path,
+            scene: Some(entry.scene.clone()),
+            node: Some(entry.node.clone()),
+        },
+    );
+    Ok(())
 }
 
 pub fn load_assets(
     assets: Singleton<SharedAssetResolver>,
     mut asli: SingletonMut<AssetLibrary>,
-) -> Result<()> {
-    let path = assets.resolve("props.json")?;
-    let coll: AssetCollection = assets.parse(&path)?;
+) -> Result<AssetCollectionWatch> {
+    let (path, coll) = read_asset_collection(&assets)?;
 
-    for entry in coll.assets {
-        let path = assets.resolve(&entry.file)?;
-        asli.load_gltf(
-            &AssetUid::new(entry.name),
-            GltfAssetDescriptor {
-                path,
-                scene: Some(entry.scene),
-                node: Some(entry.node),
-            },
-        );
+    let mut watch = AssetCollectionWatch::new(path);
+    for entry in watch.update(&coll).added {
+        load_asset_entry(&assets, &mut asli, entry)?;
+    }
+    Ok(watch)
+}
+
+/// Loads entries which were added to the asset collection file while the game is running
+fn hot_reload_assets(
+    assets: Singleton<SharedAssetResolver>,
+    mut asli: SingletonMut<AssetLibrary>,
+    mut watch: SingletonMut<AssetCollectionWatch>,
+) {
+    if watch.last_poll.elapsed() < PROPS_POLL_INTERVAL {
+        return;
+    }
+    watch.last_poll = Instant::now();
+
+    let modified = file_modified(&watch.path);
+    if modified.is_none() || modified == watch.modified {
+        return;
+    }
+    watch.modified = modified;
+
+    let coll = match read_asset_collection(&assets) {
+        Ok((_, coll)) => coll,
+        Err(err) => {
+            log::error!("failed to reload {PROPS_FILE}: {err}");
+            return;
+        }
+    };
+
+    let diff = watch.update(&coll);
+    for name in &diff.removed {
+        log::warn!("asset '{name}' was removed from {PROPS_FILE} but stays loaded until restart");
+    }
+    for entry in diff.added {
+        log::info!("loading asset '{}'", entry.name);
+        if let Err(err) = load_asset_entry(&assets, &mut asli, entry) {
+            log::error!("failed to load asset '{}': {err:?}", entry.name);
+        }
     }
-    Ok(())
 }
 
 fn load_asset_blueprints(
     mut cmd: Commands,
+    mut events: SingletonMut<PropEvents>,
+    mut link_pulse: SingletonMut<LinkPulseGroups>,
     query: Query<
         (Entity, &AssetInstance, Option<&CustomProperties>),
         (With<AssetLoaded>, Without<BlueprintApplied>),
     >,
     children: Relation<ChildOf>,
     query_tf: Query<&Transform3>,
+    query_global_tf: Query<&GlobalTransform3>,
     query_name: Query<&Name>,
+    query_props: Query<&CustomProperties>,
+    query_material: Query<&Material>,
 ) {
     for (entity, ainst, props) in query.iter() {
         // Setup colliders
@@ -140,11 +376,51 @@ fn load_asset_blueprints(
                 body: AudioEmitterBody::Aabb(aabb),
             });
 
+            // Audio clip from properties on the emitter node or the prop
+            let emitter_props = query_props.get(audio_emitter_entity).or(props);
+            if let Some(audio_props) = emitter_props.and_then(AudioEmitterProps::from_properties) {
+                cmd.entity(entity)
+                    .set(SpawnAudioEmitterSourceTask { props: audio_props });
+            }
+
             if !STATIC_SETTINGS.show_audio_emitters {
                 cmd.entity(audio_emitter_entity).set(Visibility::Hidden)
             }
         };
 
+        // Setup liquid volume
+        let liquid_entity = find_child(&children, &query_name, entity, |name| {
+            name.ends_with("LIQUID")
+        });
+        if let Some(liquid_entity) = liquid_entity {
+            cmd.entity(liquid_entity)
+                .and_set(LiquidVolume::from_properties(props));
+
+            if !STATIC_SETTINGS.show_colliders {
+                cmd.entity(liquid_entity).set(Visibility::Hidden)
+            }
+        }
+
+        // Setup walkable surface
+        if let Some(friction) = props.and_then(SurfaceFriction::from_properties) {
+            for &(collider_entity, _) in &colliders {
+                cmd.entity(collider_entity).set(friction);
+            }
+        }
+
+        // Setup level of detail
+        let lod_levels = find_lod_levels(&children, &query_name, entity);
+        if !lod_levels.is_empty() {
+            for (i, &level_entity) in lod_levels.iter().enumerate() {
+                cmd.entity(level_entity).set(if i == 0 {
+                    Visibility::Visible
+                } else {
+                    Visibility::Hidden
+                });
+            }
+            cmd.entity(entity).set(LodGroup::new(lod_levels, props));
+        }
+
         // Setup switch
         if let Some(props) = props {
             if let Some(switches) = props.get_string_list("switches") {
@@ -157,6 +433,17 @@ fn load_asset_blueprints(
             }
         }
 
+        // Setup lamps which are turned off during daylight
+        if props.and_then(|props| props.get_bool("solar")) == Some(true) {
+            let light_entity = find_child(&children, &query_name, entity, |name| {
+                name.ends_with("light")
+            });
+            cmd.entity(entity).set(SolarLamp::new(light_entity));
+        }
+
+        // Entity which pulses with props linked to this one
+        let mut pulse_emitter = None;
+
         match ainst.as_str() {
             "prop-laser" => {
                 let pointer =
@@ -168,6 +455,17 @@ fn load_asset_blueprints(
                     collider_entity: colliders[0].0,
                 });
             }
+            "prop-laser_turret" => {
+                let head_entity = find_child(&children, &query_name, entity, |name| {
+                    name.ends_with("head")
+                })
+                .unwrap();
+
+                cmd.entity(entity).set(SpawnLaserTurretTask {
+                    head_entity,
+                    collider_entity: colliders.first().map(|&(collider, _)| collider),
+                });
+            }
             "prop-beam_target" | "prop-barrier_switch" => {
                 let switch_id = query_name.get(entity).unwrap().as_str().to_owned();
 
@@ -182,6 +480,8 @@ fn load_asset_blueprints(
                     _ => unreachable!(),
                 };
 
+                pulse_emitter = Some(indicator_entity);
+
                 cmd.entity(entity).set(SpawnLaserTarget {
                     switch_id,
                     indicator_entity,
@@ -195,6 +495,8 @@ fn load_asset_blueprints(
                 })
                 .unwrap();
 
+                pulse_emitter = Some(relief_entity);
+
                 cmd.entity(entity).set(SpawnLevelGateTask { relief_entity });
             }
             "prop-gate_door" => {
@@ -231,12 +533,47 @@ fn load_asset_blueprints(
                     "prop-barrier_3x6.force_field",
                 )
                 .unwrap();
+                pulse_emitter = Some(force_field_entity);
                 cmd.entity(entity)
                     .set(SpawnBarrierTask { force_field_entity });
             }
             "prop-rift" => {
+                pulse_emitter = find_child(&children, &query_name, entity, |name| {
+                    name.starts_with("prop-rift.") && !name.contains("COLLIDER")
+                });
                 cmd.entity(entity).set(SpawnRiftTask);
             }
+            "prop-reset_lever" => {
+                let handle_entity = find_child(&children, &query_name, entity, |name| {
+                    name.ends_with("handle")
+                });
+
+                cmd.entity(entity)
+                    .set(SpawnPuzzleResetTask { handle_entity });
+            }
+            "prop-swing_door" => {
+                let leaf_entity = find_child(&children, &query_name, entity, |name| {
+                    name.ends_with("leaf")
+                })
+                .unwrap();
+
+                let collider_entity = find_child(&children, &query_name, entity, |name| {
+                    name.ends_with("leaf-COLLIDER")
+                })
+                .unwrap();
+
+                cmd.entity(entity).set(SpawnSwingDoorTask {
+                    leaf_entity,
+                    collider_entity,
+                });
+            }
+            "prop-rope" => {
+                let burn_entity = find_child(&children, &query_name, entity, |name| {
+                    name.ends_with("burn")
+                });
+
+                cmd.entity(entity).set(SpawnRopeTask { burn_entity });
+            }
             "prop-overgrowth-1"
             | "prop-overgrowth-2"
             | "prop-overgrowth-3"
@@ -252,7 +589,38 @@ fn load_asset_blueprints(
             _ => {}
         }
 
+        // Setup pulsing of linked props
+        let name = query_name.get(entity).map(|name| name.as_str());
+        if let (Some(emitter), Some(key)) =
+            (pulse_emitter, link_pulse_key(ainst.as_str(), name, props))
+        {
+            // The emission of the emitter's material is scaled
+            if query_material.get(emitter).is_some() {
+                cmd.entity(entity).set(LinkPulseGroup {
+                    group_id: link_pulse.register(&key, LINK_PULSE_PERIOD),
+                    period: LINK_PULSE_PERIOD,
+                    phase: 0.,
+                    emitter,
+                });
+            } else {
+                log::error!(
+                    "{} {entity}: pulse emitter {emitter} has no material",
+                    ainst.as_str()
+                );
+            }
+        }
+
         cmd.entity(entity).set(BlueprintApplied);
+
+        if is_gameplay_prop(ainst.as_str()) {
+            events.publish(PropEvent::PropSpawned {
+                kind: ainst.as_str().to_owned(),
+                entity,
+                position: query_global_tf
+                    .get(entity)
+                    .map_or(Vec3::ZERO, |tf| tf.translation()),
+            });
+        }
     }
 }
 
@@ -275,7 +643,25 @@ fn find_colliders(
     out
 }
 
-fn find_child_by_name(
+/// Finds children named `*-LOD0`, `*-LOD1`, .. Levels are only used up to the first missing one.
+fn find_lod_levels(
+    children: &Relation<ChildOf>,
+    query_name: &Query<&Name>,
+    entity: Entity,
+) -> Vec<Entity> {
+    let mut levels = [None; LOD_LEVEL_COUNT];
+    iter_children_by_name(children, query_name, entity, |entity, name| {
+        for (i, level) in levels.iter_mut().enumerate() {
+            if name.ends_with(&format!("-LOD{i}")) {
+                *level = Some(entity);
+            }
+        }
+        false
+    });
+    levels.into_iter().map_while(|level| level).collect()
+}
+
+pub(crate) fn find_child_by_name(
     children: &Relation<ChildOf>,
     query_name: &Query<&Name>,
     entity: Entity,
@@ -351,3 +737,107 @@ where
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, file: &str, scene: &str, node: &str) -> AssetEntry {
+        AssetEntry {
+            name: name.into(),
+            file: file.into(),
+            scene: scene.into(),
+            node: node.into(),
+        }
+    }
+
+    fn prop(name: &str) -> AssetEntry {
+        entry(name, "props.glb", "props", name)
+    }
+
+    #[test]
+    fn test_asset_collection_validate_reports_all_problems() {
+        let coll = AssetCollection {
+            assets: vec![
+                prop("prop-laser"),
+                entry("prop-rift", "rift.glb", "", "prop-rift"),
+                prop("prop-laser"),
+                entry("prop-rope", "missing.glb", "props", ""),
+            ],
+        };
+
+        let problems = coll.validate(|file| file != "missing.glb");
+        assert_eq!(
+            problems,
+            vec![
+                AssetEntryProblem::EmptyScene {
+                    name: "prop-rift".into()
+                },
+                AssetEntryProblem::DuplicateName("prop-laser".into()),
+                AssetEntryProblem::EmptyNode {
+                    name: "prop-rope".into()
+                },
+                AssetEntryProblem::MissingFile {
+                    name: "prop-rope".into(),
+                    file: "missing.glb".into()
+                },
+            ]
+        );
+
+        let coll = AssetCollection {
+            assets: vec![prop("prop-laser"), prop("prop-rift")],
+        };
+        assert!(coll.validate(|_| true).is_empty());
+    }
+
+    #[test]
+    fn test_props_file_is_valid() {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../assets/recola");
+        let coll: AssetCollection =
+            serde_json::from_str(&std::fs::read_to_string(dir.join(PROPS_FILE)).unwrap()).unwrap();
+
+        // exported glb files are not checked in but their blend sources are
+        let problems = coll.validate(|file| {
+            let path = dir.join(file);
+            path.exists() || path.with_extension("blend").exists()
+        });
+        assert!(problems.is_empty(), "{problems:?}");
+    }
+
+    #[test]
+    fn test_asset_collection_watch_update() {
+        let mut watch = AssetCollectionWatch::new(PathBuf::new());
+
+        let coll = AssetCollection {
+            assets: vec![prop("prop-laser"), prop("prop-rift")],
+        };
+        let diff = watch.update(&coll);
+        assert_eq!(diff.added.len(), 2);
+        assert!(diff.removed.is_empty());
+        assert_eq!(watch.revision(), 1);
+
+        // unchanged collection
+        let diff = watch.update(&coll);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(watch.revision(), 1);
+
+        // only new entries are loaded and removed ones are reported
+        let coll = AssetCollection {
+            assets: vec![prop("prop-rope"), prop("prop-laser")],
+        };
+        let diff = watch.update(&coll);
+        let added: Vec<_> = diff.added.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(added, vec!["prop-rope"]);
+        assert_eq!(diff.removed, vec!["prop-rift".to_string()]);
+        assert_eq!(watch.names(), ["prop-rope", "prop-laser"]);
+        assert_eq!(watch.revision(), 2);
+
+        // entries which come back are still loaded
+        let coll = AssetCollection {
+            assets: vec![prop("prop-rift")],
+        };
+        let diff = watch.update(&coll);
+        assert!(diff.added.is_empty());
+        assert_eq!(diff.removed.len(), 2);
+    }
+}
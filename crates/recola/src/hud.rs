@@ -0,0 +1,167 @@
+//! On-screen text
+//!
+//! Systems write the text to show into the [Hud] singleton. Every frame the hud is laid out into
+//! lines of screen text which are drawn over the scene.
+
+use crate::settings::*;
+use atom::prelude::*;
+use candy::text::*;
+use glam::Vec2;
+
+/// Number of text lines which fit on the screen at the default text size
+pub const HUD_LINES_PER_SCREEN: f32 = 16.;
+
+/// Vertical position of the prompt in screen heights from the top
+const HUD_PROMPT_Y: f32 = 0.85;
+
+/// Text shown on top of the scene
+#[derive(Singleton, Default, Debug, Clone)]
+pub struct Hud {
+    /// Short message at the bottom of the screen
+    pub prompt: Option<String>,
+
+    /// Text scrolling from the bottom to the top of the screen
+    pub scroll: Option<HudScroll>,
+}
+
+#[derive(Debug, Clone)]
+pub struct HudScroll {
+    pub lines: Vec<String>,
+
+    /// Number of lines scrolled past the bottom of the screen
+    pub offset: f32,
+}
+
+/// A line of text placed on the screen
+#[derive(Debug, Clone, PartialEq)]
+pub struct HudLine {
+    pub text: String,
+
+    /// Center of the line in screen coordinates from the top left (0,0) to the bottom right (1,1)
+    pub position: Vec2,
+
+    /// Text height in screen heights
+    pub height: f32,
+}
+
+impl Hud {
+    /// Lines of text currently visible on the screen. The prompt is scaled by `prompt_scale`.
+    pub fn layout(&self, prompt_scale: f32) -> Vec<HudLine> {
+        let line_height = 1. / HUD_LINES_PER_SCREEN;
+        let mut out = Vec::new();
+
+        if let Some(scroll) = &self.scroll {
+            for (i, text) in scroll.lines.iter().enumerate() {
+                let y = 1. + (i as f32 - scroll.offset) * line_height;
+                if text.is_empty() || !(-line_height..=1. + line_height).contains(&y) {
+                    continue;
+                }
+                out.push(HudLine {
+                    text: text.clone(),
+                    position: Vec2::new(0.5, y),
+                    height: line_height,
+                });
+            }
+        }
+
+        if let Some(prompt) = &self.prompt {
+            out.push(HudLine {
+                text: prompt.clone(),
+                position: Vec2::new(0.5, HUD_PROMPT_Y),
+                height: line_height * prompt_scale,
+            });
+        }
+
+        out
+    }
+}
+
+/// Entities which draw the lines of the hud
+#[derive(Singleton, Default)]
+struct HudTextEntities(Vec<Entity>);
+
+/// Draws the [Hud] over the scene
+pub struct HudMocca;
+
+impl Mocca for HudMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyTextMocca>();
+        deps.depends_on::<SettingsMocca>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(Hud::default());
+        world.set_singleton(HudTextEntities::default());
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(draw_hud);
+    }
+}
+
+fn draw_hud(
+    mut cmd: Commands,
+    settings: Singleton<GameSettings>,
+    hud: Singleton<Hud>,
+    mut entities: SingletonMut<HudTextEntities>,
+) {
+    let lines = hud.layout(settings.accessibility.subtitle_scale);
+
+    while entities.0.len() > lines.len() {
+        cmd.despawn_recursive(entities.0.pop().unwrap());
+    }
+
+    for (i, line) in lines.into_iter().enumerate() {
+        let text = ScreenText {
+            text: line.text,
+            position: line.position,
+            height: line.height,
+        };
+        match entities.0.get(i) {
+            Some(&entity) => {
+                cmd.entity(entity).set(text);
+            }
+            None => {
+                let entity = cmd.spawn((Name::from_str("hud text"), text));
+                entities.0.push(entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hud_scroll_layout() {
+        let mut hud = Hud {
+            prompt: None,
+            scroll: Some(HudScroll {
+                lines: vec!["A".into(), "".into(), "B".into()],
+                offset: 0.,
+            }),
+        };
+        // the first line starts at the bottom of the screen
+        let lines = hud.layout(1.);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].text, "A");
+        assert_eq!(lines[0].position.y, 1.);
+
+        // empty lines are skipped
+        hud.scroll.as_mut().unwrap().offset = 8.;
+        let ys: Vec<_> = hud.layout(1.).iter().map(|line| line.position.y).collect();
+        assert_eq!(ys, vec![0.5, 0.625]);
+
+        // all lines scrolled past the top
+        hud.scroll.as_mut().unwrap().offset = HUD_LINES_PER_SCREEN + 4.;
+        assert!(hud.layout(1.).is_empty());
+
+        // only the prompt follows the subtitle scale
+        hud.prompt = Some("tip".into());
+        let lines = hud.layout(1.5);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].height, 1.5 / HUD_LINES_PER_SCREEN);
+    }
+}
@@ -10,9 +10,26 @@ use std::collections::HashMap;
 /// List of loaded levels
 #[derive(Singleton, Default)]
 pub struct LevelSummary {
+    pub names: Vec<String>,
     pub pos: Vec<Vec3>,
 }
 
+impl LevelSummary {
+    /// Name of the level closest to the given position
+    pub fn nearest_level_name(&self, position: Vec3) -> Option<&str> {
+        self.names
+            .iter()
+            .zip(self.pos.iter())
+            .min_by(|(_, a), (_, b)| a.distance(position).total_cmp(&b.distance(position)))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Marks the root entity of a level. All props of the level are descendants of it. Custom
+/// properties of the level instance in the world are stored on this entity.
+#[derive(Component)]
+pub struct LevelRegion;
+
 /// Loads the world of Recola
 pub struct LevelMocca;
 
@@ -28,6 +45,10 @@ impl Mocca for LevelMocca {
         deps.depends_on::<FoundationMocca>();
     }
 
+    fn register_components(world: &mut World) {
+        world.register_component::<LevelRegion>();
+    }
+
     fn start(world: &mut World) -> Self {
         world.run(setup_sky);
         world.run(spawn_terrain);
@@ -99,9 +120,8 @@ fn spawn_levels(assets: Singleton<SharedAssetResolver>, mut cmd: Commands) -> Re
     for inst in world.instances {
         if let Ok(path) = assets.resolve(format!("levels/{}.json", &inst.name)) {
             let level: Level = assets.parse(&path)?;
-            let tf = inst.transform();
-            level_pos_by_name.push((inst.name.clone(), tf.translation));
-            spawn_level(&mut cmd, inst.name, tf, level);
+            level_pos_by_name.push((inst.name.clone(), inst.transform().translation));
+            spawn_level(&mut cmd, inst, level);
         } else {
             spawn_instance(&mut cmd, world_entity, inst);
         }
@@ -109,15 +129,19 @@ fn spawn_levels(assets: Singleton<SharedAssetResolver>, mut cmd: Commands) -> Re
 
     level_pos_by_name.sort_by_key(|(name, _)| name.clone());
 
-    cmd.set_singleton(LevelSummary {
-        pos: level_pos_by_name.into_iter().map(|(_, pos)| pos).collect(),
-    });
+    let (names, pos) = level_pos_by_name.into_iter().unzip();
+    cmd.set_singleton(LevelSummary { names, pos });
 
     Ok(())
 }
 
-fn spawn_level(cmd: &mut Commands, name: String, tf: Transform3, level: Level) {
-    let level_entity = cmd.spawn((Name::new(name), tf));
+fn spawn_level(cmd: &mut Commands, inst: Instance, level: Level) {
+    let level_entity = cmd.spawn((Name::new(inst.name.clone()), inst.transform(), LevelRegion));
+    if !inst.custom.is_empty() {
+        cmd.entity(level_entity)
+            .set(CustomProperties::from_json(&inst.custom));
+    }
+
     for inst in level.instances {
         spawn_instance(cmd, level_entity, inst);
     }
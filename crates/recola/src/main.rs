@@ -1,10 +1,22 @@
+pub mod camera_bookmarks;
 pub mod collision;
 pub mod custom_properties;
+pub mod demo;
+pub mod forge;
 pub mod foundation;
+pub mod hud;
 pub mod level;
 pub mod mechanics;
+pub mod paint_marks;
 pub mod player;
 pub mod props;
+pub mod save;
+pub mod settings;
+pub mod time_trial;
+pub mod tutorial;
+pub mod victory;
+pub mod weather;
+pub mod world_state;
 
 mod recola_mocca;
 use crate::recola_mocca::RecolaMocca;
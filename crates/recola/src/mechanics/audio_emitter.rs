@@ -0,0 +1,230 @@
+use crate::{
+    custom_properties::*, mechanics::switch::*, player::*, recola_mocca::RecolaAssetsMocca,
+};
+use atom::prelude::*;
+use candy::{audio::*, can::*, scene_tree::*};
+
+/// Emitters with a range fade out over this fraction of the range
+const AUDIO_EMITTER_RANGE_FADE: f32 = 0.25;
+
+/// Audio clip configured through custom properties of a prop with an audio emitter
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioEmitterProps {
+    /// Asset path of the clip
+    pub clip: String,
+
+    pub volume: f32,
+
+    /// Distance up to which the emitter is audible
+    pub range: Option<f32>,
+
+    pub looped: bool,
+}
+
+impl AudioEmitterProps {
+    /// Reads the `audio_clip`, `audio_volume`, `audio_range` and `audio_loop` custom properties.
+    /// Returns `None` if no clip is specified.
+    pub fn from_properties(props: &CustomProperties) -> Option<Self> {
+        let clip = props.get_string("audio_clip")?.to_owned();
+        Some(Self {
+            clip,
+            volume: props.get_float("audio_volume").map_or(1.0, |v| v as f32),
+            range: props.get_float("audio_range").map(|v| v as f32),
+            looped: props.get_bool("audio_loop").unwrap_or(true),
+        })
+    }
+}
+
+/// Attaches the audio source configured by custom properties
+#[derive(Component)]
+pub struct SpawnAudioEmitterSourceTask {
+    pub props: AudioEmitterProps,
+}
+
+/// Volume of an audio emitter configured by custom properties. Emitters of props with a switch
+/// observer are muted while the observer is inactive. Emitters with a range fade out towards the
+/// end of the range and are silent beyond it.
+#[derive(Component, Debug)]
+pub struct AudioEmitterGate {
+    pub volume: f32,
+    pub range: Option<f32>,
+}
+
+impl AudioEmitterGate {
+    /// Volume for a listener at the given distance from the emitter
+    pub fn gated_volume(&self, observer: Option<&SwitchObserverState>, distance: f32) -> f32 {
+        match observer {
+            Some(state) if !state.as_bool() => 0.,
+            _ => self.volume * self.range_falloff(distance),
+        }
+    }
+
+    fn range_falloff(&self, distance: f32) -> f32 {
+        let Some(range) = self.range else {
+            return 1.;
+        };
+        let fade = AUDIO_EMITTER_RANGE_FADE * range;
+        if fade <= 0. {
+            return if distance <= range { 1. } else { 0. };
+        }
+        ((range - distance) / fade).clamp(0., 1.)
+    }
+}
+
+/// Audio emitters configured through custom properties
+pub struct AudioEmitterMocca;
+
+impl Mocca for AudioEmitterMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyAudioMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<RecolaAssetsMocca>();
+        deps.depends_on::<SwitchMocca>();
+    }
+
+    fn start(_world: &mut World) -> Self {
+        Self
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<AudioEmitterGate>();
+        world.register_component::<SpawnAudioEmitterSourceTask>();
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(spawn_audio_emitter_source);
+        world.run(gate_audio_emitters);
+    }
+}
+
+fn spawn_audio_emitter_source(
+    mut cmd: Commands,
+    asset_resolver: Singleton<SharedAssetResolver>,
+    query: Query<(Entity, &SpawnAudioEmitterSourceTask)>,
+) {
+    for (entity, task) in query.iter() {
+        cmd.entity(entity).remove::<SpawnAudioEmitterSourceTask>();
+
+        let Ok(path) = asset_resolver.resolve(&task.props.clip) else {
+            log::warn!(
+                "audio emitter {entity}: could not resolve clip '{}'",
+                task.props.clip
+            );
+            continue;
+        };
+
+        cmd.entity(entity)
+            .and_set(AudioSource {
+                path,
+                volume: 0.,
+                state: AudioPlaybackState::Play,
+                repeat: if task.props.looped {
+                    AudioRepeatKind::Loop
+                } else {
+                    AudioRepeatKind::OneShot
+                },
+                volume_auto_play: false,
+            })
+            .and_set(AudioEmitterGate {
+                volume: task.props.volume,
+                range: task.props.range,
+            });
+    }
+}
+
+fn gate_audio_emitters(
+    player: Singleton<Player>,
+    mut query: Query<(
+        &AudioEmitterGate,
+        Option<&SwitchObserverState>,
+        &GlobalTransform3,
+        &mut AudioSource,
+    )>,
+) {
+    for (gate, observer, tf, audio) in query.iter_mut() {
+        let distance = tf.translation().distance(player.eye_position);
+        audio.volume = gate.gated_volume(observer, distance);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn props(entries: &[(&str, serde_json::Value)]) -> CustomProperties {
+        CustomProperties::from_json(&HashMap::from_iter(
+            entries.iter().map(|(k, v)| (k.to_string(), v.clone())),
+        ))
+    }
+
+    #[test]
+    fn test_audio_emitter_props() {
+        assert_eq!(AudioEmitterProps::from_properties(&props(&[])), None);
+
+        assert_eq!(
+            AudioEmitterProps::from_properties(&props(&[(
+                "audio_clip",
+                serde_json::json!("audio/effects/sfx-fire.wav")
+            )])),
+            Some(AudioEmitterProps {
+                clip: "audio/effects/sfx-fire.wav".into(),
+                volume: 1.0,
+                range: None,
+                looped: true,
+            })
+        );
+
+        assert_eq!(
+            AudioEmitterProps::from_properties(&props(&[
+                ("audio_clip", serde_json::json!("hum.wav")),
+                ("audio_volume", serde_json::json!(0.5)),
+                ("audio_range", serde_json::json!(12)),
+                ("audio_loop", serde_json::json!(false)),
+            ])),
+            Some(AudioEmitterProps {
+                clip: "hum.wav".into(),
+                volume: 0.5,
+                range: Some(12.0),
+                looped: false,
+            })
+        );
+    }
+
+    #[test]
+    fn test_audio_emitter_switch_gate() {
+        let gate = AudioEmitterGate {
+            volume: 0.7,
+            range: None,
+        };
+        assert_eq!(gate.gated_volume(None, 100.), 0.7);
+        assert_eq!(
+            gate.gated_volume(Some(&SwitchObserverState::Inactive), 0.),
+            0.
+        );
+        assert_eq!(
+            gate.gated_volume(Some(&SwitchObserverState::Active), 0.),
+            0.7
+        );
+    }
+
+    #[test]
+    fn test_audio_emitter_range() {
+        let gate = AudioEmitterGate {
+            volume: 0.8,
+            range: Some(12.),
+        };
+        assert_eq!(gate.gated_volume(None, 0.), 0.8);
+        assert_eq!(gate.gated_volume(None, 9.), 0.8);
+        assert_eq!(gate.gated_volume(None, 10.5), 0.4);
+        assert_eq!(gate.gated_volume(None, 12.), 0.);
+        assert_eq!(gate.gated_volume(None, 30.), 0.);
+        assert_eq!(
+            gate.gated_volume(Some(&SwitchObserverState::Inactive), 0.),
+            0.
+        );
+    }
+}
@@ -0,0 +1,246 @@
+use crate::{custom_properties::*, mechanics::material_swap::*, player::*, settings::*};
+use atom::prelude::*;
+use candy::time::*;
+use std::{
+    collections::HashMap,
+    f32::consts::{PI, TAU},
+};
+
+/// Duration of one pulse of linked props
+pub const LINK_PULSE_PERIOD: f32 = 2.4;
+
+/// Additional emission at the peak of a pulse
+const LINK_PULSE_AMPLITUDE: f32 = 0.6;
+
+/// Additional emission right after the player aimed at a member of a group
+const LINK_PULSE_AIM_BOOST: f32 = 1.5;
+
+/// Time for the aim boost to fade out
+const LINK_PULSE_AIM_BOOST_DURATION: f32 = 0.6;
+
+/// Props which pulse in sync because they are linked, e.g. a switch and the barrier it controls
+#[derive(Component, Debug, Clone)]
+pub struct LinkPulseGroup {
+    pub group_id: u64,
+    pub period: f32,
+
+    /// Phase of the pulse in [0, 1). Driven by the group.
+    pub phase: f32,
+
+    /// The emission of this entity pulses
+    pub emitter: Entity,
+}
+
+/// Identifies the props which are linked to each other
+pub fn link_pulse_key(
+    asset: &str,
+    name: Option<&str>,
+    props: Option<&CustomProperties>,
+) -> Option<String> {
+    if let Some(id) =
+        props.and_then(|p| p.get_integer("key_id").or_else(|| p.get_integer("rift_id")))
+    {
+        return Some(format!("key:{id}"));
+    }
+
+    // Observers with multiple switches are linked to the first one
+    if let Some(switch) = props
+        .and_then(|p| p.get_string_list("switches"))
+        .and_then(|switches| switches.into_iter().next())
+    {
+        return Some(format!("switch:{switch}"));
+    }
+
+    match asset {
+        "prop-beam_target" | "prop-barrier_switch" => name.map(|name| format!("switch:{name}")),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LinkPulseGroupState {
+    period: f32,
+    phase: f32,
+    aim_boost: f32,
+}
+
+/// Phase of every group of linked props
+#[derive(Singleton, Debug, Default)]
+pub struct LinkPulseGroups {
+    ids: HashMap<String, u64>,
+    groups: Vec<LinkPulseGroupState>,
+    aimed: Option<u64>,
+}
+
+impl LinkPulseGroups {
+    /// Id of the group with the given key. The group is created if it does not exist yet.
+    pub fn register(&mut self, key: &str, period: f32) -> u64 {
+        if let Some(&id) = self.ids.get(key) {
+            return id;
+        }
+        let id = self.groups.len() as u64;
+        self.groups.push(LinkPulseGroupState {
+            period,
+            phase: 0.,
+            aim_boost: 0.,
+        });
+        self.ids.insert(key.to_owned(), id);
+        id
+    }
+
+    pub fn phase(&self, group_id: u64) -> Option<f32> {
+        self.groups.get(group_id as usize).map(|g| g.phase)
+    }
+
+    /// Sets the group the player currently aims at. Aiming at a new group boosts its pulse.
+    pub fn set_aimed(&mut self, group_id: Option<u64>) {
+        let newly_aimed = group_id.filter(|&id| Some(id) != self.aimed);
+        if let Some(group) = newly_aimed.and_then(|id| self.groups.get_mut(id as usize)) {
+            group.aim_boost = 1.;
+        }
+        self.aimed = group_id;
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        for group in self.groups.iter_mut() {
+            group.phase = (group.phase + dt / group.period).fract();
+            group.aim_boost = (group.aim_boost - dt / LINK_PULSE_AIM_BOOST_DURATION).max(0.);
+        }
+    }
+
+    /// Emission scale of members of the group
+    pub fn intensity(&self, group_id: u64) -> f32 {
+        let Some(group) = self.groups.get(group_id as usize) else {
+            return 1.;
+        };
+        let pulse = 0.5 * (1. - (TAU * group.phase).cos());
+        let boost = (0.5 * PI * group.aim_boost).sin();
+        1. + LINK_PULSE_AMPLITUDE * pulse + LINK_PULSE_AIM_BOOST * boost
+    }
+}
+
+/// Synchronized emissive pulsing of linked props
+pub struct LinkPulseMocca;
+
+impl Mocca for LinkPulseMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<MaterialSwapMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<SettingsMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<LinkPulseGroup>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(LinkPulseGroups::default());
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(pulse_linked_props);
+    }
+}
+
+fn pulse_linked_props(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    settings: Singleton<GameSettings>,
+    mut groups: SingletonMut<LinkPulseGroups>,
+    query_input_raycast: Query<&InputRaycastController>,
+    mut query_members: Query<(Entity, &mut LinkPulseGroup)>,
+) {
+    let aimed_entity = query_input_raycast
+        .single()
+        .and_then(|input_raycast| input_raycast.raycast_entity_and_distance())
+        .map(|(entity, _)| entity);
+    let aimed = query_members
+        .iter_mut()
+        .find(|(entity, _)| Some(*entity) == aimed_entity)
+        .map(|(_, member)| member.group_id);
+    groups.set_aimed(aimed);
+    groups.step(time.sim_dt_f32());
+
+    let enabled = !settings.accessibility.disable_link_pulse;
+
+    for (_, member) in query_members.iter_mut() {
+        member.phase = groups.phase(member.group_id).unwrap_or(0.);
+
+        let intensity = if enabled {
+            groups.intensity(member.group_id)
+        } else {
+            1.
+        };
+        cmd.entity(member.emitter).set(EmissionScale(intensity));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_link_pulse_members_in_sync() {
+        let mut groups = LinkPulseGroups::default();
+        let switch = groups.register("switch:s1", LINK_PULSE_PERIOD);
+        groups.step(0.7);
+
+        // a barrier appearing later joins the running pulse of its switch
+        let barrier = groups.register("switch:s1", LINK_PULSE_PERIOD);
+        let other = groups.register("key:3", LINK_PULSE_PERIOD);
+        assert_eq!(switch, barrier);
+        assert_ne!(switch, other);
+
+        groups.step(0.5);
+        assert_abs_diff_eq!(
+            groups.phase(switch).unwrap(),
+            1.2 / LINK_PULSE_PERIOD,
+            epsilon = 1e-6
+        );
+        assert_abs_diff_eq!(groups.phase(other).unwrap(), 0.5 / LINK_PULSE_PERIOD);
+
+        for _ in 0..100 {
+            groups.step(0.1);
+            let phase = groups.phase(switch).unwrap();
+            assert!((0. ..1.).contains(&phase));
+        }
+    }
+
+    #[test]
+    fn test_link_pulse_phase_independent_of_switch_state() {
+        let mut groups = LinkPulseGroups::default();
+        let id = groups.register("switch:s1", LINK_PULSE_PERIOD);
+        groups.step(0.9);
+        let phase = groups.phase(id).unwrap();
+
+        // toggling the switch re-applies material swaps but does not touch the group
+        assert_eq!(groups.register("switch:s1", LINK_PULSE_PERIOD), id);
+        assert_abs_diff_eq!(groups.phase(id).unwrap(), phase);
+
+        groups.step(0.3);
+        assert_abs_diff_eq!(
+            groups.phase(id).unwrap(),
+            phase + 0.3 / LINK_PULSE_PERIOD,
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn test_link_pulse_aim_boost() {
+        let mut groups = LinkPulseGroups::default();
+        let id = groups.register("key:1", LINK_PULSE_PERIOD);
+        assert_abs_diff_eq!(groups.intensity(id), 1.);
+
+        groups.set_aimed(Some(id));
+        assert!(groups.intensity(id) > 1. + LINK_PULSE_AMPLITUDE);
+
+        // the boost is brief even if the player keeps aiming
+        groups.set_aimed(Some(id));
+        groups.step(LINK_PULSE_AIM_BOOST_DURATION);
+        groups.set_aimed(Some(id));
+        assert!(groups.intensity(id) <= 1. + LINK_PULSE_AMPLITUDE);
+    }
+}
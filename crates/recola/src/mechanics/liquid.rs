@@ -0,0 +1,188 @@
+use crate::{collision::*, custom_properties::*};
+use atom::prelude::*;
+use glam::{Affine3A, Vec3};
+use magi::{color::SRgbU8Color, geo::Aabb};
+
+/// Ratio of refractive indices when entering water from air
+pub const LIQUID_DEFAULT_REFRACTION_RATIO: f32 = 1.0 / 1.33;
+
+/// Absorption coefficient per meter
+pub const LIQUID_DEFAULT_ABSORPTION: f32 = 0.35;
+
+/// Beams end when their transmittance drops below this value
+pub const LIQUID_TRANSMITTANCE_CUTOFF: f32 = 0.05;
+
+pub const LIQUID_DEFAULT_TINT: SRgbU8Color = SRgbU8Color::from_rgb(40, 200, 180);
+
+/// A volume of liquid which refracts and attenuates laser beams. The volume is the unit cube
+/// transformed by the global transform of the entity and the liquid surface is its top face.
+#[derive(Component)]
+pub struct LiquidVolume {
+    /// Ratio of refractive indices n_air / n_liquid
+    pub refraction_ratio: f32,
+
+    /// Beer-Lambert absorption coefficient per meter
+    pub absorption: f32,
+
+    /// Color of beams inside the liquid
+    pub tint: SRgbU8Color,
+}
+
+impl LiquidVolume {
+    /// Parameters are read from the `refraction_ratio` and `absorption` custom properties.
+    pub fn from_properties(props: Option<&CustomProperties>) -> Self {
+        let get = |key: &str, default: f32| {
+            props
+                .and_then(|p| p.get_float(key))
+                .map_or(default, |v| v as f32)
+        };
+
+        Self {
+            refraction_ratio: get("refraction_ratio", LIQUID_DEFAULT_REFRACTION_RATIO),
+            absorption: get("absorption", LIQUID_DEFAULT_ABSORPTION).max(0.),
+            tint: LIQUID_DEFAULT_TINT,
+        }
+    }
+
+    /// Fraction of beam intensity left after traveling the given distance through the liquid
+    pub fn transmittance(&self, distance: f32) -> f32 {
+        (-self.absorption * distance).exp()
+    }
+
+    /// Distance at which the transmittance drops to [LIQUID_TRANSMITTANCE_CUTOFF]
+    pub fn cutoff_distance(&self) -> f32 {
+        if self.absorption > 0. {
+            -LIQUID_TRANSMITTANCE_CUTOFF.ln() / self.absorption
+        } else {
+            f32::INFINITY
+        }
+    }
+}
+
+/// Refracts a unit direction at a surface with unit normal pointing against the incoming
+/// direction. `eta` is the ratio of refractive indices n_incoming / n_outgoing. Returns `None` on
+/// total internal reflection.
+pub fn refract(dir: Vec3, normal: Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = -normal.dot(dir);
+    let k = 1. - eta * eta * (1. - cos_i * cos_i);
+    if k < 0. {
+        None
+    } else {
+        Some((eta * dir + (eta * cos_i - k.sqrt()) * normal).normalize())
+    }
+}
+
+/// Distance along a ray at which it enters the top face of an axis aligned volume from above
+pub fn liquid_surface_entry(origin: Vec3, dir: Vec3, aabb: &Aabb<Vec3>) -> Option<f32> {
+    let surface = aabb.max.z;
+    if origin.z <= surface || dir.z >= 0. {
+        return None;
+    }
+
+    let t = (surface - origin.z) / dir.z;
+    let p = origin + t * dir;
+    let inside = aabb.min.x <= p.x && p.x <= aabb.max.x && aabb.min.y <= p.y && p.y <= aabb.max.y;
+    inside.then_some(t)
+}
+
+/// Axis aligned bounding box of a liquid volume in world coordinates
+pub fn liquid_aabb(world_t_volume: &Affine3A) -> Option<Aabb<Vec3>> {
+    PosedCuboid::from_unit_cube_tf(*world_t_volume)
+        .ok()
+        .map(|cuboid| cuboid.aabb())
+}
+
+/// Liquid volumes which interact with laser beams
+pub struct LiquidMocca;
+
+impl Mocca for LiquidMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CustomPropertiesMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<LiquidVolume>();
+    }
+
+    fn start(_world: &mut World) -> Self {
+        Self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn incident(angle_deg: f32) -> Vec3 {
+        let a = angle_deg.to_radians();
+        Vec3::new(a.sin(), 0., -a.cos())
+    }
+
+    #[test]
+    fn test_refract_normal_incidence() {
+        let dir = refract(-Vec3::Z, Vec3::Z, LIQUID_DEFAULT_REFRACTION_RATIO).unwrap();
+        assert_abs_diff_eq!(dir.x, 0.0, epsilon = 1e-6);
+        assert_abs_diff_eq!(dir.z, -1.0, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn test_refract_snell() {
+        let eta = LIQUID_DEFAULT_REFRACTION_RATIO;
+        for angle in [15.0_f32, 30.0, 45.0, 60.0, 80.0] {
+            let dir = refract(incident(angle), Vec3::Z, eta).unwrap();
+
+            // bent towards the normal according to Snell's law
+            let sin_t = dir.x;
+            assert_abs_diff_eq!(sin_t, eta * angle.to_radians().sin(), epsilon = 1e-5);
+            assert!(dir.z < 0.);
+            assert_abs_diff_eq!(dir.length(), 1.0, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_refract_total_internal_reflection() {
+        // leaving the liquid at a flat angle
+        let eta = 1. / LIQUID_DEFAULT_REFRACTION_RATIO;
+        let up = |angle: f32| -incident(angle);
+        assert!(refract(up(30.0), -Vec3::Z, eta).is_some());
+        assert!(refract(up(60.0), -Vec3::Z, eta).is_none());
+    }
+
+    #[test]
+    fn test_attenuation_cutoff() {
+        let liquid = LiquidVolume::from_properties(None);
+        assert_abs_diff_eq!(liquid.transmittance(0.), 1.0);
+
+        let d = liquid.cutoff_distance();
+        assert_abs_diff_eq!(d, 8.559, epsilon = 1e-3);
+        assert_abs_diff_eq!(
+            liquid.transmittance(d),
+            LIQUID_TRANSMITTANCE_CUTOFF,
+            epsilon = 1e-6
+        );
+        assert!(liquid.transmittance(0.5 * d) > LIQUID_TRANSMITTANCE_CUTOFF);
+
+        let clear = LiquidVolume {
+            absorption: 0.,
+            ..LiquidVolume::from_properties(None)
+        };
+        assert_eq!(clear.cutoff_distance(), f32::INFINITY);
+    }
+
+    #[test]
+    fn test_liquid_surface_entry() {
+        let aabb = Aabb::from_points([Vec3::splat(-1.), Vec3::splat(1.)]);
+        let origin = Vec3::new(0., 0., 3.);
+        assert_abs_diff_eq!(
+            liquid_surface_entry(origin, -Vec3::Z, &aabb).unwrap(),
+            2.0,
+            epsilon = 1e-6
+        );
+        assert_eq!(liquid_surface_entry(origin, Vec3::Z, &aabb), None);
+        assert_eq!(
+            liquid_surface_entry(origin, Vec3::new(1., 0., -0.1).normalize(), &aabb),
+            None
+        );
+    }
+}
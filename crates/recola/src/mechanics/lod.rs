@@ -0,0 +1,203 @@
+use crate::{custom_properties::*, player::*, settings::*};
+use atom::prelude::*;
+use candy::scene_tree::*;
+use glam::Vec3;
+
+/// Default distances at which LOD1 and LOD2 are used
+pub const LOD_DEFAULT_THRESHOLDS: [f32; 2] = [15.0, 40.0];
+
+/// Relative width of the band around a threshold in which the LOD level is kept
+pub const LOD_HYSTERESIS: f32 = 0.1;
+
+/// Number of frames between two LOD evaluations
+const LOD_UPDATE_INTERVAL: usize = 4;
+
+/// Number of frames between two reports of [LodStats] in the log
+const LOD_STATS_LOG_INTERVAL: usize = 600;
+
+/// Maximum number of LOD levels per group
+pub const LOD_LEVEL_COUNT: usize = 3;
+
+/// A prop with multiple levels of detail. Exactly one level is visible at a time.
+#[derive(Component, Debug)]
+pub struct LodGroup {
+    /// Distances at which LOD1 and LOD2 are used
+    pub thresholds: [f32; 2],
+
+    /// Entities of LOD0, LOD1, .. Contains at least LOD0.
+    pub levels: Vec<Entity>,
+
+    /// Currently visible level
+    pub active: usize,
+}
+
+impl LodGroup {
+    /// Thresholds are read from the `lod_distance_1` and `lod_distance_2` custom properties.
+    pub fn new(levels: Vec<Entity>, props: Option<&CustomProperties>) -> Self {
+        assert!(!levels.is_empty());
+        Self {
+            thresholds: lod_thresholds(props),
+            levels,
+            active: 0,
+        }
+    }
+}
+
+/// Counters of the LOD system since start. Reported in the debug log.
+#[derive(Singleton, Default, Debug)]
+pub struct LodStats {
+    pub groups_evaluated: usize,
+    pub switches: usize,
+}
+
+/// LOD thresholds from custom properties with defaults for missing values
+pub fn lod_thresholds(props: Option<&CustomProperties>) -> [f32; 2] {
+    let get = |key: &str, default: f32| {
+        props
+            .and_then(|p| p.get_float(key))
+            .map_or(default, |v| v as f32)
+    };
+
+    let lod1 = get("lod_distance_1", LOD_DEFAULT_THRESHOLDS[0]);
+    let lod2 = get("lod_distance_2", LOD_DEFAULT_THRESHOLDS[1]);
+    if lod2 < lod1 {
+        log::warn!("LOD thresholds not increasing: {lod1} > {lod2}");
+    }
+
+    [lod1, lod2.max(lod1)]
+}
+
+/// Selects the LOD level for a given distance. The current level is kept while the distance is
+/// within the hysteresis band around a threshold.
+pub fn select_lod(distance: f32, thresholds: [f32; 2], current: usize) -> usize {
+    let mut level = current.min(LOD_LEVEL_COUNT - 1);
+    while level < LOD_LEVEL_COUNT - 1 && distance > thresholds[level] * (1. + LOD_HYSTERESIS) {
+        level += 1;
+    }
+    while level > 0 && distance < thresholds[level - 1] * (1. - LOD_HYSTERESIS) {
+        level -= 1;
+    }
+    level
+}
+
+/// Swaps prop meshes based on distance to the player
+pub struct LodMocca {
+    frame: usize,
+}
+
+impl Mocca for LodMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<SettingsMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<LodGroup>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(LodStats::default());
+        Self { frame: 0 }
+    }
+
+    fn step(&mut self, world: &mut World) {
+        if self.frame % LOD_UPDATE_INTERVAL == 0 {
+            world.run(update_lod_groups);
+        }
+        if self.frame % LOD_STATS_LOG_INTERVAL == 0 {
+            world.run(log_lod_stats);
+        }
+        self.frame += 1;
+    }
+}
+
+fn update_lod_groups(
+    mut cmd: Commands,
+    player: Singleton<Player>,
+    settings: Singleton<GameSettings>,
+    mut stats: SingletonMut<LodStats>,
+    mut query: Query<(&GlobalTransform3, &mut LodGroup)>,
+) {
+    for (tf, group) in query.iter_mut() {
+        stats.groups_evaluated += 1;
+
+        let distance = tf
+            .transform_point3(Vec3::ZERO)
+            .distance(player.eye_position);
+        let thresholds = group
+            .thresholds
+            .map(|t| t * settings.graphics.lod_quality_bias);
+
+        // Groups with fewer levels keep showing their coarsest level
+        let level = select_lod(distance, thresholds, group.active).min(group.levels.len() - 1);
+        if level == group.active {
+            continue;
+        }
+
+        cmd.entity(group.levels[group.active])
+            .set(Visibility::Hidden);
+        cmd.entity(group.levels[level]).set(Visibility::Visible);
+        group.active = level;
+        stats.switches += 1;
+    }
+}
+
+fn log_lod_stats(stats: Singleton<LodStats>) {
+    log::debug!(
+        "LOD: {} groups evaluated, {} switches",
+        stats.groups_evaluated,
+        stats.switches
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_lod_hysteresis() {
+        let th = [10.0, 30.0];
+
+        assert_eq!(select_lod(5.0, th, 0), 0);
+        assert_eq!(select_lod(10.5, th, 0), 0);
+        assert_eq!(select_lod(11.5, th, 0), 1);
+
+        // inside the band the current level is kept
+        assert_eq!(select_lod(9.5, th, 1), 1);
+        assert_eq!(select_lod(8.5, th, 1), 0);
+        assert_eq!(select_lod(31.0, th, 1), 1);
+        assert_eq!(select_lod(34.0, th, 1), 2);
+        assert_eq!(select_lod(28.0, th, 2), 2);
+
+        // large jumps skip levels
+        assert_eq!(select_lod(100.0, th, 0), 2);
+        assert_eq!(select_lod(1.0, th, 2), 0);
+    }
+
+    #[test]
+    fn test_lod_thresholds_from_properties() {
+        assert_eq!(lod_thresholds(None), LOD_DEFAULT_THRESHOLDS);
+
+        let props = CustomProperties::from_json(&HashMap::from([(
+            "lod_distance_1".to_owned(),
+            serde_json::json!(8),
+        )]));
+        assert_eq!(lod_thresholds(Some(&props)), [8.0, 40.0]);
+
+        let props = CustomProperties::from_json(&HashMap::from([
+            ("lod_distance_1".to_owned(), serde_json::json!(12.5)),
+            ("lod_distance_2".to_owned(), serde_json::json!(20.0)),
+        ]));
+        assert_eq!(lod_thresholds(Some(&props)), [12.5, 20.0]);
+
+        // thresholds are kept increasing
+        let props = CustomProperties::from_json(&HashMap::from([(
+            "lod_distance_1".to_owned(),
+            serde_json::json!(50.0),
+        )]));
+        assert_eq!(lod_thresholds(Some(&props)), [50.0, 50.0]);
+    }
+}
@@ -41,6 +41,11 @@ impl MaterialSwapTransition {
     }
 }
 
+/// Scales the emission of the material of an entity, e.g. to let it pulse. For entities with a
+/// [MaterialSwap] the scale is applied on top of the selected material.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct EmissionScale(pub f32);
+
 /// Indicates the current material used by material swap
 #[derive(Component)]
 struct MaterialSwapState {
@@ -65,11 +70,15 @@ impl Mocca for MaterialSwapMocca {
         world.register_component::<MaterialSwap>();
         world.register_component::<MaterialSwapTransition>();
         world.register_component::<MaterialSwapState>();
+        world.register_component::<EmissionScale>();
+        world.register_component::<EmissionScaleBase>();
     }
 
     fn step(&mut self, world: &mut World) {
         world.run(init_current_id);
         world.run(swap_materials);
+        world.run(init_emission_scale_base);
+        world.run(scale_emission_without_swap);
     }
 }
 
@@ -94,11 +103,12 @@ fn swap_materials(
         &MaterialSwap,
         &MaterialSwapTransition,
         &mut MaterialSwapState,
+        Option<&EmissionScale>,
     )>,
 ) {
     let dt = time.sim_dt_f32();
 
-    for (entity, mats, transition, state) in query.iter_mut() {
+    for (entity, mats, transition, state, emission_scale) in query.iter_mut() {
         if transition.index >= mats.materials.len() {
             log::error!("invalid MaterialSwapId: index={}", transition.index);
             continue;
@@ -119,6 +129,49 @@ fn swap_materials(
             .clone()
             .lerp(mats.materials[state.target].clone(), state.interp.value());
 
+        let mat = match emission_scale {
+            Some(scale) => scale_emission(mat, scale.0),
+            None => mat,
+        };
+
         cmd.entity(entity).and_set(mat).and_set(MaterialDirty);
     }
 }
+
+/// Material of an entity without material swap before its emission was scaled
+#[derive(Component)]
+struct EmissionScaleBase(Material);
+
+fn init_emission_scale_base(
+    mut cmd: Commands,
+    query: Query<
+        (Entity, &Material),
+        (
+            With<EmissionScale>,
+            Without<MaterialSwap>,
+            Without<EmissionScaleBase>,
+        ),
+    >,
+) {
+    for (entity, material) in query.iter() {
+        cmd.entity(entity).set(EmissionScaleBase(material.clone()));
+    }
+}
+
+fn scale_emission_without_swap(
+    mut cmd: Commands,
+    query: Query<(Entity, &EmissionScale, &EmissionScaleBase)>,
+) {
+    for (entity, scale, base) in query.iter() {
+        cmd.entity(entity)
+            .and_set(scale_emission(base.0.clone(), scale.0))
+            .and_set(MaterialDirty);
+    }
+}
+
+fn scale_emission(mut material: Material, scale: f32) -> Material {
+    if let Material::Pbr(pbr) = &mut material {
+        pbr.emission = pbr.emission * scale;
+    }
+    material
+}
@@ -1,2 +1,8 @@
+pub mod audio_emitter;
+pub mod link_pulse;
+pub mod liquid;
+pub mod lod;
 pub mod material_swap;
+pub mod prop_events;
+pub mod surface;
 pub mod switch;
@@ -0,0 +1,201 @@
+use atom::prelude::*;
+use glam::Vec3;
+use std::collections::VecDeque;
+
+/// Number of events kept for consumers which did not drain yet
+pub const PROP_EVENTS_CAPACITY: usize = 256;
+
+/// Something happened to a prop
+#[derive(Debug, Clone, PartialEq)]
+pub enum PropEvent {
+    /// The blueprint of a gameplay prop was applied, see [is_gameplay_prop]
+    PropSpawned {
+        kind: String,
+        entity: Entity,
+        position: Vec3,
+    },
+
+    /// A gameplay prop was removed, e.g. burned overgrowth or a prop deleted in forge mode
+    PropDespawned { kind: String, entity: Entity },
+
+    /// A named switch turned on or off
+    SwitchChanged { name: String, on: bool },
+
+    /// A door opened or closed
+    DoorStateChanged { entity: Entity, open: bool },
+
+    /// The player charged a rift
+    RiftCharged { entity: Entity, level: i64 },
+}
+
+/// Props which take part in puzzles. Spawn and despawn events are only published for these, so
+/// that loading a level full of walls and floors does not overflow [PropEvents].
+pub fn is_gameplay_prop(kind: &str) -> bool {
+    matches!(
+        kind,
+        "prop-laser"
+            | "prop-laser_turret"
+            | "prop-beam_target"
+            | "prop-barrier_switch"
+            | "prop-archway_3x6_door"
+            | "prop-gate_door"
+            | "prop-barrier_3x6"
+            | "prop-rift"
+            | "prop-reset_lever"
+            | "prop-swing_door"
+            | "prop-rope"
+            | "prop-overgrowth-1"
+            | "prop-overgrowth-2"
+            | "prop-overgrowth-3"
+            | "prop-overgrowth_3x3_1"
+    )
+}
+
+/// Bounded ring of prop events. Every consumer keeps its own [PropEventCursor] and drains new
+/// events once per frame. When the ring is full the oldest event is dropped.
+#[derive(Singleton, Debug)]
+pub struct PropEvents {
+    events: VecDeque<PropEvent>,
+    capacity: usize,
+    next_seq: u64,
+    dropped: u64,
+}
+
+/// Position of a consumer in the [PropEvents] ring
+#[derive(Debug, Default, Clone)]
+pub struct PropEventCursor {
+    next_seq: u64,
+
+    /// Number of events which were dropped before this consumer drained them
+    pub missed: u64,
+}
+
+impl PropEvents {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            next_seq: 0,
+            dropped: 0,
+        }
+    }
+
+    pub fn publish(&mut self, event: PropEvent) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+        self.next_seq += 1;
+    }
+
+    /// Total number of events dropped due to overflow
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Cursor which only receives events published from now on
+    pub fn cursor(&self) -> PropEventCursor {
+        PropEventCursor {
+            next_seq: self.next_seq,
+            missed: 0,
+        }
+    }
+
+    /// Events published since the last drain with the given cursor
+    pub fn drain<'a>(
+        &'a self,
+        cursor: &mut PropEventCursor,
+    ) -> impl Iterator<Item = &'a PropEvent> + 'a {
+        let oldest = self.next_seq - self.events.len() as u64;
+        if cursor.next_seq < oldest {
+            cursor.missed += oldest - cursor.next_seq;
+        }
+        let skip = cursor.next_seq.saturating_sub(oldest) as usize;
+        cursor.next_seq = self.next_seq;
+
+        self.events.iter().skip(skip)
+    }
+}
+
+/// Provides the [PropEvents] channel
+pub struct PropEventsMocca;
+
+impl Mocca for PropEventsMocca {
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(PropEvents::new(PROP_EVENTS_CAPACITY));
+        Self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn switch(i: usize) -> PropEvent {
+        PropEvent::SwitchChanged {
+            name: format!("switch-{i}"),
+            on: true,
+        }
+    }
+
+    #[test]
+    fn test_prop_events_delivered_once_per_consumer() {
+        let mut events = PropEvents::new(8);
+        let mut a = events.cursor();
+        let mut b = events.cursor();
+
+        events.publish(switch(0));
+        events.publish(switch(1));
+        assert_eq!(events.drain(&mut a).count(), 2);
+
+        events.publish(switch(2));
+        assert_eq!(
+            events.drain(&mut a).cloned().collect::<Vec<_>>(),
+            [switch(2)]
+        );
+        assert_eq!(events.drain(&mut a).count(), 0);
+
+        assert_eq!(
+            events.drain(&mut b).cloned().collect::<Vec<_>>(),
+            [switch(0), switch(1), switch(2)]
+        );
+        assert_eq!(events.drain(&mut b).count(), 0);
+
+        // late consumers only see new events
+        let mut c = events.cursor();
+        assert_eq!(events.drain(&mut c).count(), 0);
+        events.publish(switch(3));
+        assert_eq!(events.drain(&mut c).count(), 1);
+    }
+
+    #[test]
+    fn test_structural_props_are_not_gameplay_props() {
+        assert!(is_gameplay_prop("prop-laser"));
+        assert!(is_gameplay_prop("prop-overgrowth-2"));
+        assert!(!is_gameplay_prop("prop-floor_3x3"));
+        assert!(!is_gameplay_prop("prop-wall_block_3_3"));
+        assert!(!is_gameplay_prop("cable_up_cout_2"));
+    }
+
+    #[test]
+    fn test_prop_events_overflow_drops_oldest() {
+        let mut events = PropEvents::new(4);
+        let mut cursor = events.cursor();
+
+        for i in 0..6 {
+            events.publish(switch(i));
+        }
+        assert_eq!(events.dropped(), 2);
+
+        assert_eq!(
+            events.drain(&mut cursor).cloned().collect::<Vec<_>>(),
+            [switch(2), switch(3), switch(4), switch(5)]
+        );
+        assert_eq!(cursor.missed, 2);
+
+        events.publish(switch(6));
+        assert_eq!(events.drain(&mut cursor).count(), 1);
+        assert_eq!(cursor.missed, 2);
+    }
+}
@@ -0,0 +1,276 @@
+use crate::{collision::*, custom_properties::*, player::*};
+use atom::prelude::*;
+use candy::{camera::*, scene_tree::*, time::*};
+use glam::{Vec2, Vec3, Vec3Swizzles};
+use magi::geo::Aabb;
+
+/// Friction of a walkable surface relative to regular ground. Values below one are slippery and
+/// values above one slow the player down.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct SurfaceFriction(pub f32);
+
+impl SurfaceFriction {
+    pub const DEFAULT: Self = Self(1.0);
+    pub const ICE: Self = Self(0.1);
+    pub const MUD: Self = Self(2.5);
+
+    /// Reads the `surface` custom property. Supported values are "ice" and "mud".
+    pub fn from_properties(props: &CustomProperties) -> Option<Self> {
+        match props.get_string("surface")? {
+            "ice" => Some(Self::ICE),
+            "mud" => Some(Self::MUD),
+            other => {
+                log::warn!("unknown surface '{other}'");
+                None
+            }
+        }
+    }
+}
+
+/// Movement parameters of the first person camera controller
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MovementParams {
+    pub max_speed: f32,
+    pub acceleration: f32,
+    pub deacceleration: f32,
+}
+
+impl MovementParams {
+    pub const DEFAULT: Self = Self {
+        max_speed: 6.0,
+        acceleration: 20.0,
+        deacceleration: 25.0,
+    };
+
+    /// Low deacceleration preserves momentum on ice
+    pub const ICE: Self = Self {
+        max_speed: 6.5,
+        acceleration: 4.0,
+        deacceleration: 1.0,
+    };
+
+    pub const MUD: Self = Self {
+        max_speed: 2.5,
+        acceleration: 10.0,
+        deacceleration: 40.0,
+    };
+
+    /// All parameters multiplied by a factor
+    pub fn scaled(&self, factor: f32) -> Self {
+        Self {
+            max_speed: self.max_speed * factor,
+            acceleration: self.acceleration * factor,
+            deacceleration: self.deacceleration * factor,
+        }
+    }
+
+    /// Writes the parameters into the camera controller settings
+    pub fn apply(&self, settings: &mut FirstPersonCameraControllerSettings) {
+        settings.move_max_speed = self.max_speed;
+        settings.move_acceleration = self.acceleration;
+        settings.move_deacceleration = self.deacceleration;
+    }
+
+    pub fn lerp(&self, other: &Self, q: f32) -> Self {
+        let f = |a: f32, b: f32| a + (b - a) * q;
+        Self {
+            max_speed: f(self.max_speed, other.max_speed),
+            acceleration: f(self.acceleration, other.acceleration),
+            deacceleration: f(self.deacceleration, other.deacceleration),
+        }
+    }
+
+    /// Blends between ice, default and mud parameters. The blend is continuous in the friction.
+    pub fn from_friction(friction: f32) -> Self {
+        let ice = SurfaceFriction::ICE.0;
+        let mud = SurfaceFriction::MUD.0;
+        let friction = friction.clamp(ice, mud);
+        if friction < 1. {
+            Self::ICE.lerp(&Self::DEFAULT, (friction - ice) / (1. - ice))
+        } else {
+            Self::DEFAULT.lerp(&Self::MUD, (friction - 1.) / (mud - 1.))
+        }
+    }
+}
+
+/// Half life of the friction smoothing applied when the player moves between surfaces
+const SURFACE_FRICTION_HALFLIFE: f32 = 0.15;
+
+/// Friction is sampled at the capsule center and at points on a circle with this radius
+const SURFACE_SAMPLE_RADIUS: f32 = 0.25;
+
+/// Friction of the surface below the player
+#[derive(Singleton, Debug, Clone)]
+pub struct PlayerSurface {
+    /// Friction smoothed over time
+    pub friction: f32,
+}
+
+impl PlayerSurface {
+    pub fn step(&mut self, dt: f32, target: f32) {
+        let q = 1. - 0.5_f32.powf(dt / SURFACE_FRICTION_HALFLIFE);
+        self.friction += (target - self.friction) * q;
+    }
+}
+
+/// Average friction of the samples around the capsule center. Positions outside all surfaces
+/// count as regular ground.
+pub fn sample_surface_friction(
+    center: Vec2,
+    eye_z: f32,
+    surfaces: &[(Aabb<Vec3>, SurfaceFriction)],
+) -> f32 {
+    let offsets = [
+        Vec2::ZERO,
+        Vec2::X * SURFACE_SAMPLE_RADIUS,
+        -Vec2::X * SURFACE_SAMPLE_RADIUS,
+        Vec2::Y * SURFACE_SAMPLE_RADIUS,
+        -Vec2::Y * SURFACE_SAMPLE_RADIUS,
+    ];
+
+    let total: f32 = offsets
+        .iter()
+        .map(|offset| {
+            let p = center + *offset;
+            surfaces
+                .iter()
+                .find(|(aabb, _)| {
+                    aabb.min.x <= p.x
+                        && p.x <= aabb.max.x
+                        && aabb.min.y <= p.y
+                        && p.y <= aabb.max.y
+                        && aabb.max.z <= eye_z
+                })
+                .map_or(SurfaceFriction::DEFAULT.0, |(_, friction)| friction.0)
+        })
+        .sum();
+
+    total / offsets.len() as f32
+}
+
+/// Surfaces like ice or mud which change how the player moves
+pub struct SurfaceMocca;
+
+impl Mocca for SurfaceMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyCameraMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<PlayerMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<SurfaceFriction>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(PlayerSurface {
+            friction: SurfaceFriction::DEFAULT.0,
+        });
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(apply_surface_friction);
+    }
+}
+
+fn apply_surface_friction(
+    time: Singleton<SimClock>,
+    player: Singleton<Player>,
+    mut surface: SingletonMut<PlayerSurface>,
+    query_surface: Query<(&SurfaceFriction, &GlobalTransform3)>,
+    mut query_cam_ctrl: Query<&mut FirstPersonCameraController>,
+) {
+    // ghost mode uses its own movement settings
+    if player.cheat_ghost_mode {
+        return;
+    }
+
+    let Some(cam_ctrl) = query_cam_ctrl.single_mut() else {
+        return;
+    };
+
+    let surfaces: Vec<_> = query_surface
+        .iter()
+        .filter_map(|(friction, tf)| {
+            PosedCuboid::from_unit_cube_tf(tf.affine())
+                .ok()
+                .map(|cuboid| (cuboid.aabb(), *friction))
+        })
+        .collect();
+
+    let target =
+        sample_surface_friction(cam_ctrl.position().xy(), player.eye_position.z, &surfaces);
+    surface.step(time.sim_dt_f32(), target);
+
+    MovementParams::from_friction(surface.friction).apply(cam_ctrl.settings_mut());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn assert_params_eq(actual: MovementParams, expected: MovementParams) {
+        assert_abs_diff_eq!(actual.max_speed, expected.max_speed, epsilon = 1e-2);
+        assert_abs_diff_eq!(actual.acceleration, expected.acceleration, epsilon = 1e-2);
+        assert_abs_diff_eq!(
+            actual.deacceleration,
+            expected.deacceleration,
+            epsilon = 1e-2
+        );
+    }
+
+    #[test]
+    fn test_movement_params_blend() {
+        assert_params_eq(MovementParams::from_friction(1.0), MovementParams::DEFAULT);
+        assert_params_eq(
+            MovementParams::from_friction(SurfaceFriction::ICE.0),
+            MovementParams::ICE,
+        );
+        assert_params_eq(
+            MovementParams::from_friction(SurfaceFriction::MUD.0),
+            MovementParams::MUD,
+        );
+
+        // halfway between ice and regular ground
+        let half = MovementParams::from_friction(0.55);
+        assert_params_eq(
+            half,
+            MovementParams::ICE.lerp(&MovementParams::DEFAULT, 0.5),
+        );
+
+        // continuous around regular ground
+        let below = MovementParams::from_friction(1.0 - 1e-4);
+        let above = MovementParams::from_friction(1.0 + 1e-4);
+        assert_abs_diff_eq!(below.deacceleration, above.deacceleration, epsilon = 1e-2);
+    }
+
+    #[test]
+    fn test_surface_friction_restores_after_leaving() {
+        let ice = (
+            Aabb::from_points([Vec3::new(0., 0., -1.), Vec3::new(4., 4., 0.)]),
+            SurfaceFriction::ICE,
+        );
+
+        let on_ice = sample_surface_friction(Vec2::new(2., 2.), 1.7, &[ice]);
+        assert_abs_diff_eq!(on_ice, SurfaceFriction::ICE.0);
+
+        // partially on the edge
+        let edge = sample_surface_friction(Vec2::new(4., 2.), 1.7, &[ice]);
+        assert!(SurfaceFriction::ICE.0 < edge && edge < 1.0);
+
+        let mut surface = PlayerSurface { friction: on_ice };
+        for _ in 0..100 {
+            let target = sample_surface_friction(Vec2::new(8., 2.), 1.7, &[ice]);
+            surface.step(0.02, target);
+        }
+        assert_abs_diff_eq!(surface.friction, 1.0, epsilon = 1e-3);
+        assert_params_eq(
+            MovementParams::from_friction(surface.friction),
+            MovementParams::DEFAULT,
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::mechanics::prop_events::*;
 use atom::prelude::*;
 use std::collections::HashSet;
 
@@ -55,11 +56,38 @@ impl SwitchState {
     }
 }
 
+/// Activates the observer if all its switches are active. Latched observers stay active.
+pub fn update_switch_observer(
+    observer: &SwitchObserver,
+    state: &mut SwitchObserverState,
+    active_switches: &HashSet<&str>,
+) {
+    let active = observer
+        .switches
+        .iter()
+        .all(|id| active_switches.contains(id.as_str()));
+
+    if active {
+        *state = SwitchObserverState::Active;
+    } else if !observer.latch {
+        *state = SwitchObserverState::Inactive;
+    }
+}
+
+/// Names of switches which were on in the previous frame
+#[derive(Singleton, Default)]
+struct ActiveSwitches(HashSet<String>);
+
 /// Switches and switch observers
 pub struct SwitchMocca;
 
 impl Mocca for SwitchMocca {
-    fn start(_world: &mut World) -> Self {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<PropEventsMocca>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(ActiveSwitches::default());
         Self
     }
 
@@ -76,6 +104,8 @@ impl Mocca for SwitchMocca {
 }
 
 fn update_switch_triggers(
+    mut previous_switches: SingletonMut<ActiveSwitches>,
+    mut events: SingletonMut<PropEvents>,
     query_switches: Query<(&Switch, &SwitchState)>,
     mut query_observers: Query<(Entity, &SwitchObserver, &mut SwitchObserverState)>,
 ) {
@@ -88,30 +118,42 @@ fn update_switch_triggers(
     }
     log::trace!("active switches: {:?}", active_switches);
 
+    for &name in active_switches.iter() {
+        if !previous_switches.0.contains(name) {
+            events.publish(PropEvent::SwitchChanged {
+                name: name.to_owned(),
+                on: true,
+            });
+        }
+    }
+    for name in previous_switches.0.iter() {
+        if !active_switches.contains(name.as_str()) {
+            events.publish(PropEvent::SwitchChanged {
+                name: name.clone(),
+                on: false,
+            });
+        }
+    }
+    previous_switches.0 = active_switches
+        .iter()
+        .map(|&name| name.to_owned())
+        .collect();
+
     for (entity, observer, state) in query_observers.iter_mut() {
-        let active = observer
-            .switches
-            .iter()
-            .all(|id| active_switches.contains(id.as_str()));
+        let was_active = state.as_bool();
+        update_switch_observer(observer, state, &active_switches);
 
         log::trace!(
-            "observer {:?} with switches {:?}: {active}",
+            "observer {:?} with switches {:?}: {}",
             entity,
-            observer.switches
+            observer.switches,
+            state.as_bool()
         );
 
-        if active {
-            if !state.as_bool() {
-                log::debug!("activated switch observer {entity:?}");
-            }
-            *state = SwitchObserverState::Active;
-        } else {
-            if !observer.latch {
-                if state.as_bool() {
-                    log::debug!("de-activated switch observer {entity:?}");
-                }
-                *state = SwitchObserverState::Inactive;
-            }
+        match (was_active, state.as_bool()) {
+            (false, true) => log::debug!("activated switch observer {entity:?}"),
+            (true, false) => log::debug!("de-activated switch observer {entity:?}"),
+            _ => {}
         }
     }
 }
@@ -0,0 +1,375 @@
+//! Paint marks placed by the player to annotate puzzle surfaces
+//!
+//! V paints a mark on the surface the player aims at, C cycles the paint color and B erases the
+//! mark closest to the aimed point. Each level holds at most [PAINT_MARK_LEVEL_CAP] marks. Painting
+//! more marks recycles the oldest mark of that level. Marks are stored in save games.
+
+use crate::{level::*, player::*};
+use atom::prelude::*;
+use candy::{input::*, material::*, prims::*, scene_tree::*, utils::WindowDef};
+use glam::{Quat, Vec3};
+use magi::color::SRgbU8Color;
+use serde::{Deserialize, Serialize};
+
+/// Paint colors in the order they are cycled
+pub const PAINT_MARK_COLORS: [SRgbU8Color; 4] = [
+    SRgbU8Color::from_rgb(230, 57, 70),
+    SRgbU8Color::from_rgb(244, 200, 60),
+    SRgbU8Color::from_rgb(80, 200, 120),
+    SRgbU8Color::from_rgb(70, 130, 230),
+];
+
+/// Maximum number of marks per level
+pub const PAINT_MARK_LEVEL_CAP: usize = 32;
+
+const PAINT_MARK_INTERACTION_DISTANCE: f32 = 4.0;
+
+/// Marks are lifted off the surface to avoid z-fighting
+const PAINT_MARK_OFFSET: f32 = 0.005;
+
+const PAINT_MARK_SIZE: f32 = 0.25;
+
+const PAINT_MARK_THICKNESS: f32 = 0.002;
+
+/// Erasing picks the closest mark within this distance of the aimed point
+const PAINT_MARK_ERASE_RADIUS: f32 = 0.3;
+
+/// A mark painted on a surface
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaintMark {
+    /// Name of the level the mark is in
+    pub level: String,
+
+    /// Point on the surface
+    pub position: Vec3,
+
+    /// Surface normal pointing towards the painter
+    pub normal: Vec3,
+
+    /// Index into [PAINT_MARK_COLORS]
+    pub color: usize,
+}
+
+/// Position and rotation of a mark. The local Z axis of the mark is aligned with the surface
+/// normal.
+pub fn paint_mark_pose(point: Vec3, normal: Vec3) -> (Vec3, Quat) {
+    let normal = normal.normalize();
+    (
+        point + PAINT_MARK_OFFSET * normal,
+        Quat::from_rotation_arc(Vec3::Z, normal),
+    )
+}
+
+/// Paint marks ordered from oldest to newest. Each mark is represented by a handle, e.g. the
+/// entity which shows it.
+#[derive(Debug, Clone)]
+pub struct PaintMarkSet<H> {
+    marks: Vec<(H, PaintMark)>,
+}
+
+impl<H> Default for PaintMarkSet<H> {
+    fn default() -> Self {
+        Self { marks: Vec::new() }
+    }
+}
+
+impl<H: Copy> PaintMarkSet<H> {
+    /// Adds a mark and returns its handle. If the level of the mark already has `cap` marks the
+    /// oldest mark of that level is removed and its handle reused. Otherwise a new handle is
+    /// allocated.
+    pub fn add(&mut self, mark: PaintMark, cap: usize, alloc: impl FnOnce() -> H) -> H {
+        let count = self
+            .marks
+            .iter()
+            .filter(|(_, m)| m.level == mark.level)
+            .count();
+        let oldest = self.marks.iter().position(|(_, m)| m.level == mark.level);
+
+        let handle = match oldest {
+            Some(idx) if count >= cap => self.marks.remove(idx).0,
+            _ => alloc(),
+        };
+        self.marks.push((handle, mark));
+        handle
+    }
+
+    /// Removes the mark closest to `position` if it is within `radius`
+    pub fn erase_near(&mut self, position: Vec3, radius: f32) -> Option<(H, PaintMark)> {
+        let idx = self
+            .marks
+            .iter()
+            .enumerate()
+            .map(|(idx, (_, m))| (idx, m.position.distance(position)))
+            .filter(|(_, d)| *d <= radius)
+            .min_by(|(_, d1), (_, d2)| d1.total_cmp(d2))
+            .map(|(idx, _)| idx)?;
+        Some(self.marks.remove(idx))
+    }
+
+    pub fn marks(&self) -> impl Iterator<Item = (H, &PaintMark)> {
+        self.marks.iter().map(|(h, m)| (*h, m))
+    }
+
+    pub fn len(&self) -> usize {
+        self.marks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.marks.is_empty()
+    }
+
+    /// Removes all marks
+    pub fn drain(&mut self) -> impl Iterator<Item = (H, PaintMark)> + '_ {
+        self.marks.drain(..)
+    }
+}
+
+/// Paint marks in the world
+#[derive(Singleton, Default)]
+pub struct PaintMarks {
+    set: PaintMarkSet<Entity>,
+
+    /// Hidden mark entities which can be reused
+    pool: Vec<Entity>,
+
+    /// Index into [PAINT_MARK_COLORS] used for the next mark
+    pub color: usize,
+
+    /// Marks from a save game which replace the current marks
+    pending_load: Option<Vec<PaintMark>>,
+}
+
+impl PaintMarks {
+    /// Marks in the order they were painted
+    pub fn saved_marks(&self) -> Vec<PaintMark> {
+        self.set.marks().map(|(_, mark)| mark.clone()).collect()
+    }
+
+    /// Replaces all marks with marks from a save game
+    pub fn load(&mut self, marks: Vec<PaintMark>) {
+        self.pending_load = Some(marks);
+    }
+}
+
+/// Receives the paint mark keys
+#[derive(Component, Default)]
+pub struct PaintMarkInput {
+    paint: bool,
+    erase: bool,
+    cycle_color: bool,
+}
+
+impl PaintMarkInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if let InputEvent::KeyboardInput {
+            state: ElementState::Pressed,
+            code,
+            ..
+        } = msg.event
+        {
+            match code {
+                KeyCode::KeyV => self.paint = true,
+                KeyCode::KeyB => self.erase = true,
+                KeyCode::KeyC => self.cycle_color = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl atom::Agent for PaintMarkInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(PaintMarkInput::on_input_event);
+    }
+}
+
+/// Colorable marks which the player paints on surfaces
+pub struct PaintMarkMocca;
+
+impl Mocca for PaintMarkMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandyMaterialMocca>();
+        deps.depends_on::<CandyPrimsMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PlayerMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<PaintMarkInput>();
+        atom::register_agent_components::<PaintMarkInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(PaintMarks::default());
+        world.run(spawn_paint_mark_input);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<PaintMarkInput, _>);
+        world.run(load_paint_marks);
+        world.run(update_paint_marks);
+    }
+}
+
+fn spawn_paint_mark_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, PaintMarkInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn load_paint_marks(mut cmd: Commands, mut marks: SingletonMut<PaintMarks>) {
+    let marks = &mut *marks;
+    let Some(loaded) = marks.pending_load.take() else {
+        return;
+    };
+
+    for (entity, _) in marks.set.drain() {
+        cmd.entity(entity).set(Visibility::Hidden);
+        marks.pool.push(entity);
+    }
+    for mark in loaded {
+        add_paint_mark(&mut cmd, marks, mark);
+    }
+}
+
+fn update_paint_marks(
+    mut cmd: Commands,
+    levels: Singleton<LevelSummary>,
+    mut marks: SingletonMut<PaintMarks>,
+    mut query_input: Query<&mut PaintMarkInput>,
+    query_input_raycast: Query<&InputRaycastController>,
+) {
+    let marks = &mut *marks;
+    let Some(input) = query_input.single_mut() else {
+        return;
+    };
+    let input = std::mem::take(input);
+
+    if input.cycle_color {
+        marks.color = (marks.color + 1) % PAINT_MARK_COLORS.len();
+    }
+
+    let Some(surface) = query_input_raycast
+        .single()
+        .and_then(|input_raycast| input_raycast.raycast_surface())
+        .filter(|surface| surface.distance <= PAINT_MARK_INTERACTION_DISTANCE)
+    else {
+        return;
+    };
+
+    let erased = input
+        .erase
+        .then(|| marks.set.erase_near(surface.point, PAINT_MARK_ERASE_RADIUS))
+        .flatten();
+    if let Some((entity, _)) = erased {
+        cmd.entity(entity).set(Visibility::Hidden);
+        marks.pool.push(entity);
+    }
+
+    if input.paint {
+        let Some(level) = levels.nearest_level_name(surface.point) else {
+            return;
+        };
+        let mark = PaintMark {
+            level: level.to_owned(),
+            position: surface.point,
+            normal: surface.normal,
+            color: marks.color,
+        };
+        add_paint_mark(&mut cmd, marks, mark);
+    }
+}
+
+fn add_paint_mark(cmd: &mut Commands, marks: &mut PaintMarks, mark: PaintMark) {
+    let (position, rotation) = paint_mark_pose(mark.position, mark.normal);
+    let tf = Transform3::from_translation(position)
+        .with_rotation(rotation)
+        .with_scale(Vec3::new(
+            PAINT_MARK_SIZE,
+            PAINT_MARK_SIZE,
+            PAINT_MARK_THICKNESS,
+        ));
+    let color = PAINT_MARK_COLORS[mark.color % PAINT_MARK_COLORS.len()];
+    let material = Material::Pbr(PbrMaterial::default().with_emission(color.to_linear() * 0.5));
+
+    let pool = &mut marks.pool;
+    let entity = marks.set.add(mark, PAINT_MARK_LEVEL_CAP, || {
+        pool.pop().unwrap_or_else(|| {
+            cmd.spawn((
+                Name::from_str("paint mark"),
+                Transform3::identity(),
+                Cuboid,
+                DynamicTransform,
+                HierarchyDirty,
+            ))
+        })
+    });
+
+    cmd.entity(entity)
+        .and_set(tf)
+        .and_set(material)
+        .and_set(Visibility::Visible);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mark(level: &str, x: f32) -> PaintMark {
+        PaintMark {
+            level: level.into(),
+            position: Vec3::new(x, 0., 1.),
+            normal: Vec3::X,
+            color: 0,
+        }
+    }
+
+    #[test]
+    fn test_paint_mark_pose_aligned_with_normal() {
+        let point = Vec3::new(1., 2., 3.);
+        for normal in [
+            Vec3::X,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+            Vec3::new(1., 1., 0.).normalize(),
+        ] {
+            let (position, rotation) = paint_mark_pose(point, normal);
+            assert!((rotation * Vec3::Z).abs_diff_eq(normal, 1e-5), "{normal}");
+            assert!(position.abs_diff_eq(point + PAINT_MARK_OFFSET * normal, 1e-6));
+        }
+    }
+
+    #[test]
+    fn test_paint_mark_level_cap_recycles_oldest() {
+        let mut set = PaintMarkSet::default();
+        let mut next = 0;
+        let mut alloc = || {
+            next += 1;
+            next
+        };
+
+        assert_eq!(set.add(mark("a", 0.), 2, &mut alloc), 1);
+        assert_eq!(set.add(mark("b", 0.), 2, &mut alloc), 2);
+        assert_eq!(set.add(mark("a", 1.), 2, &mut alloc), 3);
+
+        // level a is full: its oldest mark is recycled, level b is untouched
+        assert_eq!(set.add(mark("a", 2.), 2, &mut alloc), 1);
+        assert_eq!(set.len(), 3);
+        let a: Vec<_> = set
+            .marks()
+            .filter(|(_, m)| m.level == "a")
+            .map(|(h, m)| (h, m.position.x))
+            .collect();
+        assert_eq!(a, vec![(3, 1.), (1, 2.)]);
+
+        // erasing frees a slot so the next mark gets a new handle
+        assert_eq!(set.erase_near(Vec3::new(1.1, 0., 1.), 0.3).unwrap().0, 3);
+        assert!(set.erase_near(Vec3::new(5., 0., 1.), 0.3).is_none());
+        assert_eq!(set.add(mark("a", 3.), 2, &mut alloc), 4);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{
     STATIC_SETTINGS,
     collision::*,
+    demo::*,
     level::*,
+    mechanics::surface::MovementParams,
     props::{door::KeyId, rift::RiftLevel},
     recola_mocca::RecolaAssetsMocca,
 };
@@ -34,12 +36,21 @@ pub struct Player {
     pub hours: f32,
     pub hours_target: f32,
 
+    /// If enabled live input does not reach the player controllers
+    pub input_locked: bool,
+
     /// If enabled collision detection is disabled and speed is 10x
     pub cheat_ghost_mode: bool,
 
     /// Used to track cheat teleport
     pub cheat_teleport: usize,
 
+    /// Colliders which blocked the player movement in the current frame
+    pub nav_contacts: Vec<NavContact>,
+
+    /// Set by hazards to send the player to this position in the next frame
+    pub respawn_position: Option<Vec2>,
+
     pub listener_entity: Entity,
 }
 
@@ -48,6 +59,8 @@ pub struct PlayerMocca;
 
 impl Mocca for PlayerMocca {
     fn load(mut deps: MoccaDeps) {
+        // see RecolaMocca
+        deps.depends_on::<DemoMocca>();
         deps.depends_on::<CandyAudioMocca>();
         deps.depends_on::<CandyCameraMocca>();
         deps.depends_on::<CandyCanMocca>();
@@ -86,10 +99,13 @@ impl Mocca for PlayerMocca {
             eye_position: Vec3::Z,
             rift_charges: HashSet::new(),
             keys: HashSet::new(),
-            hours: 12.0,
-            hours_target: 12.0,
+            hours: PLAYER_START_HOURS,
+            hours_target: PLAYER_START_HOURS,
+            input_locked: false,
             cheat_ghost_mode: false,
             cheat_teleport: 0,
+            nav_contacts: Vec::new(),
+            respawn_position: None,
             listener_entity,
         });
 
@@ -101,6 +117,7 @@ impl Mocca for PlayerMocca {
     fn step(&mut self, world: &mut World) {
         world.run(atom::tick_agents::<InputRaycastController, _>);
         world.run(input_raycast);
+        world.run(respawn_player);
         world.run(restrict_player_movement);
         world.run(update_player_eye);
         world.run(advance_time);
@@ -122,9 +139,54 @@ fn play_welcome_clip(mut cmd: Commands, asset_resolver: Singleton<SharedAssetRes
     ));
 }
 
-const PLAYER_SPAWN: Vec2 = Vec2::new(-4.5, -4.5);
+pub const PLAYER_SPAWN: Vec2 = Vec2::new(-4.5, -4.5);
+
+/// Time of day at the start of the game
+pub const PLAYER_START_HOURS: f32 = 12.0;
 const PLAYER_SPHERE_RADIUS: f32 = 0.333;
-const PLAYER_SPHERE_COUNT: usize = 5; // first sphere at height = radius/2, step = radius
+pub const PLAYER_SPHERE_COUNT: usize = 5; // first sphere at height = radius/2, step = radius
+
+/// Movement in ghost mode is this much faster than on regular ground
+const GHOST_MODE_SPEED_FACTOR: f32 = 4.;
+
+/// Player collision shape is an approximate capsule represented by a set of balls
+pub fn player_capsule(pos: Vec2) -> [PosBall3; PLAYER_SPHERE_COUNT] {
+    core::array::from_fn(|i| PosBall3 {
+        position: Vec3::new(pos.x, pos.y, (0.5 + i as f32) * PLAYER_SPHERE_RADIUS),
+        radius: PLAYER_SPHERE_RADIUS,
+    })
+}
+
+/// A collider which blocked the player movement
+#[derive(Debug, Clone, Copy)]
+pub struct NavContact {
+    pub collider_entity: Entity,
+
+    /// Center of the capsule ball which touched the collider
+    pub point: Vec3,
+
+    /// Movement which was blocked by the collider
+    pub motion: Vec3,
+}
+
+fn respawn_player(
+    mut player: SingletonMut<Player>,
+    mut query_cam_ctrl: Query<&mut FirstPersonCameraController>,
+) {
+    let Some(position) = player.respawn_position.take() else {
+        return;
+    };
+
+    let cam_ctrl = query_cam_ctrl
+        .single_mut()
+        .expect("must have FirstPersonCameraController");
+    cam_ctrl.set_position_xy(position);
+
+    // Teleport without collision checks on the way
+    player.previous_position = position;
+
+    log::info!("respawned player at {position}");
+}
 
 fn restrict_player_movement(
     mut player: SingletonMut<Player>,
@@ -136,6 +198,7 @@ fn restrict_player_movement(
         .expect("must have FirstPersonCameraController");
 
     let target = cam_ctrl.position().xy();
+    player.nav_contacts.clear();
 
     // ombit collision detection in ghost mode
     if player.cheat_ghost_mode {
@@ -143,20 +206,12 @@ fn restrict_player_movement(
         return;
     }
 
-    // player collision shape is an approximate capsule reprsented by a set of balls
-    let capsule_f = |pos: Vec2| -> [PosBall3; PLAYER_SPHERE_COUNT] {
-        core::array::from_fn(|i| PosBall3 {
-            position: Vec3::new(pos.x, pos.y, (0.5 + i as f32) * PLAYER_SPHERE_RADIUS),
-            radius: PLAYER_SPHERE_RADIUS,
-        })
-    };
-
     // initial conditions
     let mut position = player.previous_position;
 
     // If player is inside a collider, cast a ray in the opposite direction and move the player
     // out.
-    let capsule = capsule_f(position);
+    let capsule = player_capsule(position);
     if let Some(_) = colliders.closest_exit_multi_ball(&capsule, None, CollisionLayer::Nav) {
         // note that we cannot move to the exit point because that might be up or down ..
 
@@ -182,7 +237,7 @@ fn restrict_player_movement(
             let direction = Vec3::new(remaining.x, remaining.y, 0.) / remaining_len;
 
             // reuse capsule from exit check on first iteration
-            let capsule = capsule_f(position);
+            let capsule = player_capsule(position);
 
             // Check for collision when moving the remaining distance
             let Some(hit) =
@@ -203,6 +258,12 @@ fn restrict_player_movement(
             let safe_distance = (hit.distance - 0.001).max(0.0);
             position = position + direction.xy() * safe_distance;
 
+            player.nav_contacts.push(NavContact {
+                collider_entity: colliders[hit.id].user,
+                point: hit.point,
+                motion: direction * (remaining_len - safe_distance),
+            });
+
             // Allow sliding parallel to the collider
             remaining = remaining - remaining.dot(hit.normal.xy()) * hit.normal.xy();
         }
@@ -236,7 +297,7 @@ fn advance_time(
         .max()
         .unwrap_or(0);
 
-    player.hours_target = 12.0 + HOURS_PER_RIFT_LEVEL * rift_level as f32;
+    player.hours_target = PLAYER_START_HOURS + HOURS_PER_RIFT_LEVEL * rift_level as f32;
     if player.hours < player.hours_target {
         player.hours =
             (player.hours + time.sim_dt_f32() * HOURS_ADVANCE_RATE).min(player.hours_target);
@@ -280,10 +341,11 @@ fn setup_window_and_camera(clock: Singleton<SimClock>, mut cmd: Commands) {
     ));
     add_route::<WindowResizedEvent, _>(&mut cmd, win, cam);
 
+    let movement = MovementParams::DEFAULT;
     let cam_ctrl_settings = FirstPersonCameraControllerSettings {
-        move_max_speed: 6.0,
-        move_acceleration: 20.0,
-        move_deacceleration: 25.0,
+        move_max_speed: movement.max_speed,
+        move_acceleration: movement.acceleration,
+        move_deacceleration: movement.deacceleration,
         yaw_sensitivity: 0.0012,
         pitch_sensitivity: 0.0012,
         pitch_range: (-85.0_f32.to_radians())..(85.0_f32.to_radians()),
@@ -295,18 +357,32 @@ fn setup_window_and_camera(clock: Singleton<SimClock>, mut cmd: Commands) {
     cam_ctrl.set_yaw(90.0_f32.to_radians());
     let cam_ctrl_agent = spawn_agent(&mut cmd, cam_ctrl);
     add_route::<CameraCommand, _>(&mut cmd, cam_ctrl_agent, cam);
-    add_route::<InputEventMessage, _>(&mut cmd, win, cam_ctrl_agent);
     add_route::<Tick, _>(&mut cmd, clock.tick_agent(), cam_ctrl_agent);
 
-    let activator = InputRaycastController::new();
-    let activator_agent = spawn_agent(&mut cmd, activator);
-    add_route::<InputEventMessage, _>(&mut cmd, win, activator_agent);
+    spawn_agent(&mut cmd, InputRaycastController::new());
+
+    // Input events reach the camera controller and the raycast controller through the demo
+    // input gate so that they can be recorded and replayed.
+    let demo_gate_agent = spawn_agent(&mut cmd, DemoInputGate::default());
+    add_route::<InputEventMessage, _>(&mut cmd, win, demo_gate_agent);
+}
+
+/// Surface hit by the interaction raycast
+#[derive(Debug, Clone, Copy)]
+pub struct RaycastSurface {
+    pub point: Vec3,
+
+    /// Surface normal pointing towards the camera
+    pub normal: Vec3,
+
+    pub distance: f32,
 }
 
 #[derive(Component)]
 pub struct InputRaycastController {
     state: InputState,
     raycast_entity_and_distance: Option<(Entity, f32)>,
+    raycast_surface: Option<RaycastSurface>,
 
     cheat_ghost_mode: bool,
     cheat_teleport: usize,
@@ -317,6 +393,7 @@ impl InputRaycastController {
         Self {
             state: InputState::default(),
             raycast_entity_and_distance: None,
+            raycast_surface: None,
             cheat_ghost_mode: false,
             cheat_teleport: 0,
         }
@@ -330,6 +407,10 @@ impl InputRaycastController {
         self.raycast_entity_and_distance
     }
 
+    pub fn raycast_surface(&self) -> Option<RaycastSurface> {
+        self.raycast_surface
+    }
+
     pub fn on_input_event(&mut self, msg: InputEventMessage) {
         self.state = msg.state;
 
@@ -370,6 +451,7 @@ fn input_raycast(
 ) {
     let input_raycast = query_input_raycast.single_mut().unwrap();
     input_raycast.raycast_entity_and_distance = None;
+    input_raycast.raycast_surface = None;
 
     // Ray through center pixel
     let Some(cam) = query_cam.single() else {
@@ -378,12 +460,21 @@ fn input_raycast(
     let ray = cam.center_pixel_ray();
 
     // Find collider along ray
-    let Some((hit_entity, distance)) = colliders
-        .raycast(&ray, 0.10, None, CollisionLayer::Interact)
-        .map(|hit| (colliders[hit.id].user, hit.distance))
-    else {
+    let Some(hit) = colliders.raycast(&ray, 0.10, None, CollisionLayer::Interact) else {
         return;
     };
+    let (hit_entity, distance) = (colliders[hit.id].user, hit.distance);
+
+    let normal = if hit.normal.dot(ray.direction()) > 0. {
+        -hit.normal
+    } else {
+        hit.normal
+    };
+    input_raycast.raycast_surface = Some(RaycastSurface {
+        point: hit.point,
+        normal,
+        distance,
+    });
 
     // Find attached collider
     let Some(collisiont_routing) = query_routing.get(hit_entity) else {
@@ -405,16 +496,12 @@ fn cheats(
 
     // dev mode: toggle ghost mode
     player.cheat_ghost_mode = input_raycast.cheat_ghost_mode;
-    let settings = cam_ctrl.settings_mut();
-    if input_raycast.cheat_ghost_mode {
-        settings.move_max_speed = 6.0 * 4.;
-        settings.move_acceleration = 20.0 * 4.;
-        settings.move_deacceleration = 25.0 * 4.;
+    let movement = if input_raycast.cheat_ghost_mode {
+        MovementParams::DEFAULT.scaled(GHOST_MODE_SPEED_FACTOR)
     } else {
-        settings.move_max_speed = 6.0;
-        settings.move_acceleration = 20.0;
-        settings.move_deacceleration = 25.0;
-    }
+        MovementParams::DEFAULT
+    };
+    movement.apply(cam_ctrl.settings_mut());
 
     // dev mode: Teleport player to level start
     if player.cheat_teleport != input_raycast.cheat_teleport {
@@ -14,6 +14,12 @@ pub struct Barrier {
     is_on: bool,
 }
 
+impl Barrier {
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+}
+
 /// Laser pointers with a beam which collides with objects
 pub struct BarrierMocca;
 
@@ -1,7 +1,7 @@
 use crate::{
     collision::*,
     custom_properties::*,
-    mechanics::{material_swap::*, switch::*},
+    mechanics::{material_swap::*, prop_events::*, switch::*},
     player::*,
     recola_mocca::CRIMSON,
 };
@@ -29,6 +29,16 @@ pub struct SpawnDoubleDoorTask {
 #[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct KeyId(pub i64);
 
+/// Lowers or raises a level gate without animation, e.g. when a save is loaded
+#[derive(Component)]
+pub struct RestoreLevelGateTask {
+    pub lowered: bool,
+}
+
+/// Marks the level gate which ends the game. Set on gates with the `is_final` property.
+#[derive(Component)]
+pub struct FinalGate;
+
 /// Laser pointers with a beam which collides with objects
 pub struct DoorMocca;
 
@@ -42,6 +52,7 @@ impl Mocca for DoorMocca {
         deps.depends_on::<CustomPropertiesMocca>();
         deps.depends_on::<MaterialSwapMocca>();
         deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<PropEventsMocca>();
         deps.depends_on::<SwitchMocca>();
     }
 
@@ -51,9 +62,11 @@ impl Mocca for DoorMocca {
 
     fn register_components(world: &mut World) {
         world.register_component::<DoubleDoor>();
+        world.register_component::<FinalGate>();
         world.register_component::<GlowOnKey>();
         world.register_component::<KeyId>();
         world.register_component::<LevelGate>();
+        world.register_component::<RestoreLevelGateTask>();
         world.register_component::<SpawnDoubleDoorTask>();
         world.register_component::<SpawnLevelGateTask>();
     }
@@ -63,6 +76,7 @@ impl Mocca for DoorMocca {
         world.run(level_gate_glow_on_key);
         world.run(leve_gate_interaction);
         world.run(lower_level_gate);
+        world.run(restore_level_gate);
 
         world.run(spawn_double_door);
         world.run(open_double_door);
@@ -70,12 +84,18 @@ impl Mocca for DoorMocca {
 }
 
 #[derive(Component, Debug, Clone)]
-struct LevelGate {
+pub struct LevelGate {
     lower_progress: f32,
     progress_changed: bool,
     is_lowered: bool,
 }
 
+impl LevelGate {
+    pub fn is_lowered(&self) -> bool {
+        self.is_lowered
+    }
+}
+
 const LEVEL_GATE_INTERACTION_DISTANCE: f32 = 5.;
 const LEVEL_GATE_LOWER_MAX: f32 = 3.933;
 const LEVEL_GATE_LOWER_DURATION: f32 = 5.5; // TODO should match audio clip length!
@@ -127,6 +147,14 @@ fn spawn_level_gate(
                 PbrMaterial::diffuse(CRIMSON).with_emission(CRIMSON.to_linear() * 3.33),
             ]))
             .and_set(MaterialSwapTransition::ZERO);
+
+        if query_props
+            .get(door_entity)
+            .and_then(|props| props.get_bool("is_final"))
+            .unwrap_or(false)
+        {
+            cmd.entity(door_entity).set(FinalGate);
+        }
     }
 }
 
@@ -145,6 +173,7 @@ fn get_key_id(query_props: &Query<&CustomProperties>, door_entity: Entity) -> Re
 fn leve_gate_interaction(
     time: Singleton<SimClock>,
     player: Singleton<Player>,
+    mut events: SingletonMut<PropEvents>,
     query_input_raycast: Query<&InputRaycastController>,
     mut query_door: Query<(&mut LevelGate, &KeyId)>,
 ) {
@@ -182,6 +211,10 @@ fn leve_gate_interaction(
         if door.lower_progress >= LEVEL_GATE_LOWER_MAX {
             door.lower_progress = LEVEL_GATE_LOWER_MAX;
             door.is_lowered = true;
+            events.publish(PropEvent::DoorStateChanged {
+                entity: hit_entity,
+                open: true,
+            });
         }
         door.progress_changed = true;
     } else {
@@ -243,6 +276,37 @@ fn lower_level_gate(
     }
 }
 
+fn restore_level_gate(
+    mut cmd: Commands,
+    mut query_door: Query<(
+        Entity,
+        &RestoreLevelGateTask,
+        &mut LevelGate,
+        &mut Transform3,
+    )>,
+) {
+    for (door_entity, task, door, tf) in query_door.iter_mut() {
+        cmd.entity(door_entity).remove::<RestoreLevelGateTask>();
+
+        door.is_lowered = task.lowered;
+        door.lower_progress = if task.lowered {
+            LEVEL_GATE_LOWER_MAX
+        } else {
+            0.
+        };
+        tf.translation.z = -door.lower_progress;
+
+        cmd.entity(door_entity)
+            .and_set(ChangeCollidersLayerMaskTask {
+                mask: if task.lowered {
+                    CollisionLayerMask::none()
+                } else {
+                    CollisionLayerMask::all()
+                },
+            });
+    }
+}
+
 #[derive(Component, Debug, Clone)]
 struct DoubleDoor {
     leafes: [(Entity, f32); 2],
@@ -1,7 +1,8 @@
 use crate::{
     collision::*,
-    mechanics::{material_swap::*, switch::*},
+    mechanics::{liquid::*, material_swap::*, switch::*},
     player::*,
+    settings::*,
 };
 use atom::prelude::*;
 use candy::{
@@ -18,6 +19,88 @@ use magi::{
 pub const PROP_BARRIER_SWITCH_INDICATOR_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(20, 160, 220);
 pub const LASER_BEAM_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(205, 127, 50);
 
+/// Laser beam color in high-contrast mode
+pub const HIGH_CONTRAST_LASER_BEAM_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(240, 228, 66);
+
+/// Color of all activated beam target indicators in high-contrast mode
+pub const HIGH_CONTRAST_INDICATOR_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(86, 180, 233);
+
+/// Colors used for laser beams and beam target indicators
+pub struct LaserPalette {
+    pub beam: SRgbU8Color,
+
+    /// Overrides the activated emission color of beam target indicators
+    pub indicator: Option<SRgbU8Color>,
+}
+
+impl LaserPalette {
+    pub fn new(high_contrast: bool) -> Self {
+        if high_contrast {
+            Self {
+                beam: HIGH_CONTRAST_LASER_BEAM_COLOR,
+                indicator: Some(HIGH_CONTRAST_INDICATOR_COLOR),
+            }
+        } else {
+            Self {
+                beam: LASER_BEAM_COLOR,
+                indicator: None,
+            }
+        }
+    }
+
+    /// Emission color of an activated indicator
+    pub fn indicator_emission(&self, default: LinearColor) -> LinearColor {
+        self.indicator
+            .map_or(default, |color| color.to_linear() * 5.0)
+    }
+}
+
+/// Palette currently applied to spawned laser pointers and targets
+#[derive(Singleton)]
+struct AppliedLaserPalette {
+    high_contrast: bool,
+}
+
+/// New materials of spawned beams and indicators after the palette changed
+struct LaserPaletteUpdate<E> {
+    beams: Vec<(E, Material)>,
+    indicators: Vec<(E, MaterialSwap)>,
+}
+
+impl AppliedLaserPalette {
+    /// Computes new materials for spawned beams and indicators if the high-contrast setting
+    /// changed since the last update. Beams are given as pairs of beam and beam end and indicators
+    /// with their laser target.
+    fn update<'a, E: Copy>(
+        &mut self,
+        high_contrast: bool,
+        beams: impl IntoIterator<Item = (E, E)>,
+        indicators: impl IntoIterator<Item = (E, &'a LaserPointerTarget)>,
+    ) -> Option<LaserPaletteUpdate<E>> {
+        if self.high_contrast == high_contrast {
+            return None;
+        }
+        self.high_contrast = high_contrast;
+
+        let palette = LaserPalette::new(high_contrast);
+        Some(LaserPaletteUpdate {
+            beams: beams
+                .into_iter()
+                .flat_map(|(beam, beam_end)| {
+                    [
+                        (beam, beam_material(&palette, BEAM_EMISSION)),
+                        (beam_end, beam_material(&palette, BEAM_END_EMISSION)),
+                    ]
+                })
+                .collect(),
+            indicators: indicators
+                .into_iter()
+                .map(|(light, target)| (light, indicator_material_swap(&palette, target)))
+                .collect(),
+        })
+    }
+}
+
 /// Spawns a laser pointer on an entity
 #[derive(Component)]
 pub struct SpawnLaserPointer {
@@ -79,12 +162,18 @@ impl Mocca for LaserPointerMocca {
         deps.depends_on::<CandySceneTreeMocca>();
         deps.depends_on::<CandyTimeMocca>();
         deps.depends_on::<CollidersMocca>();
+        deps.depends_on::<LiquidMocca>();
         deps.depends_on::<MaterialSwapMocca>();
         deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<SettingsMocca>();
         deps.depends_on::<SwitchMocca>();
     }
 
-    fn start(_world: &mut World) -> Self {
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(AppliedLaserPalette {
+            high_contrast: false,
+        });
+        world.set_singleton(LaserTurnInput::default());
         Self
     }
 
@@ -99,6 +188,7 @@ impl Mocca for LaserPointerMocca {
     }
 
     fn step(&mut self, world: &mut World) {
+        world.run(apply_laser_palette);
         world.run(spawn_laser_pointer);
         world.run(spawn_laser_target);
 
@@ -118,14 +208,40 @@ impl Mocca for LaserPointerMocca {
 }
 
 #[derive(Component)]
-struct LaserPointerAzimuth {
+pub struct LaserPointerAzimuth {
     azimuth: SmoothInputF32,
     sensitivity: f32,
 
+    /// If set the beam jumps to the azimuth instead of sweeping
+    snap: bool,
+
     #[cfg(feature = "disco")]
     disco_rng_dir_cooldown: f32,
 }
 
+impl LaserPointerAzimuth {
+    fn new() -> Self {
+        Self {
+            azimuth: SmoothInputF32::default(),
+            sensitivity: 1.,
+            snap: false,
+
+            #[cfg(feature = "disco")]
+            disco_rng_dir_cooldown: 0.,
+        }
+    }
+
+    pub fn azimuth(&self) -> f32 {
+        self.azimuth.value()
+    }
+
+    /// Turns the laser pointer back to its spawn azimuth
+    pub fn reset(&mut self) {
+        self.azimuth = SmoothInputF32::default();
+        self.snap = true;
+    }
+}
+
 #[derive(Component)]
 struct LaserPointer {
     dir: Vec3,
@@ -138,6 +254,20 @@ struct LaserPointer {
     collider_height_over_ground: f32,
 
     beam_end_entity: Entity,
+
+    /// Beam segment after refraction at a liquid surface
+    underwater_beam_entity: Entity,
+    underwater: Option<UnderwaterBeam>,
+    underwater_visible: bool,
+}
+
+/// Beam segment inside a liquid volume in the local frame of the laser pointer
+struct UnderwaterBeam {
+    start: Vec3,
+    dir: Vec3,
+    length: f32,
+    transmittance: f32,
+    tint: SRgbU8Color,
 }
 
 #[derive(Component)]
@@ -145,19 +275,46 @@ struct LaserPointerTarget {
     is_activated: bool,
     target_is_activated: bool,
     light_entity: Entity,
+    activate_emission_color: LinearColor,
+    inactivate_emission_color: LinearColor,
 }
 
-const MAX_BEAM_LEN: f32 = 100.;
-const BEAM_WIDTH: f32 = 0.0167;
+pub(crate) const MAX_BEAM_LEN: f32 = 100.;
+pub(crate) const BEAM_WIDTH: f32 = 0.0167;
 const INTERACTION_MAX_DISTANCE: f32 = 3.0;
 const LASER_TARGET_HEIGHT_REL: f32 = 4.80 / 6.00;
 const LASER_POINTER_EMIT_HEIGHT: f32 = 1.333;
 
+pub(crate) fn beam_material(palette: &LaserPalette, emission: f32) -> Material {
+    Material::Pbr(
+        PbrMaterial::default()
+            .with_base_color(palette.beam)
+            .with_emission(palette.beam.to_linear() * emission),
+    )
+}
+
+fn indicator_material_swap(palette: &LaserPalette, target: &LaserPointerTarget) -> MaterialSwap {
+    MaterialSwap::from_iter([
+        PbrMaterial::diffuse_white()
+            .with_base_color(colors::BLACK)
+            .with_emission(target.inactivate_emission_color),
+        PbrMaterial::diffuse_white()
+            .with_base_color(colors::BLACK)
+            .with_emission(palette.indicator_emission(target.activate_emission_color)),
+    ])
+}
+
+pub(crate) const BEAM_EMISSION: f32 = 15.0;
+const BEAM_END_EMISSION: f32 = 20.0;
+
 fn spawn_laser_pointer(
     mut cmd: Commands,
     asset_resolver: Singleton<SharedAssetResolver>,
+    settings: Singleton<GameSettings>,
     query: Query<(Entity, &SpawnLaserPointer)>,
 ) {
+    let palette = LaserPalette::new(settings.accessibility.high_contrast);
+
     for (entity, spec) in query.iter() {
         let audio_path = asset_resolver
             .resolve("audio/effects/sfx-laser_pointer.wav")
@@ -170,11 +327,7 @@ fn spawn_laser_pointer(
             DynamicTransform,
             Visibility::Visible,
             Cuboid,
-            Material::Pbr(
-                PbrMaterial::default()
-                    .with_base_color(LASER_BEAM_COLOR)
-                    .with_emission(LASER_BEAM_COLOR.to_linear() * 15.0),
-            ),
+            beam_material(&palette, BEAM_EMISSION),
             DisableShadowCasting,
             (ChildOf, entity),
         ));
@@ -186,24 +339,24 @@ fn spawn_laser_pointer(
             DynamicTransform,
             Visibility::Visible,
             Ball,
-            Material::Pbr(
-                PbrMaterial::default()
-                    .with_base_color(LASER_BEAM_COLOR)
-                    .with_emission(LASER_BEAM_COLOR.to_linear() * 20.0),
-            ),
+            beam_material(&palette, BEAM_END_EMISSION),
+            DisableShadowCasting,
+            (ChildOf, entity),
+        ));
+
+        let underwater_beam_entity = cmd.spawn((
+            Transform3::identity().with_scale_xyz(0., BEAM_WIDTH, BEAM_WIDTH),
+            DynamicTransform,
+            Visibility::Hidden,
+            Cuboid,
+            beam_material(&palette, BEAM_EMISSION),
             DisableShadowCasting,
             (ChildOf, entity),
         ));
 
         cmd.entity(entity)
             .and_remove::<SpawnLaserPointer>()
-            .and_set(LaserPointerAzimuth {
-                azimuth: SmoothInputF32::default(),
-                sensitivity: 1.,
-
-                #[cfg(feature = "disco")]
-                disco_rng_dir_cooldown: 0.,
-            })
+            .and_set(LaserPointerAzimuth::new())
             .and_set(DynamicTransform)
             .and_set(LaserPointer {
                 dir: Vec3::Z,
@@ -213,6 +366,9 @@ fn spawn_laser_pointer(
                 beam_length: MAX_BEAM_LEN,
                 collider_height_over_ground: 6.0,
                 beam_end_entity,
+                underwater_beam_entity,
+                underwater: None,
+                underwater_visible: false,
             });
 
         cmd.entity(spec.audio_entity).and_set(AudioSource {
@@ -229,37 +385,72 @@ fn spawn_laser_pointer(
     }
 }
 
-fn spawn_laser_target(mut cmd: Commands, query: Query<(Entity, &SpawnLaserTarget)>) {
+fn spawn_laser_target(
+    mut cmd: Commands,
+    settings: Singleton<GameSettings>,
+    query: Query<(Entity, &SpawnLaserTarget)>,
+) {
+    let palette = LaserPalette::new(settings.accessibility.high_contrast);
+
     for (entity, spec) in query.iter() {
+        let target = LaserPointerTarget {
+            is_activated: false,
+            target_is_activated: false,
+            light_entity: spec.indicator_entity,
+            activate_emission_color: spec.activate_emission_color,
+            inactivate_emission_color: spec.inactivate_emission_color,
+        };
+
+        cmd.entity(spec.indicator_entity)
+            .and_set(indicator_material_swap(&palette, &target))
+            .and_set(MaterialSwapTransition::ZERO);
+
         cmd.entity(entity)
             .and_remove::<SpawnLaserTarget>()
             .and_set(BeamDetector { latch: false })
             .and_set(BeamHit::Off)
-            .and_set(LaserPointerTarget {
-                is_activated: false,
-                target_is_activated: false,
-                light_entity: spec.indicator_entity,
-            })
+            .and_set(target)
             .and_set(Switch {
                 name: spec.switch_id.clone(),
             })
             .and_set(SwitchState::Off);
 
-        cmd.entity(spec.indicator_entity)
-            .and_set(MaterialSwap::from_iter([
-                PbrMaterial::diffuse_white()
-                    .with_base_color(colors::BLACK)
-                    .with_emission(spec.inactivate_emission_color),
-                PbrMaterial::diffuse_white()
-                    .with_base_color(colors::BLACK)
-                    .with_emission(spec.activate_emission_color),
-            ]))
-            .and_set(MaterialSwapTransition::ZERO);
-
         log::debug!("spawned laser_target: {entity}");
     }
 }
 
+/// Applies the laser palette to already spawned beams and indicators when the high-contrast
+/// setting changes. Laser turrets update the material of their beams themselves as it also
+/// depends on their warning state.
+fn apply_laser_palette(
+    mut cmd: Commands,
+    settings: Singleton<GameSettings>,
+    mut applied: SingletonMut<AppliedLaserPalette>,
+    query_lp: Query<&LaserPointer>,
+    query_target: Query<&LaserPointerTarget>,
+) {
+    let Some(update) = applied.update(
+        settings.accessibility.high_contrast,
+        query_lp
+            .iter()
+            .map(|lp| (lp.beam_entity, lp.beam_end_entity)),
+        query_target
+            .iter()
+            .map(|target| (target.light_entity, target)),
+    ) else {
+        return;
+    };
+
+    for (entity, material) in update.beams {
+        cmd.entity(entity).and_set(material).and_set(MaterialDirty);
+    }
+
+    // Material swap applies the new materials on its next update
+    for (entity, swap) in update.indicators {
+        cmd.entity(entity).set(swap);
+    }
+}
+
 #[cfg(feature = "disco")]
 fn disco_laser_pointer_azimuth(
     time: Singleton<SimClock>,
@@ -284,34 +475,97 @@ const LASER_POINTER_INPUT_SETTINGS: SmoothInputF32Settings = SmoothInputF32Setti
     max_deaccel: 50.,
 };
 
+/// Direction in which the player turns a laser pointer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LaserTurn {
+    #[default]
+    None,
+    Increase,
+    Decrease,
+}
+
+impl LaserTurn {
+    fn control(self) -> SmoothInputControl {
+        match self {
+            LaserTurn::None => SmoothInputControl::Decay,
+            LaserTurn::Increase => SmoothInputControl::Increase,
+            LaserTurn::Decrease => SmoothInputControl::Decrease,
+        }
+    }
+}
+
+/// Converts mouse buttons into laser turn commands. In hold-to-toggle mode a click starts turning
+/// and a second click on the same button stops it.
+#[derive(Singleton, Default, Debug)]
+pub struct LaserTurnInput {
+    previous: LaserTurn,
+    latched: LaserTurn,
+}
+
+impl LaserTurnInput {
+    pub fn update(&mut self, held: LaserTurn, hold_to_toggle: bool) -> LaserTurn {
+        let pressed = held != LaserTurn::None && held != self.previous;
+        self.previous = held;
+
+        if !hold_to_toggle {
+            self.latched = LaserTurn::None;
+            return held;
+        }
+
+        if pressed {
+            self.latched = if self.latched == held {
+                LaserTurn::None
+            } else {
+                held
+            };
+        }
+        self.latched
+    }
+
+    /// Stops turning, e.g. when the player no longer looks at a laser pointer
+    pub fn release(&mut self, held: LaserTurn) {
+        self.previous = held;
+        self.latched = LaserTurn::None;
+    }
+}
+
 fn turn_laser_pointers(
     time: Singleton<SimClock>,
+    settings: Singleton<GameSettings>,
+    mut turn_input: SingletonMut<LaserTurnInput>,
     query_input_raycast: Query<&InputRaycastController>,
     mut query_lpa: Query<&mut LaserPointerAzimuth>,
 ) {
     let dt = time.sim_dt_f32();
     let input_raycast = &query_input_raycast.single().unwrap();
 
+    let held = if input_raycast.state().is_left_mouse_pressed {
+        LaserTurn::Increase
+    } else if input_raycast.state().is_right_mouse_pressed {
+        LaserTurn::Decrease
+    } else {
+        LaserTurn::None
+    };
+
     // Get hit entity
     let Some((hit_entity, distance)) = input_raycast.raycast_entity_and_distance() else {
+        turn_input.release(held);
         return;
     };
 
     // Get azimuth contoller
     let Some(lpa) = query_lpa.get_mut(hit_entity) else {
+        turn_input.release(held);
         return;
     };
 
     // Check for turn event
     let turn_control = if distance <= INTERACTION_MAX_DISTANCE {
-        if input_raycast.state().is_left_mouse_pressed {
-            SmoothInputControl::Increase
-        } else if input_raycast.state().is_right_mouse_pressed {
-            SmoothInputControl::Decrease
-        } else {
-            SmoothInputControl::Decay
-        }
+        turn_input
+            .update(held, settings.accessibility.hold_to_toggle)
+            .control()
     } else {
+        turn_input.release(held);
         SmoothInputControl::Decay
     };
 
@@ -343,7 +597,11 @@ fn point_laser_pointers(
         )
         .normalize();
 
-        lp.dir = lp.dir.lerp(target_dir, point_speed * dt).normalize();
+        lp.dir = if std::mem::take(&mut lpa.snap) {
+            target_dir
+        } else {
+            lp.dir.lerp(target_dir, point_speed * dt).normalize()
+        };
 
         tf.rotation = rotation_from_dir(lp.dir);
 
@@ -370,13 +628,16 @@ fn raycast_laser_beams(
     mut cmd: Commands,
     colliders: Singleton<ColliderWorld>,
     mut query_laser_pointer: Query<(&GlobalTransform3, &mut LaserPointer)>,
+    query_liquid: Query<(&GlobalTransform3, &LiquidVolume)>,
     query_collision_routing: Query<&CollisionRouting>,
     query_beam_detector: Query<&BeamDetector>,
 ) {
     for (tf, lp) in query_laser_pointer.iter_mut() {
-        let ray = Ray3::from_origin_direction(tf.translation(), tf.x_axis.into()).unwrap();
+        let origin = tf.translation();
+        let dir: Vec3 = tf.x_axis.into();
+        let ray = Ray3::from_origin_direction(origin, dir).unwrap();
 
-        let maybe_hit =
+        let mut maybe_hit =
             colliders.raycast(&ray, 0.01, Some(lp.exclude_collider), CollisionLayer::Laser);
 
         match maybe_hit {
@@ -393,6 +654,49 @@ fn raycast_laser_beams(
             }
         }
 
+        // Check if the beam enters a liquid before hitting an object
+        let liquid_entry = query_liquid
+            .iter()
+            .filter_map(|(liquid_tf, liquid)| {
+                let aabb = liquid_aabb(liquid_tf.affine())?;
+                let t = liquid_surface_entry(origin, dir, &aabb)?;
+                (t < lp.beam_length).then_some((t, liquid))
+            })
+            .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        lp.underwater = None;
+        if let Some((t, liquid)) = liquid_entry {
+            lp.beam_length = t;
+
+            // The beam is refracted at the surface and ends when it is too weak. At grazing
+            // angles with a ratio above 1 the beam is reflected away and also ends.
+            maybe_hit = None;
+            if let Some(underwater_dir) = refract(dir, Vec3::Z, liquid.refraction_ratio) {
+                let start = ray.point(t);
+                let max_length = liquid.cutoff_distance().min(MAX_BEAM_LEN);
+                let underwater_ray = Ray3::from_origin_direction(start, underwater_dir).unwrap();
+
+                maybe_hit = colliders
+                    .raycast(
+                        &underwater_ray,
+                        0.01,
+                        Some(lp.exclude_collider),
+                        CollisionLayer::Laser,
+                    )
+                    .filter(|hit| hit.distance <= max_length);
+                let length = maybe_hit.map_or(max_length, |hit| hit.distance);
+
+                let local_t_world = tf.affine().inverse();
+                lp.underwater = Some(UnderwaterBeam {
+                    start: local_t_world.transform_point3(start),
+                    dir: local_t_world.transform_vector3(underwater_dir).normalize(),
+                    length,
+                    transmittance: liquid.transmittance(0.5 * length),
+                    tint: liquid.tint,
+                });
+            }
+        }
+
         lp.collision_point = tf
             .affine()
             .inverse()
@@ -409,15 +713,53 @@ fn raycast_laser_beams(
     }
 }
 
-fn update_laser_beam_length(query_lp: Query<&LaserPointer>, mut query_tf: Query<&mut Transform3>) {
-    for lp in query_lp.iter() {
+fn update_laser_beam_length(
+    mut cmd: Commands,
+    mut query_lp: Query<&mut LaserPointer>,
+    mut query_tf: Query<&mut Transform3>,
+) {
+    for lp in query_lp.iter_mut() {
         if let Some(tf) = query_tf.get_mut(lp.beam_entity) {
             tf.scale.x = lp.beam_length;
             tf.translation.x = 0.5 * lp.beam_length;
         }
 
+        let beam_end = match &lp.underwater {
+            Some(segment) => segment.start + segment.length * segment.dir,
+            None => Vec3::new(lp.beam_length, 0., 0.),
+        };
         if let Some(tf) = query_tf.get_mut(lp.beam_end_entity) {
-            tf.translation.x = lp.beam_length;
+            tf.translation = beam_end;
+        }
+
+        if let Some(segment) = &lp.underwater {
+            if let Some(tf) = query_tf.get_mut(lp.underwater_beam_entity) {
+                tf.translation = segment.start + 0.5 * segment.length * segment.dir;
+                tf.rotation = rotation_from_dir(segment.dir);
+                tf.scale.x = segment.length;
+            }
+
+            // Beam fades with the distance traveled in the liquid
+            cmd.entity(lp.underwater_beam_entity)
+                .and_set(Material::Pbr(
+                    PbrMaterial::default()
+                        .with_base_color(segment.tint)
+                        .with_emission(
+                            segment.tint.to_linear() * (BEAM_EMISSION * segment.transmittance),
+                        ),
+                ))
+                .and_set(MaterialDirty);
+        }
+
+        let underwater_visible = lp.underwater.is_some();
+        if underwater_visible != lp.underwater_visible {
+            lp.underwater_visible = underwater_visible;
+            cmd.entity(lp.underwater_beam_entity)
+                .set(if underwater_visible {
+                    Visibility::Visible
+                } else {
+                    Visibility::Hidden
+                });
         }
     }
 }
@@ -446,3 +788,100 @@ fn set_laser_target_material(mut cmd: Commands, mut query: Query<&mut LaserPoint
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_laser_palette_reaches_spawned_beams() {
+        let mut applied = AppliedLaserPalette {
+            high_contrast: false,
+        };
+        let beams = [(1, 2), (3, 4)];
+        let no_indicators = || std::iter::empty::<(i32, &LaserPointerTarget)>();
+
+        assert!(applied.update(false, beams, no_indicators()).is_none());
+
+        let update = applied.update(true, beams, no_indicators()).unwrap();
+        let entities: Vec<_> = update.beams.iter().map(|(entity, _)| *entity).collect();
+        assert_eq!(entities, vec![1, 2, 3, 4]);
+        for (entity, material) in &update.beams {
+            let Material::Pbr(pbr) = material else {
+                panic!("beam {entity} does not have a PBR material");
+            };
+            let emission = if entity % 2 == 1 {
+                BEAM_EMISSION
+            } else {
+                BEAM_END_EMISSION
+            };
+            assert!(pbr.emission == HIGH_CONTRAST_LASER_BEAM_COLOR.to_linear() * emission);
+        }
+
+        // the palette is only applied once per change
+        assert!(applied.update(true, beams, no_indicators()).is_none());
+        assert!(applied.update(false, beams, no_indicators()).is_some());
+    }
+
+    #[test]
+    fn test_laser_turn_hold() {
+        let mut input = LaserTurnInput::default();
+        assert_eq!(
+            input.update(LaserTurn::Increase, false),
+            LaserTurn::Increase
+        );
+        assert_eq!(
+            input.update(LaserTurn::Increase, false),
+            LaserTurn::Increase
+        );
+        assert_eq!(input.update(LaserTurn::None, false), LaserTurn::None);
+        assert_eq!(
+            input.update(LaserTurn::Decrease, false),
+            LaserTurn::Decrease
+        );
+    }
+
+    #[test]
+    fn test_laser_turn_toggle() {
+        let mut input = LaserTurnInput::default();
+
+        // click starts turning which continues after release
+        assert_eq!(input.update(LaserTurn::Increase, true), LaserTurn::Increase);
+        assert_eq!(input.update(LaserTurn::None, true), LaserTurn::Increase);
+        assert_eq!(input.update(LaserTurn::None, true), LaserTurn::Increase);
+
+        // click on the other button changes direction
+        assert_eq!(input.update(LaserTurn::Decrease, true), LaserTurn::Decrease);
+        assert_eq!(input.update(LaserTurn::Decrease, true), LaserTurn::Decrease);
+        assert_eq!(input.update(LaserTurn::None, true), LaserTurn::Decrease);
+
+        // second click stops turning
+        assert_eq!(input.update(LaserTurn::Decrease, true), LaserTurn::None);
+        assert_eq!(input.update(LaserTurn::None, true), LaserTurn::None);
+
+        // looking away stops turning
+        assert_eq!(input.update(LaserTurn::Increase, true), LaserTurn::Increase);
+        input.release(LaserTurn::None);
+        assert_eq!(input.update(LaserTurn::None, true), LaserTurn::None);
+    }
+
+    #[test]
+    fn test_laser_azimuth_reset() {
+        let mut lpa = LaserPointerAzimuth::new();
+        let initial = lpa.azimuth();
+
+        for _ in 0..20 {
+            lpa.azimuth.update(
+                0.1,
+                &LASER_POINTER_INPUT_SETTINGS,
+                SmoothInputControl::Increase,
+                lpa.sensitivity,
+            );
+        }
+        assert!(lpa.azimuth() > initial + 1.0);
+
+        lpa.reset();
+        assert_eq!(lpa.azimuth(), initial);
+        assert!(lpa.snap);
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{
+    collision::*, custom_properties::*, level::*, mechanics::switch::*, player::*,
+    props::laser_pointer::*, settings::*,
+};
+use atom::prelude::*;
+use candy::{
+    audio::*, can::*, material::*, prelude::DisableShadowCasting, prims::*, scene_tree::*, time::*,
+};
+use glam::{Quat, Vec2, Vec3, Vec3Swizzles};
+use magi::color::SRgbU8Color;
+use std::f32::consts::TAU;
+
+/// Creates a turret which sweeps a laser beam and sends the player back to a respawn point when
+/// the beam hits them. The respawn point is the node named by the `turret_respawn` custom property.
+#[derive(Component)]
+pub struct SpawnLaserTurretTask {
+    /// This entity is rotated by the sweep. The beam is emitted along its X axis.
+    pub head_entity: Entity,
+
+    /// Collider of the turret which is ignored by the beam
+    pub collider_entity: Option<Entity>,
+}
+
+/// Beam color while the player is in the beam
+pub const LASER_TURRET_WARNING_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(230, 30, 30);
+
+/// Default sweep arc in degrees
+const LASER_TURRET_ARC_DEG: f32 = 90.0;
+
+/// Default duration of a full sweep back and forth
+const LASER_TURRET_PERIOD: f32 = 6.0;
+
+/// Default time the player can stay in the beam before being sent back
+const LASER_TURRET_GRACE: f32 = 0.4;
+
+/// Parameters of a laser turret
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LaserTurretSettings {
+    /// Total angle covered by the sweep in radians. The sweep is centered on the rest pose.
+    pub arc: f32,
+
+    /// Duration of a full sweep back and forth in seconds
+    pub period: f32,
+
+    /// Time the player can stay in the beam before being sent back
+    pub grace: f32,
+}
+
+impl Default for LaserTurretSettings {
+    fn default() -> Self {
+        Self {
+            arc: LASER_TURRET_ARC_DEG.to_radians(),
+            period: LASER_TURRET_PERIOD,
+            grace: LASER_TURRET_GRACE,
+        }
+    }
+}
+
+impl LaserTurretSettings {
+    /// Reads the `turret_arc` (in degrees), `turret_period` and `turret_grace` custom properties
+    pub fn from_properties(props: Option<&CustomProperties>) -> Self {
+        let mut settings = Self::default();
+        let Some(props) = props else {
+            return settings;
+        };
+        let get = |id: &str| props.get_float(id).map(|v| v as f32);
+
+        if let Some(deg) = get("turret_arc") {
+            settings.arc = deg.to_radians();
+        }
+        if let Some(period) = get("turret_period") {
+            settings.period = period;
+        }
+        if let Some(grace) = get("turret_grace") {
+            settings.grace = grace.max(0.);
+        }
+        settings
+    }
+}
+
+/// What the turret does to the player in the current frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TurretAlert {
+    /// The player is not in the beam
+    Clear,
+
+    /// The player is in the beam but the grace period is not over
+    Warning,
+
+    /// The player stayed in the beam too long
+    Fire,
+}
+
+/// Sweep and grace period state of a laser turret
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct LaserTurretState {
+    /// Position in the sweep cycle in [0, 1)
+    pub phase: f32,
+
+    /// Time the player has been in the beam without interruption
+    pub exposure: f32,
+}
+
+impl LaserTurretState {
+    /// Angle of the beam relative to the rest pose
+    pub fn sweep_angle(&self, settings: &LaserTurretSettings) -> f32 {
+        0.5 * settings.arc * (TAU * self.phase).sin()
+    }
+
+    /// Advances the sweep unless it is paused
+    pub fn advance_sweep(&mut self, dt: f32, paused: bool, settings: &LaserTurretSettings) {
+        if !paused && settings.period > 0. {
+            self.phase = (self.phase + dt / settings.period).rem_euclid(1.);
+        }
+    }
+
+    /// Tracks how long the player is in the beam
+    pub fn update_exposure(
+        &mut self,
+        dt: f32,
+        in_beam: bool,
+        settings: &LaserTurretSettings,
+    ) -> TurretAlert {
+        if !in_beam {
+            self.exposure = 0.;
+            return TurretAlert::Clear;
+        }
+
+        self.exposure += dt;
+        if self.exposure >= settings.grace {
+            self.exposure = 0.;
+            TurretAlert::Fire
+        } else {
+            TurretAlert::Warning
+        }
+    }
+}
+
+/// Look of a turret beam
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TurretBeamStyle {
+    /// The player is in the beam
+    pub is_warning: bool,
+
+    /// The high-contrast setting the beam material was created for
+    pub high_contrast: bool,
+}
+
+impl TurretBeamStyle {
+    pub fn palette(&self) -> LaserPalette {
+        if self.is_warning {
+            LaserPalette {
+                beam: LASER_TURRET_WARNING_COLOR,
+                indicator: None,
+            }
+        } else {
+            LaserPalette::new(self.high_contrast)
+        }
+    }
+}
+
+/// A turret sweeping a laser beam. The sweep pauses while the switches of the turret are on.
+#[derive(Component, Debug)]
+pub struct LaserTurret {
+    pub settings: LaserTurretSettings,
+    pub state: LaserTurretState,
+
+    /// Head and its rotation at rest
+    head: (Entity, Quat),
+
+    beam_entity: Entity,
+    exclude_collider: Option<Entity>,
+    beam_length: f32,
+
+    /// Where the player is sent when hit
+    respawn_position: Vec2,
+
+    beam_style: TurretBeamStyle,
+}
+
+/// Laser turrets which sweep a beam through a level
+pub struct LaserTurretMocca;
+
+impl Mocca for LaserTurretMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyAudioMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandyMaterialMocca>();
+        deps.depends_on::<CandyPrimsMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<CollidersMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<LaserPointerMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<SettingsMocca>();
+        deps.depends_on::<SwitchMocca>();
+    }
+
+    fn start(_world: &mut World) -> Self {
+        Self
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<LaserTurret>();
+        world.register_component::<SpawnLaserTurretTask>();
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(spawn_laser_turret);
+        world.run(sweep_laser_turret);
+        world.run(raycast_laser_turret);
+    }
+}
+
+fn spawn_laser_turret(
+    mut cmd: Commands,
+    asset_resolver: Singleton<SharedAssetResolver>,
+    settings: Singleton<GameSettings>,
+    query: Query<(Entity, &SpawnLaserTurretTask, Option<&CustomProperties>)>,
+    query_tf: Query<&Transform3>,
+    query_name: Query<(Entity, &Name)>,
+    query_global_tf: Query<&GlobalTransform3>,
+) {
+    let beam_style = TurretBeamStyle {
+        is_warning: false,
+        high_contrast: settings.accessibility.high_contrast,
+    };
+
+    for (entity, task, props) in query.iter() {
+        cmd.entity(entity).remove::<SpawnLaserTurretTask>();
+
+        let Some(head_tf) = query_tf.get(task.head_entity) else {
+            log::error!("laser turret {entity} is missing its head");
+            continue;
+        };
+
+        let Some(respawn_name) = props.and_then(|p| p.get_string("turret_respawn")) else {
+            log::error!("laser turret {entity} without 'turret_respawn' property");
+            continue;
+        };
+        let Some(respawn_position) = query_name
+            .iter()
+            .find_map(|(e, name)| (name.as_str() == respawn_name).then_some(e))
+            .and_then(|e| query_global_tf.get(e))
+            .map(|respawn_tf| respawn_tf.translation().xy())
+        else {
+            log::error!("laser turret {entity}: respawn point '{respawn_name}' not found");
+            continue;
+        };
+
+        let beam_entity = cmd.spawn((
+            Transform3::identity()
+                .with_scale_xyz(MAX_BEAM_LEN, BEAM_WIDTH, BEAM_WIDTH)
+                .with_translation_xyz(MAX_BEAM_LEN * 0.5, 0., 0.),
+            DynamicTransform,
+            Visibility::Visible,
+            Cuboid,
+            beam_material(&beam_style.palette(), BEAM_EMISSION),
+            DisableShadowCasting,
+            (ChildOf, task.head_entity),
+        ));
+
+        cmd.entity(entity).and_set(LaserTurret {
+            settings: LaserTurretSettings::from_properties(props),
+            state: LaserTurretState::default(),
+            head: (task.head_entity, head_tf.rotation),
+            beam_entity,
+            exclude_collider: task.collider_entity,
+            beam_length: MAX_BEAM_LEN,
+            respawn_position,
+            beam_style,
+        });
+        cmd.entity(task.head_entity).set(DynamicTransform);
+
+        match asset_resolver.resolve("audio/effects/sfx-turret_warning.wav") {
+            Ok(path) => {
+                cmd.entity(entity).set(AudioSource {
+                    path,
+                    volume: 1.0,
+                    state: AudioPlaybackState::Stop,
+                    repeat: AudioRepeatKind::OneShot,
+                    volume_auto_play: false,
+                });
+            }
+            Err(err) => log::warn!("laser turret {entity} has no warning sound: {err}"),
+        }
+
+        log::debug!("spawned laser turret: {entity}");
+    }
+}
+
+fn sweep_laser_turret(
+    time: Singleton<SimClock>,
+    mut query: Query<(&mut LaserTurret, Option<&SwitchObserverState>)>,
+    mut query_tf: Query<&mut Transform3>,
+) {
+    let dt = time.sim_dt_f32();
+
+    for (turret, observer) in query.iter_mut() {
+        let paused = observer.is_some_and(|state| state.as_bool());
+        let settings = turret.settings;
+        turret.state.advance_sweep(dt, paused, &settings);
+
+        let (head_entity, rest_rotation) = turret.head;
+        if let Some(tf) = query_tf.get_mut(head_entity) {
+            tf.rotation =
+                Quat::from_rotation_z(turret.state.sweep_angle(&settings)) * rest_rotation;
+        }
+    }
+}
+
+fn raycast_laser_turret(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    settings: Singleton<GameSettings>,
+    colliders: Singleton<ColliderWorld>,
+    mut player: SingletonMut<Player>,
+    mut query_turret: Query<(Entity, &mut LaserTurret)>,
+    mut query_audio: Query<&mut AudioSource>,
+    query_global_tf: Query<&GlobalTransform3>,
+    mut query_tf: Query<&mut Transform3>,
+) {
+    let dt = time.sim_dt_f32();
+    let capsule = player_capsule(player.previous_position);
+    let (first, last) = (&capsule[0], &capsule[PLAYER_SPHERE_COUNT - 1]);
+
+    for (entity, turret) in query_turret.iter_mut() {
+        let Some(head_tf) = query_global_tf.get(turret.head.0) else {
+            continue;
+        };
+        let dir: Vec3 = head_tf.x_axis.into();
+        let Some(ray) = Ray3::from_origin_direction(head_tf.translation(), dir) else {
+            continue;
+        };
+
+        turret.beam_length = colliders
+            .raycast(&ray, 0.01, turret.exclude_collider, CollisionLayer::Laser)
+            .map_or(MAX_BEAM_LEN, |hit| hit.distance);
+        if let Some(tf) = query_tf.get_mut(turret.beam_entity) {
+            tf.scale.x = turret.beam_length;
+            tf.translation.x = 0.5 * turret.beam_length;
+        }
+
+        // Player collision is disabled in ghost mode
+        let in_beam = !player.cheat_ghost_mode
+            && capsule_raycast(first.position, last.position, first.radius, &ray)
+                .is_some_and(|t| t < turret.beam_length);
+
+        let turret_settings = turret.settings;
+        let alert = turret.state.update_exposure(dt, in_beam, &turret_settings);
+
+        if alert == TurretAlert::Fire {
+            log::info!("player was hit by laser turret {entity}");
+            player.respawn_position = Some(turret.respawn_position);
+        }
+
+        // The beam material follows the warning state and the laser palette
+        let beam_style = TurretBeamStyle {
+            is_warning: alert == TurretAlert::Warning,
+            high_contrast: settings.accessibility.high_contrast,
+        };
+        if beam_style != turret.beam_style {
+            if beam_style.is_warning && !turret.beam_style.is_warning {
+                if let Some(audio) = query_audio.get_mut(entity) {
+                    audio.state = AudioPlaybackState::Play;
+                }
+            }
+
+            turret.beam_style = beam_style;
+            cmd.entity(turret.beam_entity)
+                .and_set(beam_material(&beam_style.palette(), BEAM_EMISSION))
+                .and_set(MaterialDirty);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    const DT: f32 = 1. / 60.;
+
+    #[test]
+    fn test_laser_turret_grace_period() {
+        let settings = LaserTurretSettings {
+            grace: 0.5,
+            ..Default::default()
+        };
+        let dt = 0.125;
+        let mut state = LaserTurretState::default();
+
+        // the player steps into the beam and out again before the grace period is over
+        for _ in 0..3 {
+            assert_eq!(
+                state.update_exposure(dt, true, &settings),
+                TurretAlert::Warning
+            );
+        }
+        assert_eq!(
+            state.update_exposure(dt, false, &settings),
+            TurretAlert::Clear
+        );
+        assert_eq!(state.exposure, 0.);
+
+        // staying in the beam fires once the grace period is over
+        let alerts: Vec<_> = (0..6)
+            .map(|_| state.update_exposure(dt, true, &settings))
+            .collect();
+        assert_eq!(
+            alerts,
+            [
+                TurretAlert::Warning,
+                TurretAlert::Warning,
+                TurretAlert::Warning,
+                TurretAlert::Fire,
+                TurretAlert::Warning,
+                TurretAlert::Warning,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_turret_beam_style_palette() {
+        let beam = |is_warning, high_contrast| {
+            TurretBeamStyle {
+                is_warning,
+                high_contrast,
+            }
+            .palette()
+            .beam
+        };
+        assert!(beam(false, false) == LASER_BEAM_COLOR);
+        assert!(beam(false, true) == HIGH_CONTRAST_LASER_BEAM_COLOR);
+        assert!(beam(true, false) == LASER_TURRET_WARNING_COLOR);
+        assert!(beam(true, true) == LASER_TURRET_WARNING_COLOR);
+    }
+
+    #[test]
+    fn test_laser_turret_sweep_pauses() {
+        let settings = LaserTurretSettings {
+            arc: 90f32.to_radians(),
+            period: 4.0,
+            ..Default::default()
+        };
+        let mut state = LaserTurretState::default();
+
+        // a quarter period reaches the end of the arc
+        for _ in 0..60 {
+            state.advance_sweep(DT, false, &settings);
+        }
+        assert_abs_diff_eq!(
+            state.sweep_angle(&settings),
+            45f32.to_radians(),
+            epsilon = 1e-4
+        );
+
+        // the sweep holds while paused
+        let paused = state;
+        for _ in 0..60 {
+            state.advance_sweep(DT, true, &settings);
+        }
+        assert_eq!(state, paused);
+
+        // and continues afterwards, wrapping around the cycle
+        for _ in 0..(4 * 60) {
+            state.advance_sweep(DT, false, &settings);
+        }
+        assert!((0. ..1.).contains(&state.phase));
+        assert_abs_diff_eq!(
+            state.sweep_angle(&settings),
+            45f32.to_radians(),
+            epsilon = 1e-3
+        );
+    }
+}
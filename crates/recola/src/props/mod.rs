@@ -1,5 +1,9 @@
 pub mod barrier;
 pub mod door;
 pub mod laser_pointer;
+pub mod laser_turret;
 pub mod overgrowth;
+pub mod reset_lever;
 pub mod rift;
+pub mod rope;
+pub mod swing_door;
@@ -1,4 +1,4 @@
-use crate::props::laser_pointer::*;
+use crate::{mechanics::prop_events::*, props::laser_pointer::*, world_state::*};
 use atom::prelude::*;
 use candy::{audio::*, can::*, material::*, prims::*, rng::*, scene_tree::*, time::*};
 use glam::Vec3;
@@ -22,6 +22,23 @@ pub struct Overgrowth {
 
 const OVERGROWTH_BURN_DURATION: f32 = 3.33;
 
+/// Time for partially burned overgrowth to grow back completely at night
+const OVERGROWTH_REGROW_DURATION: f32 = 20.0;
+
+/// Regrowth takes this many times longer in full daylight
+const OVERGROWTH_DAYLIGHT_REGROW_SLOWDOWN: f32 = 3.0;
+
+/// Time for partially burned overgrowth to grow back completely
+pub fn overgrowth_regrow_duration(signals: &WorldSignals) -> f32 {
+    OVERGROWTH_REGROW_DURATION
+        * (1. + (OVERGROWTH_DAYLIGHT_REGROW_SLOWDOWN - 1.) * signals.daylight)
+}
+
+/// Burn progress after regrowing for a time step
+pub fn regrow_burn_progress(burn_progress: f32, dt: f32, regrow_duration: f32) -> f32 {
+    (burn_progress - dt * OVERGROWTH_BURN_DURATION / regrow_duration).max(0.)
+}
+
 /// Owergrowth which can be burned away
 pub struct OvergrowthMocca;
 
@@ -37,6 +54,8 @@ impl Mocca for OvergrowthMocca {
         deps.depends_on::<CandyTimeMocca>();
         deps.depends_on::<CandyTimeMocca>();
         deps.depends_on::<LaserPointerMocca>();
+        deps.depends_on::<PropEventsMocca>();
+        deps.depends_on::<WorldStateMocca>();
     }
 
     fn start(_world: &mut World) -> Self {
@@ -95,34 +114,47 @@ fn init_overgrowth(
 fn burn_overgrowth(
     mut cmd: Commands,
     time: Singleton<SimClock>,
-    mut query: Query<(Entity, &mut Overgrowth, &BeamHit)>,
+    signals: Singleton<WorldSignals>,
+    mut events: SingletonMut<PropEvents>,
+    mut query: Query<(Entity, &mut Overgrowth, &BeamHit, &AssetInstance)>,
 ) {
     let color_fresh: LinearColor = SRgbU8Color::from_rgb(64, 87, 22).to_linear();
     let color_burnt: LinearColor = SRgbU8Color::from_rgb(219, 153, 53).to_linear();
 
     let dt = time.sim_dt_f32();
-    for (entity, overgrowth, hit) in query.iter_mut() {
+    let regrow_duration = overgrowth_regrow_duration(&signals);
+    for (entity, overgrowth, hit, ainst) in query.iter_mut() {
         overgrowth.is_burning = hit.as_bool();
 
         if overgrowth.is_burning {
             overgrowth.burn_particle_gen += dt;
-
             overgrowth.burn_progress += dt;
-            let q = overgrowth.burn_progress / OVERGROWTH_BURN_DURATION;
-
-            if q >= 1. {
-                cmd.despawn_recursive(entity);
-            }
+        } else if overgrowth.burn_progress > 0. {
+            // Partially burned overgrowth grows back
+            overgrowth.burn_progress =
+                regrow_burn_progress(overgrowth.burn_progress, dt, regrow_duration);
+        } else {
+            continue;
+        }
 
-            let color = color_fresh.mix(q, color_burnt);
-            let mat = PbrMaterial::diffuse_white()
-                .with_base_color(color)
-                .with_emission(color_burnt * q);
+        let q = overgrowth.burn_progress / OVERGROWTH_BURN_DURATION;
 
-            cmd.entity(overgrowth.change_mat_entity)
-                .and_set(Material::Pbr(mat))
-                .and_set(MaterialDirty);
+        if q >= 1. {
+            cmd.despawn_recursive(entity);
+            events.publish(PropEvent::PropDespawned {
+                kind: ainst.as_str().to_owned(),
+                entity,
+            });
         }
+
+        let color = color_fresh.mix(q, color_burnt);
+        let mat = PbrMaterial::diffuse_white()
+            .with_base_color(color)
+            .with_emission(color_burnt * q);
+
+        cmd.entity(overgrowth.change_mat_entity)
+            .and_set(Material::Pbr(mat))
+            .and_set(MaterialDirty);
     }
 }
 
@@ -251,3 +283,38 @@ fn animate_overgrowth_burn_particles(
         tf.translation.z += step;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn time_to_regrow(signals: &WorldSignals) -> f32 {
+        let duration = overgrowth_regrow_duration(signals);
+        let mut progress = 0.5 * OVERGROWTH_BURN_DURATION;
+        let mut time = 0.;
+        while progress > 0. {
+            progress = regrow_burn_progress(progress, 0.125, duration);
+            time += 0.125;
+        }
+        time
+    }
+
+    #[test]
+    fn test_overgrowth_regrows_slower_in_daylight() {
+        let night = WorldSignals::from_hour(22.);
+        let noon = WorldSignals::from_hour(12.);
+        assert_abs_diff_eq!(
+            overgrowth_regrow_duration(&night),
+            OVERGROWTH_REGROW_DURATION
+        );
+        assert_abs_diff_eq!(
+            overgrowth_regrow_duration(&noon),
+            OVERGROWTH_REGROW_DURATION * OVERGROWTH_DAYLIGHT_REGROW_SLOWDOWN
+        );
+
+        // half burned overgrowth needs half the regrow duration
+        assert_abs_diff_eq!(time_to_regrow(&night), 0.5 * OVERGROWTH_REGROW_DURATION);
+        assert!(time_to_regrow(&noon) > time_to_regrow(&WorldSignals::from_hour(18.)));
+    }
+}
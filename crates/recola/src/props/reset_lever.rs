@@ -0,0 +1,285 @@
+use crate::{
+    level::*,
+    mechanics::switch::*,
+    player::*,
+    props::{laser_pointer::*, rope::*},
+};
+use atom::prelude::*;
+use candy::{audio::*, can::*, scene_tree::*, time::*};
+use glam::Quat;
+use std::f32::consts::{FRAC_PI_3, PI};
+
+/// Creates a lever which resets the puzzle of the level it is placed in
+#[derive(Component)]
+pub struct SpawnPuzzleResetTask {
+    /// This entity is rotated while the lever is pulled
+    pub handle_entity: Option<Entity>,
+}
+
+/// A lever which restores the initial state of the puzzle in a level. Latched progress like rift
+/// charges, keys and burned overgrowth is kept.
+#[derive(Component, Debug)]
+pub struct PuzzleReset {
+    /// Index of the level in [LevelSummary]
+    pub level_index: usize,
+
+    /// Lever handle and its rotation at rest
+    handle: Option<(Entity, Quat)>,
+
+    /// Time since the lever was pulled
+    pull: Option<f32>,
+}
+
+const PUZZLE_RESET_INTERACTION_DISTANCE: f32 = 3.0;
+
+/// Duration of the pull animation. The puzzle is reset once the animation completes.
+const PUZZLE_RESET_PULL_DURATION: f32 = 0.8;
+
+/// Rotation of the lever handle at the end of the pull
+const PUZZLE_RESET_PULL_ANGLE: f32 = FRAC_PI_3;
+
+/// Turns a switch off unless it is permanent like the switch of a rope whose payload landed.
+/// Observers follow their switches in the next step, except latched observers like rifts.
+pub fn reset_switch(state: &mut SwitchState, is_permanent: bool) {
+    if !is_permanent {
+        *state = SwitchState::Off;
+    }
+}
+
+impl PuzzleReset {
+    /// Resets the puzzle in the next step without pulling the lever
+    pub fn trigger(&mut self) {
+        self.pull = Some(PUZZLE_RESET_PULL_DURATION);
+    }
+}
+
+/// Angle of the lever handle during the pull animation. The handle swings out and back.
+pub fn puzzle_reset_handle_angle(pull_time: f32) -> f32 {
+    let q = (pull_time / PUZZLE_RESET_PULL_DURATION).clamp(0., 1.);
+    PUZZLE_RESET_PULL_ANGLE * (PI * q).sin()
+}
+
+/// Levers which reset the puzzle of a level
+pub struct PuzzleResetMocca;
+
+impl Mocca for PuzzleResetMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyAudioMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<LaserPointerMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<RopeMocca>();
+        deps.depends_on::<SwitchMocca>();
+    }
+
+    fn start(_world: &mut World) -> Self {
+        Self
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<PuzzleReset>();
+        world.register_component::<SpawnPuzzleResetTask>();
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(spawn_puzzle_reset);
+        world.run(pull_puzzle_reset);
+        world.run(animate_puzzle_reset);
+    }
+}
+
+fn spawn_puzzle_reset(
+    mut cmd: Commands,
+    asset_resolver: Singleton<SharedAssetResolver>,
+    levels: Singleton<LevelSummary>,
+    query: Query<(Entity, &SpawnPuzzleResetTask, &GlobalTransform3)>,
+    query_tf: Query<&Transform3>,
+) {
+    let clip = asset_resolver
+        .resolve("audio/effects/sfx-double_door.wav")
+        .unwrap();
+
+    for (entity, task, tf) in query.iter() {
+        cmd.entity(entity).remove::<SpawnPuzzleResetTask>();
+
+        let Some(level_index) = levels
+            .nearest_level_name(tf.translation())
+            .and_then(|name| levels.names.iter().position(|n| n == name))
+        else {
+            log::error!("puzzle reset lever {entity} is not in a level");
+            continue;
+        };
+
+        let handle = task
+            .handle_entity
+            .and_then(|e| query_tf.get(e).map(|tf| (e, tf.rotation)));
+
+        cmd.entity(entity)
+            .and_set(PuzzleReset {
+                level_index,
+                handle,
+                pull: None,
+            })
+            .and_set(AudioSource {
+                path: clip.clone(),
+                volume: 0.8,
+                state: AudioPlaybackState::Stop,
+                repeat: AudioRepeatKind::OneShot,
+                volume_auto_play: false,
+            });
+
+        if let Some((handle_entity, _)) = handle {
+            cmd.entity(handle_entity).set(DynamicTransform);
+        }
+    }
+}
+
+fn pull_puzzle_reset(
+    query_input_raycast: Query<&InputRaycastController>,
+    mut query_reset: Query<(&mut PuzzleReset, &mut AudioSource)>,
+) {
+    let input_raycast = &query_input_raycast.single().unwrap();
+
+    if !input_raycast.state().is_left_mouse_pressed {
+        return;
+    }
+
+    let Some((hit_entity, distance)) = input_raycast.raycast_entity_and_distance() else {
+        return;
+    };
+    if distance > PUZZLE_RESET_INTERACTION_DISTANCE {
+        return;
+    }
+
+    let Some((reset, audio)) = query_reset.get_mut(hit_entity) else {
+        return;
+    };
+    if reset.pull.is_some() {
+        return;
+    }
+
+    log::info!("pulled puzzle reset lever of level {}", reset.level_index);
+    reset.pull = Some(0.);
+    audio.state = AudioPlaybackState::Play;
+}
+
+fn animate_puzzle_reset(
+    time: Singleton<SimClock>,
+    levels: Singleton<LevelSummary>,
+    children: Relation<ChildOf>,
+    query_level: Query<(Entity, &Name), With<LevelRegion>>,
+    mut query_reset: Query<&mut PuzzleReset>,
+    mut query_tf: Query<&mut Transform3>,
+    mut query_laser: Query<&mut LaserPointerAzimuth>,
+    mut query_switch: Query<(&mut SwitchState, Option<&RopeConstraint>), With<Switch>>,
+) {
+    let dt = time.sim_dt_f32();
+
+    for reset in query_reset.iter_mut() {
+        let Some(pull) = reset.pull.as_mut() else {
+            continue;
+        };
+        *pull += dt;
+        let pull = *pull;
+
+        if let Some((handle_entity, rest)) = reset.handle {
+            if let Some(tf) = query_tf.get_mut(handle_entity) {
+                tf.rotation = rest * Quat::from_rotation_x(puzzle_reset_handle_angle(pull));
+            }
+        }
+
+        if pull < PUZZLE_RESET_PULL_DURATION {
+            continue;
+        }
+        reset.pull = None;
+
+        // Only props in the region of the lever's level are reset
+        let Some(level_entity) = levels.names.get(reset.level_index).and_then(|level_name| {
+            query_level
+                .iter()
+                .find(|(_, name)| name.as_str() == level_name)
+                .map(|(entity, _)| entity)
+        }) else {
+            continue;
+        };
+
+        let mut stack = vec![level_entity];
+        while let Some(entity) = stack.pop() {
+            stack.extend(children.iter(entity));
+
+            if let Some(azimuth) = query_laser.get_mut(entity) {
+                azimuth.reset();
+            }
+            if let Some((state, rope)) = query_switch.get_mut(entity) {
+                reset_switch(state, rope.is_some());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_puzzle_reset_keeps_rift_charges_and_keys() {
+        let door = SwitchObserver {
+            switches: vec!["target-1".into()],
+            latch: false,
+        };
+        let rift = SwitchObserver {
+            switches: vec!["target-1".into(), "rope-1".into()],
+            latch: true,
+        };
+        let bridge = SwitchObserver {
+            switches: vec!["rope-1".into()],
+            latch: false,
+        };
+
+        // the puzzle was solved: the rift opened, was charged and gave its key
+        let mut target = SwitchState::On;
+        let mut rope = SwitchState::On;
+        let mut door_state = SwitchObserverState::Active;
+        let mut rift_state = SwitchObserverState::Active;
+        let mut bridge_state = SwitchObserverState::Active;
+
+        reset_switch(&mut target, false);
+        reset_switch(&mut rope, true);
+        assert_eq!(target, SwitchState::Off);
+        assert_eq!(rope, SwitchState::On);
+
+        // observers follow the switches in the next step
+        let active_switches: HashSet<&str> = [("target-1", target), ("rope-1", rope)]
+            .into_iter()
+            .filter(|(_, state)| state.as_bool())
+            .map(|(name, _)| name)
+            .collect();
+        update_switch_observer(&door, &mut door_state, &active_switches);
+        update_switch_observer(&rift, &mut rift_state, &active_switches);
+        update_switch_observer(&bridge, &mut bridge_state, &active_switches);
+
+        assert!(door_state == SwitchObserverState::Inactive);
+        // the rift stays open so its charge and key are kept, as is progress of burned ropes
+        assert!(rift_state == SwitchObserverState::Active);
+        assert!(bridge_state == SwitchObserverState::Active);
+    }
+
+    #[test]
+    fn test_puzzle_reset_handle_returns() {
+        assert_abs_diff_eq!(puzzle_reset_handle_angle(0.), 0.);
+        assert_abs_diff_eq!(
+            puzzle_reset_handle_angle(0.5 * PUZZLE_RESET_PULL_DURATION),
+            PUZZLE_RESET_PULL_ANGLE
+        );
+        assert_abs_diff_eq!(
+            puzzle_reset_handle_angle(PUZZLE_RESET_PULL_DURATION),
+            0.,
+            epsilon = 1e-6
+        );
+    }
+}
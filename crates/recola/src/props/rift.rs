@@ -1,6 +1,11 @@
 use crate::{
-    collision::*, custom_properties::*, mechanics::switch::*, player::*, props::door::KeyId,
+    collision::*,
+    custom_properties::*,
+    mechanics::{prop_events::*, switch::*},
+    player::*,
+    props::door::KeyId,
     recola_mocca::CRIMSON,
+    settings::*,
 };
 use atom::prelude::*;
 use candy::{
@@ -14,6 +19,13 @@ pub struct SpawnRiftTask;
 #[derive(Component, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct RiftLevel(pub i64);
 
+/// Sets whether a rift was consumed, e.g. when a save is loaded. A rift which is not open yet
+/// stays closed unless it is consumed.
+#[derive(Component)]
+pub struct RestoreRiftTask {
+    pub consumed: bool,
+}
+
 /// Laser pointers with a beam which collides with objects
 pub struct RiftMocca;
 
@@ -30,6 +42,8 @@ impl Mocca for RiftMocca {
         deps.depends_on::<CollidersMocca>();
         deps.depends_on::<CustomPropertiesMocca>();
         deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<PropEventsMocca>();
+        deps.depends_on::<SettingsMocca>();
         deps.depends_on::<SwitchMocca>();
     }
 
@@ -39,6 +53,7 @@ impl Mocca for RiftMocca {
 
     fn register_components(world: &mut World) {
         world.register_component::<OpenRiftTask>();
+        world.register_component::<RestoreRiftTask>();
         world.register_component::<Rift>();
         world.register_component::<RiftConsume>();
         world.register_component::<RiftConsumeParticle>();
@@ -56,6 +71,7 @@ impl Mocca for RiftMocca {
         world.run(rift_jitter);
         world.run(charge_rift_interaction);
         world.run(consume_rift);
+        world.run(restore_rift);
         world.run(spawn_rift_consume_particles);
         world.run(animate_rift_consume_particles);
     }
@@ -185,14 +201,21 @@ fn inflate_rift_shards(
     }
 }
 
+/// Scale of rift shard jitter when flashing effects are reduced
+const RIFT_JITTER_REDUCED: f32 = 0.25;
+
 fn rift_jitter(
     time: Singleton<SimClock>,
+    settings: Singleton<GameSettings>,
     mut rng: SingletonMut<Rng>,
     mut query: Query<(&mut RiftJitter, &mut Transform3)>,
 ) {
     let dt = time.sim_dt_f32();
 
-    let jitter = Vec3::new(0.133, 0.133, 0.333);
+    let mut jitter = Vec3::new(0.133, 0.133, 0.333);
+    if settings.accessibility.reduce_flashes {
+        jitter *= RIFT_JITTER_REDUCED;
+    }
 
     for (jit, tf) in query.iter_mut() {
         jit.cooldown -= dt;
@@ -251,6 +274,7 @@ fn consume_rift(
     asset_resolver: Singleton<SharedAssetResolver>,
     time: Singleton<SimClock>,
     mut player: SingletonMut<Player>,
+    mut events: SingletonMut<PropEvents>,
     mut query_rift_consume: Query<(Entity, &mut Transform3, &mut RiftConsume, &RiftLevel)>,
 ) {
     let dt = time.sim_dt_f32();
@@ -266,6 +290,10 @@ fn consume_rift(
             let key = KeyId(rift_id.0);
             log::debug!("acquired key {key:?}");
             player.keys.insert(key);
+            events.publish(PropEvent::RiftCharged {
+                entity,
+                level: rift_id.0,
+            });
 
             cmd.entity(entity).set(Visibility::Hidden);
 
@@ -290,6 +318,37 @@ fn consume_rift(
     }
 }
 
+fn restore_rift(
+    mut cmd: Commands,
+    mut query: Query<(Entity, &RestoreRiftTask, Option<&mut RiftConsume>), With<Rift>>,
+) {
+    for (entity, task, rift_consume) in query.iter_mut() {
+        cmd.entity(entity).remove::<RestoreRiftTask>();
+
+        match rift_consume {
+            Some(rift_consume) => {
+                rift_consume.is_consumed = task.consumed;
+                rift_consume.charge = 0.;
+                rift_consume.particle_charge = 0.;
+            }
+            None if task.consumed => {
+                cmd.entity(entity).and_set(RiftConsume {
+                    is_consumed: true,
+                    charge: 0.,
+                    particle_charge: 0.,
+                });
+            }
+            None => continue,
+        }
+
+        cmd.entity(entity).set(if task.consumed {
+            Visibility::Hidden
+        } else {
+            Visibility::Visible
+        });
+    }
+}
+
 #[derive(Component)]
 struct RiftConsumeParticle {
     age: f32,
@@ -0,0 +1,527 @@
+use crate::{
+    collision::*,
+    custom_properties::*,
+    foundation::find_child_by_name,
+    level::LevelRegion,
+    mechanics::{prop_events::*, switch::*},
+    props::{laser_pointer::*, overgrowth::*},
+};
+use atom::prelude::*;
+use candy::{can::*, material::*, scene_tree::*, time::*};
+use glam::{Affine3A, Quat, Vec3, Vec3Swizzles};
+use magi::color::{LinearColor, SRgbU8Color};
+
+/// Spawns a rope holding a payload. Reads the `payload`, `drop_target`, `drop_height` and `switch`
+/// custom properties of the rope. Payload and drop target are looked up by name within the level
+/// of the rope. The payload falls onto the transform of the `drop_target` node if given and
+/// otherwise straight down by `drop_height`.
+#[derive(Component)]
+pub struct SpawnRopeTask {
+    /// The material of this entity glows while the rope burns
+    pub burn_entity: Option<Entity>,
+}
+
+/// A rope holding a suspended payload
+#[derive(Component, Debug)]
+pub struct RopeConstraint {
+    pub anchor: Vec3,
+    pub payload_entity: Entity,
+    pub length: f32,
+}
+
+/// Accumulates the time a laser beam dwells on an entity
+#[derive(Component, Debug, Clone)]
+pub struct BurnDwell {
+    /// Burn progress in [0, 1]
+    pub progress: f32,
+
+    /// Time the beam needs to dwell to burn through
+    pub duration: f32,
+
+    /// Progress lost per second while the beam is not on target
+    pub decay_rate: f32,
+}
+
+impl BurnDwell {
+    pub fn new(duration: f32) -> Self {
+        Self {
+            progress: 0.,
+            duration,
+            decay_rate: ROPE_BURN_DECAY_RATE,
+        }
+    }
+
+    /// Advances burning and returns true once burned through
+    pub fn step(&mut self, dt: f32, is_hit: bool) -> bool {
+        if is_hit {
+            self.progress += dt / self.duration;
+        } else {
+            self.progress -= dt * self.decay_rate;
+        }
+        self.progress = self.progress.clamp(0., 1.);
+        self.progress >= 1.
+    }
+}
+
+/// A payload falling after its rope burned through. The payload accelerates along the straight
+/// line from its start to the target transform.
+#[derive(Component, Debug, Clone)]
+pub struct PayloadFall {
+    pub start: (Vec3, Quat),
+    pub target: (Vec3, Quat),
+    pub velocity: f32,
+
+    /// Distance traveled towards the target
+    pub distance: f32,
+}
+
+impl PayloadFall {
+    pub fn new(start: &Transform3, target: (Vec3, Quat)) -> Self {
+        Self {
+            start: (start.translation, start.rotation),
+            target,
+            velocity: 0.,
+            distance: 0.,
+        }
+    }
+
+    /// Moves the payload and returns true once landed
+    pub fn step(&mut self, dt: f32, tf: &mut Transform3) -> bool {
+        self.velocity += ROPE_PAYLOAD_GRAVITY * dt;
+        self.distance += self.velocity * dt;
+
+        let length = self.start.0.distance(self.target.0);
+        let q = if length > 0. {
+            (self.distance / length).min(1.)
+        } else {
+            1.
+        };
+        if q >= 1. {
+            (tf.translation, tf.rotation) = self.target;
+            return true;
+        }
+        tf.translation = self.start.0.lerp(self.target.0, q);
+        tf.rotation = self.start.1.slerp(self.target.1, q);
+        false
+    }
+}
+
+/// Advances a falling payload. Once it landed the switches of the ropes which held it are turned
+/// on and the overgrowth crushed beneath it is returned.
+pub fn step_payload_fall<'a, E: Copy + PartialEq>(
+    fall: &mut PayloadFall,
+    dt: f32,
+    tf: &mut Transform3,
+    payload: E,
+    ropes: impl IntoIterator<Item = (E, Option<&'a mut SwitchState>)>,
+    overgrowth: impl IntoIterator<Item = (E, Vec3)>,
+) -> Option<Vec<E>> {
+    if !fall.step(dt, tf) {
+        return None;
+    }
+
+    for (rope_payload, switch) in ropes {
+        if rope_payload == payload {
+            if let Some(switch) = switch {
+                switch.set_from_bool(true);
+            }
+        }
+    }
+
+    let landing = tf.translation;
+    Some(
+        overgrowth
+            .into_iter()
+            .filter(|(_, pos)| {
+                pos.xy().distance(landing.xy()) < ROPE_PAYLOAD_CRUSH_RADIUS && pos.z <= landing.z
+            })
+            .map(|(entity, _)| entity)
+            .collect(),
+    )
+}
+
+/// Transform of the drop target relative to the parent of the payload. The payload's transform is
+/// local to its parent while the target may be placed anywhere in the scene tree.
+pub fn drop_target_in_parent_space(
+    payload_local: &Transform3,
+    world_t_payload: Affine3A,
+    world_t_target: Affine3A,
+) -> (Vec3, Quat) {
+    let parent_t_payload = Affine3A::from_scale_rotation_translation(
+        payload_local.scale,
+        payload_local.rotation,
+        payload_local.translation,
+    );
+    let parent_t_target = parent_t_payload * world_t_payload.inverse() * world_t_target;
+    let (_, rotation, translation) = parent_t_target.to_scale_rotation_translation();
+    (translation, rotation)
+}
+
+/// Set on a rope once burned through, until its payload has landed
+#[derive(Component)]
+struct RopeBurned;
+
+const ROPE_BURN_DURATION: f32 = 2.5;
+const ROPE_BURN_DECAY_RATE: f32 = 0.1;
+const ROPE_PAYLOAD_GRAVITY: f32 = 9.81;
+const ROPE_DEFAULT_DROP_HEIGHT: f32 = 3.0;
+
+/// Horizontal distance from the landing point within which overgrowth is crushed
+const ROPE_PAYLOAD_CRUSH_RADIUS: f32 = 1.5;
+
+/// Ropes which can be burned with a laser to drop their payload
+pub struct RopeMocca;
+
+impl Mocca for RopeMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyMaterialMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<CollidersMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<LaserPointerMocca>();
+        deps.depends_on::<OvergrowthMocca>();
+        deps.depends_on::<PropEventsMocca>();
+        deps.depends_on::<SwitchMocca>();
+    }
+
+    fn start(_world: &mut World) -> Self {
+        Self
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<BurnDwell>();
+        world.register_component::<PayloadFall>();
+        world.register_component::<Rope>();
+        world.register_component::<RopeBurned>();
+        world.register_component::<RopeConstraint>();
+        world.register_component::<SpawnRopeTask>();
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(spawn_rope);
+        world.run(burn_rope);
+        world.run(drop_payload);
+    }
+}
+
+#[derive(Component)]
+struct Rope {
+    burn_entity: Option<Entity>,
+    drop_target: Option<Entity>,
+    drop_height: f32,
+}
+
+fn spawn_rope(
+    mut cmd: Commands,
+    query_task: Query<(Entity, &SpawnRopeTask, &Transform3, &CustomProperties)>,
+    children: Relation<ChildOf>,
+    query_level: Query<Entity, With<LevelRegion>>,
+    query_name: Query<&Name>,
+    query_tf: Query<&Transform3>,
+) {
+    for (entity, task, tf, props) in query_task.iter() {
+        cmd.entity(entity).remove::<SpawnRopeTask>();
+
+        let Some(payload_name) = props.get_string("payload") else {
+            log::error!("rope {entity} without 'payload' property");
+            continue;
+        };
+
+        // Names are only unique within a level
+        let Some(level_entity) = query_level
+            .iter()
+            .find(|&level| is_descendant(&children, level, entity))
+        else {
+            log::error!("rope {entity} is not part of a level");
+            continue;
+        };
+
+        let Some(payload_entity) =
+            find_child_by_name(&children, &query_name, level_entity, payload_name)
+        else {
+            log::error!("rope {entity}: payload '{payload_name}' not found");
+            continue;
+        };
+
+        let drop_target = props.get_string("drop_target").and_then(|target_name| {
+            let target = find_child_by_name(&children, &query_name, level_entity, target_name);
+            if target.is_none() {
+                log::error!("rope {entity}: drop target '{target_name}' not found");
+            }
+            target
+        });
+
+        let length = query_tf
+            .get(payload_entity)
+            .map_or(0., |payload_tf| tf.translation.z - payload_tf.translation.z);
+
+        let drop_height = props
+            .get_float("drop_height")
+            .map_or(ROPE_DEFAULT_DROP_HEIGHT, |h| h as f32);
+
+        cmd.entity(entity)
+            .and_set(Rope {
+                burn_entity: task.burn_entity,
+                drop_target,
+                drop_height,
+            })
+            .and_set(RopeConstraint {
+                anchor: tf.translation,
+                payload_entity,
+                length,
+            })
+            .and_set(BurnDwell::new(ROPE_BURN_DURATION))
+            .and_set(BeamDetector { latch: false })
+            .and_set(BeamHit::Off);
+
+        if let Some(switch) = props.get_string("switch") {
+            cmd.entity(entity)
+                .and_set(Switch {
+                    name: switch.to_owned(),
+                })
+                .and_set(SwitchState::Off);
+        }
+    }
+}
+
+fn is_descendant(children: &Relation<ChildOf>, root: Entity, entity: Entity) -> bool {
+    children
+        .iter(root)
+        .any(|child| child == entity || is_descendant(children, child, entity))
+}
+
+fn burn_rope(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    mut query: Query<
+        (Entity, &Rope, &RopeConstraint, &mut BurnDwell, &BeamHit),
+        Without<RopeBurned>,
+    >,
+    query_tf: Query<&Transform3>,
+    query_global_tf: Query<&GlobalTransform3>,
+) {
+    let color_glow: LinearColor = SRgbU8Color::from_rgb(240, 97, 26).to_linear();
+
+    let dt = time.sim_dt_f32();
+    for (entity, rope, constraint, dwell, hit) in query.iter_mut() {
+        let burned = dwell.step(dt, hit.as_bool());
+
+        if let Some(burn_entity) = rope.burn_entity {
+            let mat =
+                PbrMaterial::diffuse_white().with_emission(color_glow * (5.0 * dwell.progress));
+            cmd.entity(burn_entity)
+                .and_set(Material::Pbr(mat))
+                .and_set(MaterialDirty);
+        }
+
+        if burned {
+            log::debug!("rope {entity} burned through");
+
+            cmd.entity(entity)
+                .and_set(RopeBurned)
+                .and_set(Visibility::Hidden)
+                .and_set(ChangeCollidersLayerMaskTask {
+                    mask: CollisionLayerMask::none(),
+                });
+
+            let Some(payload_tf) = query_tf.get(constraint.payload_entity) else {
+                continue;
+            };
+            let target = match (
+                query_global_tf.get(constraint.payload_entity),
+                rope.drop_target.and_then(|e| query_global_tf.get(e)),
+            ) {
+                (Some(world_t_payload), Some(world_t_target)) => drop_target_in_parent_space(
+                    payload_tf,
+                    *world_t_payload.affine(),
+                    *world_t_target.affine(),
+                ),
+                _ => (
+                    payload_tf.translation - rope.drop_height * Vec3::Z,
+                    payload_tf.rotation,
+                ),
+            };
+            cmd.entity(constraint.payload_entity)
+                .and_set(PayloadFall::new(payload_tf, target))
+                .and_set(DynamicTransform);
+        }
+    }
+}
+
+fn drop_payload(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    mut events: SingletonMut<PropEvents>,
+    mut query_payload: Query<(Entity, &mut PayloadFall, &mut Transform3)>,
+    mut query_rope: Query<(Entity, &RopeConstraint, Option<&mut SwitchState>), With<RopeBurned>>,
+    query_overgrowth: Query<(Entity, &GlobalTransform3), With<Overgrowth>>,
+    query_ainst: Query<&AssetInstance>,
+) {
+    let dt = time.sim_dt_f32();
+
+    for (payload_entity, fall, tf) in query_payload.iter_mut() {
+        let Some(crushed) = step_payload_fall(
+            fall,
+            dt,
+            tf,
+            payload_entity,
+            query_rope
+                .iter_mut()
+                .map(|(_, constraint, switch)| (constraint.payload_entity, switch)),
+            query_overgrowth
+                .iter()
+                .map(|(entity, tf)| (entity, tf.translation())),
+        ) else {
+            continue;
+        };
+
+        log::debug!("payload {payload_entity} landed");
+
+        cmd.entity(payload_entity)
+            .and_remove::<PayloadFall>()
+            .and_set(CollidersDirtyTask);
+
+        for (rope_entity, constraint, _) in query_rope.iter_mut() {
+            // The rope is spent and can not burn again
+            if constraint.payload_entity == payload_entity {
+                cmd.entity(rope_entity)
+                    .and_remove::<RopeBurned>()
+                    .and_remove::<BurnDwell>();
+            }
+        }
+
+        for overgrowth_entity in crushed {
+            cmd.despawn_recursive(overgrowth_entity);
+            if let Some(ainst) = query_ainst.get(overgrowth_entity) {
+                events.publish(PropEvent::PropDespawned {
+                    kind: ainst.as_str().to_owned(),
+                    entity: overgrowth_entity,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_burn_dwell_accumulates() {
+        let mut dwell = BurnDwell::new(2.0);
+        for _ in 0..19 {
+            assert!(!dwell.step(0.1, true));
+        }
+        assert_abs_diff_eq!(dwell.progress, 0.95, epsilon = 1e-5);
+        assert!(dwell.step(0.1, true));
+    }
+
+    #[test]
+    fn test_burn_dwell_decays_slowly() {
+        let mut dwell = BurnDwell::new(2.0);
+        for _ in 0..10 {
+            dwell.step(0.1, true);
+        }
+        assert_abs_diff_eq!(dwell.progress, 0.5, epsilon = 1e-5);
+
+        // laser leaves for one second
+        for _ in 0..10 {
+            dwell.step(0.1, false);
+        }
+        assert_abs_diff_eq!(dwell.progress, 0.4, epsilon = 1e-5);
+
+        // and never goes below zero
+        for _ in 0..100 {
+            dwell.step(0.1, false);
+        }
+        assert_eq!(dwell.progress, 0.);
+    }
+
+    #[test]
+    fn test_payload_lands_on_target() {
+        let mut tf = Transform3::from_translation(Vec3::new(0., 0., 4.));
+        let mut fall = PayloadFall::new(&tf, (Vec3::new(0., 0., 1.), Quat::IDENTITY));
+
+        let mut steps = 0;
+        while !fall.step(0.01, &mut tf) {
+            steps += 1;
+            assert!(tf.translation.z > 1.0);
+        }
+        assert_eq!(tf.translation, Vec3::new(0., 0., 1.));
+
+        // free fall time for 3 m is about 0.78 s
+        assert!((75..80).contains(&steps), "{steps}");
+    }
+
+    #[test]
+    fn test_payload_moves_to_target_transform() {
+        let mut tf = Transform3::from_translation(Vec3::new(0., 0., 4.));
+        let target = (
+            Vec3::new(2., 1., 0.5),
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+        );
+        let mut fall = PayloadFall::new(&tf, target);
+
+        assert!(!fall.step(0.1, &mut tf));
+        assert!(tf.translation.x > 0. && tf.translation.y > 0. && tf.translation.z < 4.);
+
+        while !fall.step(0.01, &mut tf) {}
+        assert_eq!(tf.translation, target.0);
+        assert!(tf.rotation.angle_between(target.1) < 1e-4);
+    }
+
+    #[test]
+    fn test_drop_target_in_parent_space() {
+        // payload parent is rotated by 90 deg around Z and moved to (10, 0, 0)
+        let world_t_parent = Affine3A::from_rotation_translation(
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_2),
+            Vec3::new(10., 0., 0.),
+        );
+        let payload_local = Transform3::from_translation(Vec3::new(1., 0., 4.));
+        let world_t_payload =
+            world_t_parent * Affine3A::from_translation(payload_local.translation);
+        let world_t_target = Affine3A::from_rotation_translation(
+            Quat::from_rotation_z(std::f32::consts::PI),
+            Vec3::new(10., 3., 1.),
+        );
+
+        let (translation, rotation) =
+            drop_target_in_parent_space(&payload_local, world_t_payload, world_t_target);
+        assert_abs_diff_eq!(translation.x, 3., epsilon = 1e-5);
+        assert_abs_diff_eq!(translation.y, 0., epsilon = 1e-5);
+        assert_abs_diff_eq!(translation.z, 1., epsilon = 1e-5);
+        assert!(rotation.angle_between(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)) < 1e-4);
+    }
+
+    #[test]
+    fn test_payload_landing_triggers_switch() {
+        let mut tf = Transform3::from_translation(Vec3::new(0., 0., 3.));
+        let mut fall = PayloadFall::new(&tf, (Vec3::ZERO, Quat::IDENTITY));
+        let mut switch = SwitchState::Off;
+        let mut other_switch = SwitchState::Off;
+        let overgrowth = [
+            (10, Vec3::new(1., 0., 0.)),
+            (11, Vec3::new(5., 0., 0.)),
+            (12, Vec3::new(0., 0., 2.)),
+        ];
+
+        let crushed = loop {
+            let ropes = [
+                (1, Some(&mut switch)),
+                (2, Some(&mut other_switch)),
+                (1, None),
+            ];
+            if let Some(crushed) = step_payload_fall(&mut fall, 0.01, &mut tf, 1, ropes, overgrowth)
+            {
+                break crushed;
+            }
+            assert_eq!(switch, SwitchState::Off);
+        };
+
+        assert_eq!(switch, SwitchState::On);
+        assert_eq!(other_switch, SwitchState::Off);
+        assert_eq!(crushed, vec![10]);
+    }
+}
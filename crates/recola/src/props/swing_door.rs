@@ -0,0 +1,337 @@
+use crate::{collision::*, custom_properties::*, player::*};
+use atom::prelude::*;
+use candy::{scene_tree::*, time::*};
+use glam::{Affine3A, Quat, Vec3};
+
+/// Creates an interior door with a leaf which swings when the player walks into it
+#[derive(Component)]
+pub struct SpawnSwingDoorTask {
+    pub leaf_entity: Entity,
+    pub collider_entity: Entity,
+}
+
+/// Default angle limit in both directions
+const SWING_DOOR_MAX_ANGLE_DEG: f32 = 100.0;
+
+/// Default strength of the spring which closes the door
+const SWING_DOOR_STIFFNESS: f32 = 8.0;
+
+/// Default damping. Slightly above critical damping so that the door settles without swinging
+/// through the closed position.
+const SWING_DOOR_DAMPING: f32 = 6.0;
+
+/// Parameters of a swinging door leaf
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SwingDoorSettings {
+    /// Minimum and maximum leaf angle in radians. The door is closed at angle 0.
+    pub min_angle: f32,
+    pub max_angle: f32,
+
+    /// Angular acceleration per radian which pulls the leaf back to closed
+    pub stiffness: f32,
+
+    /// Angular acceleration per angular velocity which slows the leaf down
+    pub damping: f32,
+}
+
+impl Default for SwingDoorSettings {
+    fn default() -> Self {
+        Self {
+            min_angle: -SWING_DOOR_MAX_ANGLE_DEG.to_radians(),
+            max_angle: SWING_DOOR_MAX_ANGLE_DEG.to_radians(),
+            stiffness: SWING_DOOR_STIFFNESS,
+            damping: SWING_DOOR_DAMPING,
+        }
+    }
+}
+
+impl SwingDoorSettings {
+    /// Reads the `swing_min_angle` and `swing_max_angle` (in degrees), `swing_stiffness` and
+    /// `swing_damping` custom properties
+    pub fn from_properties(props: Option<&CustomProperties>) -> Self {
+        let mut settings = Self::default();
+        let Some(props) = props else {
+            return settings;
+        };
+        let get = |id: &str| props.get_float(id).map(|v| v as f32);
+
+        if let Some(deg) = get("swing_min_angle") {
+            settings.min_angle = deg.to_radians();
+        }
+        if let Some(deg) = get("swing_max_angle") {
+            settings.max_angle = deg.to_radians();
+        }
+        if let Some(stiffness) = get("swing_stiffness") {
+            settings.stiffness = stiffness;
+        }
+        if let Some(damping) = get("swing_damping") {
+            settings.damping = damping;
+        }
+        settings
+    }
+}
+
+/// Angular velocity needed to move the leaf out of the way of a contact. The contact at `point`
+/// wants to move by `motion` within `dt`. The hinge rotates around the unit vector `axis`
+/// through `hinge`.
+pub fn swing_door_push(hinge: Vec3, axis: Vec3, point: Vec3, motion: Vec3, dt: f32) -> f32 {
+    let arm = point - hinge;
+    let arm = arm - arm.dot(axis) * axis;
+    let arm_len_sq = arm.length_squared();
+    if arm_len_sq < 1e-4 || dt <= 0. {
+        return 0.;
+    }
+    axis.dot(arm.cross(motion)) / (arm_len_sq * dt)
+}
+
+/// Angle and angular velocity of a swinging door leaf
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SwingDoorState {
+    pub angle: f32,
+    pub velocity: f32,
+}
+
+impl SwingDoorState {
+    /// Integrates the spring-damper dynamics of the leaf. A push is an angular velocity which
+    /// the leaf must at least have in the direction of the push.
+    pub fn step(&mut self, dt: f32, push: f32, settings: &SwingDoorSettings) {
+        if push.abs() > 0. && self.velocity * push.signum() < push.abs() {
+            self.velocity = push;
+        }
+
+        let accel = -settings.stiffness * self.angle - settings.damping * self.velocity;
+        self.velocity += accel * dt;
+        self.angle += self.velocity * dt;
+
+        // block at the angle limits
+        if self.angle <= settings.min_angle {
+            self.angle = settings.min_angle;
+            self.velocity = self.velocity.max(0.);
+        } else if self.angle >= settings.max_angle {
+            self.angle = settings.max_angle;
+            self.velocity = self.velocity.min(0.);
+        }
+    }
+}
+
+/// An interior door leaf which swings around the Z axis of the door when pushed by the player
+/// and springs back closed
+#[derive(Component, Debug)]
+pub struct SwingDoor {
+    pub settings: SwingDoorSettings,
+    pub state: SwingDoorState,
+
+    /// Leaf and collider with their translation and rotation when closed
+    leaf: (Entity, Vec3, Quat),
+    collider: (Entity, Vec3, Quat),
+}
+
+/// Interior doors which swing when the player walks into them
+pub struct SwingDoorMocca;
+
+impl Mocca for SwingDoorMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<CollidersMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<PlayerMocca>();
+    }
+
+    fn start(_world: &mut World) -> Self {
+        Self
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<SpawnSwingDoorTask>();
+        world.register_component::<SwingDoor>();
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(spawn_swing_door);
+        world.run(swing_door);
+    }
+}
+
+fn spawn_swing_door(
+    mut cmd: Commands,
+    query: Query<(Entity, &SpawnSwingDoorTask, Option<&CustomProperties>)>,
+    query_tf: Query<&Transform3>,
+) {
+    for (entity, task, props) in query.iter() {
+        cmd.entity(entity).remove::<SpawnSwingDoorTask>();
+
+        let (Some(leaf_tf), Some(collider_tf)) = (
+            query_tf.get(task.leaf_entity),
+            query_tf.get(task.collider_entity),
+        ) else {
+            log::error!("swing door {entity} is missing its leaf");
+            continue;
+        };
+
+        cmd.entity(entity).and_set(SwingDoor {
+            settings: SwingDoorSettings::from_properties(props),
+            state: SwingDoorState::default(),
+            leaf: (task.leaf_entity, leaf_tf.translation, leaf_tf.rotation),
+            collider: (
+                task.collider_entity,
+                collider_tf.translation,
+                collider_tf.rotation,
+            ),
+        });
+        cmd.entity(task.leaf_entity).set(DynamicTransform);
+
+        log::debug!("spawned swing door: {entity}");
+    }
+}
+
+fn swing_door(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    player: Singleton<Player>,
+    mut query_door: Query<(Entity, &GlobalTransform3, &mut SwingDoor)>,
+    mut query_tf: Query<&mut Transform3>,
+) {
+    let dt = time.sim_dt_f32();
+    let capsule = player_capsule(player.previous_position);
+
+    for (door_entity, door_tf, door) in query_door.iter_mut() {
+        let world_t_door = *door_tf.affine();
+        let hinge = world_t_door.translation.into();
+        let axis = world_t_door.transform_vector3(Vec3::Z).normalize();
+
+        let push = player
+            .nav_contacts
+            .iter()
+            .filter(|contact| contact.collider_entity == door.collider.0)
+            .map(|contact| swing_door_push(hinge, axis, contact.point, contact.motion, dt))
+            .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+            .unwrap_or(0.);
+
+        let previous = door.state;
+        let settings = door.settings;
+        door.state.step(dt, push, &settings);
+        if door.state.angle == previous.angle {
+            continue;
+        }
+
+        // The leaf never moves into the player so that the player is not pushed through walls
+        let Some(collider_scale) = query_tf.get_mut(door.collider.0).map(|tf| tf.scale) else {
+            continue;
+        };
+        let penetration = |angle: f32| {
+            let (translation, rotation) = swung_pose(door.collider.1, door.collider.2, angle);
+            let world_t_collider = world_t_door
+                * Affine3A::from_scale_rotation_translation(collider_scale, rotation, translation);
+            PosedCuboid::from_unit_cube_tf(world_t_collider)
+                .map(|cuboid| {
+                    capsule
+                        .iter()
+                        .map(|pball| cuboid.signed_distance_pos_ball(pball))
+                        .fold(f32::INFINITY, f32::min)
+                })
+                .unwrap_or(f32::INFINITY)
+        };
+        let depth = penetration(door.state.angle);
+        if depth < 0. && depth < penetration(previous.angle) {
+            door.state = SwingDoorState {
+                angle: previous.angle,
+                velocity: 0.,
+            };
+            continue;
+        }
+
+        for (entity, translation, rotation) in [door.leaf, door.collider] {
+            if let Some(tf) = query_tf.get_mut(entity) {
+                (tf.translation, tf.rotation) = swung_pose(translation, rotation, door.state.angle);
+            }
+        }
+        cmd.entity(door_entity).and_set(CollidersDirtyTask);
+    }
+}
+
+/// Pose of a child of the door rotated around the hinge
+fn swung_pose(translation: Vec3, rotation: Quat, angle: f32) -> (Vec3, Quat) {
+    let hinge_rotation = Quat::from_rotation_z(angle);
+    (hinge_rotation * translation, hinge_rotation * rotation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    const DT: f32 = 1. / 60.;
+
+    #[test]
+    fn test_swing_door_push_direction() {
+        // leaf extends along +X from the hinge, player walks along +Y into it
+        let push = swing_door_push(
+            Vec3::ZERO,
+            Vec3::Z,
+            Vec3::new(1., 0., 0.5),
+            0.1 * Vec3::Y,
+            DT,
+        );
+        assert_abs_diff_eq!(push, 0.1 / DT, epsilon = 1e-3);
+
+        let push = swing_door_push(
+            Vec3::ZERO,
+            Vec3::Z,
+            Vec3::new(1., 0., 0.5),
+            -0.1 * Vec3::Y,
+            DT,
+        );
+        assert!(push < 0.);
+
+        // contacts at the hinge do not move the leaf
+        assert_eq!(
+            swing_door_push(Vec3::ZERO, Vec3::Z, Vec3::new(0., 0., 0.5), Vec3::Y, DT),
+            0.
+        );
+    }
+
+    #[test]
+    fn test_swing_door_settles_closed() {
+        let settings = SwingDoorSettings::default();
+        let mut state = SwingDoorState::default();
+
+        // the player pushes the door for half a second
+        for _ in 0..30 {
+            state.step(DT, 1.5, &settings);
+        }
+        assert!(state.angle > 0.3, "{state:?}");
+
+        // without contact the door springs back and settles closed without overshooting
+        for _ in 0..600 {
+            state.step(DT, 0., &settings);
+            assert!(state.angle > -1e-3, "{state:?}");
+        }
+        assert_abs_diff_eq!(state.angle, 0., epsilon = 1e-3);
+        assert_abs_diff_eq!(state.velocity, 0., epsilon = 1e-3);
+    }
+
+    #[test]
+    fn test_swing_door_angle_limits() {
+        let settings = SwingDoorSettings {
+            min_angle: -0.5,
+            max_angle: 1.0,
+            ..Default::default()
+        };
+        let mut state = SwingDoorState::default();
+
+        for _ in 0..120 {
+            state.step(DT, 20., &settings);
+            assert!(state.angle <= settings.max_angle);
+        }
+        assert_eq!(state.angle, settings.max_angle);
+        assert_eq!(state.velocity, 0.);
+
+        for _ in 0..120 {
+            state.step(DT, -20., &settings);
+            assert!(state.angle >= settings.min_angle);
+        }
+        assert_eq!(state.angle, settings.min_angle);
+        assert!(state.velocity >= 0.);
+    }
+}
@@ -1,4 +1,18 @@
-use crate::{STATIC_SETTINGS, level::*, player::*};
+use crate::{
+    STATIC_SETTINGS,
+    camera_bookmarks::*,
+    demo::*,
+    forge::ForgeMocca,
+    level::*,
+    paint_marks::*,
+    player::*,
+    props::{laser_turret::*, reset_lever::*},
+    save::*,
+    time_trial::*,
+    tutorial::*,
+    victory::*,
+    weather::*,
+};
 use atom::prelude::*;
 use candy::{can::*, forge::*};
 use magi::prelude::SRgbU8Color;
@@ -9,11 +23,27 @@ pub struct RecolaMocca;
 
 impl Mocca for RecolaMocca {
     fn load(mut deps: MoccaDeps) {
+        // Loaded first so that recorded inputs are applied before the camera controller steps
+        deps.depends_on::<DemoMocca>();
+        deps.depends_on::<LaserTurretMocca>();
         deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PaintMarkMocca>();
         deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<PuzzleResetMocca>();
+        deps.depends_on::<SaveMocca>();
+        deps.depends_on::<TimeTrialMocca>();
+        deps.depends_on::<TutorialMocca>();
+        deps.depends_on::<VictoryMocca>();
+        deps.depends_on::<WeatherMocca>();
+
+        // Bookmarks are used in ghost mode which is a cheat
+        if STATIC_SETTINGS.enable_cheats {
+            deps.depends_on::<CameraBookmarkMocca>();
+        }
 
         if STATIC_SETTINGS.enable_forge {
             deps.depends_on::<CandyForgeMocca>();
+            deps.depends_on::<ForgeMocca>();
         };
     }
 
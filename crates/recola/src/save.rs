@@ -0,0 +1,612 @@
+//! Save games
+//!
+//! A save stores the progression of the player as JSON with an embedded schema version. Saves are
+//! written atomically to a temporary file which is then renamed so that a crash during writing
+//! never corrupts an existing save.
+//!
+//! Autosaves are written when the player charges a rift or a door opens. They rotate
+//! through [AUTOSAVE_SLOT_COUNT] slots with slot 0 being the newest. F5 writes a quick save into
+//! manual slot 0 and F8 loads the newest save.
+
+use crate::{
+    level::*,
+    mechanics::prop_events::*,
+    paint_marks::*,
+    player::*,
+    props::{door::*, reset_lever::*, rift::*},
+    victory::*,
+};
+use atom::prelude::*;
+use candy::{camera::*, input::*, utils::WindowDef};
+use eyre::{Result, eyre};
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Version of the save format. Saves with a newer version are refused.
+pub const SAVE_SCHEMA_VERSION: u32 = 1;
+
+/// Number of rotating autosave slots
+pub const AUTOSAVE_SLOT_COUNT: usize = 3;
+
+/// Directory of save games and other persistent player data
+pub const SAVE_DIR: &str = "saves";
+
+/// Progression of the player stored in a save
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SaveGame {
+    pub version: u32,
+
+    /// Seconds since the UNIX epoch
+    pub timestamp: u64,
+
+    /// Name of the level the player was in
+    pub level: String,
+
+    /// Play time in seconds
+    pub play_time: f32,
+
+    pub position: Vec2,
+    pub hours: f32,
+    pub rift_charges: Vec<i64>,
+    pub keys: Vec<i64>,
+
+    /// Tutorial tips which were already shown
+    #[serde(default)]
+    pub tutorials_seen: Vec<String>,
+
+    /// Marks painted by the player
+    #[serde(default)]
+    pub paint_marks: Vec<PaintMark>,
+
+    /// Keys of level gates which were lowered
+    #[serde(default)]
+    pub open_gates: Vec<i64>,
+}
+
+#[derive(Deserialize)]
+struct SaveHeader {
+    version: u32,
+}
+
+impl SaveGame {
+    /// Progression at the start of the game. Tutorial tips which were already shown are kept.
+    pub fn new_game(tutorials_seen: Vec<String>) -> Self {
+        Self {
+            version: SAVE_SCHEMA_VERSION,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs()),
+            level: String::new(),
+            play_time: 0.,
+            position: PLAYER_SPAWN,
+            hours: PLAYER_START_HOURS,
+            rift_charges: Vec::new(),
+            keys: Vec::new(),
+            tutorials_seen,
+            paint_marks: Vec::new(),
+            open_gates: Vec::new(),
+        }
+    }
+
+    /// Progression restored from the save
+    pub fn progression(&self) -> Progression {
+        Progression {
+            total_time: self.play_time,
+            tutorials_seen: self.tutorials_seen.iter().cloned().collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Self> {
+        let header: SaveHeader = serde_json::from_str(text)?;
+        if header.version > SAVE_SCHEMA_VERSION {
+            return Err(eyre!(
+                "save was written by a newer version of the game \
+                 (save format {}, supported up to {SAVE_SCHEMA_VERSION})",
+                header.version
+            ));
+        }
+        Ok(serde_json::from_str(text)?)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save_atomic(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_json_atomic(self, path)
+    }
+}
+
+/// Writes JSON to a temporary file next to `path` and renames it once complete
+pub fn write_json_atomic<T: Serialize>(value: &T, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let tmp_path = tmp_path(path);
+    write_json_tmp(value, &tmp_path)?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn write_json_tmp<T: Serialize>(value: &T, tmp_path: &Path) -> Result<()> {
+    if let Some(dir) = tmp_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::fs::File::create(tmp_path)?;
+    file.write_all(serde_json::to_string_pretty(value)?.as_bytes())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn tmp_path(path: &Path) -> PathBuf {
+    path.with_extension("json.tmp")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveSlotKind {
+    Manual,
+    Autosave,
+}
+
+/// Entry of the save list shown in the load menu
+#[derive(Debug)]
+pub struct SaveSlotEntry {
+    pub kind: SaveSlotKind,
+    pub index: usize,
+    pub path: PathBuf,
+    pub save: Result<SaveGame>,
+}
+
+/// Manual and rotating autosave slots in a directory
+#[derive(Singleton, Debug, Clone)]
+pub struct SaveSlots {
+    pub dir: PathBuf,
+}
+
+impl SaveSlots {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn manual_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("save-{index}.json"))
+    }
+
+    pub fn autosave_path(&self, index: usize) -> PathBuf {
+        self.dir.join(format!("autosave-{index}.json"))
+    }
+
+    /// Writes a new autosave into slot 0 and moves older autosaves one slot back. The oldest
+    /// autosave is dropped.
+    pub fn write_autosave(&self, save: &SaveGame) -> Result<()> {
+        let newest = self.autosave_path(0);
+        let tmp = tmp_path(&newest);
+        write_json_tmp(save, &tmp)?;
+
+        for i in (1..AUTOSAVE_SLOT_COUNT).rev() {
+            let src = self.autosave_path(i - 1);
+            if src.exists() {
+                std::fs::rename(src, self.autosave_path(i))?;
+            }
+        }
+
+        std::fs::rename(tmp, newest)?;
+        Ok(())
+    }
+
+    pub fn write_manual(&self, index: usize, save: &SaveGame) -> Result<()> {
+        save.save_atomic(self.manual_path(index))
+    }
+
+    /// All existing manual saves and autosaves ordered from newest to oldest. Saves which failed
+    /// to load are listed last.
+    pub fn list(&self) -> Vec<SaveSlotEntry> {
+        let mut entries = Vec::new();
+
+        if let Ok(dir) = std::fs::read_dir(&self.dir) {
+            for path in dir.flatten().map(|entry| entry.path()) {
+                let Some(stem) = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".json"))
+                else {
+                    continue;
+                };
+
+                let (kind, index) = if let Some(index) = stem.strip_prefix("autosave-") {
+                    (SaveSlotKind::Autosave, index)
+                } else if let Some(index) = stem.strip_prefix("save-") {
+                    (SaveSlotKind::Manual, index)
+                } else {
+                    continue;
+                };
+                let Ok(index) = index.parse() else {
+                    continue;
+                };
+
+                let save = SaveGame::load(&path);
+                entries.push(SaveSlotEntry {
+                    kind,
+                    index,
+                    path,
+                    save,
+                });
+            }
+        }
+
+        entries.sort_by_key(|entry| {
+            std::cmp::Reverse(entry.save.as_ref().ok().map(|save| save.timestamp))
+        });
+        entries
+    }
+
+    /// The newest save which loads successfully
+    pub fn newest(&self) -> Option<SaveGame> {
+        self.list().into_iter().find_map(|entry| entry.save.ok())
+    }
+}
+
+/// Receives keys for quick save and quick load
+#[derive(Component, Default)]
+pub struct SaveInput {
+    quick_save: bool,
+    quick_load: bool,
+}
+
+impl SaveInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if let InputEvent::KeyboardInput {
+            state: ElementState::Pressed,
+            code,
+            ..
+        } = msg.event
+        {
+            match code {
+                KeyCode::F5 => self.quick_save = true,
+                KeyCode::F8 => self.quick_load = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl atom::Agent for SaveInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(SaveInput::on_input_event);
+    }
+}
+
+/// Save which is applied to the player and the world in the next step
+///
+/// Rifts and level gates are restored from the save. Lasers and switches are not part of a save
+/// and keep their current state, as do rifts which were opened since and burned ropes.
+#[derive(Singleton, Default)]
+pub struct PendingLoad(pub Option<SaveGame>);
+
+/// Prop events which trigger an autosave
+#[derive(Singleton, Default)]
+struct AutosaveTrigger {
+    cursor: PropEventCursor,
+}
+
+/// Saving and loading of player progression
+pub struct SaveMocca;
+
+impl Mocca for SaveMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyCameraMocca>();
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<DoorMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PaintMarkMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<PropEventsMocca>();
+        deps.depends_on::<PuzzleResetMocca>();
+        deps.depends_on::<VictoryMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<SaveInput>();
+        atom::register_agent_components::<SaveInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        let slots = SaveSlots::new(SAVE_DIR);
+        for entry in slots.list() {
+            match &entry.save {
+                Ok(save) => log::info!(
+                    "{:?} slot {}: level {}, play time {:.0} s, saved at {}",
+                    entry.kind,
+                    entry.index,
+                    save.level,
+                    save.play_time,
+                    save.timestamp
+                ),
+                Err(err) => log::warn!("{}: {err}", entry.path.display()),
+            }
+        }
+
+        world.set_singleton(slots);
+        world.set_singleton(AutosaveTrigger::default());
+        world.set_singleton(PendingLoad::default());
+        world.run(spawn_save_input);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<SaveInput, _>);
+        world.run(autosave);
+        world.run(quick_save_and_load);
+        world.run(start_over);
+        world.run(apply_pending_load);
+    }
+}
+
+fn spawn_save_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, SaveInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn current_save(
+    player: &Player,
+    levels: &LevelSummary,
+    progression: &Progression,
+    paint_marks: &PaintMarks,
+    query_gate: &Query<(&LevelGate, &KeyId)>,
+) -> SaveGame {
+    let mut rift_charges: Vec<_> = player.rift_charges.iter().map(|lvl| lvl.0).collect();
+    rift_charges.sort();
+    let mut keys: Vec<_> = player.keys.iter().map(|key| key.0).collect();
+    keys.sort();
+    let mut open_gates: Vec<_> = query_gate
+        .iter()
+        .filter(|(gate, _)| gate.is_lowered())
+        .map(|(_, key)| key.0)
+        .collect();
+    open_gates.sort();
+
+    SaveGame {
+        version: SAVE_SCHEMA_VERSION,
+        timestamp: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        level: levels
+            .nearest_level_name(player.eye_position)
+            .unwrap_or_default()
+            .to_owned(),
+        play_time: progression.total_time,
+        position: player.previous_position,
+        hours: player.hours_target,
+        rift_charges,
+        keys,
+        tutorials_seen: progression.sorted_tutorials_seen(),
+        paint_marks: paint_marks.saved_marks(),
+        open_gates,
+    }
+}
+
+fn autosave(
+    slots: Singleton<SaveSlots>,
+    player: Singleton<Player>,
+    levels: Singleton<LevelSummary>,
+    progression: Singleton<Progression>,
+    paint_marks: Singleton<PaintMarks>,
+    events: Singleton<PropEvents>,
+    mut trigger: SingletonMut<AutosaveTrigger>,
+    query_gate: Query<(&LevelGate, &KeyId)>,
+) {
+    let is_triggered = events.drain(&mut trigger.cursor).any(|event| {
+        matches!(
+            event,
+            PropEvent::RiftCharged { .. } | PropEvent::DoorStateChanged { open: true, .. }
+        )
+    });
+
+    if is_triggered {
+        let save = current_save(&player, &levels, &progression, &paint_marks, &query_gate);
+        match slots.write_autosave(&save) {
+            Ok(()) => log::info!("autosaved in level {}", save.level),
+            Err(err) => log::error!("autosave failed: {err:?}"),
+        }
+    }
+}
+
+fn quick_save_and_load(
+    slots: Singleton<SaveSlots>,
+    player: Singleton<Player>,
+    levels: Singleton<LevelSummary>,
+    progression: Singleton<Progression>,
+    paint_marks: Singleton<PaintMarks>,
+    mut pending: SingletonMut<PendingLoad>,
+    mut query_input: Query<&mut SaveInput>,
+    query_gate: Query<(&LevelGate, &KeyId)>,
+) {
+    let Some(input) = query_input.single_mut() else {
+        return;
+    };
+
+    if std::mem::take(&mut input.quick_save) {
+        let save = current_save(&player, &levels, &progression, &paint_marks, &query_gate);
+        match slots.write_manual(0, &save) {
+            Ok(()) => log::info!("saved"),
+            Err(err) => log::error!("save failed: {err:?}"),
+        }
+    }
+
+    if std::mem::take(&mut input.quick_load) {
+        match slots.newest() {
+            Some(save) => pending.0 = Some(save),
+            None => log::warn!("no save to load"),
+        }
+    }
+}
+
+/// Loads a new game once the player chose to start over after the victory sequence
+fn start_over(
+    mut victory: SingletonMut<VictorySequence>,
+    progression: Singleton<Progression>,
+    mut pending: SingletonMut<PendingLoad>,
+    mut query_reset: Query<&mut PuzzleReset>,
+) {
+    if victory.phase() != VictoryPhase::Finished(VictoryChoice::Restart) {
+        return;
+    }
+
+    log::info!("starting over");
+
+    pending.0 = Some(SaveGame::new_game(progression.sorted_tutorials_seen()));
+
+    // Lasers and switches are not part of a save
+    for reset in query_reset.iter_mut() {
+        reset.trigger();
+    }
+
+    *victory = VictorySequence::new(victory.credits_lines);
+}
+
+fn apply_pending_load(
+    mut cmd: Commands,
+    mut pending: SingletonMut<PendingLoad>,
+    mut player: SingletonMut<Player>,
+    mut progression: SingletonMut<Progression>,
+    mut paint_marks: SingletonMut<PaintMarks>,
+    query_rift: Query<(Entity, &RiftLevel)>,
+    query_gate: Query<(Entity, &KeyId), With<LevelGate>>,
+) {
+    let Some(save) = pending.0.take() else {
+        return;
+    };
+
+    log::info!("loading save from level {}", save.level);
+
+    player.rift_charges = save.rift_charges.iter().map(|&id| RiftLevel(id)).collect();
+    player.keys = save.keys.iter().map(|&id| KeyId(id)).collect();
+    player.hours = save.hours;
+    player.hours_target = save.hours;
+    player.respawn_position = Some(save.position);
+    *progression = save.progression();
+    paint_marks.load(save.paint_marks);
+
+    for (entity, rift) in query_rift.iter() {
+        cmd.entity(entity).set(RestoreRiftTask {
+            consumed: save.rift_charges.contains(&rift.0),
+        });
+    }
+    for (entity, key) in query_gate.iter() {
+        cmd.entity(entity).set(RestoreLevelGateTask {
+            lowered: save.open_gates.contains(&key.0),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("recola-save-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn save(play_time: f32) -> SaveGame {
+        SaveGame {
+            version: SAVE_SCHEMA_VERSION,
+            timestamp: play_time as u64,
+            level: "level_1".into(),
+            play_time,
+            position: Vec2::new(1., 2.),
+            hours: 12.,
+            rift_charges: vec![1],
+            keys: vec![1],
+            tutorials_seen: vec!["rift".into()],
+            paint_marks: vec![PaintMark {
+                level: "level_1".into(),
+                position: Vec3::new(1., 2., 1.5),
+                normal: Vec3::X,
+                color: 2,
+            }],
+            open_gates: vec![1],
+        }
+    }
+
+    #[test]
+    fn test_autosave_rotation() {
+        let slots = SaveSlots::new(test_dir("rotation"));
+        for i in 1..=4 {
+            slots.write_autosave(&save(i as f32)).unwrap();
+        }
+
+        let play_times: Vec<_> = (0..AUTOSAVE_SLOT_COUNT)
+            .map(|i| SaveGame::load(slots.autosave_path(i)).unwrap().play_time)
+            .collect();
+        assert_eq!(play_times, vec![4., 3., 2.]);
+        assert!(!slots.autosave_path(AUTOSAVE_SLOT_COUNT).exists());
+
+        slots.write_manual(0, &save(2.5)).unwrap();
+        let list = slots.list();
+        assert_eq!(list.len(), 4);
+        assert_eq!(list[0].kind, SaveSlotKind::Autosave);
+        assert_eq!(list[0].index, 0);
+        assert_eq!(list[3].kind, SaveSlotKind::Autosave);
+        assert_eq!(list[3].index, 2);
+
+        std::fs::remove_dir_all(&slots.dir).unwrap();
+    }
+
+    #[test]
+    fn test_autosave_survives_crash_during_write() {
+        let slots = SaveSlots::new(test_dir("crash"));
+        slots.write_autosave(&save(1.)).unwrap();
+
+        // crash while writing the next autosave leaves a truncated temporary file
+        let json = serde_json::to_string_pretty(&save(2.)).unwrap();
+        std::fs::write(
+            tmp_path(&slots.autosave_path(0)),
+            &json.as_bytes()[..json.len() / 2],
+        )
+        .unwrap();
+
+        assert_eq!(slots.newest(), Some(save(1.)));
+        assert_eq!(slots.list().len(), 1);
+
+        // the next autosave replaces the stale temporary file
+        slots.write_autosave(&save(3.)).unwrap();
+        assert_eq!(slots.newest(), Some(save(3.)));
+
+        std::fs::remove_dir_all(&slots.dir).unwrap();
+    }
+
+    #[test]
+    fn test_save_refuses_newer_version() {
+        let mut newer = save(1.);
+        newer.version = SAVE_SCHEMA_VERSION + 1;
+        let json = serde_json::to_string(&newer).unwrap();
+
+        let err = SaveGame::from_json(&json).unwrap_err();
+        assert!(err.to_string().contains("newer version"), "{err}");
+
+        let json = serde_json::to_string(&save(1.)).unwrap();
+        assert_eq!(SaveGame::from_json(&json).unwrap(), save(1.));
+    }
+
+    #[test]
+    fn test_new_game_keeps_tutorials_only() {
+        let new_game = SaveGame::new_game(vec!["move".into()]);
+        assert_eq!(new_game.position, PLAYER_SPAWN);
+        assert_eq!(new_game.hours, PLAYER_START_HOURS);
+        assert_eq!(new_game.play_time, 0.);
+        assert!(new_game.rift_charges.is_empty());
+        assert!(new_game.keys.is_empty());
+        assert!(new_game.open_gates.is_empty());
+        assert!(new_game.paint_marks.is_empty());
+        assert_eq!(new_game.tutorials_seen, vec!["move".to_owned()]);
+    }
+}
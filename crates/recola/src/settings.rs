@@ -0,0 +1,75 @@
+use atom::prelude::*;
+
+/// User facing game settings
+#[derive(Singleton, Debug, Clone)]
+pub struct GameSettings {
+    pub accessibility: AccessibilitySettings,
+    pub graphics: GraphicsSettings,
+
+    /// Shows tutorial tips to new players
+    pub tutorials: bool,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            accessibility: AccessibilitySettings::default(),
+            graphics: GraphicsSettings::default(),
+            tutorials: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AccessibilitySettings {
+    /// Uses a colorblind-safe palette for laser beams and beam target indicators
+    pub high_contrast: bool,
+
+    /// Scale factor for subtitle and prompt text
+    pub subtitle_scale: f32,
+
+    /// Converts interactions which require holding a mouse button into toggles
+    pub hold_to_toggle: bool,
+
+    /// Reduces screen shake and flashing effects
+    pub reduce_flashes: bool,
+
+    /// Disables the synchronized pulsing of linked props
+    pub disable_link_pulse: bool,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            high_contrast: false,
+            subtitle_scale: 1.0,
+            hold_to_toggle: false,
+            reduce_flashes: false,
+            disable_link_pulse: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphicsSettings {
+    /// Scales all LOD thresholds. Values above 1 keep detailed meshes for longer.
+    pub lod_quality_bias: f32,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            lod_quality_bias: 1.0,
+        }
+    }
+}
+
+/// Provides the [GameSettings] singleton
+pub struct SettingsMocca;
+
+impl Mocca for SettingsMocca {
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(GameSettings::default());
+        Self
+    }
+}
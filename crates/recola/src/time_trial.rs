@@ -0,0 +1,478 @@
+//! Time trials of completed levels
+//!
+//! Pressing F6 while aiming at an opened level gate restarts the level of that gate as a time
+//! trial and resets the puzzle of the level. The run ends when the player passes through the
+//! gate again. The path of the player is sampled at [GHOST_SAMPLE_RATE] and the fastest run of
+//! every level is stored. A translucent ghost replays the best run during later trials.
+
+use crate::{
+    level::*,
+    player::*,
+    props::{door::*, reset_lever::*},
+    save::*,
+};
+use atom::prelude::*;
+use candy::{
+    camera::*, input::*, material::*, prelude::DisableShadowCasting, prims::*, scene_tree::*,
+    time::*, utils::WindowDef,
+};
+use eyre::Result;
+use gems::lerp_angle;
+use glam::{Quat, Vec3, Vec3Swizzles};
+use magi::color::SRgbU8Color;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+/// Samples per second of recorded runs
+pub const GHOST_SAMPLE_RATE: f32 = 10.0;
+
+/// Tolerance for accumulated frame times when deciding if a sample is due
+const GHOST_SAMPLE_TOLERANCE: f32 = 1e-4;
+
+/// Distance from the gate at which a time trial finishes
+const TIME_TRIAL_FINISH_DISTANCE: f32 = 1.5;
+
+const TIME_TRIAL_START_DISTANCE: f32 = 5.0;
+
+/// Guards against finishing before the player was moved to the level start
+const TIME_TRIAL_MIN_DURATION: f32 = 1.0;
+
+const TIME_TRIAL_RECORDS_FILE: &str = "time_trials.json";
+
+const GHOST_COLOR: SRgbU8Color = SRgbU8Color::from_rgb(120, 200, 255);
+const GHOST_ALPHA: f32 = 0.35;
+const GHOST_SIZE: Vec3 = Vec3::new(0.5, 0.5, 1.8);
+
+/// Player pose at a point in time of a run
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GhostSample {
+    pub time: f32,
+    pub position: Vec3,
+    pub yaw: f32,
+}
+
+/// Recorded run through a level
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GhostRun {
+    pub level: String,
+    pub duration: f32,
+    pub samples: Vec<GhostSample>,
+}
+
+impl GhostRun {
+    /// Pose at the given time interpolated between samples
+    pub fn sample_at(&self, time: f32) -> Option<GhostSample> {
+        let first = self.samples.first()?;
+        let last = self.samples.last()?;
+        if time <= first.time {
+            return Some(*first);
+        }
+        if time >= last.time {
+            return Some(*last);
+        }
+
+        let i = self.samples.partition_point(|s| s.time <= time);
+        let (a, b) = (&self.samples[i - 1], &self.samples[i]);
+        let q = (time - a.time) / (b.time - a.time);
+
+        Some(GhostSample {
+            time,
+            position: a.position.lerp(b.position, q),
+            yaw: lerp_angle(a.yaw, b.yaw, q),
+        })
+    }
+}
+
+/// Samples the player pose at a fixed rate
+#[derive(Debug, Default)]
+pub struct GhostRecorder {
+    time: f32,
+    samples: Vec<GhostSample>,
+}
+
+impl GhostRecorder {
+    /// Advances time and stores a sample if due. The yaw follows the direction of movement.
+    pub fn record(&mut self, dt: f32, position: Vec3) {
+        if self.samples.is_empty() {
+            self.push(position);
+        }
+
+        self.time += dt;
+
+        let next = self.samples.last().map_or(0., |last| last.time) + 1. / GHOST_SAMPLE_RATE;
+        if self.time >= next - GHOST_SAMPLE_TOLERANCE {
+            self.push(position);
+        }
+    }
+
+    fn push(&mut self, position: Vec3) {
+        let yaw = match self.samples.last() {
+            Some(last) => {
+                let delta = (position - last.position).xy();
+                if delta.length_squared() > 1e-6 {
+                    delta.y.atan2(delta.x)
+                } else {
+                    last.yaw
+                }
+            }
+            None => 0.,
+        };
+        self.samples.push(GhostSample {
+            time: self.time,
+            position,
+            yaw,
+        });
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.time
+    }
+
+    pub fn finish(mut self, level: String, position: Vec3) -> GhostRun {
+        if self.samples.last().is_none_or(|last| last.time < self.time) {
+            self.push(position);
+        }
+        GhostRun {
+            level,
+            duration: self.time,
+            samples: self.samples,
+        }
+    }
+}
+
+/// Best runs by level
+#[derive(Singleton, Debug, Default, Serialize, Deserialize)]
+pub struct TimeTrialRecords {
+    pub best: BTreeMap<String, GhostRun>,
+}
+
+impl TimeTrialRecords {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_json_atomic(self, path)
+    }
+
+    /// Stores the run if it is the fastest for its level. Returns true if the run was stored.
+    pub fn submit(&mut self, run: GhostRun) -> bool {
+        match self.best.get(&run.level) {
+            Some(best) if best.duration <= run.duration => false,
+            _ => {
+                self.best.insert(run.level.clone(), run);
+                true
+            }
+        }
+    }
+}
+
+/// A time trial in progress
+pub struct TimeTrialRun {
+    pub level: String,
+    pub gate_entity: Entity,
+    pub recorder: GhostRecorder,
+    pub ghost: Option<(GhostRun, Entity)>,
+}
+
+#[derive(Singleton, Default)]
+pub struct TimeTrial {
+    pub run: Option<TimeTrialRun>,
+}
+
+impl TimeTrial {
+    /// Time shown on the timer
+    pub fn elapsed(&self) -> Option<f32> {
+        self.run.as_ref().map(|run| run.recorder.elapsed())
+    }
+}
+
+/// Receives the key which starts a time trial
+#[derive(Component, Default)]
+pub struct TimeTrialInput {
+    start: bool,
+}
+
+impl TimeTrialInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if let InputEvent::KeyboardInput {
+            state: ElementState::Pressed,
+            code: KeyCode::F6,
+            ..
+        } = msg.event
+        {
+            self.start = true;
+        }
+    }
+}
+
+impl atom::Agent for TimeTrialInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(TimeTrialInput::on_input_event);
+    }
+}
+
+/// Time trials with ghost playback of the best run
+pub struct TimeTrialMocca;
+
+impl Mocca for TimeTrialMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyCameraMocca>();
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandyMaterialMocca>();
+        deps.depends_on::<CandyPrimsMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<DoorMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<PuzzleResetMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<TimeTrialInput>();
+        atom::register_agent_components::<TimeTrialInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        let path = Path::new(SAVE_DIR).join(TIME_TRIAL_RECORDS_FILE);
+        let records = if path.exists() {
+            TimeTrialRecords::load(&path).unwrap_or_else(|err| {
+                log::warn!("failed to load time trial records: {err:?}");
+                TimeTrialRecords::default()
+            })
+        } else {
+            TimeTrialRecords::default()
+        };
+
+        world.set_singleton(records);
+        world.set_singleton(TimeTrial::default());
+        world.run(spawn_time_trial_input);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<TimeTrialInput, _>);
+        world.run(start_time_trial);
+        world.run(update_time_trial);
+    }
+}
+
+fn spawn_time_trial_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, TimeTrialInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn start_time_trial(
+    mut cmd: Commands,
+    levels: Singleton<LevelSummary>,
+    records: Singleton<TimeTrialRecords>,
+    mut player: SingletonMut<Player>,
+    mut trial: SingletonMut<TimeTrial>,
+    mut query_input: Query<&mut TimeTrialInput>,
+    query_input_raycast: Query<&InputRaycastController>,
+    query_gate: Query<(&LevelGate, &GlobalTransform3)>,
+    mut query_reset: Query<&mut PuzzleReset>,
+) {
+    let Some(input) = query_input.single_mut() else {
+        return;
+    };
+    if !std::mem::take(&mut input.start) || trial.run.is_some() {
+        return;
+    }
+
+    // Only opened gates of completed levels can start a trial
+    let Some((gate_entity, _)) = query_input_raycast
+        .single()
+        .and_then(|input_raycast| input_raycast.raycast_entity_and_distance())
+        .filter(|(_, distance)| *distance < TIME_TRIAL_START_DISTANCE)
+    else {
+        return;
+    };
+    let Some((gate, gate_tf)) = query_gate.get(gate_entity) else {
+        return;
+    };
+    if !gate.is_lowered() {
+        return;
+    }
+
+    let Some(level) = levels.nearest_level_name(gate_tf.translation()) else {
+        return;
+    };
+    let Some(level_index) = levels.names.iter().position(|name| name == level) else {
+        return;
+    };
+    let start = levels.pos[level_index];
+
+    log::info!("time trial started: {level}");
+
+    player.respawn_position = Some(start.xy());
+
+    // A solved puzzle would allow to finish the trial right away
+    for reset in query_reset.iter_mut() {
+        if reset.level_index == level_index {
+            reset.trigger();
+        }
+    }
+
+    let ghost = records.best.get(level).cloned().map(|run| {
+        let entity = cmd.spawn((
+            Name::from_str("time trial ghost"),
+            Transform3::identity().with_scale(GHOST_SIZE),
+            Cuboid,
+            Material::Unlit(UnlitMaterial::new(GHOST_COLOR.to_linear()).with_alpha(GHOST_ALPHA)),
+            DisableShadowCasting,
+            Visibility::Visible,
+            DynamicTransform,
+            HierarchyDirty,
+        ));
+        (run, entity)
+    });
+
+    trial.run = Some(TimeTrialRun {
+        level: level.to_owned(),
+        gate_entity,
+        recorder: GhostRecorder::default(),
+        ghost,
+    });
+}
+
+fn update_time_trial(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    player: Singleton<Player>,
+    mut records: SingletonMut<TimeTrialRecords>,
+    mut trial: SingletonMut<TimeTrial>,
+    query_gate: Query<&GlobalTransform3, With<LevelGate>>,
+    mut query_tf: Query<&mut Transform3>,
+) {
+    let Some(run) = trial.run.as_mut() else {
+        return;
+    };
+
+    // The ghost only moves a visual and never touches colliders or switches
+    let position = player.eye_position;
+    run.recorder.record(time.sim_dt_f32(), position);
+
+    if let Some((ghost_run, ghost_entity)) = &run.ghost {
+        if let (Some(sample), Some(tf)) = (
+            ghost_run.sample_at(run.recorder.elapsed()),
+            query_tf.get_mut(*ghost_entity),
+        ) {
+            tf.translation = sample.position.with_z(0.5 * GHOST_SIZE.z);
+            tf.rotation = Quat::from_rotation_z(sample.yaw);
+        }
+    }
+
+    let finished = run.recorder.elapsed() > TIME_TRIAL_MIN_DURATION
+        && query_gate.get(run.gate_entity).is_some_and(|tf| {
+            tf.translation().xy().distance(position.xy()) < TIME_TRIAL_FINISH_DISTANCE
+        });
+    if !finished {
+        return;
+    }
+
+    let run = trial.run.take().unwrap();
+    if let Some((_, ghost_entity)) = run.ghost {
+        cmd.despawn_recursive(ghost_entity);
+    }
+
+    let ghost_run = run.recorder.finish(run.level, position);
+    log::info!(
+        "time trial finished: {} in {:.2} s",
+        ghost_run.level,
+        ghost_run.duration
+    );
+
+    if records.submit(ghost_run) {
+        log::info!("new best time");
+        let path = Path::new(SAVE_DIR).join(TIME_TRIAL_RECORDS_FILE);
+        if let Err(err) = records.save(&path) {
+            log::error!("failed to save time trial records: {err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use std::f32::consts::{PI, TAU};
+
+    fn run(level: &str, duration: f32) -> GhostRun {
+        let mut recorder = GhostRecorder::default();
+        let steps = (duration * 60.).round() as usize;
+        for i in 0..steps {
+            recorder.record(1. / 60., Vec3::new(i as f32 / 60., 0., 1.7));
+        }
+        recorder.finish(level.into(), Vec3::new(duration, 0., 1.7))
+    }
+
+    #[test]
+    fn test_ghost_sampling_rate() {
+        let run = run("level_1", 2.0);
+        assert_abs_diff_eq!(run.duration, 2.0, epsilon = 1e-4);
+
+        // 10 Hz over two seconds plus the first and final sample
+        assert!(
+            (20..=22).contains(&run.samples.len()),
+            "{}",
+            run.samples.len()
+        );
+        for pair in run.samples.windows(2) {
+            assert!(pair[1].time > pair[0].time);
+            assert!(pair[1].time - pair[0].time <= 1. / GHOST_SAMPLE_RATE + 1. / 60. + 1e-4);
+        }
+        assert_abs_diff_eq!(run.samples[5].yaw, 0.);
+    }
+
+    #[test]
+    fn test_ghost_interpolation() {
+        let run = GhostRun {
+            level: "level_1".into(),
+            duration: 0.2,
+            samples: vec![
+                GhostSample {
+                    time: 0.,
+                    position: Vec3::ZERO,
+                    yaw: 3.0,
+                },
+                GhostSample {
+                    time: 0.1,
+                    position: Vec3::new(1., 2., 0.),
+                    yaw: -3.0,
+                },
+            ],
+        };
+
+        let mid = run.sample_at(0.05).unwrap();
+        assert_abs_diff_eq!(mid.position.x, 0.5, epsilon = 1e-5);
+        assert_abs_diff_eq!(mid.position.y, 1.0, epsilon = 1e-5);
+
+        // interpolates across the angle wrap instead of turning around
+        assert_abs_diff_eq!(mid.yaw.rem_euclid(TAU), PI, epsilon = 1e-3);
+
+        assert_eq!(run.sample_at(-1.).unwrap().position, Vec3::ZERO);
+        assert_eq!(run.sample_at(5.).unwrap().position, Vec3::new(1., 2., 0.));
+    }
+
+    #[test]
+    fn test_best_time_replacement_persists() {
+        let mut records = TimeTrialRecords::default();
+        assert!(records.submit(run("level_1", 3.0)));
+        assert!(!records.submit(run("level_1", 3.5)));
+        assert!(records.submit(run("level_2", 4.0)));
+        assert!(records.submit(run("level_1", 2.0)));
+
+        let path = std::env::temp_dir().join(format!("recola-trials-{}.json", std::process::id()));
+        records.save(&path).unwrap();
+        let loaded = TimeTrialRecords::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.best.len(), 2);
+        assert_abs_diff_eq!(loaded.best["level_1"].duration, 2.0, epsilon = 1e-4);
+        assert_eq!(loaded.best["level_1"], records.best["level_1"]);
+    }
+}
@@ -0,0 +1,399 @@
+//! One-shot tutorial tips
+//!
+//! Tips are declared at startup with a trigger condition over a [TutorialContext] which is
+//! gathered from the world every frame. Props do not need to know about tutorials. Every tip is
+//! shown at most once and consumed tips are stored in the [Progression] and thus in saves.
+//! The active tip is shown as a prompt on the [Hud].
+
+use crate::{
+    hud::*,
+    player::*,
+    props::{barrier::*, laser_pointer::LaserPointerAzimuth, rift::RiftLevel},
+    recola_mocca::RecolaAssetsMocca,
+    settings::*,
+    victory::*,
+};
+use atom::prelude::*;
+use candy::{can::*, input::*, scene_tree::*, time::*, utils::WindowDef};
+use std::collections::{HashMap, HashSet};
+
+/// Tips are dismissed automatically after this time
+pub const TUTORIAL_PROMPT_DURATION: f32 = 8.0;
+
+const TUTORIAL_BARRIER_DISTANCE: f32 = 3.0;
+const TUTORIAL_RIFT_DISTANCE: f32 = 12.0;
+
+/// State of the world relevant for tutorial triggers
+#[derive(Debug, Clone, Default)]
+pub struct TutorialContext {
+    /// Asset name of the prop the player aims at
+    pub aimed_asset: Option<String>,
+
+    /// Distance to the closest barrier which is on
+    pub active_barrier_distance: Option<f32>,
+
+    /// Distance to the closest rift
+    pub rift_distance: Option<f32>,
+}
+
+pub type TutorialTrigger = Box<dyn Fn(&TutorialContext) -> bool + Send + Sync>;
+
+pub struct TutorialTip {
+    pub id: &'static str,
+
+    /// Localization key of the prompt text
+    pub text_key: &'static str,
+
+    trigger: TutorialTrigger,
+}
+
+/// A tip currently shown to the player
+#[derive(Debug, Clone, PartialEq)]
+pub struct TutorialPrompt {
+    pub id: &'static str,
+    pub text_key: &'static str,
+    pub remaining: f32,
+}
+
+#[derive(Singleton, Default)]
+pub struct Tutorials {
+    tips: Vec<TutorialTip>,
+    active: Option<TutorialPrompt>,
+}
+
+impl Tutorials {
+    pub fn with_tip<F>(mut self, id: &'static str, text_key: &'static str, trigger: F) -> Self
+    where
+        F: Fn(&TutorialContext) -> bool + Send + Sync + 'static,
+    {
+        self.tips.push(TutorialTip {
+            id,
+            text_key,
+            trigger: Box::new(trigger),
+        });
+        self
+    }
+
+    /// The prompt to show to the player
+    pub fn active(&self) -> Option<&TutorialPrompt> {
+        self.active.as_ref()
+    }
+
+    /// Shows the first triggered tip which was not seen yet and marks it as seen. Does nothing
+    /// while a prompt is shown or if tutorials are disabled.
+    pub fn evaluate(
+        &mut self,
+        ctx: &TutorialContext,
+        enabled: bool,
+        seen: &mut HashSet<String>,
+    ) -> Option<&TutorialPrompt> {
+        if !enabled || self.active.is_some() {
+            return None;
+        }
+
+        let tip = self
+            .tips
+            .iter()
+            .find(|tip| !seen.contains(tip.id) && (tip.trigger)(ctx))?;
+
+        seen.insert(tip.id.to_owned());
+        self.active = Some(TutorialPrompt {
+            id: tip.id,
+            text_key: tip.text_key,
+            remaining: TUTORIAL_PROMPT_DURATION,
+        });
+        self.active.as_ref()
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        if let Some(prompt) = self.active.as_mut() {
+            prompt.remaining -= dt;
+            if prompt.remaining <= 0. {
+                self.active = None;
+            }
+        }
+    }
+
+    pub fn dismiss(&mut self) {
+        self.active = None;
+    }
+}
+
+/// Tips of the game
+pub fn default_tutorials() -> Tutorials {
+    Tutorials::default()
+        .with_tip("laser_pointer", "tutorial.laser_pointer", |ctx| {
+            ctx.aimed_asset.as_deref() == Some("prop-laser")
+        })
+        .with_tip("barrier", "tutorial.barrier", |ctx| {
+            ctx.active_barrier_distance
+                .is_some_and(|d| d < TUTORIAL_BARRIER_DISTANCE)
+        })
+        .with_tip("rift", "tutorial.rift", |ctx| {
+            ctx.rift_distance
+                .is_some_and(|d| d < TUTORIAL_RIFT_DISTANCE)
+        })
+}
+
+/// Prompt texts by localization key
+#[derive(Singleton, Default)]
+pub struct TutorialTexts(HashMap<String, String>);
+
+impl TutorialTexts {
+    /// Text for the key or the key itself if there is no text
+    pub fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.0.get(key).map_or(key, String::as_str)
+    }
+}
+
+/// Receives the key used to dismiss a tutorial prompt
+#[derive(Component, Default)]
+pub struct TutorialInput {
+    dismiss: bool,
+}
+
+impl TutorialInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if let InputEvent::KeyboardInput {
+            state: ElementState::Pressed,
+            code: KeyCode::Tab,
+            ..
+        } = msg.event
+        {
+            self.dismiss = true;
+        }
+    }
+}
+
+impl atom::Agent for TutorialInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(TutorialInput::on_input_event);
+    }
+}
+
+/// Contextual tips for new players
+pub struct TutorialMocca;
+
+impl Mocca for TutorialMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<BarrierMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<HudMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<RecolaAssetsMocca>();
+        deps.depends_on::<SettingsMocca>();
+        deps.depends_on::<VictoryMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<TutorialInput>();
+        atom::register_agent_components::<TutorialInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(default_tutorials());
+        world.run(load_tutorial_texts);
+        world.run(spawn_tutorial_input);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<TutorialInput, _>);
+        world.run(trigger_tutorials);
+    }
+}
+
+fn load_tutorial_texts(mut cmd: Commands, asset_resolver: Singleton<SharedAssetResolver>) {
+    let texts = match asset_resolver
+        .resolve("text/tutorials.json")
+        .and_then(|path| asset_resolver.parse::<HashMap<String, String>>(&path))
+    {
+        Ok(texts) => texts,
+        Err(err) => {
+            log::warn!("failed to load tutorial texts: {err:?}");
+            HashMap::new()
+        }
+    };
+
+    cmd.set_singleton(TutorialTexts(texts));
+}
+
+fn spawn_tutorial_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, TutorialInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn trigger_tutorials(
+    time: Singleton<SimClock>,
+    settings: Singleton<GameSettings>,
+    player: Singleton<Player>,
+    texts: Singleton<TutorialTexts>,
+    mut progression: SingletonMut<Progression>,
+    mut tutorials: SingletonMut<Tutorials>,
+    mut hud: SingletonMut<Hud>,
+    mut query_input: Query<&mut TutorialInput>,
+    query_input_raycast: Query<&InputRaycastController>,
+    query_asset: Query<&AssetInstance>,
+    query_lpa: Query<&LaserPointerAzimuth>,
+    query_barrier: Query<(&Barrier, &GlobalTransform3)>,
+    query_rift: Query<&GlobalTransform3, With<RiftLevel>>,
+) {
+    let was_active = tutorials.active().is_some();
+
+    let dismiss = query_input
+        .single_mut()
+        .is_some_and(|input| std::mem::take(&mut input.dismiss));
+    if dismiss {
+        tutorials.dismiss();
+    }
+
+    tutorials.step(time.sim_dt_f32());
+
+    let aimed_asset = query_input_raycast
+        .single()
+        .and_then(|input_raycast| input_raycast.raycast_entity_and_distance())
+        .and_then(|(entity, _)| aimed_asset_name(entity, &query_asset, &query_lpa));
+
+    let active_barrier_distance = query_barrier
+        .iter()
+        .filter(|(barrier, _)| barrier.is_on())
+        .map(|(_, tf)| tf.translation().distance(player.eye_position))
+        .min_by(f32::total_cmp);
+
+    let rift_distance = query_rift
+        .iter()
+        .map(|tf| tf.translation().distance(player.eye_position))
+        .min_by(f32::total_cmp);
+
+    let ctx = TutorialContext {
+        aimed_asset,
+        active_barrier_distance,
+        rift_distance,
+    };
+
+    if let Some(prompt) =
+        tutorials.evaluate(&ctx, settings.tutorials, &mut progression.tutorials_seen)
+    {
+        log::info!("tutorial: {}", prompt.text_key);
+    }
+
+    match tutorials.active() {
+        Some(prompt) => {
+            let text = texts.get(prompt.text_key);
+            if hud.prompt.as_deref() != Some(text) {
+                hud.prompt = Some(text.to_owned());
+            }
+        }
+        None if was_active => hud.prompt = None,
+        None => {}
+    }
+}
+
+/// Asset name of the prop hit by the interaction raycast
+fn aimed_asset_name(
+    entity: Entity,
+    query_asset: &Query<&AssetInstance>,
+    query_lpa: &Query<&LaserPointerAzimuth>,
+) -> Option<String> {
+    if let Some(ainst) = query_asset.get(entity) {
+        return Some(ainst.as_str().to_owned());
+    }
+
+    // Laser pointers are hit on their pointer child which is not an asset instance
+    query_lpa.get(entity).map(|_| "prop-laser".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::save::*;
+
+    fn near_rift() -> TutorialContext {
+        TutorialContext {
+            rift_distance: Some(5.0),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_tutorial_one_shot_across_save_load() {
+        let mut tutorials = default_tutorials();
+        let mut seen = HashSet::new();
+
+        let prompt = tutorials.evaluate(&near_rift(), true, &mut seen).cloned();
+        assert_eq!(prompt.map(|p| p.id), Some("rift"));
+        tutorials.dismiss();
+
+        assert_eq!(tutorials.evaluate(&near_rift(), true, &mut seen), None);
+
+        // seen tips are restored from a save
+        let progression = Progression {
+            tutorials_seen: seen,
+            ..Default::default()
+        };
+        let dir = std::env::temp_dir().join(format!("recola-tutorial-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let slots = SaveSlots::new(&dir);
+        slots
+            .write_manual(0, &SaveGame::new_game(progression.sorted_tutorials_seen()))
+            .unwrap();
+        let mut restored = slots.newest().unwrap().progression().tutorials_seen;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut tutorials = default_tutorials();
+        assert_eq!(tutorials.evaluate(&near_rift(), true, &mut restored), None);
+
+        // other tips still trigger
+        let ctx = TutorialContext {
+            aimed_asset: Some("prop-laser".into()),
+            ..near_rift()
+        };
+        let prompt = tutorials.evaluate(&ctx, true, &mut restored).cloned();
+        assert_eq!(prompt.map(|p| p.id), Some("laser_pointer"));
+    }
+
+    #[test]
+    fn test_tutorial_prompt_expires() {
+        let mut tutorials = default_tutorials();
+        let mut seen = HashSet::new();
+        tutorials.evaluate(&near_rift(), true, &mut seen);
+
+        tutorials.step(TUTORIAL_PROMPT_DURATION - 0.5);
+        assert!(tutorials.active().is_some());
+        tutorials.step(1.0);
+        assert_eq!(tutorials.active(), None);
+    }
+
+    #[test]
+    fn test_tutorial_texts_cover_all_tips() {
+        let texts = TutorialTexts(
+            serde_json::from_str(include_str!("../../../assets/recola/text/tutorials.json"))
+                .unwrap(),
+        );
+        for tip in default_tutorials().tips {
+            assert_ne!(texts.get(tip.text_key), tip.text_key);
+        }
+
+        assert_eq!(
+            TutorialTexts::default().get("tutorial.rift"),
+            "tutorial.rift"
+        );
+    }
+
+    #[test]
+    fn test_tutorial_disabled() {
+        let mut tutorials = default_tutorials();
+        let mut seen = HashSet::new();
+
+        assert_eq!(tutorials.evaluate(&near_rift(), false, &mut seen), None);
+        assert!(seen.is_empty());
+
+        // tips suppressed while disabled still show once enabled
+        assert!(tutorials.evaluate(&near_rift(), true, &mut seen).is_some());
+    }
+}
@@ -0,0 +1,532 @@
+//! Ending of the game
+//!
+//! Passing through the final level gate starts the victory sequence: a stinger, a camera
+//! pullback and the credits. Afterwards the player chooses to start over or to quit.
+
+use crate::{
+    camera_bookmarks::{CameraSpline, CameraSplineKey},
+    hud::*,
+    player::*,
+    props::{door::*, rift::RiftLevel},
+    recola_mocca::RecolaAssetsMocca,
+};
+use atom::prelude::*;
+use candy::{audio::*, camera::*, can::*, input::*, scene_tree::*, time::*, utils::WindowDef};
+use glam::{Vec2, Vec3Swizzles};
+use std::collections::HashSet;
+
+const VICTORY_STINGER_DURATION: f32 = 3.0;
+const VICTORY_PULLBACK_DURATION: f32 = 4.0;
+
+/// Distance the camera moves back from the final gate during the pullback
+const VICTORY_PULLBACK_DISTANCE: f32 = 8.0;
+
+/// Credits can be skipped after this time since the start of the sequence
+pub const VICTORY_SKIP_AFTER: f32 = 5.0;
+
+/// Default credits scroll speed in lines per second
+pub const CREDITS_DEFAULT_SPEED: f32 = 1.5;
+
+/// Distance from the final gate at which the player counts as passing through
+const FINAL_GATE_PASS_DISTANCE: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VictoryChoice {
+    Restart,
+    Quit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VictoryPhase {
+    Playing,
+    Stinger,
+    Pullback,
+    Credits,
+    Choice,
+    Finished(VictoryChoice),
+}
+
+/// State machine of the victory sequence
+#[derive(Singleton, Debug, Clone)]
+pub struct VictorySequence {
+    phase: VictoryPhase,
+
+    /// Time spent in the current phase
+    phase_time: f32,
+
+    /// Time since the sequence started
+    total_time: f32,
+
+    /// Credits scroll speed in lines per second
+    pub credits_speed: f32,
+
+    /// Number of lines in the credits
+    pub credits_lines: usize,
+}
+
+impl VictorySequence {
+    pub fn new(credits_lines: usize) -> Self {
+        Self {
+            phase: VictoryPhase::Playing,
+            phase_time: 0.,
+            total_time: 0.,
+            credits_speed: CREDITS_DEFAULT_SPEED,
+            credits_lines,
+        }
+    }
+
+    pub fn phase(&self) -> VictoryPhase {
+        self.phase
+    }
+
+    /// Time spent in the current phase
+    pub fn phase_time(&self) -> f32 {
+        self.phase_time
+    }
+
+    /// Player input is locked while the sequence plays
+    pub fn is_input_locked(&self) -> bool {
+        matches!(
+            self.phase,
+            VictoryPhase::Stinger | VictoryPhase::Pullback | VictoryPhase::Credits
+        )
+    }
+
+    /// Scroll offset of the credits in lines
+    pub fn credits_scroll(&self) -> f32 {
+        match self.phase {
+            VictoryPhase::Credits => self.phase_time * self.credits_speed,
+            _ => 0.,
+        }
+    }
+
+    pub fn start(&mut self) {
+        if self.phase == VictoryPhase::Playing {
+            self.set_phase(VictoryPhase::Stinger);
+            self.total_time = 0.;
+        }
+    }
+
+    pub fn step(&mut self, dt: f32) {
+        if matches!(
+            self.phase,
+            VictoryPhase::Playing | VictoryPhase::Choice | VictoryPhase::Finished(_)
+        ) {
+            return;
+        }
+
+        self.phase_time += dt;
+        self.total_time += dt;
+
+        match self.phase {
+            VictoryPhase::Stinger if self.phase_time >= VICTORY_STINGER_DURATION => {
+                self.set_phase(VictoryPhase::Pullback)
+            }
+            VictoryPhase::Pullback if self.phase_time >= VICTORY_PULLBACK_DURATION => {
+                self.set_phase(VictoryPhase::Credits)
+            }
+            VictoryPhase::Credits
+                if self.credits_scroll() >= self.credits_lines as f32 + HUD_LINES_PER_SCREEN =>
+            {
+                self.set_phase(VictoryPhase::Choice)
+            }
+            _ => {}
+        }
+    }
+
+    /// Skips to the final choice. Returns false if skipping is not allowed yet.
+    pub fn skip(&mut self) -> bool {
+        if !self.is_input_locked() || self.total_time < VICTORY_SKIP_AFTER {
+            return false;
+        }
+        self.set_phase(VictoryPhase::Choice);
+        true
+    }
+
+    pub fn choose(&mut self, choice: VictoryChoice) {
+        if self.phase == VictoryPhase::Choice {
+            self.set_phase(VictoryPhase::Finished(choice));
+        }
+    }
+
+    fn set_phase(&mut self, phase: VictoryPhase) {
+        log::debug!("victory phase: {phase:?}");
+        self.phase = phase;
+        self.phase_time = 0.;
+    }
+}
+
+/// Overall progress of the player
+#[derive(Singleton, Default, Debug, Clone)]
+pub struct Progression {
+    pub completed: bool,
+
+    /// Play time in seconds
+    pub total_time: f32,
+
+    /// Fraction of charged rifts at completion
+    pub collectible_fraction: f32,
+
+    /// Tutorial tips which were already shown
+    pub tutorials_seen: HashSet<String>,
+}
+
+impl Progression {
+    /// Tutorial tips which were already shown in a stable order
+    pub fn sorted_tutorials_seen(&self) -> Vec<String> {
+        let mut tutorials_seen: Vec<_> = self.tutorials_seen.iter().cloned().collect();
+        tutorials_seen.sort();
+        tutorials_seen
+    }
+}
+
+/// Receives keys used to skip the credits and to choose how to continue
+#[derive(Component, Default)]
+pub struct VictoryInput {
+    skip: bool,
+    choice: Option<VictoryChoice>,
+}
+
+impl VictoryInput {
+    pub fn on_input_event(&mut self, msg: InputEventMessage) {
+        if let InputEvent::KeyboardInput {
+            state: ElementState::Pressed,
+            code,
+            ..
+        } = msg.event
+        {
+            match code {
+                KeyCode::Space | KeyCode::Enter => self.skip = true,
+                KeyCode::KeyR => self.choice = Some(VictoryChoice::Restart),
+                KeyCode::KeyQ | KeyCode::Escape => self.choice = Some(VictoryChoice::Quit),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl atom::Agent for VictoryInput {
+    fn setup_message_handlers(handler: &mut atom::MessageHandler<Self>) {
+        handler.add(VictoryInput::on_input_event);
+    }
+}
+
+/// Ending sequence of the game
+pub struct VictoryMocca;
+
+impl Mocca for VictoryMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyAudioMocca>();
+        deps.depends_on::<CandyCameraMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandyInputMocca>();
+        deps.depends_on::<CandySceneTreeMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<DoorMocca>();
+        deps.depends_on::<HudMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<RecolaAssetsMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<VictoryInput>();
+        atom::register_agent_components::<VictoryInput, _>(world);
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.run(load_credits);
+        world.set_singleton(Progression::default());
+        world.set_singleton(VictoryPullback::default());
+        world.run(spawn_victory_input);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(atom::tick_agents::<VictoryInput, _>);
+        world.run(track_play_time);
+        world.run(detect_final_gate_pass);
+        world.run(advance_victory_sequence);
+        world.run(present_victory_sequence);
+    }
+}
+
+/// Credits text
+#[derive(Singleton)]
+pub struct Credits {
+    pub lines: Vec<String>,
+}
+
+/// Camera path of the pullback from the final gate
+#[derive(Singleton, Default)]
+struct VictoryPullback(Option<CameraSpline>);
+
+fn load_credits(mut cmd: Commands, asset_resolver: Singleton<SharedAssetResolver>) {
+    let lines = match asset_resolver
+        .resolve("text/credits.json")
+        .and_then(|path| asset_resolver.parse::<Vec<String>>(&path))
+    {
+        Ok(lines) => lines,
+        Err(err) => {
+            log::warn!("failed to load credits: {err:?}");
+            vec!["RECOLA".to_owned()]
+        }
+    };
+
+    cmd.set_singleton(VictorySequence::new(lines.len()));
+    cmd.set_singleton(Credits { lines });
+}
+
+fn spawn_victory_input(mut cmd: Commands, query_window: Query<Entity, With<WindowDef>>) {
+    let agent = spawn_agent(&mut cmd, VictoryInput::default());
+    for win in query_window.iter() {
+        add_route::<InputEventMessage, _>(&mut cmd, win, agent);
+    }
+}
+
+fn track_play_time(
+    time: Singleton<SimClock>,
+    victory: Singleton<VictorySequence>,
+    mut progression: SingletonMut<Progression>,
+) {
+    if victory.phase() == VictoryPhase::Playing {
+        progression.total_time += time.sim_dt_f32();
+    }
+}
+
+fn detect_final_gate_pass(
+    player: Singleton<Player>,
+    mut victory: SingletonMut<VictorySequence>,
+    mut progression: SingletonMut<Progression>,
+    query_gate: Query<(&GlobalTransform3, &LevelGate), With<FinalGate>>,
+    query_rift: Query<&RiftLevel>,
+) {
+    if victory.phase() != VictoryPhase::Playing {
+        return;
+    }
+
+    let passed = query_gate.iter().any(|(tf, gate)| {
+        gate.is_lowered()
+            && tf.translation().xy().distance(player.eye_position.xy()) < FINAL_GATE_PASS_DISTANCE
+    });
+    if !passed {
+        return;
+    }
+
+    log::info!("final gate passed");
+
+    let rifts = query_rift.iter().map(|lvl| lvl.0).collect::<HashSet<_>>();
+    progression.completed = true;
+    progression.collectible_fraction = if rifts.is_empty() {
+        1.
+    } else {
+        player.rift_charges.len() as f32 / rifts.len() as f32
+    };
+
+    victory.start();
+}
+
+fn advance_victory_sequence(
+    mut cmd: Commands,
+    time: Singleton<SimClock>,
+    asset_resolver: Singleton<SharedAssetResolver>,
+    credits: Singleton<Credits>,
+    mut player: SingletonMut<Player>,
+    mut victory: SingletonMut<VictorySequence>,
+    mut pullback: SingletonMut<VictoryPullback>,
+    mut hud: SingletonMut<Hud>,
+    mut query_input: Query<&mut VictoryInput>,
+    query_cam: Query<&CameraMatrices, With<MainCamera>>,
+    query_cam_ctrl: Query<&FirstPersonCameraController>,
+    query_window: Query<Entity, With<WindowDef>>,
+) {
+    let Some(input) = query_input.single_mut() else {
+        return;
+    };
+    let skip = std::mem::take(&mut input.skip);
+    let choice = input.choice.take();
+
+    let previous = victory.phase();
+
+    if skip {
+        victory.skip();
+    }
+    if let Some(choice) = choice {
+        victory.choose(choice);
+    }
+    victory.step(time.sim_dt_f32());
+
+    player.input_locked = victory.is_input_locked();
+
+    if previous == victory.phase() {
+        return;
+    }
+
+    // Leaving a phase removes its presentation
+    match previous {
+        VictoryPhase::Pullback => pullback.0 = None,
+        VictoryPhase::Credits => hud.scroll = None,
+        VictoryPhase::Choice => hud.prompt = None,
+        _ => {}
+    }
+
+    match victory.phase() {
+        VictoryPhase::Stinger => {
+            if let Ok(path) = asset_resolver.resolve("audio/music/victory-stinger.wav") {
+                cmd.spawn((
+                    Name::from_str("victory stinger"),
+                    AudioSource::new(path).with_repeat(AudioRepeatKind::OneShot),
+                    GlobalAudioEmitter,
+                ));
+            }
+        }
+        VictoryPhase::Pullback => {
+            let Some(cam_ctrl) = query_cam_ctrl.single() else {
+                return;
+            };
+            let position = cam_ctrl.position().xy();
+            let yaw = query_cam.single().map_or(0., |cam| {
+                let dir = cam.center_pixel_ray().direction();
+                dir.y.atan2(dir.x)
+            });
+            pullback.0 = Some(pullback_spline(position, yaw));
+        }
+        VictoryPhase::Credits => {
+            hud.scroll = Some(HudScroll {
+                lines: credits.lines.clone(),
+                offset: 0.,
+            });
+        }
+        VictoryPhase::Choice => {
+            hud.prompt = Some("R: start over    Q: quit".to_owned());
+        }
+        VictoryPhase::Finished(VictoryChoice::Restart) => {
+            // The save mocca loads a new game and restarts the sequence
+        }
+        VictoryPhase::Finished(VictoryChoice::Quit) => {
+            // Closing the window ends the app like any other shutdown
+            log::info!("quit by player");
+            for window in query_window.iter() {
+                cmd.despawn_recursive(window);
+            }
+        }
+        VictoryPhase::Playing => {}
+    }
+}
+
+/// Camera path which moves back from the current pose while looking at the final gate
+fn pullback_spline(position: Vec2, yaw: f32) -> CameraSpline {
+    let back = -Vec2::from_angle(yaw) * VICTORY_PULLBACK_DISTANCE;
+    CameraSpline {
+        keys: vec![
+            CameraSplineKey {
+                time: 0.,
+                position,
+                yaw,
+            },
+            CameraSplineKey {
+                time: VICTORY_PULLBACK_DURATION,
+                position: position + back,
+                yaw,
+            },
+        ],
+    }
+}
+
+/// Moves the camera along the pullback and scrolls the credits
+fn present_victory_sequence(
+    victory: Singleton<VictorySequence>,
+    pullback: Singleton<VictoryPullback>,
+    mut player: SingletonMut<Player>,
+    mut hud: SingletonMut<Hud>,
+    mut query_cam_ctrl: Query<&mut FirstPersonCameraController>,
+) {
+    let pose = pullback
+        .0
+        .as_ref()
+        .and_then(|spline| spline.pose(victory.phase_time()));
+    if let (Some((position, yaw)), Some(cam_ctrl)) = (pose, query_cam_ctrl.single_mut()) {
+        cam_ctrl.set_position_xy(position);
+        cam_ctrl.set_yaw(yaw);
+
+        // Teleport without collision checks on the way
+        player.previous_position = position;
+    }
+
+    if let Some(scroll) = hud.scroll.as_mut() {
+        scroll.offset = victory.credits_scroll();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(seq: &mut VictorySequence, duration: f32) {
+        for _ in 0..(duration / 0.1).round() as usize {
+            seq.step(0.1);
+        }
+    }
+
+    #[test]
+    fn test_victory_sequence_transitions() {
+        let mut seq = VictorySequence::new(30);
+        assert_eq!(seq.phase(), VictoryPhase::Playing);
+        assert!(!seq.is_input_locked());
+
+        run(&mut seq, 10.);
+        assert_eq!(seq.phase(), VictoryPhase::Playing);
+
+        seq.start();
+        assert_eq!(seq.phase(), VictoryPhase::Stinger);
+        assert!(seq.is_input_locked());
+
+        run(&mut seq, VICTORY_STINGER_DURATION + 0.05);
+        assert_eq!(seq.phase(), VictoryPhase::Pullback);
+
+        run(&mut seq, VICTORY_PULLBACK_DURATION + 0.05);
+        assert_eq!(seq.phase(), VictoryPhase::Credits);
+
+        // choices are ignored while credits roll
+        seq.choose(VictoryChoice::Quit);
+        assert_eq!(seq.phase(), VictoryPhase::Credits);
+
+        // credits end once the last line scrolled off the screen
+        run(&mut seq, 30. / CREDITS_DEFAULT_SPEED);
+        assert_eq!(seq.phase(), VictoryPhase::Credits);
+        run(&mut seq, HUD_LINES_PER_SCREEN / CREDITS_DEFAULT_SPEED + 0.1);
+        assert_eq!(seq.phase(), VictoryPhase::Choice);
+        assert!(!seq.is_input_locked());
+
+        seq.choose(VictoryChoice::Restart);
+        assert_eq!(seq.phase(), VictoryPhase::Finished(VictoryChoice::Restart));
+    }
+
+    #[test]
+    fn test_victory_pullback_moves_away_from_gate() {
+        let spline = pullback_spline(Vec2::new(2., 3.), 0.);
+        assert_eq!(spline.duration(), VICTORY_PULLBACK_DURATION);
+        assert_eq!(spline.pose(0.), Some((Vec2::new(2., 3.), 0.)));
+        assert_eq!(
+            spline.pose(VICTORY_PULLBACK_DURATION),
+            Some((Vec2::new(2. - VICTORY_PULLBACK_DISTANCE, 3.), 0.))
+        );
+    }
+
+    #[test]
+    fn test_victory_skip_releases_input_lock() {
+        let mut seq = VictorySequence::new(100);
+        seq.start();
+
+        run(&mut seq, 2.);
+        assert!(!seq.skip());
+        assert!(seq.is_input_locked());
+
+        run(&mut seq, VICTORY_SKIP_AFTER);
+        assert!(seq.skip());
+        assert_eq!(seq.phase(), VictoryPhase::Choice);
+        assert!(!seq.is_input_locked());
+
+        seq.choose(VictoryChoice::Quit);
+        assert_eq!(seq.phase(), VictoryPhase::Finished(VictoryChoice::Quit));
+    }
+}
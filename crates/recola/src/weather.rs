@@ -0,0 +1,503 @@
+//! Weather layered on top of the day-night cycle
+//!
+//! Each level has a default [WeatherKind] set by the `weather` custom property of the level
+//! instance. The default of the level the player is in can be overridden by scripted weather,
+//! e.g. charging the final rift triggers a storm. Changing the weather blends the sky parameters
+//! and the ambient audio bed over the transition duration of the new weather.
+
+use crate::{
+    custom_properties::*, level::*, player::*, props::rift::RiftLevel,
+    recola_mocca::RecolaAssetsMocca, settings::*,
+};
+use atom::prelude::*;
+use candy::{audio::*, can::*, scene_tree::*, sky::*, time::*};
+
+/// Raw sun radiance under a clear sky
+const SUN_RAW_RADIANCE: f32 = 15.0;
+
+/// Raw moon radiance under a clear sky
+const MOON_RAW_RADIANCE: f32 = 0.35;
+
+/// Time between two lightning strikes during a storm
+pub const LIGHTNING_PERIOD: f32 = 7.0;
+
+/// Duration of a single lightning flash
+pub const LIGHTNING_FLASH_DURATION: f32 = 0.15;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum WeatherKind {
+    #[default]
+    Clear,
+    Overcast,
+    Fog,
+    Storm,
+}
+
+impl WeatherKind {
+    pub const ALL: [WeatherKind; 4] = [
+        WeatherKind::Clear,
+        WeatherKind::Overcast,
+        WeatherKind::Fog,
+        WeatherKind::Storm,
+    ];
+
+    /// Reads the `weather` custom property: one of `clear`, `overcast`, `fog` or `storm`
+    pub fn from_properties(props: &CustomProperties) -> Option<Self> {
+        match props.get_string("weather")? {
+            "clear" => Some(WeatherKind::Clear),
+            "overcast" => Some(WeatherKind::Overcast),
+            "fog" => Some(WeatherKind::Fog),
+            "storm" => Some(WeatherKind::Storm),
+            other => {
+                log::warn!("unknown weather '{other}'");
+                None
+            }
+        }
+    }
+
+    /// Target sky parameters of this weather
+    pub fn params(self) -> WeatherParams {
+        match self {
+            WeatherKind::Clear => WeatherParams {
+                sun_radiance_scale: 1.00,
+                moon_radiance_scale: 1.00,
+                ambient_volume: 0.00,
+            },
+            WeatherKind::Overcast => WeatherParams {
+                sun_radiance_scale: 0.45,
+                moon_radiance_scale: 0.40,
+                ambient_volume: 0.25,
+            },
+            WeatherKind::Fog => WeatherParams {
+                sun_radiance_scale: 0.30,
+                moon_radiance_scale: 0.20,
+                ambient_volume: 0.15,
+            },
+            WeatherKind::Storm => WeatherParams {
+                sun_radiance_scale: 0.12,
+                moon_radiance_scale: 0.05,
+                ambient_volume: 0.80,
+            },
+        }
+    }
+
+    /// Time in seconds to blend into this weather
+    pub fn transition_duration(self) -> f32 {
+        match self {
+            WeatherKind::Clear => 20.0,
+            WeatherKind::Overcast => 15.0,
+            WeatherKind::Fog => 12.0,
+            WeatherKind::Storm => 6.0,
+        }
+    }
+
+    /// Looping ambient audio played with this weather
+    pub fn ambient_audio(self) -> Option<&'static str> {
+        match self {
+            WeatherKind::Clear => None,
+            WeatherKind::Overcast | WeatherKind::Fog => Some("audio/ambient/wind.wav"),
+            WeatherKind::Storm => Some("audio/ambient/storm.wav"),
+        }
+    }
+}
+
+/// Sky parameters controlled by the weather
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WeatherParams {
+    pub sun_radiance_scale: f32,
+    pub moon_radiance_scale: f32,
+    pub ambient_volume: f32,
+}
+
+impl WeatherParams {
+    /// Linear blend between `a` and `b`; `q` is clamped to [0, 1].
+    pub fn lerp(a: &WeatherParams, b: &WeatherParams, q: f32) -> WeatherParams {
+        let q = q.clamp(0., 1.);
+        let mix = |u: f32, v: f32| u + (v - u) * q;
+        WeatherParams {
+            sun_radiance_scale: mix(a.sun_radiance_scale, b.sun_radiance_scale),
+            moon_radiance_scale: mix(a.moon_radiance_scale, b.moon_radiance_scale),
+            ambient_volume: mix(a.ambient_volume, b.ambient_volume),
+        }
+    }
+}
+
+/// Current weather and transition state
+#[derive(Singleton, Debug, Clone)]
+pub struct Weather {
+    level_default: WeatherKind,
+    scripted: Option<WeatherKind>,
+
+    /// Weather currently blended towards
+    kind: WeatherKind,
+
+    /// Weather blended away from
+    previous: WeatherKind,
+
+    /// Parameters at the start of the current transition
+    source: WeatherParams,
+
+    /// Time spent in the current transition
+    elapsed: f32,
+
+    params: WeatherParams,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self::new(WeatherKind::default())
+    }
+}
+
+impl Weather {
+    pub fn new(kind: WeatherKind) -> Self {
+        let params = kind.params();
+        Self {
+            level_default: kind,
+            scripted: None,
+            kind,
+            previous: kind,
+            source: params,
+            elapsed: kind.transition_duration(),
+            params,
+        }
+    }
+
+    /// Weather the sky currently blends towards
+    pub fn kind(&self) -> WeatherKind {
+        self.kind
+    }
+
+    /// Current blended parameters
+    pub fn params(&self) -> &WeatherParams {
+        &self.params
+    }
+
+    /// Weather which should be active: scripted weather overrides the level default
+    pub fn target(&self) -> WeatherKind {
+        self.scripted.unwrap_or(self.level_default)
+    }
+
+    pub fn set_level_default(&mut self, kind: WeatherKind) {
+        self.level_default = kind;
+    }
+
+    /// Overrides the level default until cleared with `None`
+    pub fn set_scripted(&mut self, kind: Option<WeatherKind>) {
+        self.scripted = kind;
+    }
+
+    /// Progress of the current transition in [0, 1]
+    pub fn transition_progress(&self) -> f32 {
+        (self.elapsed / self.kind.transition_duration()).min(1.)
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition_progress() < 1.
+    }
+
+    /// True while a lightning flash is visible. Flashes only happen once the storm fully arrived.
+    pub fn is_lightning_flash(&self) -> bool {
+        self.time_since_lightning()
+            .is_some_and(|t| t < LIGHTNING_FLASH_DURATION)
+    }
+
+    /// Time until the next lightning flash, or `None` if there is no storm
+    pub fn time_to_next_lightning(&self) -> Option<f32> {
+        self.time_since_lightning()
+            .map(|t| if t == 0. { 0. } else { LIGHTNING_PERIOD - t })
+    }
+
+    fn time_since_lightning(&self) -> Option<f32> {
+        if self.kind != WeatherKind::Storm || self.is_transitioning() {
+            return None;
+        }
+        let storm_time = self.elapsed - self.kind.transition_duration();
+        Some(storm_time.rem_euclid(LIGHTNING_PERIOD))
+    }
+
+    /// Volume of the ambient loop of the given weather. The loop of the previous weather fades
+    /// out while the loop of the current weather fades in.
+    pub fn ambient_volume(&self, kind: WeatherKind) -> f32 {
+        let q = self.transition_progress();
+        let mut volume = 0.;
+        if kind == self.kind {
+            volume += q * self.kind.params().ambient_volume;
+        }
+        if kind == self.previous {
+            volume += (1. - q) * self.source.ambient_volume;
+        }
+        volume
+    }
+
+    /// Advances the weather transition by `dt` seconds
+    pub fn step(&mut self, dt: f32) {
+        let target = self.target();
+        if target != self.kind {
+            // start a new transition from the current blended state
+            self.source = self.params;
+            self.previous = self.kind;
+            self.kind = target;
+            self.elapsed = 0.;
+        }
+
+        self.elapsed += dt;
+        self.params = WeatherParams::lerp(
+            &self.source,
+            &self.kind.params(),
+            self.transition_progress(),
+        );
+    }
+}
+
+/// Looping ambient audio of a weather kind
+#[derive(Component)]
+pub struct WeatherAmbience {
+    pub kind: WeatherKind,
+}
+
+/// Weather and ambient audio
+pub struct WeatherMocca;
+
+impl Mocca for WeatherMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<CandyAudioMocca>();
+        deps.depends_on::<CandyCanMocca>();
+        deps.depends_on::<CandySkyMocca>();
+        deps.depends_on::<CandyTimeMocca>();
+        deps.depends_on::<CustomPropertiesMocca>();
+        deps.depends_on::<LevelMocca>();
+        deps.depends_on::<PlayerMocca>();
+        deps.depends_on::<RecolaAssetsMocca>();
+        deps.depends_on::<SettingsMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<WeatherAmbience>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(Weather::default());
+        world.run(spawn_weather_ambience);
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(apply_level_weather);
+        world.run(script_final_rift_storm);
+        world.run(advance_weather);
+        world.run(apply_weather_to_sky);
+        world.run(crossfade_weather_ambience);
+    }
+}
+
+fn spawn_weather_ambience(mut cmd: Commands, asset_resolver: Singleton<SharedAssetResolver>) {
+    for kind in WeatherKind::ALL {
+        let Some(path) = kind.ambient_audio() else {
+            continue;
+        };
+        let Ok(path) = asset_resolver.resolve(path) else {
+            log::warn!("missing ambient audio for weather {kind:?}: {path}");
+            continue;
+        };
+
+        cmd.spawn((
+            Name::from_str(&format!("weather ambience {kind:?}")),
+            WeatherAmbience { kind },
+            AudioSource {
+                path,
+                volume: 0.,
+                state: AudioPlaybackState::Play,
+                repeat: AudioRepeatKind::Loop,
+                volume_auto_play: false,
+            },
+            GlobalAudioEmitter,
+        ));
+    }
+}
+
+/// Uses the default weather of the level the player is in
+fn apply_level_weather(
+    player: Singleton<Player>,
+    levels: Singleton<LevelSummary>,
+    mut weather: SingletonMut<Weather>,
+    query_level: Query<(&Name, Option<&CustomProperties>), With<LevelRegion>>,
+) {
+    let Some(level) = levels.nearest_level_name(player.eye_position) else {
+        return;
+    };
+    let kind = query_level
+        .iter()
+        .find(|(name, _)| name.as_str() == level)
+        .and_then(|(_, props)| props)
+        .and_then(WeatherKind::from_properties)
+        .unwrap_or_default();
+
+    if weather.level_default != kind {
+        log::info!("weather of level {level}: {kind:?}");
+        weather.set_level_default(kind);
+    }
+}
+
+/// Charging the final rift triggers a storm
+fn script_final_rift_storm(
+    player: Singleton<Player>,
+    mut weather: SingletonMut<Weather>,
+    query_rift: Query<&RiftLevel>,
+) {
+    let Some(final_level) = query_rift.iter().map(|lvl| lvl.0).max() else {
+        return;
+    };
+
+    if player.rift_charges.iter().any(|lvl| lvl.0 == final_level)
+        && weather.scripted != Some(WeatherKind::Storm)
+    {
+        log::info!("final rift charged: storm is coming");
+        weather.set_scripted(Some(WeatherKind::Storm));
+    }
+}
+
+fn advance_weather(time: Singleton<SimClock>, mut weather: SingletonMut<Weather>) {
+    weather.step(time.sim_dt_f32());
+}
+
+fn apply_weather_to_sky(
+    settings: Singleton<GameSettings>,
+    weather: Singleton<Weather>,
+    mut sky: SingletonMut<SkyModel>,
+) {
+    let params = weather.params();
+
+    // lightning briefly lights up the scene
+    let flash = if weather.is_lightning_flash() && !settings.accessibility.reduce_flashes {
+        4.
+    } else {
+        1.
+    };
+
+    sky.set_sun_raw_radiance(SUN_RAW_RADIANCE * params.sun_radiance_scale * flash);
+    sky.set_moon_raw_radiance(MOON_RAW_RADIANCE * params.moon_radiance_scale * flash);
+}
+
+fn crossfade_weather_ambience(
+    weather: Singleton<Weather>,
+    mut query: Query<(&WeatherAmbience, &mut AudioSource)>,
+) {
+    for (ambience, audio) in query.iter_mut() {
+        audio.volume = weather.ambient_volume(ambience.kind);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    fn assert_params_eq(a: &WeatherParams, b: &WeatherParams) {
+        assert_abs_diff_eq!(a.sun_radiance_scale, b.sun_radiance_scale, epsilon = 1e-5);
+        assert_abs_diff_eq!(a.moon_radiance_scale, b.moon_radiance_scale, epsilon = 1e-5);
+        assert_abs_diff_eq!(a.ambient_volume, b.ambient_volume, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn test_weather_from_level_properties() {
+        use std::collections::HashMap;
+
+        let props = |weather: &str| {
+            CustomProperties::from_json(&HashMap::from([(
+                "weather".to_owned(),
+                serde_json::json!(weather),
+            )]))
+        };
+        assert_eq!(
+            WeatherKind::from_properties(&props("fog")),
+            Some(WeatherKind::Fog)
+        );
+        assert_eq!(
+            WeatherKind::from_properties(&props("storm")),
+            Some(WeatherKind::Storm)
+        );
+        assert_eq!(WeatherKind::from_properties(&props("sunny")), None);
+        assert_eq!(
+            WeatherKind::from_properties(&CustomProperties::from_json(&HashMap::new())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_weather_params_blending() {
+        let clear = WeatherKind::Clear.params();
+        let storm = WeatherKind::Storm.params();
+
+        assert_params_eq(&WeatherParams::lerp(&clear, &storm, 0.), &clear);
+        assert_params_eq(&WeatherParams::lerp(&clear, &storm, 1.), &storm);
+        assert_params_eq(&WeatherParams::lerp(&clear, &storm, 7.), &storm);
+
+        let mid = WeatherParams::lerp(&clear, &storm, 0.5);
+        assert_abs_diff_eq!(mid.sun_radiance_scale, 0.56, epsilon = 1e-5);
+        assert_abs_diff_eq!(mid.ambient_volume, 0.40, epsilon = 1e-5);
+
+        let mut weather = Weather::new(WeatherKind::Clear);
+        weather.set_level_default(WeatherKind::Storm);
+        weather.step(0.5 * WeatherKind::Storm.transition_duration());
+        assert!(weather.is_transitioning());
+        assert_params_eq(weather.params(), &mid);
+        assert_abs_diff_eq!(
+            weather.ambient_volume(WeatherKind::Storm),
+            0.40,
+            epsilon = 1e-5
+        );
+        assert_abs_diff_eq!(weather.ambient_volume(WeatherKind::Fog), 0.);
+
+        weather.step(WeatherKind::Storm.transition_duration());
+        assert!(!weather.is_transitioning());
+        assert_params_eq(weather.params(), &storm);
+    }
+
+    #[test]
+    fn test_weather_transition_starts_from_blended_state() {
+        let mut weather = Weather::new(WeatherKind::Clear);
+        weather.set_level_default(WeatherKind::Storm);
+        weather.step(0.5 * WeatherKind::Storm.transition_duration());
+        let halfway = *weather.params();
+
+        weather.set_level_default(WeatherKind::Clear);
+        weather.step(0.);
+        assert_eq!(weather.kind(), WeatherKind::Clear);
+        assert_params_eq(weather.params(), &halfway);
+    }
+
+    #[test]
+    fn test_scripted_weather_overrides_level_default() {
+        let mut weather = Weather::new(WeatherKind::Fog);
+        weather.set_scripted(Some(WeatherKind::Storm));
+        weather.set_level_default(WeatherKind::Overcast);
+        weather.step(0.1);
+        assert_eq!(weather.kind(), WeatherKind::Storm);
+
+        weather.set_scripted(None);
+        weather.step(0.1);
+        assert_eq!(weather.kind(), WeatherKind::Overcast);
+    }
+
+    #[test]
+    fn test_lightning_only_during_storm() {
+        let mut weather = Weather::new(WeatherKind::Overcast);
+        assert_eq!(weather.time_to_next_lightning(), None);
+
+        weather.set_scripted(Some(WeatherKind::Storm));
+        weather.step(0.5 * WeatherKind::Storm.transition_duration());
+        assert_eq!(weather.time_to_next_lightning(), None);
+
+        weather.step(0.5 * WeatherKind::Storm.transition_duration());
+        assert!(weather.is_lightning_flash());
+
+        weather.step(1.0);
+        assert!(!weather.is_lightning_flash());
+        assert_abs_diff_eq!(
+            weather.time_to_next_lightning().unwrap(),
+            LIGHTNING_PERIOD - 1.0,
+            epsilon = 1e-4
+        );
+    }
+}
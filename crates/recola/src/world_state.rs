@@ -0,0 +1,146 @@
+//! World state signals derived from the progress of the player
+//!
+//! Charging rifts advances the time of day. [WorldSignals] are computed once per frame from the
+//! current hour and read by systems which let the world react to it, e.g. overgrowth regrows
+//! slower in bright daylight and solar lamps turn off during the day.
+
+use crate::{mechanics::material_swap::*, player::*};
+use atom::prelude::*;
+use gems::Curve1D;
+use std::sync::LazyLock;
+
+/// Solar lamps are lit while the daylight factor is below this value
+pub const SOLAR_LAMP_DAYLIGHT_THRESHOLD: f32 = 0.5;
+
+/// Daylight factor over the hour of the day
+static DAYLIGHT_CURVE: LazyLock<Curve1D> =
+    LazyLock::new(|| Curve1D::new([(5.0, 0.0), (7.0, 1.0), (17.0, 1.0), (19.5, 0.0)]));
+
+/// Signals which systems read instead of the [Player] singleton
+#[derive(Singleton, Debug, Clone, Copy, PartialEq)]
+pub struct WorldSignals {
+    /// Hour of the day in [0, 24)
+    pub hour: f32,
+
+    /// 1 during the day, 0 during the night and blended during dawn and dusk
+    pub daylight: f32,
+}
+
+impl WorldSignals {
+    pub fn from_hour(hours: f32) -> Self {
+        let hour = hours.rem_euclid(24.);
+        Self {
+            hour,
+            daylight: DAYLIGHT_CURVE.sample(hour),
+        }
+    }
+
+    pub fn solar_lamps_lit(&self) -> bool {
+        self.daylight < SOLAR_LAMP_DAYLIGHT_THRESHOLD
+    }
+}
+
+/// Lamp which is turned off during daylight. Set on props with the `solar` property.
+#[derive(Component, Debug, Default)]
+pub struct SolarLamp {
+    /// Entity with the emissive material of the lamp
+    pub light_entity: Option<Entity>,
+
+    lit: Option<bool>,
+}
+
+impl SolarLamp {
+    pub fn new(light_entity: Option<Entity>) -> Self {
+        Self {
+            light_entity,
+            lit: None,
+        }
+    }
+
+    /// Returns the new state if it changed
+    pub fn update(&mut self, signals: &WorldSignals) -> Option<bool> {
+        let lit = signals.solar_lamps_lit();
+        if self.lit == Some(lit) {
+            return None;
+        }
+        self.lit = Some(lit);
+        Some(lit)
+    }
+}
+
+pub struct WorldStateMocca;
+
+impl Mocca for WorldStateMocca {
+    fn load(mut deps: MoccaDeps) {
+        deps.depends_on::<MaterialSwapMocca>();
+        deps.depends_on::<PlayerMocca>();
+    }
+
+    fn register_components(world: &mut World) {
+        world.register_component::<SolarLamp>();
+    }
+
+    fn start(world: &mut World) -> Self {
+        world.set_singleton(WorldSignals::from_hour(12.));
+        Self
+    }
+
+    fn step(&mut self, world: &mut World) {
+        world.run(update_world_signals);
+        world.run(switch_solar_lamps);
+    }
+}
+
+fn update_world_signals(player: Singleton<Player>, mut signals: SingletonMut<WorldSignals>) {
+    *signals = WorldSignals::from_hour(player.hours);
+}
+
+fn switch_solar_lamps(
+    mut cmd: Commands,
+    signals: Singleton<WorldSignals>,
+    mut query: Query<(Entity, &mut SolarLamp)>,
+) {
+    for (entity, lamp) in query.iter_mut() {
+        if let Some(lit) = lamp.update(&signals) {
+            let light_entity = lamp.light_entity.unwrap_or(entity);
+            cmd.entity(light_entity)
+                .set(EmissionScale(if lit { 1. } else { 0. }));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn test_world_signals_boundary_hours() {
+        assert_abs_diff_eq!(WorldSignals::from_hour(0.).daylight, 0.);
+        assert_abs_diff_eq!(WorldSignals::from_hour(5.).daylight, 0.);
+        assert_abs_diff_eq!(WorldSignals::from_hour(6.).daylight, 0.5);
+        assert_abs_diff_eq!(WorldSignals::from_hour(7.).daylight, 1.);
+        assert_abs_diff_eq!(WorldSignals::from_hour(12.).daylight, 1.);
+        assert_abs_diff_eq!(WorldSignals::from_hour(17.).daylight, 1.);
+        assert_abs_diff_eq!(WorldSignals::from_hour(19.5).daylight, 0.);
+
+        // hours past midnight wrap around
+        let late = WorldSignals::from_hour(30.);
+        assert_abs_diff_eq!(late.hour, 6.);
+        assert_abs_diff_eq!(late.daylight, 0.5);
+    }
+
+    #[test]
+    fn test_solar_lamp_follows_daylight() {
+        let mut lamp = SolarLamp::new(None);
+
+        // the first update always reports the state
+        assert_eq!(lamp.update(&WorldSignals::from_hour(12.)), Some(false));
+        assert_eq!(lamp.update(&WorldSignals::from_hour(17.)), None);
+
+        // dusk
+        assert_eq!(lamp.update(&WorldSignals::from_hour(18.)), None);
+        assert_eq!(lamp.update(&WorldSignals::from_hour(18.5)), Some(true));
+        assert_eq!(lamp.update(&WorldSignals::from_hour(20.)), None);
+    }
+}